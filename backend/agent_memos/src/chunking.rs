@@ -0,0 +1,236 @@
+// agent_memos/src/chunking.rs
+
+// 长记忆分块：整段会议纪要只有一个向量时语义被平均掉，召回很难命中其中的某个细节。
+// - 超过 CHUNK_THRESHOLD_CHARS 的记忆按段落与句子边界切成若干块，每块单独向量化，作为挂在父记忆下的“块点”写入同一集合；
+// - 块点的 payload 复制父记忆的层级、标签、时间、取代等字段（过滤条件对块同样生效），另记 parent_id 与 chunk_index，
+//   父记忆的点照常保留整段内容，并记下 chunk_count，用于下次同步时清理多余的块；
+// - 块点 ID 由父记忆 ID 与块序号编码而成，不会与记忆 ID 冲突；
+// - 召回时命中的块映射回父记忆（small-to-big）：回答问题时返回命中块前后的窗口（父记忆不长时直接返回全文），
+//   定位修改、删除目标时返回父记忆全文，因此修改和删除总是作用于父记忆。
+// 分块由内容唯一决定，窗口在召回时按父记忆的当前内容重新切分得到，不单独落库。
+
+use crate::vector_store::{fields, Condition, Filter, Payload, ScoredMemo};
+use rusqlite::{Connection, OptionalExtension};
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+
+/// 超过这个字符数的记忆才分块
+pub(crate) const CHUNK_THRESHOLD_CHARS: usize = 500;
+/// 单块的最大字符数；单句超过它时硬切
+const TARGET_CHUNK_CHARS: usize = 300;
+/// 遇到段落结尾时，当前块至少有这么多字符就在此处断开，尽量让块与段落对齐
+const MIN_PARAGRAPH_CHUNK_CHARS: usize = 100;
+/// 每条记忆最多的块数，超出部分不再单独索引（整段仍由父记忆的点覆盖）
+const MAX_CHUNKS_PER_FACT: usize = 256;
+/// 块点 ID 的起点：块点 ID = CHUNK_ID_BASE + 父记忆 ID × MAX_CHUNKS_PER_FACT + 块序号
+const CHUNK_ID_BASE: i64 = 1 << 52;
+
+/// 父记忆不超过这个字符数时，命中块直接返回父记忆全文
+const WHOLE_PARENT_MAX_CHARS: usize = 1200;
+/// 返回窗口时，命中块前后各带上的块数
+const WINDOW_RADIUS: usize = 1;
+
+/// 句末标点；英文句点只有后面跟空白或位于末尾时才算
+const SENTENCE_ENDS: &[char] = &['。', '！', '？', '!', '?', '；', ';', '…'];
+/// 紧跟在句末标点后、应归入前一句的收尾符号
+const CLOSERS: &[char] = &['”', '’', '"', '\'', '）', ')', '」', '』', '】', '…'];
+
+/// 一个块：在父记忆内容中的字符区间 [start, end) 与去掉首尾空白后的文本
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Chunk {
+    pub index: usize,
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+/// 把内容切成句子片段 (start, end, 是否段落结尾)，区间为字符下标
+fn segments(chars: &[char]) -> Vec<(usize, usize, bool)> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let paragraph_end = c == '\n';
+        let sentence_end = SENTENCE_ENDS.contains(&c) || (c == '.' && chars.get(i + 1).is_none_or(|n| n.is_whitespace()));
+        i += 1;
+        if paragraph_end || sentence_end {
+            while i < chars.len() && CLOSERS.contains(&chars[i]) {
+                i += 1;
+            }
+            // 句末之后的换行同样结束段落
+            let paragraph_end = paragraph_end || chars.get(i) == Some(&'\n');
+            segments.push((start, i, paragraph_end));
+            start = i;
+        }
+    }
+    if start < chars.len() {
+        segments.push((start, chars.len(), true));
+    }
+    segments
+}
+
+/// 按段落与句子边界分块；内容不够长时返回空列表（不分块）
+pub(crate) fn split(content: &str) -> Vec<Chunk> {
+    let chars: Vec<char> = content.chars().collect();
+    if chars.len() <= CHUNK_THRESHOLD_CHARS {
+        return Vec::new();
+    }
+
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    let mut current: Option<(usize, usize)> = None;
+    for (seg_start, seg_end, paragraph_end) in segments(&chars) {
+        // 过长的单句按 TARGET_CHUNK_CHARS 硬切
+        let mut piece_start = seg_start;
+        while piece_start < seg_end {
+            let piece_end = (piece_start + TARGET_CHUNK_CHARS).min(seg_end);
+            current = match current {
+                Some((start, end)) if piece_end - start > TARGET_CHUNK_CHARS => {
+                    ranges.push((start, end));
+                    Some((piece_start, piece_end))
+                }
+                Some((start, _)) => Some((start, piece_end)),
+                None => Some((piece_start, piece_end)),
+            };
+            piece_start = piece_end;
+        }
+        if let Some((start, end)) = current.filter(|(start, end)| paragraph_end && end - start >= MIN_PARAGRAPH_CHUNK_CHARS) {
+            ranges.push((start, end));
+            current = None;
+        }
+    }
+    ranges.extend(current);
+
+    let mut chunks: Vec<Chunk> = Vec::new();
+    for (start, end) in ranges {
+        let text: String = chars[start..end].iter().collect::<String>().trim().to_string();
+        if text.is_empty() {
+            continue;
+        }
+        if chunks.len() == MAX_CHUNKS_PER_FACT {
+            println!("[MemosAgent-Chunk] Memo exceeds {} chunks; the rest is covered by the parent point only.", MAX_CHUNKS_PER_FACT);
+            break;
+        }
+        chunks.push(Chunk { index: chunks.len(), start, end, text });
+    }
+    chunks
+}
+
+/// 命中第 index 块时返回的窗口：前后各 radius 块连同原文中的分隔一起截出；块不存在时返回 None
+pub(crate) fn window(content: &str, index: usize, radius: usize) -> Option<String> {
+    let chunks = split(content);
+    let hit = chunks.get(index)?;
+    let first = &chunks[index.saturating_sub(radius)];
+    let last = &chunks[(index + radius).min(chunks.len() - 1)];
+    let text: String = content.chars().skip(first.start).take(last.end - first.start).collect();
+    let text = text.trim();
+    Some(if text.is_empty() { hit.text.clone() } else { text.to_string() })
+}
+
+/// 块点 ID
+pub(crate) fn chunk_point_id(fact_id: i64, index: usize) -> i64 {
+    CHUNK_ID_BASE + fact_id * MAX_CHUNKS_PER_FACT as i64 + index as i64
+}
+
+/// 父记忆 ID 为 fact_id、序号在 [from, to) 内的块点 ID
+pub(crate) fn chunk_point_ids(fact_id: i64, from: usize, to: usize) -> Vec<i64> {
+    (from..to.min(MAX_CHUNKS_PER_FACT)).map(|i| chunk_point_id(fact_id, i)).collect()
+}
+
+/// 是否为块点（而不是记忆本身的点）
+pub(crate) fn is_chunk_point(id: i64) -> bool {
+    id >= CHUNK_ID_BASE
+}
+
+/// 由块点 ID 反推 (父记忆 ID, 块序号)；不是块点时返回 None
+pub(crate) fn split_chunk_point_id(id: i64) -> Option<(i64, usize)> {
+    if !is_chunk_point(id) {
+        return None;
+    }
+    let offset = id - CHUNK_ID_BASE;
+    Some((offset / MAX_CHUNKS_PER_FACT as i64, (offset % MAX_CHUNKS_PER_FACT as i64) as usize))
+}
+
+/// 父记忆点 payload 中记录的块数
+pub(crate) fn chunk_count(payload: Option<&Payload>) -> usize {
+    payload.and_then(|p| p.get(fields::CHUNK_COUNT)).and_then(|v| v.as_u64()).map_or(0, |n| n as usize)
+}
+
+/// 块点 payload 中的 (父记忆 ID, 块序号)；不是块点时返回 None
+pub(crate) fn parent_of(payload: &Payload) -> Option<(i64, usize)> {
+    let parent_id = payload.get(fields::PARENT_ID)?.as_i64()?;
+    let index = payload.get(fields::CHUNK_INDEX).and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+    Some((parent_id, index))
+}
+
+/// 只保留记忆本身的点（排除块点），用于去重、矛盾检测等以整条记忆为单位的比较
+pub(crate) fn parents_only() -> Filter {
+    Filter::must([Condition::is_empty(fields::PARENT_ID)])
+}
+
+/// small-to-big：把命中的块点映射回父记忆，同一条记忆只保留排名最靠前的一次。
+/// whole_parent 为 true 时内容取父记忆全文（定位修改、删除目标），否则取命中块附近的窗口；父记忆已不存在的块丢弃。
+pub(crate) fn resolve_hits(conn: &Connection, points: Vec<ScoredMemo>, whole_parent: bool) -> rusqlite::Result<Vec<ScoredMemo>> {
+    let mut parents: HashMap<i64, Option<String>> = HashMap::new();
+    let mut seen = HashSet::new();
+    let mut resolved = Vec::with_capacity(points.len());
+    for mut point in points {
+        if let Some((parent_id, index)) = parent_of(&point.payload) {
+            let parent = match parents.get(&parent_id) {
+                Some(parent) => parent.clone(),
+                None => {
+                    let content: Option<String> = conn
+                        .query_row("SELECT content FROM facts WHERE id = ?1", [parent_id], |row| row.get(0))
+                        .optional()?;
+                    parents.insert(parent_id, content.clone());
+                    content
+                }
+            };
+            let Some(parent) = parent else { continue };
+            let content = if whole_parent || parent.chars().count() <= WHOLE_PARENT_MAX_CHARS {
+                parent
+            } else {
+                window(&parent, index, WINDOW_RADIUS).unwrap_or_else(|| point.content().unwrap_or_default().to_string())
+            };
+            point.id = parent_id;
+            point.payload.remove(fields::PARENT_ID);
+            point.payload.insert(fields::CONTENT.to_string(), json!(content));
+        }
+        if seen.insert(point.id) {
+            resolved.push(point);
+        }
+    }
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunk_point_ids_map_back_to_parents() {
+        for (fact_id, index) in [(1, 0), (42, 7), (123_456, MAX_CHUNKS_PER_FACT - 1)] {
+            let id = chunk_point_id(fact_id, index);
+            assert!(is_chunk_point(id));
+            assert_eq!(split_chunk_point_id(id), Some((fact_id, index)));
+        }
+        assert!(!is_chunk_point(42));
+        assert_eq!(split_chunk_point_id(42), None);
+        assert_eq!(chunk_point_ids(5, 2, 4), vec![chunk_point_id(5, 2), chunk_point_id(5, 3)]);
+    }
+
+    #[test]
+    fn long_content_is_split_on_sentence_boundaries() {
+        assert!(split("短句。").is_empty());
+        let sentence = "今天和小李讨论了下个季度的预算安排以及人员调整方案。";
+        let content = sentence.repeat(30);
+        let chunks = split(&content);
+        assert!(chunks.len() > 1);
+        for (i, chunk) in chunks.iter().enumerate() {
+            assert_eq!(chunk.index, i);
+            assert!(chunk.text.chars().count() <= TARGET_CHUNK_CHARS);
+            assert!(chunk.text.ends_with('。'), "chunk {} does not end on a sentence: {}", i, chunk.text);
+        }
+        assert_eq!(window(&content, 0, 0).as_deref(), Some(chunks[0].text.as_str()));
+    }
+}
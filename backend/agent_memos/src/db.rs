@@ -1,28 +1,50 @@
+use crate::migrations;
 use r2d2_sqlite::SqliteConnectionManager;
-use rusqlite::Result;
+use rusqlite::{Connection, OptionalExtension, Result};
+use std::path::PathBuf;
 
 // 定义连接池的类型别名，方便使用
 pub type DbPool = r2d2::Pool<SqliteConnectionManager>;
 
-/// 初始化数据库并创建表
-pub fn init_db(pool: &DbPool) -> Result<()> {
+/// 数据目录：默认 ~/.memos_agent，可通过环境变量 MEMOS_DATA_DIR 覆盖（便于 CI 和多实例隔离）
+pub fn data_dir() -> Result<PathBuf, anyhow::Error> {
+    if let Ok(dir) = std::env::var("MEMOS_DATA_DIR") {
+        return Ok(PathBuf::from(dir));
+    }
+    let home_dir = dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?;
+    Ok(home_dir.join(".memos_agent"))
+}
+
+/// 初始化数据库：按 user_version 依次应用尚未执行的 schema 迁移（见 migrations.rs）
+pub fn init_db(pool: &DbPool) -> Result<(), anyhow::Error> {
     // 从连接池中获取一个连接
-    let conn = pool.get().expect("Failed to get DB connection from pool");
-    
-    // V5.0 升级：创建 'facts' 表，增加 expires_at 和 metadata 字段
-    // id 已经是主键，我们确保它自增
+    let mut conn = pool.get()?;
+    let from = migrations::current_version(&conn)?;
+    let applied = migrations::run(&mut conn)?;
+    if applied > 0 {
+        println!("[MemosAgent-DB] Database schema migrated from v{} to v{}.", from, migrations::latest_version());
+    } else {
+        println!("[MemosAgent-DB] Database schema is up to date (v{}).", from);
+    }
+    Ok(())
+}
+
+/// 读取一项索引元数据
+pub fn get_meta(conn: &Connection, key: &str) -> Result<Option<String>> {
+    conn.query_row("SELECT value FROM index_meta WHERE key = ?1", [key], |row| row.get(0)).optional()
+}
+
+/// 写入（或覆盖）一项索引元数据
+pub fn set_meta(conn: &Connection, key: &str, value: &str) -> Result<()> {
     conn.execute(
-        "CREATE TABLE IF NOT EXISTS facts (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            content TEXT NOT NULL,
-            metadata TEXT,
-            expires_at DATETIME,
-            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
-        )",
-        [],
+        "INSERT INTO index_meta (key, value) VALUES (?1, ?2) ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        [key, value],
     )?;
+    Ok(())
+}
 
-    println!("[MemosAgent-DB] Database initialized and 'facts' table created/updated for V5.0.");
+/// 删除一项索引元数据
+pub fn delete_meta(conn: &Connection, key: &str) -> Result<()> {
+    conn.execute("DELETE FROM index_meta WHERE key = ?1", [key])?;
     Ok(())
-}
\ No newline at end of file
+}
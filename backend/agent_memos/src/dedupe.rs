@@ -0,0 +1,273 @@
+// agent_memos/src/dedupe.rs
+
+// 去重：保存前先与已有记忆比对，避免同一句话被记成多条，进而让修改/删除流程陷入多选澄清。
+// - 规范化后文本完全相同，或向量相似度极高，视为重复（Duplicate），不再写入；
+//   规范化文本存放在 facts.content_key 列（带索引），与 facts 的写入在同一事务中由 sync_content_key() 维护，精确比对只需一次索引查询；
+// - 已过期（包括归档保留）与已被取代的记忆不参与比对，同一句话可以重新记下；
+// - 相似度较高但不足以判定重复，返回 Similar，由上层询问用户合并、替换还是都保留；
+// - dedupe_store() 对已有存储做一次批量整理，每组保留最早的一条，其余的删除（可通过修订历史撤销）。
+
+use crate::chunking;
+use crate::expiry;
+use crate::supersede;
+use crate::vector_store::ScoredMemo;
+use crate::MemosAgent;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::Ordering;
+
+/// 向量相似度达到该值视为重复
+const DUPLICATE_SCORE_THRESHOLD: f32 = 0.95;
+/// 向量相似度达到该值视为相似，需要用户决定
+const SIMILAR_SCORE_THRESHOLD: f32 = 0.85;
+const SIMILAR_CANDIDATE_LIMIT: u64 = 5;
+
+/// 一次保存的结果
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub enum SaveOutcome {
+    /// 已写入，附带新记忆的 ID
+    Inserted(i64),
+    /// 与已有记忆重复，未写入
+    Duplicate(i64),
+    /// 与若干已有记忆相似，未写入，等待调用方决定（合并、替换或调用 insert 都保留）
+    Similar(Vec<i64>),
+}
+
+/// 一组重复记忆
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DuplicateGroup {
+    /// 保留的记忆（组内最早保存的一条）
+    pub keep: i64,
+    pub duplicates: Vec<i64>,
+}
+
+/// 批量去重的结果
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct DedupeReport {
+    pub scanned: usize,
+    pub groups: Vec<DuplicateGroup>,
+    /// 实际删除的数量；试运行时为 0
+    pub removed: usize,
+}
+
+/// 规范化文本用于精确比对：忽略空白、标点与大小写
+pub(crate) fn normalize(content: &str) -> String {
+    content.chars()
+        .filter(|c| !c.is_whitespace() && !c.is_ascii_punctuation() && !"，。！？；：、“”‘’（）《》…—".contains(*c))
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+/// 让 fact_id 的 content_key 与当前内容一致；在写入 content 的同一事务中调用
+pub(crate) fn sync_content_key(conn: &Connection, fact_id: i64) -> rusqlite::Result<()> {
+    let content: Option<String> = conn
+        .query_row("SELECT content FROM facts WHERE id = ?1", [fact_id], |row| row.get(0))
+        .optional()?;
+    if let Some(content) = content {
+        conn.execute("UPDATE facts SET content_key = ?1 WHERE id = ?2", params![normalize(&content), fact_id])?;
+    }
+    Ok(())
+}
+
+impl MemosAgent {
+    /// 检查一段待保存的内容是否与已有记忆重复或相似；都不是时返回 None
+    pub async fn check_duplicates(&self, content: &str) -> Result<Option<SaveOutcome>, anyhow::Error> {
+        let exact: Option<i64> = {
+            let conn = self.sql_pool.get()?;
+            let now = expiry::to_db_timestamp(self.clock.now());
+            conn.query_row(
+                "SELECT id FROM facts WHERE content_key = ?1 AND superseded_by IS NULL
+                   AND (expires_at IS NULL OR expires_at > ?2) ORDER BY id LIMIT 1",
+                params![normalize(content), now],
+                |row| row.get(0),
+            ).optional()?
+        };
+        if let Some(id) = exact {
+            println!("[MemosAgent-Dedupe] Exact duplicate of memo {}.", id);
+            return Ok(Some(SaveOutcome::Duplicate(id)));
+        }
+
+        // 索引与嵌入模型不一致时向量比对没有意义，只做精确比对
+        if self.needs_reindex.load(Ordering::SeqCst) {
+            return Ok(None);
+        }
+        // 向量库暂时不可用时不阻塞保存，只是跳过近似比对
+        let similar = match self.similar_memos(content, SIMILAR_SCORE_THRESHOLD).await {
+            Ok(similar) => similar,
+            Err(e) => {
+                eprintln!("[MemosAgent-Dedupe] Similarity check skipped: {}", e);
+                return Ok(None);
+            }
+        };
+        if let Some(top) = similar.first().filter(|p| p.score >= DUPLICATE_SCORE_THRESHOLD) {
+            println!("[MemosAgent-Dedupe] Near duplicate of memo {} (score {:.3}).", top.id, top.score);
+            return Ok(Some(SaveOutcome::Duplicate(top.id)));
+        }
+        if !similar.is_empty() {
+            let ids: Vec<i64> = similar.iter().map(|p| p.id).collect();
+            println!("[MemosAgent-Dedupe] Similar to existing memos {:?}.", ids);
+            return Ok(Some(SaveOutcome::Similar(ids)));
+        }
+        Ok(None)
+    }
+
+    /// 在当前集合中查找与 content 相似度不低于 threshold 的未过期、未被取代的记忆，按分数降序
+    async fn similar_memos(&self, content: &str, threshold: f32) -> Result<Vec<ScoredMemo>, anyhow::Error> {
+        let collection = self.indexer.active_collection();
+        let vector = self.get_embedding(content).await?;
+        let filter = supersede::current_only().and(chunking::parents_only());
+        let points = self.vector_store.search(&collection, vector, SIMILAR_CANDIDATE_LIMIT, Some(threshold), Some(&filter)).await?;
+        Ok(self.drop_expired(points))
+    }
+
+    /// 全部未过期、未被取代的记忆：已被取代的旧说法或过期后归档的记忆再次出现时应当重新记下，而不是当作重复
+    fn all_facts(&self) -> Result<Vec<(i64, String)>, anyhow::Error> {
+        let conn = self.sql_pool.get()?;
+        let now = expiry::to_db_timestamp(self.clock.now());
+        let mut stmt = conn.prepare(
+            "SELECT id, content FROM facts WHERE superseded_by IS NULL AND (expires_at IS NULL OR expires_at > ?1) ORDER BY id",
+        )?;
+        let rows = stmt.query_map([now], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<(i64, String)>, _>>()?;
+        Ok(rows)
+    }
+
+    /// 批量整理已有存储中的重复记忆。apply 为 false 时只报告不删除。
+    pub async fn dedupe_store(&self, apply: bool) -> Result<DedupeReport, anyhow::Error> {
+        let facts = self.all_facts()?;
+        let mut report = DedupeReport { scanned: facts.len(), ..Default::default() };
+        println!("[MemosAgent-Dedupe] Scanning {} memo(s) for duplicates (apply: {})...", facts.len(), apply);
+
+        // 1. 规范化文本完全相同的
+        let mut groups: Vec<(i64, Vec<i64>)> = Vec::new();
+        let mut first_by_text: HashMap<String, usize> = HashMap::new();
+        let mut assigned: HashSet<i64> = HashSet::new();
+        for (id, content) in &facts {
+            match first_by_text.get(&normalize(content)) {
+                Some(&group_idx) => {
+                    groups[group_idx].1.push(*id);
+                    assigned.insert(*id);
+                }
+                None => {
+                    first_by_text.insert(normalize(content), groups.len());
+                    groups.push((*id, Vec::new()));
+                }
+            }
+        }
+        groups.retain(|(_, dups)| !dups.is_empty());
+
+        // 2. 向量近似重复的（按 ID 升序处理，每组保留最早的一条）
+        if !self.needs_reindex.load(Ordering::SeqCst) {
+            for (id, content) in &facts {
+                if assigned.contains(id) {
+                    continue;
+                }
+                let matches: Vec<i64> = self.similar_memos(content, DUPLICATE_SCORE_THRESHOLD).await?
+                    .into_iter()
+                    .map(|p| p.id)
+                    .filter(|other| other > id && !assigned.contains(other))
+                    .collect();
+                if matches.is_empty() {
+                    continue;
+                }
+                assigned.extend(matches.iter().copied());
+                match groups.iter_mut().find(|(keep, _)| keep == id) {
+                    Some((_, dups)) => dups.extend(matches),
+                    None => groups.push((*id, matches)),
+                }
+            }
+        }
+
+        report.groups = groups.into_iter()
+            .map(|(keep, mut duplicates)| {
+                duplicates.sort_unstable();
+                DuplicateGroup { keep, duplicates }
+            })
+            .collect();
+        report.groups.sort_by_key(|g| g.keep);
+
+        if apply {
+            for group in &report.groups {
+                for id in &group.duplicates {
+                    self.delete(*id, Some(&format!("合并重复记忆（保留 {}）", group.keep))).await?;
+                    report.removed += 1;
+                }
+            }
+        }
+        println!(
+            "[MemosAgent-Dedupe] Found {} duplicate group(s); removed {} memo(s).",
+            report.groups.len(), report.removed
+        );
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::RevisionOp;
+    use crate::test_support::{embedded_agent, TestDir};
+    use crate::ManualClock;
+    use chrono::{Duration as ChronoDuration, TimeZone, Utc};
+    use std::sync::Arc;
+
+    #[test]
+    fn normalize_ignores_spacing_punctuation_and_case() {
+        assert_eq!(normalize("我的车 停在 B2-103。"), "我的车停在b2103");
+        assert_eq!(normalize("“周五”，和小李吃火锅！"), "周五和小李吃火锅");
+    }
+
+    #[tokio::test]
+    async fn exact_duplicates_are_found_through_the_content_key() {
+        let dir = TestDir::new();
+        let clock = Arc::new(ManualClock::new(Utc.with_ymd_and_hms(2025, 3, 10, 9, 0, 0).unwrap()));
+        let agent = embedded_agent(&dir, clock).await;
+        let SaveOutcome::Inserted(id) = agent.save("我的车停在B2-103").await.unwrap() else { panic!("expected insert") };
+        assert_eq!(agent.save("我的车 停在 b2-103。").await.unwrap(), SaveOutcome::Duplicate(id));
+
+        // 修改后 content_key 随内容更新
+        agent.update(id, "周五晚上和小李吃火锅", None).await.unwrap();
+        let content_key: String = {
+            let conn = agent.sql_pool.get().unwrap();
+            conn.query_row("SELECT content_key FROM facts WHERE id = ?1", [id], |row| row.get(0)).unwrap()
+        };
+        assert_eq!(content_key, "周五晚上和小李吃火锅");
+        assert_eq!(agent.check_duplicates("周五晚上，和小李吃火锅").await.unwrap(), Some(SaveOutcome::Duplicate(id)));
+    }
+
+    #[tokio::test]
+    async fn expired_memos_are_not_duplicates() {
+        let dir = TestDir::new();
+        let clock = Arc::new(ManualClock::new(Utc.with_ymd_and_hms(2025, 3, 10, 9, 0, 0).unwrap()));
+        let agent = embedded_agent(&dir, clock.clone()).await;
+        let SaveOutcome::Inserted(first) = agent.save("今天停车在B2-103").await.unwrap() else { panic!("expected insert") };
+        assert_eq!(agent.save("今天停车在B2-103").await.unwrap(), SaveOutcome::Duplicate(first));
+
+        // 第二天：昨天那条已过期（归档策略下行仍保留），同一句话应当重新记下
+        clock.advance(ChronoDuration::days(2));
+        agent.sweep_expired().await.unwrap();
+        assert!(agent.get_by_id(first).await.unwrap().is_some());
+        let SaveOutcome::Inserted(second) = agent.save("今天停车在B2-103").await.unwrap() else { panic!("expected insert") };
+        assert_ne!(second, first);
+        assert!(agent.all_facts().unwrap().iter().all(|(id, _)| *id != first));
+    }
+
+    #[tokio::test]
+    async fn content_and_tag_changes_from_one_update_undo_together() {
+        let dir = TestDir::new();
+        let clock = Arc::new(ManualClock::new(Utc.with_ymd_and_hms(2025, 3, 10, 9, 0, 0).unwrap()));
+        let agent = embedded_agent(&dir, clock).await;
+        let id = agent.insert("周五和小李吃火锅", None, &["聚餐".to_string()], None).await.unwrap();
+
+        agent.update_with_tags(id, "周五晚上和小李、小王吃火锅", &["小王".to_string(), "聚餐".to_string()], None).await.unwrap();
+        assert_eq!(agent.tags_of(id).unwrap(), vec!["聚餐", "小王"]);
+        let revisions = agent.history(id).unwrap();
+        assert_eq!(revisions.iter().map(|r| r.op).collect::<Vec<_>>(), vec![RevisionOp::Create, RevisionOp::Update]);
+
+        let outcome = agent.undo_last_change(None).await.unwrap().unwrap();
+        assert_eq!(outcome.content.as_deref(), Some("周五和小李吃火锅"));
+        assert_eq!(agent.tags_of(id).unwrap(), vec!["聚餐"]);
+
+        assert!(agent.update_with_tags(id + 1, "不存在", &["聚餐".to_string()], None).await.is_err());
+    }
+}
@@ -0,0 +1,114 @@
+// agent_memos/src/embedding/hashing.rs
+
+// 确定性哈希向量：把字符一元组和二元组哈希到固定维度的桶里再做 L2 归一化。
+// 它没有任何语义理解能力，但相同或字面相近的文本会得到相同或相近的向量，
+// 足以让集成测试在没有模型服务的情况下跑通 save/recall/update/delete 全流程。
+
+use super::EmbeddingProvider;
+use async_trait::async_trait;
+
+const DEFAULT_DIMENSION: usize = 512;
+
+pub struct HashingEmbeddingProvider {
+    dimension: usize,
+}
+
+impl HashingEmbeddingProvider {
+    pub fn new(dimension: usize) -> Self {
+        Self { dimension: dimension.max(1) }
+    }
+
+    pub fn embed_sync(&self, text: &str) -> Vec<f32> {
+        let chars: Vec<char> = text.to_lowercase().chars().filter(|c| !c.is_whitespace()).collect();
+        let mut vector = vec![0.0f32; self.dimension];
+        let mut add = |feature: &str| {
+            let hash = fnv1a(feature.as_bytes());
+            let index = (hash % self.dimension as u64) as usize;
+            let sign = if (hash >> 63) & 1 == 0 { 1.0 } else { -1.0 };
+            vector[index] += sign;
+        };
+        for (i, c) in chars.iter().enumerate() {
+            add(&c.to_string());
+            if let Some(next) = chars.get(i + 1) {
+                add(&format!("{}{}", c, next));
+            }
+        }
+        let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|v| *v /= norm);
+        }
+        vector
+    }
+}
+
+impl Default for HashingEmbeddingProvider {
+    fn default() -> Self {
+        Self::new(DEFAULT_DIMENSION)
+    }
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+
+/// FNV-1a：跨平台、跨 Rust 版本稳定，不像 DefaultHasher 那样不保证输出
+fn fnv1a(bytes: &[u8]) -> u64 {
+    fnv1a_update(FNV_OFFSET_BASIS, bytes)
+}
+
+/// 在已有的 FNV-1a 状态上继续累加，可用于分段或多个文件的哈希；初始状态为 fnv1a(&[])
+pub(super) fn fnv1a_update(mut hash: u64, bytes: &[u8]) -> u64 {
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+pub(super) fn fnv1a_empty() -> u64 {
+    FNV_OFFSET_BASIS
+}
+
+#[async_trait]
+impl EmbeddingProvider for HashingEmbeddingProvider {
+    fn model_name(&self) -> &str { "hashing" }
+
+    fn dimension(&self) -> Option<usize> { Some(self.dimension) }
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>, anyhow::Error> {
+        Ok(self.embed_sync(text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embedding_has_requested_dimension_and_unit_norm() {
+        for dimension in [1, 8, 512] {
+            let provider = HashingEmbeddingProvider::new(dimension);
+            let vector = provider.embed_sync("明天下午三点在B2开会");
+            assert_eq!(vector.len(), dimension);
+            assert_eq!(provider.dimension(), Some(dimension));
+            let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+            assert!((norm - 1.0).abs() < 1e-5, "dimension {} norm {}", dimension, norm);
+        }
+        // 维度 0 按 1 处理，空文本得到全零向量而不是 NaN
+        let provider = HashingEmbeddingProvider::new(0);
+        assert_eq!(provider.embed_sync("").len(), 1);
+        assert_eq!(HashingEmbeddingProvider::default().embed_sync(" ").iter().filter(|v| **v != 0.0).count(), 0);
+    }
+
+    #[tokio::test]
+    async fn embedding_is_deterministic() {
+        let provider = HashingEmbeddingProvider::default();
+        let first = provider.embed("我的车停在 B2-103").await.unwrap();
+        let second = HashingEmbeddingProvider::default().embed("我的车停在 B2-103").await.unwrap();
+        assert_eq!(first, second);
+        // 大小写与空白不影响结果，字面相近的文本比无关文本更相似
+        assert_eq!(first, provider.embed_sync("我的车停在b2-103"));
+        let near = provider.embed_sync("我的车停在 B2-105");
+        let far = provider.embed_sync("周五晚上和小李吃火锅");
+        let cos = |a: &[f32], b: &[f32]| a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>();
+        assert!(cos(&first, &near) > cos(&first, &far));
+    }
+}
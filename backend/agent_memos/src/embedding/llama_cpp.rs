@@ -0,0 +1,51 @@
+// agent_memos/src/embedding/llama_cpp.rs
+
+use super::EmbeddingProvider;
+use async_trait::async_trait;
+use reqwest::Client;
+
+#[derive(serde::Serialize)]
+struct EmbeddingRequest<'a> {
+    content: &'a str,
+}
+#[derive(Debug, serde::Deserialize)]
+struct EmbeddingData {
+    embedding: Vec<Vec<f32>>,
+}
+#[derive(Debug, serde::Deserialize)]
+struct EmbeddingResponse(Vec<EmbeddingData>);
+
+/// llama.cpp server 的原生 `/embedding` 接口
+pub struct LlamaCppEmbeddingProvider {
+    client: Client,
+    embedding_url: String,
+    model_name: String,
+}
+
+impl LlamaCppEmbeddingProvider {
+    /// model_name 只用于标识（llama.cpp 服务端加载哪个模型由其启动参数决定）
+    pub fn new(embedding_url: &str, model_name: &str) -> Self {
+        Self { client: Client::new(), embedding_url: embedding_url.to_string(), model_name: model_name.to_string() }
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for LlamaCppEmbeddingProvider {
+    fn model_name(&self) -> &str { &self.model_name }
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>, anyhow::Error> {
+        println!("[MemosAgent-Embed] Requesting vector for text: '{}'", text);
+        let request_url = format!("{}/embedding", self.embedding_url);
+        let response = self.client.post(&request_url).json(&EmbeddingRequest { content: text }).send().await?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("Embedding service returned an error: {}", response.text().await?));
+        }
+        let mut embedding_response = response.json::<EmbeddingResponse>().await?;
+        if let Some(mut first_item) = embedding_response.0.pop() {
+            if let Some(embedding_vector) = first_item.embedding.pop() {
+                println!("[MemosAgent-Embed] Received {}d vector.", embedding_vector.len());
+                Ok(embedding_vector)
+            } else { Err(anyhow::anyhow!("Embedding service returned empty embedding list.")) }
+        } else { Err(anyhow::anyhow!("Embedding service returned empty array.")) }
+    }
+}
@@ -0,0 +1,61 @@
+// agent_memos/src/embedding/mod.rs
+
+// 向量化抽象层：MemosAgent 只通过 EmbeddingProvider 获取向量，
+// 具体走 llama.cpp 服务、OpenAI 兼容接口、进程内 ONNX 还是确定性哈希，由启动配置决定。
+
+mod hashing;
+mod llama_cpp;
+mod onnx;
+mod openai;
+
+pub use hashing::HashingEmbeddingProvider;
+pub use llama_cpp::LlamaCppEmbeddingProvider;
+pub use onnx::OnnxEmbeddingProvider;
+pub use openai::OpenAiEmbeddingProvider;
+
+use async_trait::async_trait;
+use std::path::Path;
+use std::sync::Arc;
+
+/// 所有向量化后端必须实现的行为
+#[async_trait]
+pub trait EmbeddingProvider: Send + Sync {
+    /// 模型名称，用于日志以及后续的模型一致性校验
+    fn model_name(&self) -> &str;
+
+    /// 已知的输出维度；远程服务在第一次调用前通常无法得知，返回 None
+    fn dimension(&self) -> Option<usize> {
+        None
+    }
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>, anyhow::Error>;
+}
+
+/// 根据环境变量构造向量化后端：
+/// - MEMOS_EMBEDDING_PROVIDER=llama（默认）：llama.cpp 服务的 `/embedding` 接口
+/// - MEMOS_EMBEDDING_PROVIDER=openai：OpenAI 兼容的 `/v1/embeddings`，密钥取自 MEMOS_EMBEDDING_API_KEY
+/// - MEMOS_EMBEDDING_PROVIDER=onnx：加载 models 目录下的 embedding.onnx 与 embedding_vocab.txt，
+///   模型名取 MEMOS_EMBEDDING_MODEL_ID，未设置时为两个文件的内容指纹
+/// - MEMOS_EMBEDDING_PROVIDER=hash：确定性哈希向量，仅用于测试
+///
+/// 远程服务的模型名取自 MEMOS_EMBEDDING_MODEL（默认 bge-small-zh），它会被记录进索引元数据，
+/// 换模型后启动时即可发现与现有向量集合不一致。
+pub fn provider_from_env(embedding_url: &str, models_path: &Path) -> Result<Arc<dyn EmbeddingProvider>, anyhow::Error> {
+    let kind = std::env::var("MEMOS_EMBEDDING_PROVIDER").unwrap_or_else(|_| "llama".to_string());
+    println!("[EmbeddingProvider] Selected provider: '{}'", kind);
+    let model = std::env::var("MEMOS_EMBEDDING_MODEL").unwrap_or_else(|_| "bge-small-zh".to_string());
+    let provider: Arc<dyn EmbeddingProvider> = match kind.to_lowercase().as_str() {
+        "llama" | "llama.cpp" => Arc::new(LlamaCppEmbeddingProvider::new(embedding_url, &model)),
+        "openai" => {
+            let api_key = std::env::var("MEMOS_EMBEDDING_API_KEY").ok();
+            Arc::new(OpenAiEmbeddingProvider::new(embedding_url, &model, api_key))
+        }
+        "onnx" => Arc::new(OnnxEmbeddingProvider::load(
+            models_path.join("embedding.onnx"),
+            models_path.join("embedding_vocab.txt"),
+        )?),
+        "hash" => Arc::new(HashingEmbeddingProvider::default()),
+        other => return Err(anyhow::anyhow!("Unknown MEMOS_EMBEDDING_PROVIDER '{}'", other)),
+    };
+    Ok(provider)
+}
@@ -0,0 +1,78 @@
+// agent_memos/src/embedding/onnx.rs
+
+use super::hashing::{fnv1a_empty, fnv1a_update};
+use super::EmbeddingProvider;
+use async_trait::async_trait;
+use micromodels::TextEmbedder;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// 进程内 ONNX 向量模型，基于 micromodels::TextEmbedder
+pub struct OnnxEmbeddingProvider {
+    embedder: Mutex<TextEmbedder>,
+    model_name: String,
+    dimension: usize,
+}
+
+impl OnnxEmbeddingProvider {
+    /// 模型名称优先取 MEMOS_EMBEDDING_MODEL_ID，否则由模型与词表文件的内容指纹得出，
+    /// 这样替换成同名、同维度的另一个模型后，启动时同样能发现需要重建索引
+    pub fn load(model_path: impl AsRef<Path>, vocab_path: impl AsRef<Path>) -> Result<Self, anyhow::Error> {
+        let model_name = match std::env::var("MEMOS_EMBEDDING_MODEL_ID").ok().filter(|id| !id.trim().is_empty()) {
+            Some(id) => format!("onnx:{}", id.trim()),
+            None => model_id(model_path.as_ref(), vocab_path.as_ref())?,
+        };
+        let embedder = TextEmbedder::load(model_path, vocab_path)?;
+        let dimension = embedder.dimension();
+        Ok(Self { embedder: Mutex::new(embedder), model_name, dimension })
+    }
+}
+
+/// onnx:<文件名>@<模型与词表内容的 FNV-1a 指纹>
+fn model_id(model_path: &Path, vocab_path: &Path) -> Result<String, anyhow::Error> {
+    let mut hash = fnv1a_empty();
+    for path in [model_path, vocab_path] {
+        let bytes = std::fs::read(path).map_err(|e| anyhow::anyhow!("Failed to read {:?}: {}", path, e))?;
+        hash = fnv1a_update(hash, &bytes);
+    }
+    let stem = model_path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_else(|| "model".to_string());
+    Ok(format!("onnx:{}@{:016x}", stem, hash))
+}
+
+#[async_trait]
+impl EmbeddingProvider for OnnxEmbeddingProvider {
+    fn model_name(&self) -> &str { &self.model_name }
+
+    fn dimension(&self) -> Option<usize> { Some(self.dimension) }
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>, anyhow::Error> {
+        self.embedder.lock().unwrap().embed(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+
+    #[test]
+    fn model_id_follows_file_contents() {
+        let dir = TestDir::new();
+        let model = dir.path().join("embedding.onnx");
+        let vocab = dir.path().join("embedding_vocab.txt");
+        std::fs::write(&model, b"model-a").unwrap();
+        std::fs::write(&vocab, b"[PAD]\n[UNK]\n").unwrap();
+        let first = model_id(&model, &vocab).unwrap();
+        assert!(first.starts_with("onnx:embedding@"), "{}", first);
+        assert_eq!(model_id(&model, &vocab).unwrap(), first);
+
+        // 同名文件换成另一个模型，或只换词表，名称都会变
+        std::fs::write(&model, b"model-b").unwrap();
+        let second = model_id(&model, &vocab).unwrap();
+        assert_ne!(second, first);
+        std::fs::write(&vocab, b"[PAD]\n[UNK]\n[CLS]\n").unwrap();
+        assert_ne!(model_id(&model, &vocab).unwrap(), second);
+
+        assert!(model_id(&dir.path().join("missing.onnx"), &vocab).is_err());
+    }
+}
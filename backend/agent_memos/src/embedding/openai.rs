@@ -0,0 +1,62 @@
+// agent_memos/src/embedding/openai.rs
+
+use super::EmbeddingProvider;
+use async_trait::async_trait;
+use reqwest::Client;
+
+#[derive(serde::Serialize)]
+struct EmbeddingRequest<'a> {
+    model: &'a str,
+    input: &'a str,
+}
+#[derive(Debug, serde::Deserialize)]
+struct EmbeddingData {
+    embedding: Vec<f32>,
+}
+#[derive(Debug, serde::Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+/// OpenAI 兼容的 `/v1/embeddings` 接口（llama.cpp、vLLM、Ollama 等均支持）
+pub struct OpenAiEmbeddingProvider {
+    client: Client,
+    base_url: String,
+    model: String,
+    api_key: Option<String>,
+}
+
+impl OpenAiEmbeddingProvider {
+    pub fn new(base_url: &str, model: &str, api_key: Option<String>) -> Self {
+        Self {
+            client: Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model: model.to_string(),
+            api_key,
+        }
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for OpenAiEmbeddingProvider {
+    fn model_name(&self) -> &str { &self.model }
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>, anyhow::Error> {
+        println!("[MemosAgent-Embed] Requesting vector for text: '{}'", text);
+        let request_url = format!("{}/v1/embeddings", self.base_url);
+        let mut request = self.client.post(&request_url).json(&EmbeddingRequest { model: &self.model, input: text });
+        if let Some(key) = &self.api_key {
+            request = request.bearer_auth(key);
+        }
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("Embedding service returned an error: {}", response.text().await?));
+        }
+        let embedding_response = response.json::<EmbeddingResponse>().await?;
+        let embedding_vector = embedding_response.data.into_iter().next()
+            .map(|d| d.embedding)
+            .ok_or_else(|| anyhow::anyhow!("Embedding service returned empty data array."))?;
+        println!("[MemosAgent-Embed] Received {}d vector.", embedding_vector.len());
+        Ok(embedding_vector)
+    }
+}
@@ -0,0 +1,445 @@
+// agent_memos/src/entity_graph.rs
+
+// 实体图：NER 从每条记忆中识别出的实体，规范化后存入 entities 表，记忆与实体的关联存入 fact_entities。
+// - 同一实体的不同写法（“Titan”“泰坦项目”）通过 entity_aliases 指向同一个实体，合并后关联随之转移；
+// - 关联由 Indexer 在跑 NER 时写入（保存、修改后立即执行，失败随发件箱重试），记忆被删除时一并移除；
+//   引入实体图之前的记忆可通过 rebuild 补齐；
+// - 两个实体出现在同一条记忆里即为共现，共现次数作为“邻居”的强度；
+//   已被取代、或已过期但尚未被清理的记忆不计入共现，与实体扩展检索的过滤一致；
+// - 召回时由查询中的实体出发，取这些实体及其最强邻居关联的记忆，作为实体扩展一路参与融合。
+
+use crate::expiry;
+use crate::memory_tier_manager::MemoryTier;
+use crate::tags;
+use crate::temporal::TimeRange;
+use crate::vector_store::{fields, Payload, ScoredMemo};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// 邻居实体在扩展中的权重上限（查询中直接提到的实体为 1）
+const NEIGHBOUR_WEIGHT: f32 = 0.5;
+
+/// 一个实体及其别名、关联的记忆数
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct EntitySummary {
+    pub id: i64,
+    pub name: String,
+    /// 除名称外的其他写法
+    pub aliases: Vec<String>,
+    /// 关联的（未被取代的）记忆数
+    pub memo_count: usize,
+}
+
+/// 与某实体共现的实体
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct EntityNeighbour {
+    pub id: i64,
+    pub name: String,
+    /// 共同出现的记忆数
+    pub shared: usize,
+}
+
+/// 关联到某实体的一条记忆
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct LinkedMemo {
+    pub id: i64,
+    pub content: String,
+    pub created_at: Option<String>,
+}
+
+/// 实体详情：关联的记忆（最新的在前）与共现最多的邻居
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct EntityDetail {
+    #[serde(flatten)]
+    pub entity: EntitySummary,
+    pub memos: Vec<LinkedMemo>,
+    pub neighbours: Vec<EntityNeighbour>,
+}
+
+/// 召回时由查询实体扩展出的一个实体
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ExpandedEntity {
+    pub id: i64,
+    pub name: String,
+    /// 查询中直接提到的为 1，邻居按共现强度折算
+    pub weight: f32,
+    /// 邻居是从哪个查询实体扩展来的；直接提到的为 None
+    pub via: Option<String>,
+}
+
+/// 别名比对用的写法：去掉首尾空白、合并空白、英文小写
+fn normalize_key(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+/// 按名称或别名查找实体
+pub(crate) fn resolve(conn: &Connection, name: &str) -> rusqlite::Result<Option<i64>> {
+    conn.query_row("SELECT entity_id FROM entity_aliases WHERE alias_key = ?1", [normalize_key(name)], |row| row.get(0))
+        .optional()
+}
+
+/// 按名称或别名查找实体，不存在时以 name 为名称新建
+fn resolve_or_create(conn: &Connection, name: &str, now: &str) -> rusqlite::Result<i64> {
+    if let Some(id) = resolve(conn, name)? {
+        return Ok(id);
+    }
+    let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+    conn.execute("INSERT INTO entities (name, created_at) VALUES (?1, ?2)", params![name, now])?;
+    let id = conn.last_insert_rowid();
+    conn.execute("INSERT INTO entity_aliases (alias_key, alias, entity_id) VALUES (?1, ?2, ?3)", params![normalize_key(&name), name, id])?;
+    Ok(id)
+}
+
+/// 用 NER 的结果覆盖某条记忆的实体关联
+pub(crate) fn link(conn: &Connection, fact_id: i64, mentions: &[String], now: &str) -> rusqlite::Result<()> {
+    unlink(conn, fact_id)?;
+    for mention in mentions.iter().filter(|m| !normalize_key(m).is_empty()) {
+        let entity_id = resolve_or_create(conn, mention, now)?;
+        conn.execute(
+            "INSERT OR IGNORE INTO fact_entities (fact_id, entity_id, mention) VALUES (?1, ?2, ?3)",
+            params![fact_id, entity_id, mention],
+        )?;
+    }
+    Ok(())
+}
+
+/// 移除某条记忆的全部实体关联
+pub(crate) fn unlink(conn: &Connection, fact_id: i64) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM fact_entities WHERE fact_id = ?1", [fact_id])?;
+    Ok(())
+}
+
+fn summary(conn: &Connection, entity_id: i64) -> rusqlite::Result<Option<EntitySummary>> {
+    let Some(name) = conn
+        .query_row("SELECT name FROM entities WHERE id = ?1", [entity_id], |row| row.get::<_, String>(0))
+        .optional()? else { return Ok(None) };
+    let mut stmt = conn.prepare("SELECT alias FROM entity_aliases WHERE entity_id = ?1 AND alias_key != ?2 ORDER BY alias")?;
+    let aliases = stmt.query_map(params![entity_id, normalize_key(&name)], |row| row.get(0))?.collect::<Result<Vec<String>, _>>()?;
+    let memo_count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM fact_entities fe JOIN facts f ON f.id = fe.fact_id WHERE fe.entity_id = ?1 AND f.superseded_by IS NULL",
+        [entity_id],
+        |row| row.get(0),
+    )?;
+    Ok(Some(EntitySummary { id: entity_id, name, aliases, memo_count: memo_count as usize }))
+}
+
+/// 关联了记忆的实体，按关联记忆数降序
+pub(crate) fn list(conn: &Connection, limit: usize) -> rusqlite::Result<Vec<EntitySummary>> {
+    let ids: Vec<i64> = {
+        let mut stmt = conn.prepare(
+            "SELECT fe.entity_id FROM fact_entities fe JOIN facts f ON f.id = fe.fact_id
+             WHERE f.superseded_by IS NULL
+             GROUP BY fe.entity_id ORDER BY COUNT(*) DESC, fe.entity_id LIMIT ?1",
+        )?;
+        let ids = stmt.query_map([limit.min(i64::MAX as usize) as i64], |row| row.get(0))?.collect::<Result<_, _>>()?;
+        ids
+    };
+    ids.into_iter().filter_map(|id| summary(conn, id).transpose()).collect()
+}
+
+/// 与某实体共现最多的实体（只统计 now 时仍有效的记忆）
+pub(crate) fn neighbours(conn: &Connection, entity_id: i64, now: DateTime<Utc>, limit: usize) -> rusqlite::Result<Vec<EntityNeighbour>> {
+    let mut stmt = conn.prepare(
+        "SELECT e.id, e.name, COUNT(*) AS shared
+         FROM fact_entities a
+         JOIN fact_entities b ON b.fact_id = a.fact_id AND b.entity_id != a.entity_id
+         JOIN entities e ON e.id = b.entity_id
+         JOIN facts f ON f.id = a.fact_id
+         WHERE a.entity_id = ?1 AND f.superseded_by IS NULL AND (f.expires_at IS NULL OR f.expires_at > ?3)
+         GROUP BY e.id ORDER BY shared DESC, e.id LIMIT ?2",
+    )?;
+    let rows = stmt.query_map(params![entity_id, limit.min(i64::MAX as usize) as i64, expiry::to_db_timestamp(now)], |row| {
+        Ok(EntityNeighbour { id: row.get(0)?, name: row.get(1)?, shared: row.get::<_, i64>(2)? as usize })
+    })?;
+    rows.collect()
+}
+
+/// 实体详情（记忆与邻居只取 now 时仍有效的）；名称或别名不存在时返回 None
+pub(crate) fn detail(conn: &Connection, name: &str, now: DateTime<Utc>, memo_limit: usize, neighbour_limit: usize) -> rusqlite::Result<Option<EntityDetail>> {
+    let Some(entity_id) = resolve(conn, name)? else { return Ok(None) };
+    let Some(entity) = summary(conn, entity_id)? else { return Ok(None) };
+    let memos = {
+        let mut stmt = conn.prepare(
+            "SELECT f.id, f.content, f.created_at FROM fact_entities fe JOIN facts f ON f.id = fe.fact_id
+             WHERE fe.entity_id = ?1 AND f.superseded_by IS NULL AND (f.expires_at IS NULL OR f.expires_at > ?3)
+             ORDER BY datetime(f.created_at) DESC, f.id DESC LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![entity_id, memo_limit.min(i64::MAX as usize) as i64, expiry::to_db_timestamp(now)], |row| {
+            Ok(LinkedMemo { id: row.get(0)?, content: row.get(1)?, created_at: row.get(2)? })
+        })?.collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+    let neighbours = neighbours(conn, entity_id, now, neighbour_limit)?;
+    Ok(Some(EntityDetail { entity, memos, neighbours }))
+}
+
+/// 把 source 合并进 into：关联与别名全部转移，source 本身删除
+fn merge_into(conn: &Connection, into: i64, source: i64) -> rusqlite::Result<()> {
+    if into == source {
+        return Ok(());
+    }
+    conn.execute("UPDATE OR IGNORE fact_entities SET entity_id = ?1 WHERE entity_id = ?2", params![into, source])?;
+    conn.execute("DELETE FROM fact_entities WHERE entity_id = ?1", [source])?;
+    conn.execute("UPDATE entity_aliases SET entity_id = ?1 WHERE entity_id = ?2", params![into, source])?;
+    conn.execute("DELETE FROM entities WHERE id = ?1", [source])?;
+    Ok(())
+}
+
+/// 为实体 name 添加别名；别名已是另一个实体的名称或别名时，两个实体合并为 name
+pub(crate) fn add_alias(conn: &Connection, name: &str, alias: &str) -> Result<EntitySummary, anyhow::Error> {
+    let entity_id = resolve(conn, name)?.ok_or_else(|| anyhow::anyhow!("Entity '{}' not found", name))?;
+    let alias = alias.split_whitespace().collect::<Vec<_>>().join(" ");
+    if alias.is_empty() {
+        return Err(anyhow::anyhow!("Alias must not be empty"));
+    }
+    match resolve(conn, &alias)? {
+        Some(existing) => merge_into(conn, entity_id, existing)?,
+        None => {
+            conn.execute(
+                "INSERT INTO entity_aliases (alias_key, alias, entity_id) VALUES (?1, ?2, ?3)",
+                params![normalize_key(&alias), alias, entity_id],
+            )?;
+        }
+    }
+    summary(conn, entity_id)?.ok_or_else(|| anyhow::anyhow!("Entity '{}' not found", name))
+}
+
+/// 把 sources 中的实体全部合并进 into，它们的名称成为 into 的别名
+pub(crate) fn merge(conn: &Connection, into: &str, sources: &[String]) -> Result<EntitySummary, anyhow::Error> {
+    let into_id = resolve(conn, into)?.ok_or_else(|| anyhow::anyhow!("Entity '{}' not found", into))?;
+    for source in sources {
+        let source_id = resolve(conn, source)?.ok_or_else(|| anyhow::anyhow!("Entity '{}' not found", source))?;
+        merge_into(conn, into_id, source_id)?;
+    }
+    summary(conn, into_id)?.ok_or_else(|| anyhow::anyhow!("Entity '{}' not found", into))
+}
+
+/// 由查询实体扩展：已知实体本身（权重 1），加上每个实体共现最多的 neighbour_limit 个邻居（按共现次数折算权重）
+pub(crate) fn expand(conn: &Connection, mentions: &[String], now: DateTime<Utc>, neighbour_limit: usize) -> rusqlite::Result<Vec<ExpandedEntity>> {
+    let mut expanded: Vec<ExpandedEntity> = Vec::new();
+    let mut seeds: Vec<(i64, String)> = Vec::new();
+    for mention in mentions {
+        let Some(id) = resolve(conn, mention)? else { continue };
+        if expanded.iter().any(|e| e.id == id) {
+            continue;
+        }
+        let name: String = conn.query_row("SELECT name FROM entities WHERE id = ?1", [id], |row| row.get(0))?;
+        expanded.push(ExpandedEntity { id, name: name.clone(), weight: 1.0, via: None });
+        seeds.push((id, name));
+    }
+    for (seed, seed_name) in seeds {
+        let neighbours = neighbours(conn, seed, now, neighbour_limit)?;
+        let max_shared = neighbours.iter().map(|n| n.shared).max().unwrap_or(1).max(1) as f32;
+        for neighbour in neighbours {
+            let weight = NEIGHBOUR_WEIGHT * neighbour.shared as f32 / max_shared;
+            match expanded.iter_mut().find(|e| e.id == neighbour.id) {
+                Some(existing) => {
+                    if existing.via.is_some() && existing.weight < weight {
+                        existing.weight = weight;
+                        existing.via = Some(seed_name.clone());
+                    }
+                }
+                None => expanded.push(ExpandedEntity { id: neighbour.id, name: neighbour.name, weight, via: Some(seed_name.clone()) }),
+            }
+        }
+    }
+    Ok(expanded)
+}
+
+/// 实体扩展一路：关联到扩展实体的记忆，分数为所关联实体的权重之和
+pub(crate) fn search(
+    conn: &Connection,
+    expansion: &[ExpandedEntity],
+    tier: MemoryTier,
+    time_range: Option<&TimeRange>,
+    tag_filter: &[String],
+    now: DateTime<Utc>,
+    limit: usize,
+) -> Result<Vec<ScoredMemo>, anyhow::Error> {
+    if expansion.is_empty() {
+        return Ok(Vec::new());
+    }
+    let weights: HashMap<i64, f32> = expansion.iter().map(|e| (e.id, e.weight)).collect();
+    let tier_clause = match tier {
+        MemoryTier::Active => "f.tier != 'archive'",
+        MemoryTier::Archive => "f.tier = 'archive'",
+    };
+    // 实体 ID 来自数据库，直接拼进 IN 列表
+    let sql = format!(
+        "SELECT f.id, fe.entity_id, f.content, f.tier, f.created_at, f.updated_at, f.expires_at
+         FROM fact_entities fe JOIN facts f ON f.id = fe.fact_id
+         WHERE fe.entity_id IN ({}) AND {} AND f.superseded_by IS NULL AND {} AND {}",
+        weights.keys().map(i64::to_string).collect::<Vec<_>>().join(", "),
+        tier_clause,
+        time_range.map_or_else(|| "1".to_string(), |r| r.sql_predicate("f.created_at")),
+        tags::sql_predicate("f.metadata", tag_filter),
+    );
+    let mut points: HashMap<i64, ScoredMemo> = HashMap::new();
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let fact_id: i64 = row.get(0)?;
+        let weight = weights.get(&row.get::<_, i64>(1)?).copied().unwrap_or(0.0);
+        if let Some(point) = points.get_mut(&fact_id) {
+            point.score += weight;
+            continue;
+        }
+        let mut payload = Payload::new();
+        payload.insert(fields::CONTENT.to_string(), Value::from(row.get::<_, String>(2)?));
+        payload.insert(fields::TIER.to_string(), Value::from(row.get::<_, String>(3)?));
+        for (idx, field) in [(4, fields::CREATED_AT), (5, fields::UPDATED_AT), (6, fields::EXPIRES_AT)] {
+            if let Some(value) = row.get::<_, Option<String>>(idx)? {
+                payload.insert(field.to_string(), Value::from(value));
+            }
+        }
+        points.insert(fact_id, ScoredMemo { id: fact_id, score: weight, payload });
+    }
+
+    let mut points: Vec<ScoredMemo> = points.into_values().filter(|p| !expiry::is_expired(&p.payload, now)).collect();
+    points.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal).then(b.id.cmp(&a.id)));
+    points.truncate(limit);
+    Ok(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{migrated_pool, TestDir};
+    use chrono::TimeZone;
+
+    const NOW: &str = "2025-03-10T09:00:00+00:00";
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 3, 10, 9, 0, 0).unwrap()
+    }
+
+    /// 写入一条记忆并关联 mentions 中的实体
+    fn memo(conn: &Connection, content: &str, mentions: &[&str], expires_at: Option<&str>) -> i64 {
+        conn.execute(
+            "INSERT INTO facts (content, expires_at, created_at, updated_at) VALUES (?1, ?2, ?3, ?3)",
+            params![content, expires_at, NOW],
+        ).unwrap();
+        let id = conn.last_insert_rowid();
+        link(conn, id, &mentions.iter().map(|m| m.to_string()).collect::<Vec<_>>(), NOW).unwrap();
+        id
+    }
+
+    fn linked(conn: &Connection, fact_id: i64) -> Vec<String> {
+        let mut stmt = conn.prepare(
+            "SELECT e.name FROM fact_entities fe JOIN entities e ON e.id = fe.entity_id WHERE fe.fact_id = ?1 ORDER BY e.name",
+        ).unwrap();
+        let names = stmt.query_map([fact_id], |row| row.get(0)).unwrap().collect::<Result<Vec<String>, _>>().unwrap();
+        names
+    }
+
+    #[test]
+    fn link_resolves_spellings_and_replaces_previous_links() {
+        let dir = TestDir::new();
+        let pool = migrated_pool(&dir);
+        let conn = pool.get().unwrap();
+        let id = memo(&conn, "Titan 项目和小李开会", &["Titan", " titan ", "小李", " "], None);
+        assert_eq!(linked(&conn, id), vec!["Titan", "小李"]);
+        assert_eq!(resolve(&conn, "TITAN").unwrap(), resolve(&conn, "Titan").unwrap());
+
+        link(&conn, id, &["小王".to_string()], NOW).unwrap();
+        assert_eq!(linked(&conn, id), vec!["小王"]);
+        unlink(&conn, id).unwrap();
+        assert!(linked(&conn, id).is_empty());
+    }
+
+    #[test]
+    fn add_alias_registers_a_new_spelling_or_merges_the_owner() {
+        let dir = TestDir::new();
+        let pool = migrated_pool(&dir);
+        let conn = pool.get().unwrap();
+        // 第一条记忆同时提到两种写法，合并时会撞上 (fact_id, entity_id) 唯一约束
+        let both = memo(&conn, "Titan（泰坦项目）启动", &["Titan", "泰坦项目"], None);
+        let chinese = memo(&conn, "泰坦项目延期", &["泰坦项目"], None);
+        let titan = resolve(&conn, "Titan").unwrap().unwrap();
+        let source = resolve(&conn, "泰坦项目").unwrap().unwrap();
+
+        let summary = add_alias(&conn, "Titan", "T  项目").unwrap();
+        assert_eq!(summary.aliases, vec!["T 项目"]);
+        assert_eq!(resolve(&conn, "t 项目").unwrap(), Some(titan));
+
+        let summary = add_alias(&conn, "titan", "泰坦项目").unwrap();
+        assert_eq!((summary.id, summary.memo_count), (titan, 2));
+        assert_eq!(summary.aliases, vec!["T 项目", "泰坦项目"]);
+        assert_eq!(resolve(&conn, "泰坦项目").unwrap(), Some(titan));
+        assert_eq!(linked(&conn, both), vec!["Titan"]);
+        assert_eq!(linked(&conn, chinese), vec!["Titan"]);
+        let remaining: i64 = conn.query_row("SELECT COUNT(*) FROM entities WHERE id = ?1", [source], |row| row.get(0)).unwrap();
+        assert_eq!(remaining, 0);
+
+        assert!(add_alias(&conn, "Titan", "  ").is_err());
+        assert!(add_alias(&conn, "不存在", "别名").is_err());
+    }
+
+    #[test]
+    fn merge_moves_every_source_into_the_target() {
+        let dir = TestDir::new();
+        let pool = migrated_pool(&dir);
+        let conn = pool.get().unwrap();
+        memo(&conn, "小李和李工吃火锅", &["小李", "李工"], None);
+        memo(&conn, "李明周五出差", &["李明"], None);
+
+        let summary = merge(&conn, "小李", &["李工".to_string(), "李明".to_string()]).unwrap();
+        assert_eq!(summary.aliases, vec!["李工", "李明"]);
+        assert_eq!(summary.memo_count, 2);
+        assert_eq!(list(&conn, 10).unwrap().iter().map(|e| e.name.as_str()).collect::<Vec<_>>(), vec!["小李"]);
+        assert!(merge(&conn, "小李", &["不存在".to_string()]).is_err());
+    }
+
+    #[test]
+    fn expand_weights_neighbours_by_shared_memos_and_skips_stale_ones() {
+        let dir = TestDir::new();
+        let pool = migrated_pool(&dir);
+        let conn = pool.get().unwrap();
+        memo(&conn, "小李爱吃火锅", &["小李", "火锅"], None);
+        memo(&conn, "周五和小李吃火锅", &["小李", "火锅"], None);
+        memo(&conn, "小李和小王同组", &["小李", "小王"], None);
+        // 已过期（尚未清理）与已被取代的记忆不计入共现
+        memo(&conn, "今天小李的车停在B2", &["小李", "B2"], Some("2025-03-10T08:00:00Z"));
+        let superseded = memo(&conn, "小李在B2开会", &["小李", "B2"], None);
+        conn.execute("UPDATE facts SET superseded_by = 1 WHERE id = ?1", [superseded]).unwrap();
+
+        let expanded = expand(&conn, &["小李".to_string(), "不认识".to_string()], now(), 5).unwrap();
+        let weights: Vec<(&str, f32, Option<&str>)> =
+            expanded.iter().map(|e| (e.name.as_str(), e.weight, e.via.as_deref())).collect();
+        assert_eq!(weights, vec![("小李", 1.0, None), ("火锅", 0.5, Some("小李")), ("小王", 0.25, Some("小李"))]);
+        let neighbours = neighbours(&conn, expanded[0].id, now(), 1).unwrap();
+        assert_eq!(neighbours.iter().map(|n| (n.name.as_str(), n.shared)).collect::<Vec<_>>(), vec![("火锅", 2)]);
+
+        // 查询中直接提到的实体保持权重 1，不会被当作邻居降权
+        let expanded = expand(&conn, &["小李".to_string(), "火锅".to_string()], now(), 5).unwrap();
+        assert_eq!(expanded.iter().filter(|e| e.via.is_none()).count(), 2);
+        assert!(expanded.iter().filter(|e| e.via.is_none()).all(|e| e.weight == 1.0));
+    }
+
+    #[test]
+    fn search_sums_entity_weights_and_applies_filters() {
+        let dir = TestDir::new();
+        let pool = migrated_pool(&dir);
+        let conn = pool.get().unwrap();
+        let hotpot = memo(&conn, "周五和小李吃火锅", &["小李", "火锅"], None);
+        let colleague = memo(&conn, "小李和小王同组", &["小李", "小王"], None);
+        let archived = memo(&conn, "去年小李请吃火锅", &["小李", "火锅"], None);
+        conn.execute("UPDATE facts SET tier = 'archive' WHERE id = ?1", [archived]).unwrap();
+        memo(&conn, "今天小李的车停在B2", &["小李"], Some("2025-03-10T08:00:00Z"));
+
+        let expansion = expand(&conn, &["小李".to_string()], now(), 5).unwrap();
+        let active = search(&conn, &expansion, MemoryTier::Active, None, &[], now(), 10).unwrap();
+        let scores: Vec<(i64, f32)> = active.iter().map(|p| (p.id, p.score)).collect();
+        // 小李 1 + 火锅 0.5；小李 1 + 小王 0.25
+        assert_eq!(scores, vec![(hotpot, 1.5), (colleague, 1.25)]);
+        assert_eq!(active[0].payload.get(fields::CONTENT), Some(&Value::from("周五和小李吃火锅")));
+
+        let archive = search(&conn, &expansion, MemoryTier::Archive, None, &[], now(), 10).unwrap();
+        assert_eq!(archive.iter().map(|p| p.id).collect::<Vec<_>>(), vec![archived]);
+        assert!(search(&conn, &expansion, MemoryTier::Active, None, &["工作".to_string()], now(), 10).unwrap().is_empty());
+        assert_eq!(search(&conn, &expansion, MemoryTier::Active, None, &[], now(), 1).unwrap().len(), 1);
+        assert!(search(&conn, &[], MemoryTier::Active, None, &[], now(), 10).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,295 @@
+// agent_memos/src/expiry.rs
+
+// 限时记忆：facts.expires_at 记录一条记忆的失效时间（UTC，RFC3339）。
+// - 保存时可显式给出，也可根据“今天”“这周”等措辞推断；
+// - recall 会丢弃已过期的结果，Indexer 也不会再为过期的行建立向量；
+// - 后台 sweeper 周期性地把新过期的记忆从向量库移除，并按策略删除或保留（归档）SQLite 中的行。
+// 所有“现在”都来自可注入的 Clock，便于测试。
+
+use crate::db::{self, DbPool};
+use crate::history::{self, RevisionOp, RevisionSource};
+use crate::keyword_index;
+use crate::outbox::{self, OutboxOp};
+use crate::vector_store::{fields, Payload};
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, NaiveDate, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const META_LAST_EXPIRY_SWEEP: &str = "last_expiry_sweep";
+
+/// 时间来源
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// 系统时钟
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// 手动拨动的时钟，供测试和回放使用
+pub struct ManualClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl ManualClock {
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self { now: Mutex::new(now) }
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    pub fn advance(&self, by: ChronoDuration) {
+        *self.now.lock().unwrap() += by;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+}
+
+/// 统一的时间戳格式（秒级、以 Z 结尾），保证 SQLite 中按字符串比较与按时间比较一致
+pub(crate) fn to_db_timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// 解析 facts 表中的时间：新数据是 RFC3339，老数据是 SQLite CURRENT_TIMESTAMP 的 "YYYY-MM-DD HH:MM:SS"（UTC）
+pub(crate) fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(t) = DateTime::parse_from_rfc3339(value) {
+        return Some(t.with_timezone(&Utc));
+    }
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").ok().map(|t| t.and_utc())
+}
+
+/// 某条记忆（按 payload 中的 expires_at 判断）在 now 时刻是否已过期
+pub(crate) fn is_expired(payload: &Payload, now: DateTime<Utc>) -> bool {
+    payload.get(fields::EXPIRES_AT)
+        .and_then(|v| v.as_str())
+        .and_then(parse_timestamp)
+        .is_some_and(|t| t <= now)
+}
+
+/// 根据措辞推断失效时间，时间范围以 now 所在的时区计算。
+/// 同时出现多个时间词时取最晚的一个，宁可多留也不误删。
+pub fn infer_expiry<Tz: TimeZone>(text: &str, now: &DateTime<Tz>) -> Option<DateTime<Utc>> {
+    let today = now.date_naive();
+    let end_of_week = today + ChronoDuration::days(6 - today.weekday().num_days_from_monday() as i64);
+    let end_of_month = {
+        let (y, m) = if today.month() == 12 { (today.year() + 1, 1) } else { (today.year(), today.month() + 1) };
+        NaiveDate::from_ymd_opt(y, m, 1)? - ChronoDuration::days(1)
+    };
+
+    let rules: [(&[&str], NaiveDate); 5] = [
+        (&["今天", "今日", "今晚", "今早", "今夜"], today),
+        (&["明天", "明早", "明晚"], today + ChronoDuration::days(1)),
+        (&["后天"], today + ChronoDuration::days(2)),
+        (&["这周", "本周", "这星期", "这个星期", "这礼拜"], end_of_week),
+        (&["这个月", "本月"], end_of_month),
+    ];
+    let last_day = rules.iter()
+        .filter(|(words, _)| words.iter().any(|w| text.contains(w)))
+        .map(|(_, day)| *day)
+        .max()?;
+
+    // 失效时间取那一天结束（次日零点）
+    let midnight = (last_day + ChronoDuration::days(1)).and_hms_opt(0, 0, 0)?;
+    now.timezone().from_local_datetime(&midnight).earliest().map(|t| t.with_timezone(&Utc))
+}
+
+/// 过期记忆的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpiryPolicy {
+    /// 从 SQLite 与向量库中彻底删除
+    Delete,
+    /// 只移出向量索引，SQLite 中的行保留备查
+    Archive,
+}
+
+impl ExpiryPolicy {
+    /// 通过环境变量 MEMOS_EXPIRY_POLICY=delete 切换为删除，默认归档（推断可能出错，归档不丢数据）
+    pub fn from_env() -> Self {
+        match std::env::var("MEMOS_EXPIRY_POLICY") {
+            Ok(v) if v.eq_ignore_ascii_case("delete") => ExpiryPolicy::Delete,
+            _ => ExpiryPolicy::Archive,
+        }
+    }
+}
+
+/// 一次清扫的结果
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct SweepReport {
+    /// 本次新发现的过期记忆 ID
+    pub expired: Vec<i64>,
+    /// 其中从 SQLite 删除的数量（归档策略下为 0）
+    pub deleted: usize,
+}
+
+/// 过期清扫器：只操作 SQLite，向量库的移除通过发件箱交给 Indexer
+pub(crate) struct ExpirySweeper {
+    pool: DbPool,
+    clock: Arc<dyn Clock>,
+    policy: ExpiryPolicy,
+}
+
+impl ExpirySweeper {
+    pub(crate) fn new(pool: DbPool, clock: Arc<dyn Clock>, policy: ExpiryPolicy) -> Self {
+        Self { pool, clock, policy }
+    }
+
+    /// 找出上次清扫之后新过期的记忆，按策略处理，并在同一事务中写入发件箱
+    pub(crate) fn sweep(&self) -> Result<SweepReport, anyhow::Error> {
+        let swept_at = self.clock.now();
+        let now = to_db_timestamp(swept_at);
+        let queued_at = swept_at.to_rfc3339();
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+
+        // 归档策略下行会保留，用水位线避免每次都重复处理同一批
+        let since = match self.policy {
+            ExpiryPolicy::Archive => db::get_meta(&tx, META_LAST_EXPIRY_SWEEP)?.unwrap_or_default(),
+            ExpiryPolicy::Delete => String::new(),
+        };
+        let expired: Vec<i64> = {
+            let mut stmt = tx.prepare(
+                "SELECT id FROM facts WHERE expires_at IS NOT NULL AND expires_at > ?1 AND expires_at <= ?2 ORDER BY id",
+            )?;
+            let ids = stmt.query_map([&since, &now], |row| row.get(0))?.collect::<Result<Vec<i64>, _>>()?;
+            ids
+        };
+
+        let mut deleted = 0;
+        for id in &expired {
+            if self.policy == ExpiryPolicy::Delete {
+                history::record(&tx, *id, RevisionOp::Delete, RevisionSource::System, Some("过期自动清理"), &now)?;
+                deleted += tx.execute("DELETE FROM facts WHERE id = ?1", [id])?;
+                keyword_index::sync(&tx, *id)?;
+            }
+            outbox::enqueue(&tx, *id, OutboxOp::Delete, &queued_at)?;
+        }
+        db::set_meta(&tx, META_LAST_EXPIRY_SWEEP, &now)?;
+        tx.commit()?;
+
+        if !expired.is_empty() {
+            println!("[MemosAgent-Expiry] {} memo(s) expired ({:?}): {:?}", expired.len(), self.policy, expired);
+        }
+        Ok(SweepReport { expired, deleted })
+    }
+}
+
+/// 启动后台 sweeper；清扫出的记忆立即尝试从向量库移除，失败的留给发件箱 worker
+pub(crate) fn spawn_sweeper(sweeper: Arc<ExpirySweeper>, indexer: Arc<outbox::Indexer>, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match sweeper.sweep() {
+                Ok(report) => {
+                    for id in report.expired {
+                        indexer.flush_fact(id).await;
+                    }
+                }
+                Err(e) => eprintln!("[MemosAgent-Expiry] Sweep failed: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{migrated_pool, TestDir};
+
+    fn start() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 3, 10, 9, 0, 0).unwrap()
+    }
+
+    /// 写入三条记忆：当天中午过期、次日中午过期、长期有效，返回它们的 ID
+    fn seed(pool: &DbPool) -> [i64; 3] {
+        let conn = pool.get().unwrap();
+        let mut ids = [0; 3];
+        let expiries = [Some(start() + ChronoDuration::hours(3)), Some(start() + ChronoDuration::hours(27)), None];
+        for (i, expires_at) in expiries.into_iter().enumerate() {
+            conn.execute(
+                "INSERT INTO facts (content, expires_at, created_at, updated_at) VALUES (?1, ?2, ?3, ?3)",
+                rusqlite::params![format!("记忆{}", i), expires_at.map(to_db_timestamp), start().to_rfc3339()],
+            ).unwrap();
+            ids[i] = conn.last_insert_rowid();
+        }
+        ids
+    }
+
+    fn row_exists(pool: &DbPool, id: i64) -> bool {
+        let conn = pool.get().unwrap();
+        conn.query_row("SELECT COUNT(*) FROM facts WHERE id = ?1", [id], |row| row.get::<_, i64>(0)).unwrap() > 0
+    }
+
+    fn outbox(pool: &DbPool) -> Vec<(i64, String, String)> {
+        let conn = pool.get().unwrap();
+        let mut stmt = conn.prepare("SELECT fact_id, op, next_attempt_at FROM index_outbox ORDER BY id").unwrap();
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        rows
+    }
+
+    #[test]
+    fn archive_policy_keeps_rows_and_advances_watermark() {
+        let dir = TestDir::new();
+        let pool = migrated_pool(&dir);
+        let [today, tomorrow, forever] = seed(&pool);
+        let clock = Arc::new(ManualClock::new(start()));
+        let sweeper = ExpirySweeper::new(pool.clone(), clock.clone(), ExpiryPolicy::Archive);
+
+        assert!(sweeper.sweep().unwrap().expired.is_empty());
+
+        clock.advance(ChronoDuration::hours(4));
+        let report = sweeper.sweep().unwrap();
+        assert_eq!(report.expired, vec![today]);
+        assert_eq!(report.deleted, 0);
+        assert!(row_exists(&pool, today));
+        // 发件箱中的待办以注入的时钟为准，立即到期
+        assert_eq!(outbox(&pool), vec![(today, "delete".to_string(), clock.now().to_rfc3339())]);
+
+        // 水位线之前已处理过的记忆不会重复出现
+        assert!(sweeper.sweep().unwrap().expired.is_empty());
+        clock.advance(ChronoDuration::days(1));
+        assert_eq!(sweeper.sweep().unwrap().expired, vec![tomorrow]);
+        assert!(sweeper.sweep().unwrap().expired.is_empty());
+        assert!(row_exists(&pool, today) && row_exists(&pool, tomorrow) && row_exists(&pool, forever));
+        assert_eq!(outbox(&pool).len(), 2);
+    }
+
+    #[test]
+    fn delete_policy_removes_rows_and_records_history() {
+        let dir = TestDir::new();
+        let pool = migrated_pool(&dir);
+        let [today, tomorrow, forever] = seed(&pool);
+        let clock = Arc::new(ManualClock::new(start() + ChronoDuration::days(2)));
+        let sweeper = ExpirySweeper::new(pool.clone(), clock.clone(), ExpiryPolicy::Delete);
+
+        let report = sweeper.sweep().unwrap();
+        assert_eq!(report.expired, vec![today, tomorrow]);
+        assert_eq!(report.deleted, 2);
+        assert!(!row_exists(&pool, today) && !row_exists(&pool, tomorrow));
+        assert!(row_exists(&pool, forever));
+        {
+            let conn = pool.get().unwrap();
+            let revisions = history::list(&conn, today).unwrap();
+            assert_eq!(revisions.last().map(|r| r.op), Some(RevisionOp::Delete));
+        }
+        let queued_at = clock.now().to_rfc3339();
+        assert_eq!(outbox(&pool), vec![
+            (today, "delete".to_string(), queued_at.clone()),
+            (tomorrow, "delete".to_string(), queued_at),
+        ]);
+
+        clock.advance(ChronoDuration::days(30));
+        assert_eq!(sweeper.sweep().unwrap().deleted, 0);
+    }
+}
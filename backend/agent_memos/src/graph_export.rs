@@ -0,0 +1,571 @@
+// agent_memos/src/graph_export.rs
+
+// 知识图谱导出：把记忆、实体（实体图中的规范名与别名）以及两类边写成外部工具能读的格式。
+// - 提及边：记忆 —— 它提到的实体；
+// - 共现边：实体 —— 实体，权重为导出范围内两者同时出现的记忆数。
+// 支持按标签、创建时间与实体筛选记忆，实体与边只保留与选中记忆相关的部分。
+// 格式：Graphviz DOT（无向图）、GraphML（yEd、Gephi 等）、JSON-LD（schema.org 词汇）。
+
+use crate::entity_graph;
+use crate::expiry;
+use crate::tags;
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
+use rusqlite::Connection;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+/// 导出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GraphFormat {
+    Dot,
+    GraphMl,
+    JsonLd,
+}
+
+impl GraphFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "dot" | "gv" | "graphviz" => Some(GraphFormat::Dot),
+            "graphml" => Some(GraphFormat::GraphMl),
+            "jsonld" | "json-ld" => Some(GraphFormat::JsonLd),
+            _ => None,
+        }
+    }
+
+    /// 常用的文件扩展名
+    pub fn extension(&self) -> &'static str {
+        match self {
+            GraphFormat::Dot => "dot",
+            GraphFormat::GraphMl => "graphml",
+            GraphFormat::JsonLd => "jsonld",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            GraphFormat::Dot => "text/vnd.graphviz; charset=utf-8",
+            GraphFormat::GraphMl => "application/graphml+xml; charset=utf-8",
+            GraphFormat::JsonLd => "application/ld+json",
+        }
+    }
+}
+
+/// 导出范围；各条件同时满足，全为空时导出全部未被取代的记忆
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct GraphFilter {
+    /// 只导出同时带有这些标签的记忆
+    pub tags: Vec<String>,
+    /// 创建时间下限（含）
+    pub since: Option<DateTime<Utc>>,
+    /// 创建时间上限（不含）
+    pub until: Option<DateTime<Utc>>,
+    /// 只导出提到该实体（名称或别名）的记忆
+    pub entity: Option<String>,
+}
+
+/// 解析时间边界：RFC3339、“YYYY-MM-DD HH:MM:SS”（UTC），或“YYYY-MM-DD”（本地时区零点）
+pub fn parse_time_bound(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    expiry::parse_timestamp(value).or_else(|| {
+        let midnight = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?.and_hms_opt(0, 0, 0)?;
+        Local.from_local_datetime(&midnight).earliest().map(|t| t.with_timezone(&Utc))
+    })
+}
+
+/// 图中的一条记忆
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct GraphMemo {
+    pub id: i64,
+    pub content: String,
+    pub created_at: Option<String>,
+    pub tags: Vec<String>,
+}
+
+/// 图中的一个实体
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct GraphEntity {
+    pub id: i64,
+    pub name: String,
+    pub aliases: Vec<String>,
+}
+
+/// 两个实体的共现边（source < target）
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CoOccurrence {
+    pub source: i64,
+    pub target: i64,
+    pub weight: usize,
+}
+
+/// 导出范围内的知识图谱
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct KnowledgeGraph {
+    pub memos: Vec<GraphMemo>,
+    pub entities: Vec<GraphEntity>,
+    /// 提及边 (记忆 ID, 实体 ID)
+    pub mentions: Vec<(i64, i64)>,
+    pub cooccurrences: Vec<CoOccurrence>,
+}
+
+/// 按 filter 从 SQLite 收集图谱；实体不存在时返回空图
+pub(crate) fn collect(conn: &Connection, filter: &GraphFilter) -> Result<KnowledgeGraph, anyhow::Error> {
+    let mut predicates = vec!["f.superseded_by IS NULL".to_string()];
+    if let Some(since) = filter.since {
+        predicates.push(format!("datetime(f.created_at) >= datetime('{}')", expiry::to_db_timestamp(since)));
+    }
+    if let Some(until) = filter.until {
+        predicates.push(format!("datetime(f.created_at) < datetime('{}')", expiry::to_db_timestamp(until)));
+    }
+    let tag_filter = tags::normalize_all(&filter.tags);
+    predicates.push(tags::sql_predicate("f.metadata", &tag_filter));
+    if let Some(entity) = &filter.entity {
+        let Some(entity_id) = entity_graph::resolve(conn, entity)? else { return Ok(KnowledgeGraph::default()) };
+        predicates.push(format!("EXISTS (SELECT 1 FROM fact_entities x WHERE x.fact_id = f.id AND x.entity_id = {})", entity_id));
+    }
+    let where_clause = predicates.join(" AND ");
+
+    let memos: Vec<GraphMemo> = {
+        let mut stmt = conn.prepare(&format!("SELECT f.id, f.content, f.created_at, f.metadata FROM facts f WHERE {} ORDER BY f.id", where_clause))?;
+        let rows = stmt.query_map([], |row| {
+            Ok(GraphMemo {
+                id: row.get(0)?,
+                content: row.get(1)?,
+                created_at: row.get(2)?,
+                tags: tags::from_metadata(row.get::<_, Option<String>>(3)?.as_deref()),
+            })
+        })?.collect::<Result<_, _>>()?;
+        rows
+    };
+    let mentions: Vec<(i64, i64)> = {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT fe.fact_id, fe.entity_id FROM fact_entities fe JOIN facts f ON f.id = fe.fact_id WHERE {} ORDER BY fe.fact_id, fe.entity_id",
+            where_clause
+        ))?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?.collect::<Result<_, _>>()?;
+        rows
+    };
+
+    // 共现只统计导出范围内的记忆
+    let mut by_fact: BTreeMap<i64, Vec<i64>> = BTreeMap::new();
+    for (fact_id, entity_id) in &mentions {
+        by_fact.entry(*fact_id).or_default().push(*entity_id);
+    }
+    let mut pairs: BTreeMap<(i64, i64), usize> = BTreeMap::new();
+    for entity_ids in by_fact.values() {
+        for (i, a) in entity_ids.iter().enumerate() {
+            for b in &entity_ids[i + 1..] {
+                *pairs.entry((*a.min(b), *a.max(b))).or_default() += 1;
+            }
+        }
+    }
+    let cooccurrences = pairs.into_iter().map(|((source, target), weight)| CoOccurrence { source, target, weight }).collect();
+
+    let mut entities: BTreeMap<i64, GraphEntity> = BTreeMap::new();
+    for (_, entity_id) in &mentions {
+        if entities.contains_key(entity_id) {
+            continue;
+        }
+        let (name, aliases): (String, Vec<String>) = {
+            let name: String = conn.query_row("SELECT name FROM entities WHERE id = ?1", [entity_id], |row| row.get(0))?;
+            let mut stmt = conn.prepare("SELECT alias FROM entity_aliases WHERE entity_id = ?1 AND alias != ?2 ORDER BY alias")?;
+            let aliases = stmt.query_map(rusqlite::params![entity_id, name], |row| row.get(0))?.collect::<Result<_, _>>()?;
+            (name, aliases)
+        };
+        entities.insert(*entity_id, GraphEntity { id: *entity_id, name, aliases });
+    }
+
+    Ok(KnowledgeGraph { memos, entities: entities.into_values().collect(), mentions, cooccurrences })
+}
+
+/// 按格式输出
+pub(crate) fn render(graph: &KnowledgeGraph, format: GraphFormat) -> String {
+    match format {
+        GraphFormat::Dot => to_dot(graph),
+        GraphFormat::GraphMl => to_graphml(graph),
+        GraphFormat::JsonLd => to_jsonld(graph).to_string(),
+    }
+}
+
+/// DOT 节点标签最多保留的字符数，完整内容放在 tooltip 中
+const DOT_LABEL_CHARS: usize = 24;
+
+fn dot_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push(' '),
+            // \r（\r\n 换行只保留 \n）与其他控制字符直接丢弃
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn to_dot(graph: &KnowledgeGraph) -> String {
+    let mut out = String::from("graph memos {\n  node [fontname=\"sans-serif\"];\n");
+    for memo in &graph.memos {
+        let mut label: String = memo.content.chars().take(DOT_LABEL_CHARS).collect();
+        if memo.content.chars().count() > DOT_LABEL_CHARS {
+            label.push('…');
+        }
+        let _ = writeln!(
+            out,
+            "  \"memo_{}\" [shape=box, label=\"#{} {}\", tooltip=\"{}\"];",
+            memo.id, memo.id, dot_escape(&label), dot_escape(&memo.content)
+        );
+    }
+    for entity in &graph.entities {
+        let _ = writeln!(out, "  \"entity_{}\" [shape=ellipse, style=filled, fillcolor=lightblue, label=\"{}\"];", entity.id, dot_escape(&entity.name));
+    }
+    for (memo_id, entity_id) in &graph.mentions {
+        let _ = writeln!(out, "  \"memo_{}\" -- \"entity_{}\";", memo_id, entity_id);
+    }
+    for edge in &graph.cooccurrences {
+        let _ = writeln!(
+            out,
+            "  \"entity_{}\" -- \"entity_{}\" [style=dashed, weight={}, penwidth={}, label=\"{}\"];",
+            edge.source, edge.target, edge.weight, edge.weight.min(8), edge.weight
+        );
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // XML 1.0 不允许的控制字符直接丢弃
+            c if (c as u32) < 0x20 && !matches!(c, '\t' | '\n' | '\r') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn to_graphml(graph: &KnowledgeGraph) -> String {
+    let mut out = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+        "  <key id=\"kind\" for=\"node\" attr.name=\"kind\" attr.type=\"string\"/>\n",
+        "  <key id=\"label\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>\n",
+        "  <key id=\"created_at\" for=\"node\" attr.name=\"created_at\" attr.type=\"string\"/>\n",
+        "  <key id=\"tags\" for=\"node\" attr.name=\"tags\" attr.type=\"string\"/>\n",
+        "  <key id=\"aliases\" for=\"node\" attr.name=\"aliases\" attr.type=\"string\"/>\n",
+        "  <key id=\"relation\" for=\"edge\" attr.name=\"relation\" attr.type=\"string\"/>\n",
+        "  <key id=\"weight\" for=\"edge\" attr.name=\"weight\" attr.type=\"int\"/>\n",
+        "  <graph id=\"memos\" edgedefault=\"undirected\">\n",
+    ));
+    for memo in &graph.memos {
+        let _ = writeln!(out, "    <node id=\"memo_{}\">", memo.id);
+        let _ = writeln!(out, "      <data key=\"kind\">memo</data>");
+        let _ = writeln!(out, "      <data key=\"label\">{}</data>", xml_escape(&memo.content));
+        if let Some(created_at) = &memo.created_at {
+            let _ = writeln!(out, "      <data key=\"created_at\">{}</data>", xml_escape(created_at));
+        }
+        if !memo.tags.is_empty() {
+            let _ = writeln!(out, "      <data key=\"tags\">{}</data>", xml_escape(&memo.tags.join(",")));
+        }
+        out.push_str("    </node>\n");
+    }
+    for entity in &graph.entities {
+        let _ = writeln!(out, "    <node id=\"entity_{}\">", entity.id);
+        let _ = writeln!(out, "      <data key=\"kind\">entity</data>");
+        let _ = writeln!(out, "      <data key=\"label\">{}</data>", xml_escape(&entity.name));
+        if !entity.aliases.is_empty() {
+            let _ = writeln!(out, "      <data key=\"aliases\">{}</data>", xml_escape(&entity.aliases.join(",")));
+        }
+        out.push_str("    </node>\n");
+    }
+    for (memo_id, entity_id) in &graph.mentions {
+        let _ = writeln!(
+            out,
+            "    <edge source=\"memo_{}\" target=\"entity_{}\"><data key=\"relation\">mentions</data></edge>",
+            memo_id, entity_id
+        );
+    }
+    for edge in &graph.cooccurrences {
+        let _ = writeln!(
+            out,
+            "    <edge source=\"entity_{}\" target=\"entity_{}\"><data key=\"relation\">co_occurs</data><data key=\"weight\">{}</data></edge>",
+            edge.source, edge.target, edge.weight
+        );
+    }
+    out.push_str("  </graph>\n</graphml>\n");
+    out
+}
+
+fn to_jsonld(graph: &KnowledgeGraph) -> Value {
+    let memo_iri = |id: i64| format!("urn:memos:memo:{}", id);
+    let entity_iri = |id: i64| format!("urn:memos:entity:{}", id);
+    let mut mentioned: HashMap<i64, Vec<String>> = HashMap::new();
+    for (memo_id, entity_id) in &graph.mentions {
+        mentioned.entry(*memo_id).or_default().push(entity_iri(*entity_id));
+    }
+
+    let mut nodes: Vec<Value> = Vec::new();
+    for memo in &graph.memos {
+        let mut node = json!({
+            "@id": memo_iri(memo.id),
+            "@type": "NoteDigitalDocument",
+            "identifier": memo.id,
+            "text": memo.content,
+            "mentions": mentioned.remove(&memo.id).unwrap_or_default(),
+        });
+        if let Some(created_at) = &memo.created_at {
+            node["dateCreated"] = json!(created_at);
+        }
+        if !memo.tags.is_empty() {
+            node["keywords"] = json!(memo.tags);
+        }
+        nodes.push(node);
+    }
+    for entity in &graph.entities {
+        let mut node = json!({ "@id": entity_iri(entity.id), "@type": "Thing", "identifier": entity.id, "name": entity.name });
+        if !entity.aliases.is_empty() {
+            node["alternateName"] = json!(entity.aliases);
+        }
+        nodes.push(node);
+    }
+    for edge in &graph.cooccurrences {
+        nodes.push(json!({
+            "@id": format!("urn:memos:cooccurrence:{}-{}", edge.source, edge.target),
+            "@type": "memos:CoOccurrence",
+            "memos:source": entity_iri(edge.source),
+            "memos:target": entity_iri(edge.target),
+            "memos:weight": edge.weight,
+        }));
+    }
+
+    json!({
+        "@context": {
+            "@vocab": "https://schema.org/",
+            "memos": "urn:memos:vocab:",
+            "mentions": { "@type": "@id" },
+            "memos:source": { "@type": "@id" },
+            "memos:target": { "@type": "@id" },
+        },
+        "@graph": nodes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{migrated_pool, TestDir};
+    use rusqlite::params;
+
+    /// 两条记忆、两个实体：第一条内容含引号、&、<>、换行与控制字符，第二条超过 DOT 标签长度
+    fn sample() -> KnowledgeGraph {
+        KnowledgeGraph {
+            memos: vec![
+                GraphMemo {
+                    id: 1,
+                    content: "和\"小李\"吃火锅 & <聊> Titan\r\n第二行\u{7}".to_string(),
+                    created_at: Some("2025-03-07T12:00:00+00:00".to_string()),
+                    tags: vec!["聚餐".to_string(), "r&d".to_string()],
+                },
+                GraphMemo { id: 2, content: "一二三四五六七八九十一二三四五六七八九十一二三四五".to_string(), created_at: None, tags: Vec::new() },
+            ],
+            entities: vec![
+                GraphEntity { id: 3, name: "小李".to_string(), aliases: vec!["李工".to_string(), "O'Brien".to_string()] },
+                GraphEntity { id: 4, name: "Titan \\ \"T\"".to_string(), aliases: Vec::new() },
+            ],
+            mentions: vec![(1, 3), (1, 4), (2, 3)],
+            cooccurrences: vec![CoOccurrence { source: 3, target: 4, weight: 9 }],
+        }
+    }
+
+    #[test]
+    fn escapes_cover_quotes_newlines_and_control_characters() {
+        assert_eq!(dot_escape("a\"b\\c\r\nd\te\u{1}"), "a\\\"b\\\\c\\nd e");
+        assert_eq!(xml_escape("<a href='x'>\"&\"</a>\r\n\t\u{1}\u{1f}"), "&lt;a href=&apos;x&apos;&gt;&quot;&amp;&quot;&lt;/a&gt;\r\n\t");
+    }
+
+    #[test]
+    fn dot_output_is_stable() {
+        let expected = concat!(
+            "graph memos {\n",
+            "  node [fontname=\"sans-serif\"];\n",
+            "  \"memo_1\" [shape=box, label=\"#1 和\\\"小李\\\"吃火锅 & <聊> Titan\\n第二…\", tooltip=\"和\\\"小李\\\"吃火锅 & <聊> Titan\\n第二行\"];\n",
+            "  \"memo_2\" [shape=box, label=\"#2 一二三四五六七八九十一二三四五六七八九十一二三四…\", tooltip=\"一二三四五六七八九十一二三四五六七八九十一二三四五\"];\n",
+            "  \"entity_3\" [shape=ellipse, style=filled, fillcolor=lightblue, label=\"小李\"];\n",
+            "  \"entity_4\" [shape=ellipse, style=filled, fillcolor=lightblue, label=\"Titan \\\\ \\\"T\\\"\"];\n",
+            "  \"memo_1\" -- \"entity_3\";\n",
+            "  \"memo_1\" -- \"entity_4\";\n",
+            "  \"memo_2\" -- \"entity_3\";\n",
+            "  \"entity_3\" -- \"entity_4\" [style=dashed, weight=9, penwidth=8, label=\"9\"];\n",
+            "}\n",
+        );
+        assert_eq!(render(&sample(), GraphFormat::Dot), expected);
+    }
+
+    #[test]
+    fn graphml_output_is_stable() {
+        let expected = concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+            "  <key id=\"kind\" for=\"node\" attr.name=\"kind\" attr.type=\"string\"/>\n",
+            "  <key id=\"label\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>\n",
+            "  <key id=\"created_at\" for=\"node\" attr.name=\"created_at\" attr.type=\"string\"/>\n",
+            "  <key id=\"tags\" for=\"node\" attr.name=\"tags\" attr.type=\"string\"/>\n",
+            "  <key id=\"aliases\" for=\"node\" attr.name=\"aliases\" attr.type=\"string\"/>\n",
+            "  <key id=\"relation\" for=\"edge\" attr.name=\"relation\" attr.type=\"string\"/>\n",
+            "  <key id=\"weight\" for=\"edge\" attr.name=\"weight\" attr.type=\"int\"/>\n",
+            "  <graph id=\"memos\" edgedefault=\"undirected\">\n",
+            "    <node id=\"memo_1\">\n",
+            "      <data key=\"kind\">memo</data>\n",
+            "      <data key=\"label\">和&quot;小李&quot;吃火锅 &amp; &lt;聊&gt; Titan\r\n第二行</data>\n",
+            "      <data key=\"created_at\">2025-03-07T12:00:00+00:00</data>\n",
+            "      <data key=\"tags\">聚餐,r&amp;d</data>\n",
+            "    </node>\n",
+            "    <node id=\"memo_2\">\n",
+            "      <data key=\"kind\">memo</data>\n",
+            "      <data key=\"label\">一二三四五六七八九十一二三四五六七八九十一二三四五</data>\n",
+            "    </node>\n",
+            "    <node id=\"entity_3\">\n",
+            "      <data key=\"kind\">entity</data>\n",
+            "      <data key=\"label\">小李</data>\n",
+            "      <data key=\"aliases\">李工,O&apos;Brien</data>\n",
+            "    </node>\n",
+            "    <node id=\"entity_4\">\n",
+            "      <data key=\"kind\">entity</data>\n",
+            "      <data key=\"label\">Titan \\ &quot;T&quot;</data>\n",
+            "    </node>\n",
+            "    <edge source=\"memo_1\" target=\"entity_3\"><data key=\"relation\">mentions</data></edge>\n",
+            "    <edge source=\"memo_1\" target=\"entity_4\"><data key=\"relation\">mentions</data></edge>\n",
+            "    <edge source=\"memo_2\" target=\"entity_3\"><data key=\"relation\">mentions</data></edge>\n",
+            "    <edge source=\"entity_3\" target=\"entity_4\"><data key=\"relation\">co_occurs</data><data key=\"weight\">9</data></edge>\n",
+            "  </graph>\n",
+            "</graphml>\n",
+        );
+        assert_eq!(render(&sample(), GraphFormat::GraphMl), expected);
+    }
+
+    #[test]
+    fn jsonld_output_is_stable() {
+        let expected = json!({
+            "@context": {
+                "@vocab": "https://schema.org/",
+                "memos": "urn:memos:vocab:",
+                "mentions": { "@type": "@id" },
+                "memos:source": { "@type": "@id" },
+                "memos:target": { "@type": "@id" },
+            },
+            "@graph": [
+                {
+                    "@id": "urn:memos:memo:1",
+                    "@type": "NoteDigitalDocument",
+                    "identifier": 1,
+                    "text": "和\"小李\"吃火锅 & <聊> Titan\r\n第二行\u{7}",
+                    "mentions": ["urn:memos:entity:3", "urn:memos:entity:4"],
+                    "dateCreated": "2025-03-07T12:00:00+00:00",
+                    "keywords": ["聚餐", "r&d"],
+                },
+                {
+                    "@id": "urn:memos:memo:2",
+                    "@type": "NoteDigitalDocument",
+                    "identifier": 2,
+                    "text": "一二三四五六七八九十一二三四五六七八九十一二三四五",
+                    "mentions": ["urn:memos:entity:3"],
+                },
+                { "@id": "urn:memos:entity:3", "@type": "Thing", "identifier": 3, "name": "小李", "alternateName": ["李工", "O'Brien"] },
+                { "@id": "urn:memos:entity:4", "@type": "Thing", "identifier": 4, "name": "Titan \\ \"T\"" },
+                {
+                    "@id": "urn:memos:cooccurrence:3-4",
+                    "@type": "memos:CoOccurrence",
+                    "memos:source": "urn:memos:entity:3",
+                    "memos:target": "urn:memos:entity:4",
+                    "memos:weight": 9,
+                },
+            ],
+        });
+        assert_eq!(to_jsonld(&sample()), expected);
+        let rendered: Value = serde_json::from_str(&render(&sample(), GraphFormat::JsonLd)).unwrap();
+        assert_eq!(rendered, expected);
+    }
+
+    /// 写入一条记忆并关联实体
+    fn memo(conn: &Connection, content: &str, created_at: &str, tags: &[&str], mentions: &[&str]) -> i64 {
+        let metadata = (!tags.is_empty()).then(|| json!({ "tags": tags }).to_string());
+        conn.execute(
+            "INSERT INTO facts (content, metadata, created_at, updated_at) VALUES (?1, ?2, ?3, ?3)",
+            params![content, metadata, created_at],
+        ).unwrap();
+        let id = conn.last_insert_rowid();
+        let mentions: Vec<String> = mentions.iter().map(|m| m.to_string()).collect();
+        entity_graph::link(conn, id, &mentions, created_at).unwrap();
+        id
+    }
+
+    fn memo_ids(graph: &KnowledgeGraph) -> Vec<i64> {
+        graph.memos.iter().map(|m| m.id).collect()
+    }
+
+    fn entity_names(graph: &KnowledgeGraph) -> Vec<&str> {
+        graph.entities.iter().map(|e| e.name.as_str()).collect()
+    }
+
+    #[test]
+    fn collect_applies_tag_time_and_entity_filters() {
+        let dir = TestDir::new();
+        let pool = migrated_pool(&dir);
+        let conn = pool.get().unwrap();
+        let dinner = memo(&conn, "周五和小李、小王吃火锅", "2025-03-07T12:00:00+00:00", &["聚餐"], &["小李", "小王", "火锅"]);
+        let meeting = memo(&conn, "小李和小王开 Titan 周会", "2025-02-20T09:00:00+00:00", &["工作"], &["小李", "小王", "Titan"]);
+        let old = memo(&conn, "去年的火锅局", "2024-03-10T12:00:00+00:00", &["聚餐"], &["火锅"]);
+        let superseded = memo(&conn, "火锅改到周六", "2025-03-08T12:00:00+00:00", &["聚餐"], &["火锅", "小李"]);
+        conn.execute("UPDATE facts SET superseded_by = ?1 WHERE id = ?2", params![dinner, superseded]).unwrap();
+        entity_graph::add_alias(&conn, "Titan", "泰坦项目").unwrap();
+
+        let all = collect(&conn, &GraphFilter::default()).unwrap();
+        assert_eq!(memo_ids(&all), vec![dinner, meeting, old]);
+        assert_eq!(entity_names(&all), vec!["小李", "小王", "火锅", "Titan"]);
+        assert_eq!(all.entities[3].aliases, vec!["泰坦项目"]);
+        // 小李、小王在两条记忆中共现；被取代的那条不计入
+        let li_wang = all.cooccurrences.iter().find(|c| c.source == all.entities[0].id && c.target == all.entities[1].id).unwrap();
+        assert_eq!(li_wang.weight, 2);
+        assert_eq!(all.cooccurrences.iter().map(|c| c.weight).sum::<usize>(), 2 + 1 + 1 + 1 + 1);
+
+        let tagged = collect(&conn, &GraphFilter { tags: vec!["#聚餐".to_string()], ..GraphFilter::default() }).unwrap();
+        assert_eq!(memo_ids(&tagged), vec![dinner, old]);
+
+        let march = GraphFilter {
+            since: parse_time_bound("2025-03-01T00:00:00Z"),
+            until: parse_time_bound("2025-04-01 00:00:00"),
+            ..GraphFilter::default()
+        };
+        let in_march = collect(&conn, &march).unwrap();
+        assert_eq!(memo_ids(&in_march), vec![dinner]);
+        assert_eq!(entity_names(&in_march), vec!["小李", "小王", "火锅"]);
+        assert_eq!(in_march.mentions.len(), 3);
+
+        let titan = collect(&conn, &GraphFilter { entity: Some("泰坦项目".to_string()), ..GraphFilter::default() }).unwrap();
+        assert_eq!(memo_ids(&titan), vec![meeting]);
+        assert_eq!(titan.cooccurrences.len(), 3);
+        assert!(titan.cooccurrences.iter().all(|c| c.source < c.target && c.weight == 1));
+
+        let unknown = collect(&conn, &GraphFilter { entity: Some("不认识".to_string()), ..GraphFilter::default() }).unwrap();
+        assert!(unknown.memos.is_empty() && unknown.entities.is_empty());
+    }
+
+    #[test]
+    fn time_bounds_accept_rfc3339_and_plain_dates() {
+        assert_eq!(parse_time_bound(" 2025-03-01T08:00:00+08:00 "), parse_time_bound("2025-03-01 00:00:00"));
+        let local_midnight = Local.with_ymd_and_hms(2025, 3, 1, 0, 0, 0).unwrap().with_timezone(&Utc);
+        assert_eq!(parse_time_bound("2025-03-01"), Some(local_midnight));
+        assert_eq!(parse_time_bound("上周"), None);
+    }
+}
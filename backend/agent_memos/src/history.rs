@@ -0,0 +1,392 @@
+// agent_memos/src/history.rs
+
+// 记忆修订历史：facts 表只保存当前状态，fact_revisions 保存每一次变更后的完整快照。
+// - 写操作在同一事务里调用 record()，删除则在删除前记录；
+// - restore() 把某个修订的快照写回 facts（行已删除时按原 ID 重新插入）；
+// - undo() 撤销最近一次尚未撤销的用户变更，撤销本身记为 revert，并回填 reverted_by，
+//   因此可以连续撤销、一步步往回退。撤销修改类操作时只写回该操作改动的列，
+//   之后由系统做的变更（取代、层级调整等）保持不变。
+
+use crate::tags;
+use rusqlite::{params, Connection, OptionalExtension};
+
+/// 修订对应的操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RevisionOp {
+    Create,
+    Update,
+    Delete,
+    Restore,
+    Revert,
+    Supersede,
+    /// 修改标签（含标签改名、合并）
+    Retag,
+}
+
+impl RevisionOp {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RevisionOp::Create => "create",
+            RevisionOp::Update => "update",
+            RevisionOp::Delete => "delete",
+            RevisionOp::Restore => "restore",
+            RevisionOp::Revert => "revert",
+            RevisionOp::Supersede => "supersede",
+            RevisionOp::Retag => "retag",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "create" => Some(RevisionOp::Create),
+            "update" => Some(RevisionOp::Update),
+            "delete" => Some(RevisionOp::Delete),
+            "restore" => Some(RevisionOp::Restore),
+            "revert" => Some(RevisionOp::Revert),
+            "supersede" => Some(RevisionOp::Supersede),
+            "retag" => Some(RevisionOp::Retag),
+            _ => None,
+        }
+    }
+}
+
+/// 变更的发起方：只有用户发起的变更才会被“撤销刚才的修改”撤销
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RevisionSource {
+    User,
+    System,
+}
+
+impl RevisionSource {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RevisionSource::User => "user",
+            RevisionSource::System => "system",
+        }
+    }
+}
+
+/// 一条修订记录
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct FactRevision {
+    pub id: i64,
+    pub fact_id: i64,
+    /// 该记忆内从 1 开始递增的修订号
+    pub revision: i64,
+    pub op: RevisionOp,
+    /// 本次变更后的内容（删除操作记录的是删除前的内容）
+    pub content: String,
+    /// 本次变更后的标签
+    pub tags: Vec<String>,
+    /// 触发这次变更的用户原话
+    pub request: Option<String>,
+    /// 已被哪条修订撤销
+    pub reverted_by: Option<i64>,
+    /// 此修订时记忆是否已被另一条记忆取代
+    pub superseded_by: Option<i64>,
+    pub created_at: Option<String>,
+}
+
+/// 一次撤销的结果
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct UndoOutcome {
+    pub fact_id: i64,
+    /// 被撤销的操作
+    pub undone: RevisionOp,
+    /// 撤销前的内容；撤销的是删除时为 None（记忆当时已不存在）
+    pub previous_content: Option<String>,
+    /// 撤销后的内容；撤销的是创建时为 None（记忆已被移除）
+    pub content: Option<String>,
+}
+
+/// 记录 fact_id 当前状态的快照；行不存在时不记录并返回 None
+pub(crate) fn record(
+    conn: &Connection,
+    fact_id: i64,
+    op: RevisionOp,
+    source: RevisionSource,
+    request: Option<&str>,
+    now: &str,
+) -> rusqlite::Result<Option<i64>> {
+    let revision: i64 = conn.query_row(
+        "SELECT COALESCE(MAX(revision), 0) + 1 FROM fact_revisions WHERE fact_id = ?1",
+        [fact_id],
+        |row| row.get(0),
+    )?;
+    let inserted = conn.execute(
+        "INSERT INTO fact_revisions (fact_id, revision, op, source, content, metadata, expires_at, tier, superseded_by, request, created_at)
+         SELECT id, ?2, ?3, ?4, content, metadata, expires_at, tier, superseded_by, ?5, ?6 FROM facts WHERE id = ?1",
+        params![fact_id, revision, op.as_str(), source.as_str(), request, now],
+    )?;
+    Ok((inserted > 0).then(|| conn.last_insert_rowid()))
+}
+
+/// 某条记忆的全部修订，按时间先后排列
+pub(crate) fn list(conn: &Connection, fact_id: i64) -> Result<Vec<FactRevision>, anyhow::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT id, fact_id, revision, op, content, request, reverted_by, superseded_by, created_at, metadata
+         FROM fact_revisions WHERE fact_id = ?1 ORDER BY revision",
+    )?;
+    let rows = stmt.query_map([fact_id], |row| {
+        let op: String = row.get(3)?;
+        Ok(FactRevision {
+            id: row.get(0)?,
+            fact_id: row.get(1)?,
+            revision: row.get(2)?,
+            op: RevisionOp::parse(&op).unwrap_or(RevisionOp::Update),
+            content: row.get(4)?,
+            request: row.get(5)?,
+            reverted_by: row.get(6)?,
+            superseded_by: row.get(7)?,
+            created_at: row.get(8)?,
+            tags: tags::from_metadata(row.get::<_, Option<String>>(9)?.as_deref()),
+        })
+    })?.collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+fn current_content(conn: &Connection, fact_id: i64) -> rusqlite::Result<Option<String>> {
+    conn.query_row("SELECT content FROM facts WHERE id = ?1", [fact_id], |row| row.get(0)).optional()
+}
+
+/// 把某个修订的快照写回 facts：行存在则覆盖，已删除则按原 ID 重新插入
+fn apply_snapshot(conn: &Connection, fact_id: i64, revision: i64, now: &str) -> Result<(), anyhow::Error> {
+    let exists = current_content(conn, fact_id)?.is_some();
+    let changed = if exists {
+        conn.execute(
+            "UPDATE facts SET (content, metadata, expires_at, tier, superseded_by, updated_at) =
+                (SELECT content, metadata, expires_at, tier, superseded_by, ?3 FROM fact_revisions WHERE fact_id = ?1 AND revision = ?2)
+             WHERE id = ?1 AND EXISTS (SELECT 1 FROM fact_revisions WHERE fact_id = ?1 AND revision = ?2)",
+            params![fact_id, revision, now],
+        )?
+    } else {
+        conn.execute(
+            "INSERT INTO facts (id, content, metadata, expires_at, tier, superseded_by, created_at, updated_at)
+             SELECT fact_id, content, metadata, expires_at, tier, superseded_by,
+                    (SELECT MIN(created_at) FROM fact_revisions WHERE fact_id = ?1), ?3
+             FROM fact_revisions WHERE fact_id = ?1 AND revision = ?2",
+            params![fact_id, revision, now],
+        )?
+    };
+    if changed == 0 {
+        return Err(anyhow::anyhow!("Revision {} of memo {} not found", revision, fact_id));
+    }
+    Ok(())
+}
+
+/// 撤销 op 时需要写回的列，即该操作会改动的列。
+/// 层级只由系统调整，从不随撤销回退。
+fn undone_columns(op: RevisionOp) -> &'static [&'static str] {
+    match op {
+        // 修改内容时可能同时追加标签（合并相似记忆）
+        RevisionOp::Update => &["content", "metadata"],
+        RevisionOp::Retag => &["metadata"],
+        RevisionOp::Supersede => &["superseded_by"],
+        _ => &["content", "metadata", "expires_at", "superseded_by"],
+    }
+}
+
+/// 把 columns 写回 previous 修订中的值，其余列保持当前状态
+fn revert_columns(conn: &Connection, fact_id: i64, previous: i64, columns: &[&str], now: &str) -> Result<(), anyhow::Error> {
+    let assignments = columns.iter().map(|c| format!("{c} = r.{c}")).collect::<Vec<_>>().join(", ");
+    let changed = conn.execute(
+        &format!(
+            "UPDATE facts SET {assignments}, updated_at = ?3 FROM fact_revisions r
+             WHERE facts.id = ?1 AND r.fact_id = ?1 AND r.revision = ?2"
+        ),
+        params![fact_id, previous, now],
+    )?;
+    if changed == 0 {
+        return Err(anyhow::anyhow!("Revision {} of memo {} not found", previous, fact_id));
+    }
+    Ok(())
+}
+
+/// 恢复到指定修订，并把恢复本身记为一条新修订
+pub(crate) fn restore(conn: &Connection, fact_id: i64, revision: i64, request: Option<&str>, now: &str) -> Result<(), anyhow::Error> {
+    apply_snapshot(conn, fact_id, revision, now)?;
+    record(conn, fact_id, RevisionOp::Restore, RevisionSource::User, request, now)?;
+    println!("[MemosAgent-History] Restored memo {} to revision {}.", fact_id, revision);
+    Ok(())
+}
+
+/// 撤销最近一次尚未撤销的用户变更；没有可撤销的变更时返回 None
+pub(crate) fn undo(conn: &Connection, request: Option<&str>, now: &str) -> Result<Option<UndoOutcome>, anyhow::Error> {
+    // 记忆已被（例如过期清理）移除时，针对它的创建与修改已无从撤销，跳过
+    let target: Option<(i64, i64, i64, String)> = conn.query_row(
+        "SELECT id, fact_id, revision, op FROM fact_revisions r
+         WHERE source = 'user' AND reverted_by IS NULL AND op != 'revert'
+           AND (op = 'delete' OR EXISTS (SELECT 1 FROM facts f WHERE f.id = r.fact_id))
+         ORDER BY id DESC LIMIT 1",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+    ).optional()?;
+    let Some((target_id, fact_id, revision, op)) = target else { return Ok(None) };
+    let op = RevisionOp::parse(&op).unwrap_or(RevisionOp::Update);
+    let previous_content = current_content(conn, fact_id)?;
+
+    let revert_id = match op {
+        // 撤销创建：先记下当前内容，再移除记忆
+        RevisionOp::Create => {
+            let revert_id = record(conn, fact_id, RevisionOp::Revert, RevisionSource::User, request, now)?;
+            conn.execute("DELETE FROM facts WHERE id = ?1", [fact_id])?;
+            revert_id
+        }
+        // 撤销删除：删除修订里保存的就是删除前的状态
+        RevisionOp::Delete => {
+            apply_snapshot(conn, fact_id, revision, now)?;
+            record(conn, fact_id, RevisionOp::Revert, RevisionSource::User, request, now)?
+        }
+        // 撤销修改、改标签、取代或恢复：只把这次操作改动的列写回上一个修订的值
+        _ => {
+            let previous: i64 = conn.query_row(
+                "SELECT MAX(revision) FROM fact_revisions WHERE fact_id = ?1 AND revision < ?2",
+                params![fact_id, revision],
+                |row| row.get::<_, Option<i64>>(0),
+            )?.ok_or_else(|| anyhow::anyhow!("Memo {} has no earlier revision to return to", fact_id))?;
+            revert_columns(conn, fact_id, previous, undone_columns(op), now)?;
+            record(conn, fact_id, RevisionOp::Revert, RevisionSource::User, request, now)?
+        }
+    };
+    conn.execute("UPDATE fact_revisions SET reverted_by = ?1 WHERE id = ?2", params![revert_id, target_id])?;
+    println!("[MemosAgent-History] Undid {:?} of memo {} (revision {}).", op, fact_id, revision);
+
+    Ok(Some(UndoOutcome {
+        fact_id,
+        undone: op,
+        previous_content,
+        content: current_content(conn, fact_id)?,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{embedded_agent, migrated_pool, TestDir};
+    use crate::ManualClock;
+    use chrono::{TimeZone, Utc};
+    use std::sync::Arc;
+
+    const NOW: &str = "2025-03-10T09:00:00+00:00";
+
+    fn create(conn: &Connection, content: &str) -> i64 {
+        conn.execute("INSERT INTO facts (content, created_at, updated_at) VALUES (?1, ?2, ?2)", params![content, NOW]).unwrap();
+        let id = conn.last_insert_rowid();
+        record(conn, id, RevisionOp::Create, RevisionSource::User, Some(content), NOW).unwrap();
+        id
+    }
+
+    fn update(conn: &Connection, id: i64, content: &str) {
+        conn.execute("UPDATE facts SET content = ?1 WHERE id = ?2", params![content, id]).unwrap();
+        record(conn, id, RevisionOp::Update, RevisionSource::User, None, NOW).unwrap();
+    }
+
+    fn delete(conn: &Connection, id: i64) {
+        record(conn, id, RevisionOp::Delete, RevisionSource::User, None, NOW).unwrap();
+        conn.execute("DELETE FROM facts WHERE id = ?1", [id]).unwrap();
+    }
+
+    fn ops(conn: &Connection, id: i64) -> Vec<(RevisionOp, Option<i64>)> {
+        list(conn, id).unwrap().into_iter().map(|r| (r.op, r.reverted_by)).collect()
+    }
+
+    #[test]
+    fn repeated_undo_walks_back_through_updates_and_create() {
+        let dir = TestDir::new();
+        let pool = migrated_pool(&dir);
+        let conn = pool.get().unwrap();
+        let id = create(&conn, "周五和小李吃火锅");
+        update(&conn, id, "周六和小李吃火锅");
+        update(&conn, id, "周六和小王吃火锅");
+
+        let outcome = undo(&conn, None, NOW).unwrap().unwrap();
+        assert_eq!(outcome.undone, RevisionOp::Update);
+        assert_eq!(outcome.previous_content.as_deref(), Some("周六和小王吃火锅"));
+        assert_eq!(outcome.content.as_deref(), Some("周六和小李吃火锅"));
+
+        let outcome = undo(&conn, None, NOW).unwrap().unwrap();
+        assert_eq!(outcome.content.as_deref(), Some("周五和小李吃火锅"));
+
+        let outcome = undo(&conn, None, NOW).unwrap().unwrap();
+        assert_eq!(outcome.undone, RevisionOp::Create);
+        assert_eq!(outcome.content, None);
+        assert!(current_content(&conn, id).unwrap().is_none());
+        assert!(undo(&conn, None, NOW).unwrap().is_none());
+    }
+
+    #[test]
+    fn undoing_a_delete_recreates_the_memo_under_its_id() {
+        let dir = TestDir::new();
+        let pool = migrated_pool(&dir);
+        let conn = pool.get().unwrap();
+        let id = create(&conn, "车停在B2-103");
+        delete(&conn, id);
+
+        let outcome = undo(&conn, None, NOW).unwrap().unwrap();
+        assert_eq!(outcome.undone, RevisionOp::Delete);
+        assert_eq!(outcome.previous_content, None);
+        assert_eq!(current_content(&conn, id).unwrap().as_deref(), Some("车停在B2-103"));
+        let revisions = list(&conn, id).unwrap();
+        assert_eq!(revisions.iter().map(|r| r.op).collect::<Vec<_>>(), vec![RevisionOp::Create, RevisionOp::Delete, RevisionOp::Revert]);
+        assert_eq!(revisions[1].reverted_by, Some(revisions[2].id));
+    }
+
+    #[test]
+    fn restore_writes_back_a_revision_and_can_be_undone() {
+        let dir = TestDir::new();
+        let pool = migrated_pool(&dir);
+        let conn = pool.get().unwrap();
+        let id = create(&conn, "周五和小李吃火锅");
+        update(&conn, id, "周六和小李吃火锅");
+
+        restore(&conn, id, 1, None, NOW).unwrap();
+        assert_eq!(current_content(&conn, id).unwrap().as_deref(), Some("周五和小李吃火锅"));
+        assert!(restore(&conn, id, 9, None, NOW).is_err());
+
+        let outcome = undo(&conn, None, NOW).unwrap().unwrap();
+        assert_eq!(outcome.undone, RevisionOp::Restore);
+        assert_eq!(outcome.content.as_deref(), Some("周六和小李吃火锅"));
+        assert_eq!(ops(&conn, id).last(), Some(&(RevisionOp::Revert, None)));
+    }
+
+    #[test]
+    fn undo_keeps_changes_made_by_later_system_revisions() {
+        let dir = TestDir::new();
+        let pool = migrated_pool(&dir);
+        let conn = pool.get().unwrap();
+        let id = create(&conn, "周五和小李吃火锅");
+        let successor = create(&conn, "周五的火锅取消了");
+        update(&conn, id, "周六和小李吃火锅");
+        // 修改之后，系统把这条记忆移入归档层并标记为已被取代
+        conn.execute("UPDATE facts SET tier = 'archive', superseded_by = ?1 WHERE id = ?2", params![successor, id]).unwrap();
+        record(&conn, id, RevisionOp::Supersede, RevisionSource::System, None, NOW).unwrap();
+
+        let outcome = undo(&conn, None, NOW).unwrap().unwrap();
+        assert_eq!((outcome.fact_id, outcome.undone), (id, RevisionOp::Update));
+        let (content, tier, superseded_by): (String, String, Option<i64>) = conn.query_row(
+            "SELECT content, tier, superseded_by FROM facts WHERE id = ?1",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        ).unwrap();
+        assert_eq!(content, "周五和小李吃火锅");
+        assert_eq!(tier, "archive");
+        assert_eq!(superseded_by, Some(successor));
+        // 系统修订本身不会被撤销，下一次撤销轮到后创建的那条记忆
+        assert_eq!(undo(&conn, None, NOW).unwrap().map(|o| (o.fact_id, o.undone)), Some((successor, RevisionOp::Create)));
+    }
+
+    #[tokio::test]
+    async fn deleting_a_missing_memo_is_an_error() {
+        let dir = TestDir::new();
+        let clock = Arc::new(ManualClock::new(Utc.with_ymd_and_hms(2025, 3, 10, 9, 0, 0).unwrap()));
+        let agent = embedded_agent(&dir, clock).await;
+        let id = agent.insert("周五和小李吃火锅", None, &[], None).await.unwrap();
+        agent.delete(id, None).await.unwrap();
+
+        let error = agent.delete(id, None).await.unwrap_err();
+        assert_eq!(error.to_string(), format!("Memo {} not found", id));
+        // 失败的删除不留下修订
+        assert_eq!(agent.history(id).unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,47 @@
+// agent_memos/src/hyde.rs
+
+// HyDE（Hypothetical Document Embeddings）召回通道：
+// 先让本地 LLM 针对问题写一句“假想的第一人称记忆”，再用它的向量去检索。
+// 问句与陈述句的向量往往相距较远，假想记忆与真实记忆在措辞上更接近。
+// 生成与向量化都受召回参数中的时间预算约束，超时即放弃这一路，不拖慢整体召回。
+
+use crate::prompts;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+
+pub(crate) struct HydeGenerator {
+    client: Client,
+    llm_url: String,
+}
+
+impl HydeGenerator {
+    pub(crate) fn new(llm_url: &str) -> Self {
+        Self { client: Client::new(), llm_url: llm_url.to_string() }
+    }
+
+    /// 为查询生成一句假想记忆
+    pub(crate) async fn generate(&self, query: &str) -> Result<String, anyhow::Error> {
+        #[derive(Deserialize)] struct ChatChoice { message: ChatMessageContent }
+        #[derive(Deserialize)] struct ChatMessageContent { content: String }
+        #[derive(Deserialize)] struct ChatCompletionResponse { choices: Vec<ChatChoice> }
+
+        let request_body = json!({
+            "messages": [
+                { "role": "system", "content": prompts::get_hyde_prompt_v2() },
+                { "role": "user", "content": query },
+            ],
+            "temperature": 0.0,
+            "max_tokens": 64,
+        });
+        let chat_url = format!("{}/v1/chat/completions", self.llm_url);
+        let response = self.client.post(&chat_url).json(&request_body).send().await?.error_for_status()?;
+        let chat_response: ChatCompletionResponse = response.json().await?;
+        let document = chat_response.choices.first()
+            .map(|c| c.message.content.trim().to_string())
+            .filter(|c| !c.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("HyDE LLM response is empty"))?;
+        println!("[MemosAgent-HyDE] Hypothetical memory: '{}'", document);
+        Ok(document)
+    }
+}
@@ -0,0 +1,212 @@
+// agent_memos/src/keyword_index.rs
+
+// 关键词索引：SQLite FTS5 虚表 facts_fts，rowid 即 facts.id，tokens 列存放 jieba 分词后以空格连接的词。
+// - FTS5 自带的 unicode61 分词器按空白切分，不会把中文拆成单字，因此分词在写入前由 jieba 完成；
+// - 与 facts 的写入在同一事务中调用 sync()，修改、删除、恢复、撤销之后索引随之更新；
+// - search() 用 BM25 排序，层级、取代与过期的过滤通过关联 facts 表完成，结果直接进入 RRF 融合。
+
+use crate::expiry;
+use crate::memory_tier_manager::MemoryTier;
+use crate::tags;
+use crate::temporal::TimeRange;
+use crate::vector_store::{fields, Payload, ScoredMemo};
+use chrono::{DateTime, Utc};
+use jieba_rs::Jieba;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::json;
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+fn jieba() -> &'static Jieba {
+    static JIEBA: OnceLock<Jieba> = OnceLock::new();
+    JIEBA.get_or_init(Jieba::new)
+}
+
+fn stop_words() -> &'static HashSet<String> {
+    static STOP_WORDS: OnceLock<HashSet<String>> = OnceLock::new();
+    STOP_WORDS.get_or_init(|| stop_words::get(stop_words::LANGUAGE::Chinese).into_iter().collect())
+}
+
+/// 搜索引擎模式分词，转小写，去掉停用词与纯标点/空白的词。写入索引与查询共用这一套规则。
+pub(crate) fn tokenize(text: &str) -> Vec<String> {
+    jieba().cut_for_search(text, true)
+        .into_iter()
+        .map(|s| s.trim().to_lowercase())
+        .filter(|w| w.chars().any(char::is_alphanumeric) && !stop_words().contains(w))
+        .collect()
+}
+
+/// 让 facts_fts 中 fact_id 的条目与 facts 表当前内容一致（行已删除则移除条目）
+pub(crate) fn sync(conn: &Connection, fact_id: i64) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM facts_fts WHERE rowid = ?1", [fact_id])?;
+    let content: Option<String> = conn
+        .query_row("SELECT content FROM facts WHERE id = ?1", [fact_id], |row| row.get(0))
+        .optional()?;
+    if let Some(content) = content {
+        conn.execute(
+            "INSERT INTO facts_fts (rowid, tokens) VALUES (?1, ?2)",
+            params![fact_id, tokenize(&content).join(" ")],
+        )?;
+    }
+    Ok(())
+}
+
+/// 在某个层级内按 BM25 检索包含任一关键词的记忆（命中的词越多、越稀有，排名越靠前）。
+/// 已被取代与在 now 时刻已过期的记忆不返回；给定 time_range 时只返回在该区间内创建的。分数为 BM25 的相反数，越大越相关。
+pub(crate) fn search(
+    conn: &Connection,
+    keywords: &[String],
+    tier: MemoryTier,
+    time_range: Option<&TimeRange>,
+    tags: &[String],
+    now: DateTime<Utc>,
+    limit: usize,
+) -> Result<Vec<ScoredMemo>, anyhow::Error> {
+    // 每个词按短语加引号，避免词中的符号被当作 FTS5 查询语法
+    let query = keywords.iter()
+        .filter(|k| !k.trim().is_empty())
+        .map(|k| format!("\"{}\"", k.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" OR ");
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // 没有 tier 的旧数据视为 Active，与向量库的过滤语义一致
+    let tier_clause = match tier {
+        MemoryTier::Active => "f.tier != 'archive'",
+        MemoryTier::Archive => "f.tier = 'archive'",
+    };
+    let sql = format!(
+        "SELECT f.id, f.content, f.tier, f.created_at, f.updated_at, f.expires_at, bm25(facts_fts) AS rank
+         FROM facts_fts JOIN facts f ON f.id = facts_fts.rowid
+         WHERE facts_fts MATCH ?1 AND {} AND f.superseded_by IS NULL AND {} AND {}
+         ORDER BY rank LIMIT ?2",
+        tier_clause,
+        time_range.map_or_else(|| "1".to_string(), |r| r.sql_predicate("f.created_at")),
+        tags::sql_predicate("f.metadata", tags),
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let limit = limit.min(i64::MAX as usize) as i64;
+    let rows = stmt.query_map(params![query, limit], |row| {
+        let mut payload = Payload::new();
+        payload.insert(fields::CONTENT.to_string(), json!(row.get::<_, String>(1)?));
+        payload.insert(fields::TIER.to_string(), json!(row.get::<_, String>(2)?));
+        for (idx, field) in [(3, fields::CREATED_AT), (4, fields::UPDATED_AT), (5, fields::EXPIRES_AT)] {
+            if let Some(value) = row.get::<_, Option<String>>(idx)? {
+                payload.insert(field.to_string(), json!(value));
+            }
+        }
+        Ok(ScoredMemo { id: row.get(0)?, score: -row.get::<_, f64>(6)? as f32, payload })
+    })?.collect::<Result<Vec<_>, _>>()?;
+
+    Ok(rows.into_iter().filter(|p| !expiry::is_expired(&p.payload, now)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{migrated_pool, TestDir};
+    use chrono::TimeZone;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 3, 10, 9, 0, 0).unwrap()
+    }
+
+    /// 写入一条记忆并同步索引；created_at 为 RFC3339
+    fn memo(conn: &Connection, content: &str, created_at: &str, metadata: Option<&str>) -> i64 {
+        conn.execute(
+            "INSERT INTO facts (content, metadata, created_at, updated_at) VALUES (?1, ?2, ?3, ?3)",
+            params![content, metadata, created_at],
+        ).unwrap();
+        let id = conn.last_insert_rowid();
+        sync(conn, id).unwrap();
+        id
+    }
+
+    fn ids(points: &[ScoredMemo]) -> Vec<i64> {
+        points.iter().map(|p| p.id).collect()
+    }
+
+    fn keywords(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn tokenize_lowercases_and_drops_stop_words_and_punctuation() {
+        assert_eq!(tokenize("我的车停在B2-103！"), vec!["车", "停", "b2-103"]);
+        assert_eq!(tokenize("Titan 项目"), vec!["titan", "项目"]);
+        assert!(tokenize("的，了。").is_empty());
+    }
+
+    #[test]
+    fn sync_follows_updates_and_deletes() {
+        let dir = TestDir::new();
+        let pool = migrated_pool(&dir);
+        let conn = pool.get().unwrap();
+        let id = memo(&conn, "周五和小李吃火锅", "2025-03-07T12:00:00+00:00", None);
+        let tokens = |conn: &Connection| -> Option<String> {
+            conn.query_row("SELECT tokens FROM facts_fts WHERE rowid = ?1", [id], |row| row.get(0)).optional().unwrap()
+        };
+        assert_eq!(tokens(&conn).as_deref(), Some("周五 李 火锅 吃火锅"));
+
+        conn.execute("UPDATE facts SET content = 'Titan 项目周会' WHERE id = ?1", [id]).unwrap();
+        sync(&conn, id).unwrap();
+        assert_eq!(tokens(&conn).as_deref(), Some("titan 项目 周会"));
+        assert!(search(&conn, &keywords(&["火锅"]), MemoryTier::Active, None, &[], now(), 10).unwrap().is_empty());
+
+        conn.execute("DELETE FROM facts WHERE id = ?1", [id]).unwrap();
+        sync(&conn, id).unwrap();
+        assert_eq!(tokens(&conn), None);
+    }
+
+    #[test]
+    fn search_ranks_by_bm25() {
+        let dir = TestDir::new();
+        let pool = migrated_pool(&dir);
+        let conn = pool.get().unwrap();
+        let one_hit = memo(&conn, "火锅店换了地址", "2025-03-01T12:00:00+00:00", None);
+        let both_hits = memo(&conn, "周五和小李吃火锅", "2025-03-07T12:00:00+00:00", None);
+        memo(&conn, "小王下周出差", "2025-03-08T12:00:00+00:00", None);
+
+        let points = search(&conn, &keywords(&["火锅", "李"]), MemoryTier::Active, None, &[], now(), 10).unwrap();
+        assert_eq!(ids(&points), vec![both_hits, one_hit]);
+        assert!(points[0].score > points[1].score && points[1].score > 0.0);
+        assert_eq!(points[0].payload.get(fields::CONTENT), Some(&json!("周五和小李吃火锅")));
+        assert_eq!(ids(&search(&conn, &keywords(&["火锅", "李"]), MemoryTier::Active, None, &[], now(), 1).unwrap()), vec![both_hits]);
+        // 关键词中的引号与 FTS5 语法字符按字面处理
+        assert!(search(&conn, &keywords(&["\"火锅 OR", " "]), MemoryTier::Active, None, &[], now(), 10).unwrap().is_empty());
+        assert!(search(&conn, &[], MemoryTier::Active, None, &[], now(), 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn search_applies_tier_time_tag_and_lifecycle_filters() {
+        let dir = TestDir::new();
+        let pool = migrated_pool(&dir);
+        let conn = pool.get().unwrap();
+        let this_week = memo(&conn, "周五和小李吃火锅", "2025-03-07T12:00:00+00:00", Some(r#"{"tags":["聚餐"]}"#));
+        let last_month = memo(&conn, "上个月的火锅不错", "2025-02-10T12:00:00+00:00", None);
+        let archived = memo(&conn, "去年的火锅局", "2024-03-10T12:00:00+00:00", None);
+        conn.execute("UPDATE facts SET tier = 'archive' WHERE id = ?1", [archived]).unwrap();
+        let superseded = memo(&conn, "火锅改到周六", "2025-03-08T12:00:00+00:00", None);
+        conn.execute("UPDATE facts SET superseded_by = ?1 WHERE id = ?2", params![this_week, superseded]).unwrap();
+        let expired = memo(&conn, "今晚火锅别迟到", "2025-03-09T12:00:00+00:00", None);
+        conn.execute("UPDATE facts SET expires_at = '2025-03-09T23:59:59Z' WHERE id = ?1", [expired]).unwrap();
+
+        let hotpot = keywords(&["火锅"]);
+        let mut active = ids(&search(&conn, &hotpot, MemoryTier::Active, None, &[], now(), 10).unwrap());
+        active.sort();
+        assert_eq!(active, vec![this_week, last_month]);
+        assert_eq!(ids(&search(&conn, &hotpot, MemoryTier::Archive, None, &[], now(), 10).unwrap()), vec![archived]);
+
+        let march = TimeRange {
+            start: Utc.with_ymd_and_hms(2025, 3, 1, 0, 0, 0).unwrap(),
+            end: Utc.with_ymd_and_hms(2025, 4, 1, 0, 0, 0).unwrap(),
+            expression: "这个月".to_string(),
+        };
+        assert_eq!(ids(&search(&conn, &hotpot, MemoryTier::Active, Some(&march), &[], now(), 10).unwrap()), vec![this_week]);
+        let tagged = search(&conn, &hotpot, MemoryTier::Active, None, &["聚餐".to_string()], now(), 10).unwrap();
+        assert_eq!(ids(&tagged), vec![this_week]);
+        assert!(search(&conn, &hotpot, MemoryTier::Active, None, &["工作".to_string()], now(), 10).unwrap().is_empty());
+    }
+}
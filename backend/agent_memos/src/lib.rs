@@ -1,160 +1,383 @@
 // agent_memos/src/lib.rs (已完成编译修复与NER能力植入)
 
+mod chunking;
 mod db; 
+mod dedupe;
+mod entity_graph;
+mod expiry;
+mod graph_export;
+mod history;
+mod hyde;
+mod keyword_index;
+mod memory_tier_manager;
+mod migrations;
+mod ner;
+mod outbox;
 mod query_expander;
+mod recall_options;
+mod recall_trace;
+mod reindex;
+mod retention;
+mod supersede;
+mod synonym_miner;
+mod tags;
+mod target_resolver;
+mod temporal;
+#[cfg(test)]
+mod test_support;
+pub mod embedding;
+pub mod prompts;
+pub mod vector_store;
 use query_expander::QueryExpander;
 use memos_core::{Agent, Command, Response};
 use async_trait::async_trait;
 use r2d2_sqlite::SqliteConnectionManager;
 use r2d2::Pool;
-use chrono::Utc;
-use std::collections::HashMap;
-// --- 核心修复 START ---
-// 1. 移除未使用的 HashSet
-// 2. 导入 Qdrant Filter 所需的类型
-use qdrant_client::qdrant::{
-    PointStruct, Vector, VectorParamsBuilder, CreateCollectionBuilder, Distance, 
-    UpsertPointsBuilder, SearchPointsBuilder, ScoredPoint, point_id, 
-    vector::Vector as QdrantVectorEnums, DenseVector, PointsIdsList, DeletePointsBuilder,
-    Filter, Condition, r#match::MatchValue // <-- 编译器指示需要导入的类型
-};
-// --- 核心修复 END ---
-use qdrant_client::{Payload, Qdrant};
-use serde_json::json;
+use chrono::{DateTime, Local, Utc};
 use std::any::Any;
 use std::path::Path;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex, RwLock};
+use db::DbPool;
+pub use vector_store::{fields, Condition, Filter, Payload, ScoredMemo, VectorBackend, VectorPoint, VectorStore};
+use vector_store::{EmbeddedVectorStore, QdrantVectorStore};
+pub use embedding::EmbeddingProvider;
+use ner::EntityExtractor;
+pub use outbox::ReconcileReport;
+pub use expiry::{infer_expiry, Clock, ExpiryPolicy, ManualClock, SweepReport, SystemClock};
+use expiry::ExpirySweeper;
+pub use memory_tier_manager::{MemoryTier, TierChange, TierPolicy, TieredMemo};
+use memory_tier_manager::TierManager;
+pub use history::{FactRevision, RevisionOp, UndoOutcome};
+use history::RevisionSource;
+use outbox::{Indexer, OutboxOp};
+use hyde::HydeGenerator;
+use common_utils::PerformanceMode;
+pub use reindex::{ReindexProgress, ReindexReport};
+pub use retention::{parse_importance_mark, ImportanceSource, MemoStats, RetentionPolicy, DEFAULT_IMPORTANCE};
+pub use dedupe::{DedupeReport, DuplicateGroup, SaveOutcome};
+pub use recall_options::{ChannelOptions, FusionMethod, RecallOptions};
+pub use entity_graph::{EntityDetail, EntityNeighbour, EntitySummary, ExpandedEntity, LinkedMemo};
+pub use graph_export::{parse_time_bound, CoOccurrence, GraphEntity, GraphFilter, GraphFormat, GraphMemo, KnowledgeGraph};
+pub use synonym_miner::SynonymSuggestion;
+pub use tags::{extract_hashtags, normalize as normalize_tag, TagCount};
+pub use target_resolver::{RecallMode, TargetScore};
+use target_resolver::TargetEvidence;
+pub use temporal::{parse_time_range, TimeRange};
+pub use recall_trace::{ChannelTrace, FusedHit, FusionContribution, HydeTrace, RecallTrace, RerankScore, ThresholdTrace, TierTrace, TraceHit};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 // 3. 导入 micromodels (依赖修复后，这里将能正常工作)
 use micromodels::NerClassifier;
 
-#[derive(serde::Serialize)]
-struct EmbeddingRequest<'a> {
-    content: &'a str,
-}
-#[derive(Debug, serde::Deserialize)]
-struct EmbeddingData {
-    embedding: Vec<Vec<f32>>,
-}
-#[derive(Debug, serde::Deserialize)]
-struct EmbeddingResponse(Vec<EmbeddingData>);
-
-type DbPool = Pool<SqliteConnectionManager>;
-const COLLECTION_NAME: &str = "memos";
-const EMBEDDING_DIM: u64 = 512; 
+const OUTBOX_WORKER_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+const EXPIRY_SWEEP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(300);
+const TIER_POLICY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(3600);
 
 pub struct MemosAgent {
     sql_pool: DbPool,
-    qdrant_client: Qdrant,
+    vector_store: Arc<dyn VectorStore>,
     query_expander: QueryExpander,
-    embedding_url: String,
-    ner_classifier: Mutex<NerClassifier>,
+    embedder: Arc<dyn EmbeddingProvider>,
+    entity_extractor: Arc<dyn EntityExtractor>,
+    indexer: Arc<Indexer>,
+    clock: Arc<dyn Clock>,
+    expiry_sweeper: Arc<ExpirySweeper>,
+    tier_manager: Arc<TierManager>,
+    needs_reindex: AtomicBool,
+    reindex_lock: tokio::sync::Mutex<()>,
+    recall_options: RwLock<RecallOptions>,
+    hyde: Option<HydeGenerator>,
+}
+
+/// 一次模糊召回中各路检索的输入：各向量通道的查询向量（未启用或被跳过的为 None）、关键词、时间区间与实体扩展
+struct RecallQuery {
+    original: Option<Vec<f32>>,
+    expanded: Option<Vec<f32>>,
+    hyde: Option<Vec<f32>>,
+    keywords: Vec<String>,
+    time_range: Option<TimeRange>,
+    entity_expansion: Vec<ExpandedEntity>,
 }
 
 
 impl MemosAgent {
     pub fn extract_entities(&self, text: &str) -> Result<Vec<String>, anyhow::Error> {
-        self.ner_classifier.lock().unwrap().predict(text)
+        self.entity_extractor.extract(text)
+    }
+
+    pub async fn new(vector_backend: VectorBackend, embedder: Arc<dyn EmbeddingProvider>, models_path: &Path) -> Result<Self, anyhow::Error> {
+        Self::new_with_clock(vector_backend, embedder, models_path, Arc::new(SystemClock)).await
     }
 
-    pub async fn new(qdrant_url: &str, embedding_url: &str, models_path: &Path) -> Result<Self, anyhow::Error> {
-        let home_dir = dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?;
-        let db_dir = home_dir.join(".memos_agent");
+    /// 与 new 相同，但使用注入的时钟（过期判断、过期清扫与时间戳都以它为准）
+    pub async fn new_with_clock(
+        vector_backend: VectorBackend,
+        embedder: Arc<dyn EmbeddingProvider>,
+        models_path: &Path,
+        clock: Arc<dyn Clock>,
+    ) -> Result<Self, anyhow::Error> {
+        let db_dir = db::data_dir()?;
         std::fs::create_dir_all(&db_dir)?;
         let sql_db_path = db_dir.join("memos.db");
-        let manager = SqliteConnectionManager::file(sql_db_path);
+        // 后台索引 worker 与前台请求会并发写库，遇到锁时等待而不是立刻失败
+        let manager = SqliteConnectionManager::file(sql_db_path)
+            .with_init(|c| c.busy_timeout(std::time::Duration::from_secs(5)));
         let sql_pool = Pool::new(manager)?;
         
         db::init_db(&sql_pool)?;
-        println!("[MemosAgent-DB] SQLite database initialization delegated to db::init_db.");
 
-        let qdrant_client = Qdrant::from_url(qdrant_url).build()?;
-        println!("[MemosAgent-DB] Qdrant client initialized.");
-
-        if !qdrant_client.collection_exists(COLLECTION_NAME).await? {
-            qdrant_client.create_collection(
-                CreateCollectionBuilder::new(COLLECTION_NAME)
-                    .vectors_config(VectorParamsBuilder::new(EMBEDDING_DIM, Distance::Cosine))
-            ).await?;
-            println!("[MemosAgent-DB] Qdrant collection '{}' created.", COLLECTION_NAME);
-        }
+        let vector_store: Arc<dyn VectorStore> = match vector_backend {
+            VectorBackend::Qdrant { url } => Arc::new(QdrantVectorStore::new(&url)?),
+            VectorBackend::Embedded => Arc::new(EmbeddedVectorStore::new(sql_pool.clone())?),
+        };
 
         let ner_model_path = models_path.join("ner_core_entity.onnx");
         let ner_preprocessor_path = models_path.join("ner_core_entity_preprocessor.bin");
-        let ner_classifier = NerClassifier::load(ner_model_path, ner_preprocessor_path)?;
+        let ner_classifier = Arc::new(Mutex::new(NerClassifier::load(ner_model_path, ner_preprocessor_path)?));
+
+        Self::from_parts(sql_pool, vector_store, embedder, ner_classifier, clock, &db_dir).await
+    }
+
+    /// 用已经建好（并完成迁移）的各组件组装 MemosAgent，并启动后台 worker；测试可借此注入内嵌存储与替身模型
+    pub(crate) async fn from_parts(
+        sql_pool: DbPool,
+        vector_store: Arc<dyn VectorStore>,
+        embedder: Arc<dyn EmbeddingProvider>,
+        entity_extractor: Arc<dyn EntityExtractor>,
+        clock: Arc<dyn Clock>,
+        db_dir: &Path,
+    ) -> Result<Self, anyhow::Error> {
+        println!("[MemosAgent-Embed] Using embedding provider '{}'.", embedder.model_name());
+        let index_state = reindex::resolve_index_state(&sql_pool, embedder.as_ref()).await?;
+        vector_store.ensure_collection(&index_state.collection, index_state.dim).await?;
+        println!("[MemosAgent-DB] Vector store '{}' ready with collection '{}'.", vector_store.name(), index_state.collection);
+
+        let indexer = Arc::new(Indexer::new(
+            sql_pool.clone(),
+            vector_store.clone(),
+            embedder.clone(),
+            entity_extractor.clone(),
+            clock.clone(),
+            &index_state.collection,
+        ));
+        outbox::spawn_worker(indexer.clone(), OUTBOX_WORKER_INTERVAL);
+        println!("[MemosAgent-Outbox] Index worker started with {} pending entrie(s).", indexer.pending_count()?);
+
+        let expiry_policy = ExpiryPolicy::from_env();
+        let expiry_sweeper = Arc::new(ExpirySweeper::new(sql_pool.clone(), clock.clone(), expiry_policy));
+        expiry::spawn_sweeper(expiry_sweeper.clone(), indexer.clone(), EXPIRY_SWEEP_INTERVAL);
+        println!("[MemosAgent-Expiry] Expiry sweeper started (policy: {:?}).", expiry_policy);
+
+        let tier_policy = TierPolicy::from_env();
+        println!("[TierManager] Tier policy: {:?}", tier_policy);
+        let tier_manager = Arc::new(TierManager::new(sql_pool.clone(), clock.clone(), tier_policy));
+        memory_tier_manager::spawn_tier_worker(tier_manager.clone(), indexer.clone(), TIER_POLICY_INTERVAL);
+
+        let recall_options = RecallOptions::from_env();
+        println!("[MemosAgent-Recall] Recall options: {:?}", recall_options);
 
         Ok(Self { 
             sql_pool, 
-            qdrant_client,
-            query_expander: QueryExpander::new(),
-            embedding_url: embedding_url.to_string(),
-            ner_classifier: Mutex::new(ner_classifier),  
+            vector_store,
+            query_expander: QueryExpander::new(db_dir)?,
+            embedder,
+            entity_extractor,
+            indexer,
+            clock,
+            expiry_sweeper,
+            tier_manager,
+            needs_reindex: AtomicBool::new(index_state.needs_reindex),
+            reindex_lock: tokio::sync::Mutex::new(()),
+            recall_options: RwLock::new(recall_options),
+            hyde: None,
         })
     }
 
-    fn reciprocal_rank_fusion_multi(&self, ranked_lists: Vec<Vec<ScoredPoint>>, k: u32) -> Vec<ScoredPoint> {
-        println!("[MemosAgent-RRF] Fusing {} ranked lists...", ranked_lists.len());
-        let mut fused_scores: HashMap<u64, f32> = HashMap::new();
-        let mut point_data: HashMap<u64, ScoredPoint> = HashMap::new();
-        for list in ranked_lists {
-            for (rank, point) in list.into_iter().enumerate() {
-                if let Some(point_id::PointIdOptions::Num(memo_id)) = point.id.as_ref().and_then(|p| p.point_id_options.as_ref()) {
-                    let score = 1.0 / (k as f32 + (rank + 1) as f32);
-                    *fused_scores.entry(*memo_id).or_insert(0.0) += score;
-                    point_data.entry(*memo_id).or_insert(point);
-                }
-            }
+    /// 启用 HyDE 召回通道（使用 llm_url 上的本地 LLM）；性能优先模式下不启用
+    pub fn with_hyde(mut self, llm_url: &str, mode: PerformanceMode) -> Self {
+        if mode == PerformanceMode::PerformanceFirst {
+            println!("[MemosAgent-HyDE] Performance-First mode, HyDE channel disabled.");
+            return self;
         }
-        let mut sorted_fused_results: Vec<(u64, f32)> = fused_scores.into_iter().collect();
-        sorted_fused_results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
-        let final_ranked_list: Vec<ScoredPoint> = sorted_fused_results
-            .into_iter()
-            .filter_map(|(id, rrf_score)| {
-                if let Some(mut point) = point_data.remove(&id) {
-                    point.score = rrf_score;
-                    Some(point)
-                } else { None }
-            })
-            .collect();
-        println!("[MemosAgent-RRF] Fusion completed. Final ranked list has {} items.", final_ranked_list.len());
-        final_ranked_list
+        println!("[MemosAgent-HyDE] HyDE channel enabled with LLM at {}.", llm_url);
+        self.hyde = Some(HydeGenerator::new(llm_url));
+        self
+    }
+
+    fn fuse_ranked_lists(&self, ranked_lists: Vec<(&str, f32, Vec<ScoredMemo>)>, method: &FusionMethod) -> (Vec<ScoredMemo>, Vec<FusedHit>) {
+        println!("[MemosAgent-Fusion] Fusing {} ranked lists with {:?}...", ranked_lists.len(), method);
+        let (points, fused) = recall_options::fuse(ranked_lists, method);
+        println!("[MemosAgent-Fusion] Fusion completed. Final ranked list has {} items.", points.len());
+        (points, fused)
+    }
+
+    /// 当前默认的召回参数
+    pub fn recall_options(&self) -> RecallOptions {
+        self.recall_options.read().unwrap().clone()
+    }
+
+    /// 替换默认的召回参数（之后不带参数的 recall 都使用它）；参数不合理时返回错误且不做改动
+    pub fn set_recall_options(&self, options: RecallOptions) -> Result<(), anyhow::Error> {
+        options.validate()?;
+        println!("[MemosAgent-Recall] Recall options updated: {:?}", options);
+        *self.recall_options.write().unwrap() = options;
+        Ok(())
     }
 
     // --- 【神经连接手术 - SAVE】 ---
-    pub async fn save(&self, content: &str) -> Result<i64, anyhow::Error> {
+    /// 保存一条记忆；内容中带有“今天”“这周”等措辞时自动设置失效时间，#话题 写法作为标签
+    pub async fn save(&self, content: &str) -> Result<SaveOutcome, anyhow::Error> {
+        let (content, tags) = tags::extract_hashtags(content);
+        self.save_with_expiry(&content, self.infer_expiry(&content), &tags, None).await
+    }
+
+    /// 保存一条记忆，并显式指定失效时间（None 表示长期有效）与标签；request 是触发保存的用户原话，记入修订历史。
+    /// 与已有记忆重复或相似时不写入，而是返回对应的 SaveOutcome 交给调用方决定。
+    pub async fn save_with_expiry(
+        &self,
+        content: &str,
+        expires_at: Option<DateTime<Utc>>,
+        tags: &[String],
+        request: Option<&str>,
+    ) -> Result<SaveOutcome, anyhow::Error> {
+        if let Some(outcome) = self.check_duplicates(content).await? {
+            return Ok(outcome);
+        }
+        Ok(SaveOutcome::Inserted(self.insert(content, expires_at, tags, request).await?))
+    }
+
+    /// 不做重复检查，直接写入一条记忆（用户确认“都保留”时使用）
+    pub async fn insert(&self, content: &str, expires_at: Option<DateTime<Utc>>, tags: &[String], request: Option<&str>) -> Result<i64, anyhow::Error> {
         println!("[MemosAgent] Saving memo: '{}'", content);
-        let conn = self.sql_pool.get()?;
-        // 4. 移除行尾的非法 `\` 字符
-        let now = Utc::now().to_rfc3339();
-        conn.execute("INSERT INTO facts (content, created_at) VALUES (?1, ?2)", &[content, &now])?;
-        let memo_id = conn.last_insert_rowid();
+        use rusqlite::params;
+        let now = self.clock.now().to_rfc3339();
+        let expires_at = expires_at.map(expiry::to_db_timestamp);
+        if let Some(expires_at) = &expires_at {
+            println!("[MemosAgent-Expiry] Memo will expire at {}", expires_at);
+        }
+        let tier = memory_tier_manager::determine_tier(content);
+        let tags = tags::normalize_all(tags);
+        // 事实与索引待办在同一事务中写入，向量化失败也不会丢失索引任务
+        let memo_id = {
+            let mut conn = self.sql_pool.get()?;
+            let tx = conn.transaction()?;
+            tx.execute(
+                "INSERT INTO facts (content, expires_at, tier, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?4)",
+                params![content, expires_at, tier.as_str(), now],
+            )?;
+            let memo_id = tx.last_insert_rowid();
+            if !tags.is_empty() {
+                tags::set(&tx, memo_id, &tags)?;
+                println!("[MemosAgent-Tags] Tagged memo {} with {:?}", memo_id, tags);
+            }
+            history::record(&tx, memo_id, RevisionOp::Create, RevisionSource::User, request, &now)?;
+            keyword_index::sync(&tx, memo_id)?;
+            dedupe::sync_content_key(&tx, memo_id)?;
+            outbox::enqueue(&tx, memo_id, OutboxOp::Upsert, &now)?;
+            tx.commit()?;
+            memo_id
+        };
         println!("[MemosAgent-DB] Saved to SQLite with ID: {}", memo_id);
 
-        // A. 调用 NER 分类器提取实体
-        let entities: Vec<String> = self.ner_classifier.lock().unwrap().predict(content)?;
-        println!("[MemosAgent-NER] Extracted entities: {:?}\n", entities);
-
-        let vector_data = self.get_embedding(content).await?;
-        let qdrant_vector = Vector { vector: Some(QdrantVectorEnums::Dense(DenseVector { data: vector_data })), ..Default::default() };
-        
-        // B. 将提取出的实体存入 Qdrant payload
-        let payload: Payload = json!({
-            "content": content,
-            "created_at": now,
-            "entities": entities // <-- 新增的字段
-        }).try_into().unwrap();
-
-        let points = vec![PointStruct::new(memo_id as u64, qdrant_vector, payload)];
-        self.qdrant_client.upsert_points(UpsertPointsBuilder::new(COLLECTION_NAME.to_string(), points)).await?;
-        println!("[MemosAgent-DB] Upserted point to Qdrant with ID: {}", memo_id);
+        // NER、向量化与写入向量库由 Indexer 完成；失败时留在发件箱由后台重试
+        if self.indexer.flush_fact(memo_id).await {
+            println!("[MemosAgent-DB] Upserted point to vector store with ID: {}", memo_id);
+        }
         Ok(memo_id)
     }
 
+    /// 按当前时钟（本地时区）从措辞推断失效时间
+    pub fn infer_expiry(&self, text: &str) -> Option<DateTime<Utc>> {
+        expiry::infer_expiry(text, &self.clock.now().with_timezone(&Local))
+    }
+
+    /// 立即执行一次过期清扫（后台 sweeper 也会周期性地执行）
+    pub async fn sweep_expired(&self) -> Result<SweepReport, anyhow::Error> {
+        let report = self.expiry_sweeper.sweep()?;
+        for id in &report.expired {
+            self.indexer.flush_fact(*id).await;
+        }
+        Ok(report)
+    }
+
+    /// 丢弃已过期的召回结果（sweeper 尚未清扫到的也一并过滤）
+    fn drop_expired(&self, points: Vec<ScoredMemo>) -> Vec<ScoredMemo> {
+        self.split_expired(points).0
+    }
+
+    /// 把召回结果分为未过期的与已过期的（后者只返回 ID）
+    fn split_expired(&self, points: Vec<ScoredMemo>) -> (Vec<ScoredMemo>, Vec<i64>) {
+        let now = self.clock.now();
+        let (expired, points): (Vec<ScoredMemo>, Vec<ScoredMemo>) = points.into_iter().partition(|p| expiry::is_expired(&p.payload, now));
+        if !expired.is_empty() {
+            println!("[MemosAgent-Expiry] Dropped {} expired result(s).", expired.len());
+        }
+        (points, expired.into_iter().map(|p| p.id).collect())
+    }
+
     // --- 【神经连接手术 - RECALL】 ---
-    pub async fn recall(&self, query_text: &str, context_entities: Option<Vec<String>>) -> Result<Vec<ScoredPoint>, anyhow::Error> {
-        println!("[MemosAgent] Recalling for: '{}'", query_text);
+    /// 按 mode 召回：Fuzzy 用于回答问题，TargetLookup 用于定位要修改或删除的记忆（结果分数为置信度）
+    pub async fn recall(&self, query_text: &str, context_entities: Option<Vec<String>>, mode: RecallMode) -> Result<Vec<ScoredMemo>, anyhow::Error> {
+        let options = self.recall_options();
+        self.recall_with_options(query_text, context_entities, mode, &options).await
+    }
+
+    /// 与 recall 相同，但使用调用方给出的召回参数
+    pub async fn recall_with_options(
+        &self,
+        query_text: &str,
+        context_entities: Option<Vec<String>>,
+        mode: RecallMode,
+        options: &RecallOptions,
+    ) -> Result<Vec<ScoredMemo>, anyhow::Error> {
+        let (points, _) = self.run_recall(query_text, context_entities, mode, options).await?;
+        Ok(points)
+    }
+
+    /// 执行一次召回并返回每一步的诊断信息（options 为 None 时使用当前默认参数）。只用于排查。
+    pub async fn recall_explain(
+        &self,
+        query_text: &str,
+        context_entities: Option<Vec<String>>,
+        mode: RecallMode,
+        options: Option<&RecallOptions>,
+    ) -> Result<RecallTrace, anyhow::Error> {
+        let options = options.cloned().unwrap_or_else(|| self.recall_options());
+        let (_, trace) = self.run_recall(query_text, context_entities, mode, &options).await?;
+        Ok(trace)
+    }
 
-        let is_precise_intent = query_text.contains("修改") || query_text.contains("删除") || query_text.contains("那条关于");
+    /// 召回的完整流程。召回本身不记录访问：真正交给用户的记忆由调用方通过 mark_accessed 记录
+    async fn run_recall(
+        &self,
+        query_text: &str,
+        context_entities: Option<Vec<String>>,
+        mode: RecallMode,
+        options: &RecallOptions,
+    ) -> Result<(Vec<ScoredMemo>, RecallTrace), anyhow::Error> {
+        println!("[MemosAgent] Recalling for: '{}'", query_text);
+        options.validate()?;
+        // 查询中的时间表达式（“上周”“昨天”）限定记忆的创建时间；语义与关键词检索使用去掉时间词后的查询。
+        // 只说“最近”而没有给出区间时不做硬过滤（否则较早的记忆全被挡掉），改为打开新近度通道让新记忆排得更靠前
+        let time_range = temporal::parse_time_range(query_text, &self.clock.now().with_timezone(&Local));
+        let recency = if time_range.is_none() && temporal::mentions_recency(query_text) {
+            println!("[MemosAgent-Temporal] Query mentions recency; boosting recent memos.");
+            ChannelOptions { enabled: true, ..options.recency.clone() }
+        } else {
+            options.recency.clone()
+        };
+        let options = &RecallOptions { tags: tags::normalize_all(&options.tags), recency, ..options.clone() };
+        if self.needs_reindex.load(Ordering::SeqCst) {
+            return Err(anyhow::anyhow!("向量索引与当前嵌入模型不一致，请先执行 /reindex 重建索引。"));
+        }
+        let collection = self.indexer.active_collection();
+        let mut trace = RecallTrace {
+            query: query_text.to_string(),
+            options: options.clone(),
+            entities_from_context: context_entities.is_some(),
+            mode,
+            ..Default::default()
+        };
 
         // --- 修复：优先使用从上下文传入的实体 ---
         let entities_to_use = if let Some(entities) = context_entities {
@@ -163,123 +386,642 @@ impl MemosAgent {
             entities
         } else {
             // 否则，才从当前查询文本中提取实体
-            self.ner_classifier.lock().unwrap().predict(query_text)?
+            self.entity_extractor.extract(query_text)?
         };
-        
-        if is_precise_intent {
-            println!("[MemosAgent] Precise intent detected. Attempting NER-based entity linking.");
-            
-            println!("[MemosAgent-NER] Entities for search: {:?}", entities_to_use);
-
-            if !entities_to_use.is_empty() {
-                let filter = Filter::must(
-                    entities_to_use.iter().map(|e| Condition::matches("entities", MatchValue::Text(e.clone())))
-                );
-
-                let scroll_response = self.qdrant_client.scroll(
-                    qdrant_client::qdrant::ScrollPointsBuilder::new(COLLECTION_NAME.to_string()).filter(filter).limit(5).with_payload(true)
-                ).await?;
-
-                if !scroll_response.result.is_empty() {
-                    println!("[MemosAgent-DB] Entity linking found {} precise results. Returning immediately.", scroll_response.result.len());
-                    let precise_points: Vec<ScoredPoint> = scroll_response.result.into_iter().map(|p| ScoredPoint {
-                        id: p.id, payload: p.payload, score: 1.0,
-                        version: 0,
-                        vectors: p.vectors, order_value: p.order_value, shard_key: p.shard_key,
-                    }).collect();
-                    return Ok(precise_points);
-                } else {
-                    println!("[MemosAgent-DB] NER extracted entities, but no precise match found in Qdrant.");
-                }
-            } else {
-                println!("[MemosAgent-NER] No entities found for precise search.");
-            }
+        trace.entities = entities_to_use;
+
+        let content_query = time_range.as_ref().and_then(|r| r.strip_from(query_text)).unwrap_or_else(|| query_text.to_string());
+        trace.time_range = time_range.clone();
+
+        if mode == RecallMode::TargetLookup {
+            return self.target_lookup(&content_query, &collection, time_range.as_ref(), options, trace).await;
         }
-        
-        // F. 如果不是精确意图或实体链接失败，则回退到标准的三路模糊搜索
-        println!("[MemosAgent] Precise search failed or not applicable. Falling back to standard fuzzy recall.");
-        let expansions = self.query_expander.expand(query_text);
-        let original_query = expansions.get(0).unwrap_or(&query_text.to_string()).clone();
+
+        // F. 模糊召回：多路检索后融合
+        let expansions = self.query_expander.expand(&content_query);
+        let original_query = expansions.first().cloned().unwrap_or_else(|| query_text.to_string());
         let expanded_query_str = expansions.join(" ");
 
-        const VECTOR_SCORE_THRESHOLD: f32 = 0.5;
+        let (original_vector, expanded_vector, (hyde_vector, hyde_trace)) = tokio::try_join!(
+            async {
+                if !options.original_vector.enabled { return Ok(None); }
+                self.get_embedding(&original_query).await.map(Some)
+            },
+            async {
+                if !options.expanded_vector.enabled { return Ok(None); }
+                self.get_embedding(&expanded_query_str).await.map(Some)
+            },
+            async { Ok::<_, anyhow::Error>(self.hyde_vector(query_text, options).await) },
+        )?;
+        let keywords = if options.keyword.enabled { self.extract_keywords(&content_query) } else { Vec::new() };
+        let entity_expansion = if options.entity.enabled && !trace.entities.is_empty() {
+            let conn = self.sql_pool.get()?;
+            entity_graph::expand(&conn, &trace.entities, self.clock.now(), options.entity_neighbours)?
+        } else {
+            Vec::new()
+        };
+        trace.expansions = expansions;
+        trace.keywords = keywords.clone();
+        trace.entity_expansion = entity_expansion.clone();
+        let query = RecallQuery { original: original_vector, expanded: expanded_vector, hyde: hyde_vector, keywords, time_range, entity_expansion };
+        trace.hyde = hyde_trace;
+
+        // 先在 Active 层检索，没有结果再回退到 Archive 层；已被取代的记忆不参与召回
+        for tier in MemoryTier::RECALL_ORDER {
+            let mut tier_trace = TierTrace::new(tier);
+            let filtered_points = self.fuzzy_recall_in_tier(&collection, tier, &query, options, &mut tier_trace).await?;
+            trace.tiers.push(tier_trace);
+            if !filtered_points.is_empty() {
+                println!("[MemosAgent] Fuzzy recall found {} result(s) in '{}' tier.", filtered_points.len(), tier.as_str());
+                return Ok(self.finish_recall(filtered_points, "fuzzy", trace));
+            }
+            println!("[MemosAgent] No results in '{}' tier.", tier.as_str());
+        }
+        Ok(self.finish_recall(Vec::new(), "none", trace))
+    }
+
+    /// 目标查找：逐层收集实体、关键词与语义三路命中并计算置信度，某层有候选即停止
+    async fn target_lookup(
+        &self,
+        query_text: &str,
+        collection: &str,
+        time_range: Option<&TimeRange>,
+        options: &RecallOptions,
+        mut trace: RecallTrace,
+    ) -> Result<(Vec<ScoredMemo>, RecallTrace), anyhow::Error> {
+        println!("[MemosAgent-Target] Resolving target for '{}' with entities {:?}", query_text, trace.entities);
+        let keywords = self.extract_keywords(query_text);
+        let vector = self.get_embedding(query_text).await?;
+        trace.keywords = keywords.clone();
+        let limit = options.precise_limit;
+
+        for tier in MemoryTier::RECALL_ORDER {
+            let mut tier_filter = tier.filter().and(supersede::current_only());
+            tier_filter.must.extend(time_range.map(TimeRange::condition));
+            tier_filter.must.extend(tags::conditions(&options.tags));
+            let mut evidence = TargetEvidence::default();
+            for entity in &trace.entities {
+                let filter = Filter::must([Condition::text_contains(fields::ENTITIES, entity)]).and(tier_filter.clone());
+                evidence.entity_hits.extend(self.vector_store.scroll(collection, &filter, limit).await?);
+            }
+            evidence.entity_hits = self.drop_expired(evidence.entity_hits);
+            // 命中块时以父记忆全文作为目标，修改、删除都作用于父记忆
+            let semantic_hits = self.vector_store.search(collection, vector.clone(), limit as u64, Some(options.vector_score_threshold), Some(&tier_filter)).await?;
+            let semantic_hits = {
+                let conn = self.sql_pool.get()?;
+                chunking::resolve_hits(&conn, semantic_hits, true)?
+            };
+            evidence.semantic_hits = self.drop_expired(semantic_hits);
+            if !keywords.is_empty() {
+                let conn = self.sql_pool.get()?;
+                evidence.keyword_hits = keyword_index::search(&conn, &keywords, tier, time_range, &options.tags, self.clock.now(), limit as usize)?;
+            }
 
-        let (vec_original_res, vec_expanded_res, keyword_scroll_res) = tokio::try_join!(
+            let (points, scores) = target_resolver::score(evidence, &trace.entities, self.clock.now(), limit as usize);
+            let mut tier_trace = TierTrace::new(tier);
+            tier_trace.targets = Some(scores);
+            trace.tiers.push(tier_trace);
+            if !points.is_empty() {
+                println!("[MemosAgent-Target] Found {} candidate(s) in '{}' tier, top confidence {:.3}.", points.len(), tier.as_str(), points[0].score);
+                return Ok(self.finish_recall(points, "target", trace));
+            }
+            println!("[MemosAgent-Target] No candidates in '{}' tier.", tier.as_str());
+        }
+        Ok(self.finish_recall(Vec::new(), "none", trace))
+    }
+
+    fn finish_recall(&self, points: Vec<ScoredMemo>, resolved_by: &str, mut trace: RecallTrace) -> (Vec<ScoredMemo>, RecallTrace) {
+        trace.resolved_by = resolved_by.to_string();
+        trace.results = points.iter().map(TraceHit::from).collect();
+        (points, trace)
+    }
+
+    /// 为 HyDE 通道生成假想记忆并向量化，受 hyde_budget_ms 约束；未启用、未配置、失败或超时都返回 None 向量
+    async fn hyde_vector(&self, query_text: &str, options: &RecallOptions) -> (Option<Vec<f32>>, Option<HydeTrace>) {
+        if !options.hyde.enabled {
+            return (None, None);
+        }
+        let skipped = |elapsed_ms: u64, reason: String| (None, Some(HydeTrace { document: None, elapsed_ms, skipped: Some(reason) }));
+        let Some(generator) = &self.hyde else {
+            return skipped(0, "HyDE is not configured (performance-first mode)".to_string());
+        };
+
+        let started = std::time::Instant::now();
+        let budget = std::time::Duration::from_millis(options.hyde_budget_ms);
+        let result = tokio::time::timeout(budget, async {
+            let document = generator.generate(query_text).await?;
+            let vector = self.get_embedding(&document).await?;
+            Ok::<_, anyhow::Error>((document, vector))
+        }).await;
+        let elapsed_ms = started.elapsed().as_millis() as u64;
+        match result {
+            Ok(Ok((document, vector))) => (Some(vector), Some(HydeTrace { document: Some(document), elapsed_ms, skipped: None })),
+            Ok(Err(e)) => {
+                eprintln!("[MemosAgent-HyDE] Skipped: {}", e);
+                skipped(elapsed_ms, format!("failed: {}", e))
+            }
+            Err(_) => {
+                println!("[MemosAgent-HyDE] Skipped: exceeded budget of {} ms.", options.hyde_budget_ms);
+                skipped(elapsed_ms, format!("exceeded budget of {} ms", options.hyde_budget_ms))
+            }
+        }
+    }
+
+    /// 在单个层级内执行多路检索（原始向量、扩展向量、HyDE 向量、BM25 关键词、时间区间、实体扩展）并融合；未启用的一路向量为 None 或输入为空。
+    /// 各路命中、融合与截断的过程记入 tier_trace。
+    async fn fuzzy_recall_in_tier(
+        &self,
+        collection: &str,
+        tier: MemoryTier,
+        query: &RecallQuery,
+        options: &RecallOptions,
+        tier_trace: &mut TierTrace,
+    ) -> Result<Vec<ScoredMemo>, anyhow::Error> {
+        let mut tier_filter = tier.filter().and(supersede::current_only());
+        tier_filter.must.extend(query.time_range.as_ref().map(TimeRange::condition));
+        tier_filter.must.extend(tags::conditions(&options.tags));
+        let vector_search = |vector: &Option<Vec<f32>>, channel: &ChannelOptions, label: &'static str| {
+            let vector = vector.clone();
+            let limit = channel.limit;
+            let tier_filter = &tier_filter;
+            async move {
+                let Some(vector) = vector else { return Ok(None) };
+                self.vector_store.search(collection, vector, limit, Some(options.vector_score_threshold), Some(tier_filter))
+                    .await.map(Some).map_err(|e| anyhow::anyhow!("{} vector search failed: {}", label, e))
+            }
+        };
+
+        let (vec_original_res, vec_expanded_res, vec_hyde_res, keyword_search_res, time_range_res, entity_res) = tokio::try_join!(
+            vector_search(&query.original, &options.original_vector, "Original"),
+            vector_search(&query.expanded, &options.expanded_vector, "Expanded"),
+            vector_search(&query.hyde, &options.hyde, "HyDE"),
             async {
-                let vector = self.get_embedding(&original_query).await?;
-                self.qdrant_client.search_points(
-                    SearchPointsBuilder::new(COLLECTION_NAME, vector, 5)
-                        .with_payload(true)
-                        .score_threshold(VECTOR_SCORE_THRESHOLD)
-                ).await.map_err(|e| anyhow::anyhow!("Original vector search failed: {}", e))
+                if query.keywords.is_empty() { return Ok(None); }
+                let conn = self.sql_pool.get()?;
+                let keyword_points = keyword_index::search(&conn, &query.keywords, tier, query.time_range.as_ref(), &options.tags, self.clock.now(), options.keyword.limit as usize)
+                    .map_err(|e| anyhow::anyhow!("Keyword search failed: {}", e))?;
+                Ok(Some(keyword_points))
             },
             async {
-                let vector = self.get_embedding(&expanded_query_str).await?;
-                self.qdrant_client.search_points(
-                    SearchPointsBuilder::new(COLLECTION_NAME, vector, 5)
-                        .with_payload(true)
-                        .score_threshold(VECTOR_SCORE_THRESHOLD)
-                ).await.map_err(|e| anyhow::anyhow!("Expanded vector search failed: {}", e))
+                let Some(range) = query.time_range.as_ref().filter(|_| options.time_range.enabled) else { return Ok(None) };
+                let conn = self.sql_pool.get()?;
+                let points = temporal::search_in_range(&conn, range, tier, &options.tags, self.clock.now(), options.time_range.limit as usize)
+                    .map_err(|e| anyhow::anyhow!("Time range search failed: {}", e))?;
+                Ok(Some(points))
             },
             async {
-                let keywords = self.extract_keywords(query_text);
-                if keywords.is_empty() { return Ok(None); }
-                let filter = Filter::must(keywords.iter().map(|k| Condition::matches("content", MatchValue::Text(k.clone()))));
-                let scroll_response = self.qdrant_client.scroll(
-                    qdrant_client::qdrant::ScrollPointsBuilder::new(COLLECTION_NAME).filter(filter).limit(5).with_payload(true)
-                ).await.map_err(|e| anyhow::anyhow!("Keyword search failed: {}", e))?;
-                Ok(Some(scroll_response))
+                if query.entity_expansion.is_empty() { return Ok(None); }
+                let conn = self.sql_pool.get()?;
+                let points = entity_graph::search(
+                    &conn, &query.entity_expansion, tier, query.time_range.as_ref(), &options.tags, self.clock.now(), options.entity.limit as usize,
+                ).map_err(|e| anyhow::anyhow!("Entity expansion search failed: {}", e))?;
+                Ok(Some(points))
             }
         )?;
 
-        let mut all_results: Vec<Vec<ScoredPoint>> = Vec::new();
-        all_results.push(vec_original_res.result);
-        all_results.push(vec_expanded_res.result);
-        if let Some(scroll_res) = keyword_scroll_res {
-            let keyword_points = scroll_res.result.into_iter().map(|p: qdrant_client::qdrant::RetrievedPoint| {
-                ScoredPoint {
-                    id: p.id, payload: p.payload, score: 1.0, 
-                    version: 0, // RetrievedPoint 没有 version，我们提供一个默认值
-                    vectors: p.vectors, order_value: p.order_value, shard_key: p.shard_key,
+        let channels = [
+            ("original_vector", options.original_vector.weight, vec_original_res),
+            ("expanded_vector", options.expanded_vector.weight, vec_expanded_res),
+            ("hyde", options.hyde.weight, vec_hyde_res),
+            ("keyword", options.keyword.weight, keyword_search_res),
+            ("time_range", options.time_range.weight, time_range_res),
+            ("entity", options.entity.weight, entity_res),
+        ];
+        let mut all_results: Vec<(&str, f32, Vec<ScoredMemo>)> = Vec::new();
+        for (channel, weight, points) in channels {
+            let Some(points) = points else { continue };
+            // 向量通道可能命中长记忆的块，映射回父记忆并返回命中处的窗口
+            let points = {
+                let conn = self.sql_pool.get()?;
+                chunking::resolve_hits(&conn, points, false)?
+            };
+            let (points, dropped_expired) = self.split_expired(points);
+            tier_trace.channels.push(ChannelTrace {
+                channel: channel.to_string(),
+                weight,
+                hits: points.iter().map(TraceHit::from).collect(),
+                dropped_expired,
+            });
+            all_results.push((channel, weight, points));
+        }
+        if options.recency.enabled {
+            let recency_points = self.rank_by_recency(&all_results, options);
+            tier_trace.channels.push(ChannelTrace {
+                channel: "recency".to_string(),
+                weight: options.recency.weight,
+                hits: recency_points.iter().map(TraceHit::from).collect(),
+                dropped_expired: Vec::new(),
+            });
+            all_results.push(("recency", options.recency.weight, recency_points));
+        }
+        if options.retention.enabled {
+            let retention_points = self.rank_by_retention(&all_results, options)?;
+            tier_trace.channels.push(ChannelTrace {
+                channel: "retention".to_string(),
+                weight: options.retention.weight,
+                hits: retention_points.iter().map(TraceHit::from).collect(),
+                dropped_expired: Vec::new(),
+            });
+            all_results.push(("retention", options.retention.weight, retention_points));
+        }
+        let (fused_points, fused) = self.fuse_ranked_lists(all_results, &options.fusion);
+        tier_trace.fused = fused;
+        let (points, threshold) = self.apply_dynamic_threshold(fused_points, options);
+        tier_trace.threshold = Some(threshold);
+        Ok(points)
+    }
+
+    /// 新近度通道：其他各路的全部候选按新近度降序，取前 recency.limit 条，分数为新近度
+    fn rank_by_recency(&self, channels: &[(&str, f32, Vec<ScoredMemo>)], options: &RecallOptions) -> Vec<ScoredMemo> {
+        let now = self.clock.now();
+        let mut seen = std::collections::HashSet::new();
+        let mut points: Vec<ScoredMemo> = channels.iter()
+            .flat_map(|(_, _, points)| points.iter())
+            .filter(|p| seen.insert(p.id))
+            .map(|p| ScoredMemo { score: temporal::recency_score(&p.payload, now, options.recency_half_life_days), ..p.clone() })
+            .collect();
+        points.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        points.truncate(options.recency.limit as usize);
+        points
+    }
+
+    /// 保持度通道：其他各路的全部候选按保持度降序，取前 retention.limit 条，分数为保持度
+    fn rank_by_retention(&self, channels: &[(&str, f32, Vec<ScoredMemo>)], options: &RecallOptions) -> Result<Vec<ScoredMemo>, anyhow::Error> {
+        let now = self.clock.now();
+        let mut seen = std::collections::HashSet::new();
+        let candidates: Vec<&ScoredMemo> = channels.iter()
+            .flat_map(|(_, _, points)| points.iter())
+            .filter(|p| seen.insert(p.id))
+            .collect();
+        let ids: Vec<i64> = candidates.iter().map(|p| p.id).collect();
+        let stats = {
+            let conn = self.sql_pool.get()?;
+            retention::load(&conn, &ids)?
+        };
+        let policy = &self.tier_manager.policy().retention;
+        let mut points: Vec<ScoredMemo> = candidates.into_iter()
+            .filter_map(|p| stats.get(&p.id).map(|s| ScoredMemo { score: policy.score(s, now), ..p.clone() }))
+            .collect();
+        points.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        points.truncate(options.retention.limit as usize);
+        Ok(points)
+    }
+
+    /// 记录一次访问：这些记忆被交给了用户（作为回答依据或引用）。累加访问次数、更新最近访问时间，
+    /// 被访问的 Archive 记忆按策略升回 Active。只影响后续的排序与层级决策，失败时只记日志。
+    pub async fn mark_accessed(&self, ids: &[i64]) {
+        if ids.is_empty() {
+            return;
+        }
+        match self.tier_manager.record_access(ids) {
+            Ok(promoted) => {
+                for id in promoted {
+                    self.indexer.flush_fact(id).await;
                 }
-            }).collect();
-            all_results.push(keyword_points);
+            }
+            Err(e) => eprintln!("[TierManager] Failed to record access for {:?}: {}", ids, e),
+        }
+    }
+
+    /// 设置一条记忆的重要度（0 到 1）。LLM 的估计不会覆盖用户的标记，此时返回 false
+    pub fn set_importance(&self, id: i64, importance: f32, source: ImportanceSource) -> Result<bool, anyhow::Error> {
+        let conn = self.sql_pool.get()?;
+        let updated = retention::set_importance(&conn, id, importance, source)?
+            .ok_or_else(|| anyhow::anyhow!("Memo {} not found", id))?;
+        if updated {
+            println!("[MemosAgent-Retention] Memo {} importance set to {:.2} ({}).", id, importance.clamp(0.0, 1.0), source.as_str());
+        }
+        Ok(updated)
+    }
+
+    /// 一条记忆当前的统计（重要度、访问次数等）与保持度；记忆不存在时返回 None
+    pub fn retention_of(&self, id: i64) -> Result<Option<(MemoStats, f32)>, anyhow::Error> {
+        let stats = {
+            let conn = self.sql_pool.get()?;
+            retention::load(&conn, &[id])?.remove(&id)
+        };
+        let policy = &self.tier_manager.policy().retention;
+        Ok(stats.map(|s| {
+            let score = policy.score(&s, self.clock.now());
+            (s, score)
+        }))
+    }
+
+    /// 按层级列出记忆（None 表示全部层级），最近保存的在前
+    pub fn list_memos(&self, tier: Option<MemoryTier>, limit: usize) -> Result<Vec<TieredMemo>, anyhow::Error> {
+        self.tier_manager.list(tier, limit)
+    }
+
+    /// 手动调整一条记忆的层级；层级未变化时返回 None
+    pub async fn set_tier(&self, id: i64, tier: MemoryTier) -> Result<Option<TierChange>, anyhow::Error> {
+        let change = self.tier_manager.set_tier(id, tier)?;
+        if change.is_some() {
+            self.indexer.flush_fact(id).await;
+        }
+        Ok(change)
+    }
+
+    /// 立即按层级策略执行一次迁移（后台 worker 也会周期性地执行）
+    pub async fn apply_tier_policy(&self) -> Result<Vec<TierChange>, anyhow::Error> {
+        let changes = self.tier_manager.apply_policy()?;
+        for change in &changes {
+            self.indexer.flush_fact(change.id).await;
         }
-        let fused_points = self.reciprocal_rank_fusion_multi(all_results, 60);
-        let filtered_points = self.apply_dynamic_threshold(fused_points);
+        Ok(changes)
+    }
 
-        Ok(filtered_points)
+    /// 修改一条记忆；request 是触发修改的用户原话，记入修订历史
+    pub async fn update(&self, id: i64, new_content: &str, request: Option<&str>) -> Result<(), anyhow::Error> {
+        self.update_with_tags(id, new_content, &[], request).await
     }
 
-    pub async fn update(&self, id: i64, new_content: &str) -> Result<(), anyhow::Error> {
+    /// 修改内容并追加标签：同一事务、只记一条修订，撤销时内容与标签一起回退
+    pub async fn update_with_tags(&self, id: i64, new_content: &str, added_tags: &[String], request: Option<&str>) -> Result<(), anyhow::Error> {
         println!("[MemosAgent] Updating memo ID: {}", id);
         use rusqlite::params;
-        let conn = self.sql_pool.get()?;
-        let now = Utc::now().to_rfc3339();
-        conn.execute("UPDATE facts SET content = ?1, updated_at = ?2 WHERE id = ?3", params![new_content, now, id])?;
+        let now = self.clock.now().to_rfc3339();
+        {
+            let mut conn = self.sql_pool.get()?;
+            let tx = conn.transaction()?;
+            let changed = tx.execute("UPDATE facts SET content = ?1, updated_at = ?2 WHERE id = ?3", params![new_content, now, id])?;
+            if changed == 0 {
+                return Err(anyhow::anyhow!("Memo {} not found", id));
+            }
+            if !added_tags.is_empty() {
+                let mut merged = tags::get(&tx, id)?;
+                merged.extend(added_tags.iter().cloned());
+                tags::set(&tx, id, &tags::normalize_all(&merged))?;
+            }
+            history::record(&tx, id, RevisionOp::Update, RevisionSource::User, request, &now)?;
+            keyword_index::sync(&tx, id)?;
+            dedupe::sync_content_key(&tx, id)?;
+            outbox::enqueue(&tx, id, OutboxOp::Upsert, &now)?;
+            tx.commit()?;
+        }
         println!("[MemosAgent-DB] Updated SQLite for ID: {}", id);
-        let vector_data = self.get_embedding(new_content).await?;
-        let qdrant_vector = Vector { vector: Some(QdrantVectorEnums::Dense(DenseVector { data: vector_data })), ..Default::default() };
-        let payload: Payload = json!({"content": new_content, "updated_at": now}).try_into()?;
-        let point = PointStruct::new(id as u64, qdrant_vector, payload);
-        self.qdrant_client.upsert_points(UpsertPointsBuilder::new(COLLECTION_NAME.to_string(), vec![point])).await?;
-        println!("[MemosAgent-DB] Re-upserted point to Qdrant for ID: {}", id);
+        if self.indexer.flush_fact(id).await {
+            println!("[MemosAgent-DB] Re-upserted point to vector store for ID: {}", id);
+        }
         Ok(())
     }
 
-    pub async fn delete(&self, id: i64) -> Result<(), anyhow::Error> {
+    /// 删除一条记忆；删除前的内容保留在修订历史中，可以撤销
+    pub async fn delete(&self, id: i64, request: Option<&str>) -> Result<(), anyhow::Error> {
         println!("[MemosAgent] Deleting memo ID: {}", id);
         use rusqlite::params;
-        let conn = self.sql_pool.get()?;
-        conn.execute("DELETE FROM facts WHERE id = ?1", params![id])?;
+        let now = self.clock.now().to_rfc3339();
+        {
+            let mut conn = self.sql_pool.get()?;
+            let tx = conn.transaction()?;
+            history::record(&tx, id, RevisionOp::Delete, RevisionSource::User, request, &now)?;
+            let changed = tx.execute("DELETE FROM facts WHERE id = ?1", params![id])?;
+            if changed == 0 {
+                return Err(anyhow::anyhow!("Memo {} not found", id));
+            }
+            keyword_index::sync(&tx, id)?;
+            outbox::enqueue(&tx, id, OutboxOp::Delete, &now)?;
+            tx.commit()?;
+        }
         println!("[MemosAgent-DB] Deleted from SQLite for ID: {}", id);
-        let point_id_to_delete = point_id::PointIdOptions::Num(id as u64);
-        let points_list = PointsIdsList { ids: vec![point_id_to_delete.into()] };
-        self.qdrant_client.delete_points(DeletePointsBuilder::new(COLLECTION_NAME.to_string()).points(points_list)).await?;
-        println!("[MemosAgent-DB] Deleted point from Qdrant for ID: {}", id);
+        if self.indexer.flush_fact(id).await {
+            println!("[MemosAgent-DB] Deleted point from vector store for ID: {}", id);
+        }
+        Ok(())
+    }
+
+    /// 一条记忆的全部修订历史，按时间先后排列
+    pub fn history(&self, id: i64) -> Result<Vec<FactRevision>, anyhow::Error> {
+        let conn = self.sql_pool.get()?;
+        history::list(&conn, id)
+    }
+
+    /// 把一条记忆恢复到指定修订号的内容（记忆已被删除时会重新创建）
+    pub async fn restore_revision(&self, id: i64, revision: i64, request: Option<&str>) -> Result<(), anyhow::Error> {
+        let now = self.clock.now().to_rfc3339();
+        {
+            let mut conn = self.sql_pool.get()?;
+            let tx = conn.transaction()?;
+            history::restore(&tx, id, revision, request, &now)?;
+            keyword_index::sync(&tx, id)?;
+            dedupe::sync_content_key(&tx, id)?;
+            outbox::enqueue(&tx, id, OutboxOp::Upsert, &now)?;
+            tx.commit()?;
+        }
+        self.indexer.flush_fact(id).await;
         Ok(())
     }
 
+    /// 撤销最近一次用户发起的创建、修改、删除或恢复；没有可撤销的变更时返回 None
+    pub async fn undo_last_change(&self, request: Option<&str>) -> Result<Option<UndoOutcome>, anyhow::Error> {
+        let now = self.clock.now().to_rfc3339();
+        let outcome = {
+            let mut conn = self.sql_pool.get()?;
+            let tx = conn.transaction()?;
+            let outcome = history::undo(&tx, request, &now)?;
+            if let Some(outcome) = &outcome {
+                let op = if outcome.content.is_some() { OutboxOp::Upsert } else { OutboxOp::Delete };
+                keyword_index::sync(&tx, outcome.fact_id)?;
+                dedupe::sync_content_key(&tx, outcome.fact_id)?;
+                outbox::enqueue(&tx, outcome.fact_id, op, &now)?;
+            }
+            tx.commit()?;
+            outcome
+        };
+        if let Some(outcome) = &outcome {
+            self.indexer.flush_fact(outcome.fact_id).await;
+        }
+        Ok(outcome)
+    }
+
+    /// 校验并修复 SQLite 与向量库之间的不一致
+    pub async fn reconcile(&self) -> Result<ReconcileReport, anyhow::Error> {
+        self.indexer.reconcile().await
+    }
+
+    /// 发件箱中等待同步到向量库的待办数量
+    pub fn pending_index_ops(&self) -> Result<usize, anyhow::Error> {
+        self.indexer.pending_count()
+    }
+
+    /// 当前的同义词表（数据目录下的 synonyms.tsv）
+    pub fn synonyms(&self) -> std::collections::BTreeMap<String, Vec<String>> {
+        self.query_expander.synonyms()
+    }
+
+    /// 为 word 添加同义词，立即参与查询扩展；已存在时返回 false
+    pub fn add_synonym(&self, word: &str, synonym: &str) -> Result<bool, anyhow::Error> {
+        self.query_expander.add_synonym(word, synonym)
+    }
+
+    /// 删除 word 的一个同义词；不存在时返回 false
+    pub fn remove_synonym(&self, word: &str, synonym: &str) -> Result<bool, anyhow::Error> {
+        self.query_expander.remove_synonym(word, synonym)
+    }
+
+    /// 根据记忆中的共现关系为 word 推荐同义词（不含已登记的）
+    pub fn suggest_synonyms(&self, word: &str, limit: usize) -> Result<Vec<SynonymSuggestion>, anyhow::Error> {
+        let known = self.query_expander.synonyms().remove(word.trim()).unwrap_or_default();
+        let conn = self.sql_pool.get()?;
+        synonym_miner::suggest(&conn, word, &known, limit)
+    }
+
+    /// 关联了记忆的实体，按关联记忆数降序
+    pub fn list_entities(&self, limit: usize) -> Result<Vec<EntitySummary>, anyhow::Error> {
+        let conn = self.sql_pool.get()?;
+        Ok(entity_graph::list(&conn, limit)?)
+    }
+
+    /// 按名称或别名查看实体：关联的记忆（最新的在前）与共现最多的邻居；实体不存在时返回 None
+    pub fn entity(&self, name: &str, memo_limit: usize, neighbour_limit: usize) -> Result<Option<EntityDetail>, anyhow::Error> {
+        let conn = self.sql_pool.get()?;
+        Ok(entity_graph::detail(&conn, name, self.clock.now(), memo_limit, neighbour_limit)?)
+    }
+
+    /// 与某实体共现最多的实体；实体不存在时返回 None
+    pub fn entity_neighbours(&self, name: &str, limit: usize) -> Result<Option<Vec<EntityNeighbour>>, anyhow::Error> {
+        let conn = self.sql_pool.get()?;
+        let Some(entity_id) = entity_graph::resolve(&conn, name)? else { return Ok(None) };
+        Ok(Some(entity_graph::neighbours(&conn, entity_id, self.clock.now(), limit)?))
+    }
+
+    /// 为实体添加别名；别名已属于另一个实体时两者合并
+    pub fn add_entity_alias(&self, name: &str, alias: &str) -> Result<EntitySummary, anyhow::Error> {
+        let mut conn = self.sql_pool.get()?;
+        let tx = conn.transaction()?;
+        let entity = entity_graph::add_alias(&tx, name, alias)?;
+        tx.commit()?;
+        println!("[MemosAgent-Entity] '{}' is now an alias of '{}'.", alias, entity.name);
+        Ok(entity)
+    }
+
+    /// 把若干实体合并进 into，它们的名称成为 into 的别名
+    pub fn merge_entities(&self, into: &str, sources: &[String]) -> Result<EntitySummary, anyhow::Error> {
+        let mut conn = self.sql_pool.get()?;
+        let tx = conn.transaction()?;
+        let entity = entity_graph::merge(&tx, into, sources)?;
+        tx.commit()?;
+        println!("[MemosAgent-Entity] Merged {:?} into '{}'.", sources, entity.name);
+        Ok(entity)
+    }
+
+    /// 对全部记忆重新跑 NER 并重建实体关联（引入实体图之前的记忆用它补齐），返回处理的记忆数
+    pub fn rebuild_entity_graph(&self) -> Result<usize, anyhow::Error> {
+        let now = self.clock.now().to_rfc3339();
+        let mut conn = self.sql_pool.get()?;
+        let tx = conn.transaction()?;
+        let facts: Vec<(i64, String)> = {
+            let mut stmt = tx.prepare("SELECT id, content FROM facts ORDER BY id")?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?.collect::<Result<_, _>>()?;
+            rows
+        };
+        for (id, content) in &facts {
+            let entities = self.entity_extractor.extract(content)?;
+            entity_graph::link(&tx, *id, &entities, &now)?;
+        }
+        tx.commit()?;
+        println!("[MemosAgent-Entity] Rebuilt entity links for {} memo(s).", facts.len());
+        Ok(facts.len())
+    }
+
+    /// 按筛选条件收集知识图谱（记忆、实体、提及边与共现边）
+    pub fn knowledge_graph(&self, filter: &GraphFilter) -> Result<KnowledgeGraph, anyhow::Error> {
+        let conn = self.sql_pool.get()?;
+        graph_export::collect(&conn, filter)
+    }
+
+    /// 把知识图谱导出为 DOT / GraphML / JSON-LD 文本
+    pub fn export_graph(&self, format: GraphFormat, filter: &GraphFilter) -> Result<String, anyhow::Error> {
+        let graph = self.knowledge_graph(filter)?;
+        println!(
+            "[MemosAgent-Graph] Exporting {} memo(s), {} entity(ies), {} co-occurrence edge(s) as {:?}.",
+            graph.memos.len(), graph.entities.len(), graph.cooccurrences.len(), format
+        );
+        Ok(graph_export::render(&graph, format))
+    }
+
+    /// 按当前时钟（本地时区）解析“上周”“最近三天”等时间表达
+    pub fn parse_time_range(&self, text: &str) -> Option<TimeRange> {
+        temporal::parse_time_range(text, &self.clock.now().with_timezone(&Local))
+    }
+
+    /// 正在使用的全部标签及各自的记忆数，按使用次数降序
+    pub fn list_tags(&self) -> Result<Vec<TagCount>, anyhow::Error> {
+        let conn = self.sql_pool.get()?;
+        Ok(tags::list(&conn)?)
+    }
+
+    /// 某条记忆当前的标签
+    pub fn tags_of(&self, id: i64) -> Result<Vec<String>, anyhow::Error> {
+        let conn = self.sql_pool.get()?;
+        Ok(tags::get(&conn, id)?)
+    }
+
+    /// 覆盖一条记忆的标签（记入修订历史，可撤销），返回规范化后的标签；记忆不存在时报错
+    pub async fn set_tags(&self, id: i64, tags: &[String], request: Option<&str>) -> Result<Vec<String>, anyhow::Error> {
+        let normalized = tags::normalize_all(tags);
+        if let Some(invalid) = tags.iter().find(|t| tags::normalize(t).is_none()) {
+            return Err(anyhow::anyhow!("Invalid tag '{}'", invalid));
+        }
+        let now = self.clock.now().to_rfc3339();
+        let changed = {
+            let mut conn = self.sql_pool.get()?;
+            let tx = conn.transaction()?;
+            let exists: bool = tx.query_row("SELECT EXISTS (SELECT 1 FROM facts WHERE id = ?1)", [id], |row| row.get(0))?;
+            if !exists {
+                return Err(anyhow::anyhow!("Memo {} not found", id));
+            }
+            let changed = tags::retag(&tx, id, &normalized, request, &now)?;
+            tx.commit()?;
+            changed
+        };
+        if changed {
+            println!("[MemosAgent-Tags] Memo {} now tagged {:?}", id, normalized);
+            self.indexer.flush_fact(id).await;
+        }
+        Ok(normalized)
+    }
+
+    /// 为一条记忆添加标签，返回添加后的全部标签
+    pub async fn add_tags(&self, id: i64, tags: &[String], request: Option<&str>) -> Result<Vec<String>, anyhow::Error> {
+        let mut current = self.tags_of(id)?;
+        current.extend(tags.iter().cloned());
+        self.set_tags(id, &current, request).await
+    }
+
+    /// 去掉一条记忆的若干标签，返回剩下的标签
+    pub async fn remove_tags(&self, id: i64, tags: &[String], request: Option<&str>) -> Result<Vec<String>, anyhow::Error> {
+        let removed = tags::normalize_all(tags);
+        let remaining: Vec<String> = self.tags_of(id)?.into_iter().filter(|t| !removed.contains(t)).collect();
+        self.set_tags(id, &remaining, request).await
+    }
+
+    /// 标签改名，返回被改写的记忆 ID
+    pub async fn rename_tag(&self, from: &str, to: &str, request: Option<&str>) -> Result<Vec<i64>, anyhow::Error> {
+        self.merge_tags(&[from.to_string()], to, request).await
+    }
+
+    /// 把若干标签合并为 into（每条受影响的记忆各记一条修订），返回被改写的记忆 ID
+    pub async fn merge_tags(&self, sources: &[String], into: &str, request: Option<&str>) -> Result<Vec<i64>, anyhow::Error> {
+        let into = tags::normalize(into).ok_or_else(|| anyhow::anyhow!("Invalid tag '{}'", into))?;
+        let sources = tags::normalize_all(sources);
+        if sources.is_empty() {
+            return Err(anyhow::anyhow!("No valid source tags to merge"));
+        }
+        let now = self.clock.now().to_rfc3339();
+        let affected = {
+            let mut conn = self.sql_pool.get()?;
+            let tx = conn.transaction()?;
+            let affected = tags::merge(&tx, &sources, &into, request, &now)?;
+            tx.commit()?;
+            affected
+        };
+        println!("[MemosAgent-Tags] Merged {:?} into '{}' on {} memo(s).", sources, into, affected.len());
+        for id in &affected {
+            self.indexer.flush_fact(*id).await;
+        }
+        Ok(affected)
+    }
+
     pub async fn get_by_id(&self, id: i64) -> Result<Option<String>, anyhow::Error> {
         let conn = self.sql_pool.get()?;
         let mut stmt = conn.prepare("SELECT content FROM facts WHERE id = ?1")?;
@@ -292,49 +1034,54 @@ impl MemosAgent {
     }
 
     async fn get_embedding(&self, text: &str) -> Result<Vec<f32>, anyhow::Error> {
-        println!("[MemosAgent-Embed] Requesting vector for text: '{}'", text);
-        let client = reqwest::Client::new();
-        let request_url = format!("{}/embedding", self.embedding_url); 
-        let response = client.post(&request_url).json(&EmbeddingRequest { content: text }).send().await?;
-        if !response.status().is_success() {
-            return Err(anyhow::anyhow!("Embedding service returned an error: {}", response.text().await?));
-        }
-        let mut embedding_response = response.json::<EmbeddingResponse>().await?;
-        if let Some(mut first_item) = embedding_response.0.pop() {
-            if let Some(embedding_vector) = first_item.embedding.pop() {
-                println!("[MemosAgent-Embed] Received {}d vector.", embedding_vector.len());
-                Ok(embedding_vector)
-            } else { Err(anyhow::anyhow!("Embedding service returned empty embedding list.")) }
-        } else { Err(anyhow::anyhow!("Embedding service returned empty array.")) }
+        self.embedder.embed(text).await
     }
 
     fn extract_keywords(&self, query_text: &str) -> Vec<String> {
-        use jieba_rs::Jieba;
-        use stop_words::{get, LANGUAGE};
-        let jieba = Jieba::new();
-        let stop_words = get(LANGUAGE::Chinese);
-        let keywords: Vec<String> = jieba.cut_for_search(query_text, true)
-            .into_iter().map(|s| s.to_lowercase()).filter(|word| !stop_words.contains(word)).collect();
+        // 与关键词索引写入时使用同一套分词规则
+        let keywords = keyword_index::tokenize(query_text);
         println!("[MemosAgent-Keyword] Extracted keywords: {:?}", keywords);
         keywords
     }
     
-    fn apply_dynamic_threshold(&self, points: Vec<ScoredPoint>) -> Vec<ScoredPoint> {
-        if points.is_empty() { return points; }
+    fn apply_dynamic_threshold(&self, points: Vec<ScoredMemo>, options: &RecallOptions) -> (Vec<ScoredMemo>, ThresholdTrace) {
+        let trace = |points: &[ScoredMemo], cut_at: Option<usize>, reason: &str| ThresholdTrace {
+            cut_at,
+            kept: points.len(),
+            reason: reason.to_string(),
+        };
+        if points.is_empty() {
+            let t = trace(&points, None, "no candidates");
+            return (points, t);
+        }
         let scores: Vec<f32> = points.iter().map(|p| p.score).collect();
-        if scores.len() == 1 { return if scores[0] > 0.01 { points } else { vec![] }; }
+        if scores.len() == 1 {
+            if scores[0] > options.min_single_score {
+                let t = trace(&points, None, "single candidate above min_single_score");
+                return (points, t);
+            }
+            return (Vec::new(), trace(&[], Some(0), "single candidate below min_single_score"));
+        }
+        if options.drop_ratio <= 0.0 {
+            let t = trace(&points, None, "dynamic threshold disabled");
+            return (points, t);
+        }
         let mut best_drop_index = 0;
         let mut max_drop = 0.0;
         for i in 1..scores.len() {
             let drop = scores[i-1] - scores[i];
             if drop > max_drop { max_drop = drop; best_drop_index = i; }
         }
-        if max_drop > scores[best_drop_index - 1] * 0.3 && best_drop_index > 0 {
+        if best_drop_index > 0 && max_drop > scores[best_drop_index - 1] * options.drop_ratio {
             println!("[MemosAgent-Threshold] Found score drop at index {}, truncating.", best_drop_index);
-            return points.into_iter().take(best_drop_index).collect();
+            let kept: Vec<ScoredMemo> = points.into_iter().take(best_drop_index).collect();
+            let reason = format!("score drop {:.4} exceeds {:.0}% of {:.4}", max_drop, options.drop_ratio * 100.0, scores[best_drop_index - 1]);
+            let t = trace(&kept, Some(best_drop_index), &reason);
+            return (kept, t);
         }
         println!("[MemosAgent-Threshold] No significant drop found, returning all points.");
-        points
+        let t = trace(&points, None, "no significant score drop");
+        (points, t)
     }
 }
 
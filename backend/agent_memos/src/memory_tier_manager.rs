@@ -1,25 +1,368 @@
 // in: agent_memos/src/memory_tier_manager.rs
 
-#[derive(Debug, PartialEq)]
+// 记忆分层：每条记忆持久化一个 tier（facts.tier 列，同时写入向量 payload 的 tier 字段）。
+// - 保存时由 determine_tier 按内容初判；
+// - recall 先在 Active 层检索，没有结果再回退到 Archive 层；
+// - TierPolicy 决定层级迁移：闲置一段时间且保持度（见 retention）已经衰减的 Active 记忆降级归档，被召回的 Archive 记忆升回 Active。
+
+use crate::db::DbPool;
+use crate::expiry::{self, Clock};
+use crate::outbox::{self, Indexer, OutboxOp};
+use crate::retention::{self, MemoStats, RetentionPolicy};
+use crate::vector_store::{fields, Condition, Filter};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use rusqlite::{params, OptionalExtension};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum MemoryTier {
     Active,
     Archive,
 }
 
+impl MemoryTier {
+    /// 召回时的检索顺序
+    pub const RECALL_ORDER: [MemoryTier; 2] = [MemoryTier::Active, MemoryTier::Archive];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MemoryTier::Active => "active",
+            MemoryTier::Archive => "archive",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "active" | "活跃" => Some(MemoryTier::Active),
+            "archive" | "archived" | "归档" => Some(MemoryTier::Archive),
+            _ => None,
+        }
+    }
+
+    /// 对应的向量库过滤条件。没有 tier 字段的旧点视为 Active，因此 Active 用 must_not 表达。
+    pub(crate) fn filter(&self) -> Filter {
+        match self {
+            MemoryTier::Active => Filter::must_not([Condition::equals(fields::TIER, MemoryTier::Archive.as_str())]),
+            MemoryTier::Archive => Filter::must([Condition::equals(fields::TIER, MemoryTier::Archive.as_str())]),
+        }
+    }
+}
+
 /// 根据记忆内容，决定其应被放入哪个层级。
 /// 这是未来可以持续优化的智能决策核心。
+/// 长文本不再因为长度被直接归档：它们会被分块索引（见 chunking），召回能命中其中的细节。
 pub fn determine_tier(content: &str) -> MemoryTier {
-    // 初版智能规则：基于关键词和内容长度的决策
+    // 初版智能规则：基于关键词的决策
     let archival_keywords = ["总结", "原理", "复盘", "思考", "报告", "长期规划"];
-    
-    let contains_archival_keyword = archival_keywords.iter().any(|&kw| content.contains(kw));
-    let is_very_long = content.chars().count() > 500;
 
-    if contains_archival_keyword || is_very_long {
-        println!("[TierManager] Content classified as 'Archive' due to keywords or length.");
+    if archival_keywords.iter().any(|&kw| content.contains(kw)) {
+        println!("[TierManager] Content classified as 'Archive' due to keywords.");
         MemoryTier::Archive
     } else {
         println!("[TierManager] Content classified as 'Active'.");
         MemoryTier::Active
     }
-}
\ No newline at end of file
+}
+
+/// 层级迁移策略
+#[derive(Debug, Clone)]
+pub struct TierPolicy {
+    /// Active 记忆至少连续这么多天未被召回（从未召回则从创建时算起）才会考虑降级；0 表示不自动降级
+    pub archive_after_idle_days: i64,
+    /// 闲置够久的 Active 记忆，保持度低于该值时降级为 Archive
+    pub archive_below_retention: f32,
+    /// 计算保持度的遗忘曲线参数
+    pub retention: RetentionPolicy,
+    /// Archive 记忆被召回后是否自动升回 Active
+    pub promote_on_access: bool,
+}
+
+impl Default for TierPolicy {
+    fn default() -> Self {
+        // 中等重要、从未召回的记忆约 90 天后归档；不重要的 30 天起即可归档，重要或常被召回的保留得更久
+        Self { archive_after_idle_days: 30, archive_below_retention: 0.5, retention: RetentionPolicy::default(), promote_on_access: true }
+    }
+}
+
+impl TierPolicy {
+    /// 可通过 MEMOS_ARCHIVE_AFTER_DAYS 调整最少闲置天数（0 表示不自动降级），MEMOS_ARCHIVE_BELOW_RETENTION 调整保持度阈值
+    pub fn from_env() -> Self {
+        let mut policy = Self { retention: RetentionPolicy::from_env(), ..Self::default() };
+        if let Some(days) = std::env::var("MEMOS_ARCHIVE_AFTER_DAYS").ok().and_then(|v| v.parse().ok()) {
+            policy.archive_after_idle_days = days;
+        }
+        if let Some(threshold) = std::env::var("MEMOS_ARCHIVE_BELOW_RETENTION").ok().and_then(|v| v.parse::<f32>().ok()) {
+            policy.archive_below_retention = threshold.clamp(0.0, 1.0);
+        }
+        policy
+    }
+
+    /// 按闲置时间与保持度评估一条记忆应迁往的层级，不需要迁移时返回 None
+    pub fn evaluate(&self, tier: MemoryTier, stats: &MemoStats, now: DateTime<Utc>) -> Option<MemoryTier> {
+        match tier {
+            MemoryTier::Active if self.archive_after_idle_days > 0 => {
+                let last_activity = stats.last_accessed_at.unwrap_or(stats.created_at).max(stats.created_at);
+                let idle = now - last_activity >= ChronoDuration::days(self.archive_after_idle_days);
+                (idle && self.retention.score(stats, now) < self.archive_below_retention).then_some(MemoryTier::Archive)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// 一次层级迁移
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TierChange {
+    pub id: i64,
+    pub from: MemoryTier,
+    pub to: MemoryTier,
+}
+
+/// 按层级列出记忆时的一行
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TieredMemo {
+    pub id: i64,
+    pub content: String,
+    pub tier: MemoryTier,
+    pub created_at: Option<String>,
+    pub last_accessed_at: Option<String>,
+    pub access_count: i64,
+    /// 重要度；未评估时为 None
+    pub importance: Option<f32>,
+    /// 当前的保持度；缺少创建时间时为 None
+    pub retention: Option<f32>,
+}
+
+/// 负责 facts.tier 的读写；层级变化通过发件箱同步到向量 payload
+pub(crate) struct TierManager {
+    pool: DbPool,
+    clock: Arc<dyn Clock>,
+    policy: TierPolicy,
+}
+
+impl TierManager {
+    pub(crate) fn new(pool: DbPool, clock: Arc<dyn Clock>, policy: TierPolicy) -> Self {
+        Self { pool, clock, policy }
+    }
+
+    pub(crate) fn policy(&self) -> &TierPolicy {
+        &self.policy
+    }
+
+    /// 手动设置层级；层级未变时返回 None
+    pub(crate) fn set_tier(&self, id: i64, tier: MemoryTier) -> Result<Option<TierChange>, anyhow::Error> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+        let current: Option<String> = tx.query_row("SELECT tier FROM facts WHERE id = ?1", [id], |row| row.get(0)).optional()?;
+        let current = current.ok_or_else(|| anyhow::anyhow!("Memo {} not found", id))?;
+        let from = MemoryTier::parse(&current).unwrap_or(MemoryTier::Active);
+        if from == tier {
+            return Ok(None);
+        }
+        tx.execute("UPDATE facts SET tier = ?1 WHERE id = ?2", params![tier.as_str(), id])?;
+        outbox::enqueue(&tx, id, OutboxOp::Upsert, &self.clock.now().to_rfc3339())?;
+        tx.commit()?;
+        println!("[TierManager] Memo {} moved from '{}' to '{}'.", id, from.as_str(), tier.as_str());
+        Ok(Some(TierChange { id, from, to: tier }))
+    }
+
+    /// 对全部 Active 记忆执行降级策略，返回发生的迁移
+    pub(crate) fn apply_policy(&self) -> Result<Vec<TierChange>, anyhow::Error> {
+        let now = self.clock.now();
+        let queued_at = now.to_rfc3339();
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+        let candidates: Vec<(i64, Option<MemoStats>)> = {
+            let mut stmt = tx.prepare("SELECT id, created_at, last_accessed_at, access_count, importance FROM facts WHERE tier = ?1")?;
+            let rows = stmt.query_map([MemoryTier::Active.as_str()], |row| Ok((row.get(0)?, retention::stats_from_row(row, 1)?)))?
+                .collect::<Result<Vec<_>, _>>()?;
+            rows
+        };
+
+        let mut changes = Vec::new();
+        for (id, stats) in candidates {
+            let Some(stats) = stats else { continue };
+            if let Some(to) = self.policy.evaluate(MemoryTier::Active, &stats, now) {
+                tx.execute("UPDATE facts SET tier = ?1 WHERE id = ?2", params![to.as_str(), id])?;
+                outbox::enqueue(&tx, id, OutboxOp::Upsert, &queued_at)?;
+                changes.push(TierChange { id, from: MemoryTier::Active, to });
+            }
+        }
+        tx.commit()?;
+        if !changes.is_empty() {
+            println!("[TierManager] Policy archived {} fading memo(s).", changes.len());
+        }
+        Ok(changes)
+    }
+
+    /// 记录一次召回命中；按策略把被命中的 Archive 记忆升回 Active，返回升级的 ID
+    pub(crate) fn record_access(&self, ids: &[i64]) -> Result<Vec<i64>, anyhow::Error> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let accessed_at = self.clock.now();
+        let now = expiry::to_db_timestamp(accessed_at);
+        let queued_at = accessed_at.to_rfc3339();
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+        let mut promoted = Vec::new();
+        for id in ids {
+            tx.execute(
+                "UPDATE facts SET access_count = access_count + 1, last_accessed_at = ?1 WHERE id = ?2",
+                params![now, id],
+            )?;
+            if self.policy.promote_on_access {
+                let changed = tx.execute(
+                    "UPDATE facts SET tier = ?1 WHERE id = ?2 AND tier = ?3",
+                    params![MemoryTier::Active.as_str(), id, MemoryTier::Archive.as_str()],
+                )?;
+                if changed > 0 {
+                    outbox::enqueue(&tx, *id, OutboxOp::Upsert, &queued_at)?;
+                    promoted.push(*id);
+                }
+            }
+        }
+        tx.commit()?;
+        if !promoted.is_empty() {
+            println!("[TierManager] Promoted recalled memo(s) back to 'active': {:?}", promoted);
+        }
+        Ok(promoted)
+    }
+
+    /// 按层级列出记忆（None 表示全部），最近创建的在前
+    pub(crate) fn list(&self, tier: Option<MemoryTier>, limit: usize) -> Result<Vec<TieredMemo>, anyhow::Error> {
+        let now = self.clock.now();
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, content, tier, created_at, last_accessed_at, access_count, importance FROM facts
+             WHERE ?1 IS NULL OR tier = ?1 ORDER BY id DESC LIMIT ?2",
+        )?;
+        let limit = limit.min(i64::MAX as usize) as i64;
+        let rows = stmt.query_map(params![tier.map(|t| t.as_str()), limit], |row| {
+            let tier: String = row.get(2)?;
+            let stats = retention::stats_from_row(row, 3)?;
+            Ok(TieredMemo {
+                id: row.get(0)?,
+                content: row.get(1)?,
+                tier: MemoryTier::parse(&tier).unwrap_or(MemoryTier::Active),
+                created_at: row.get(3)?,
+                last_accessed_at: row.get(4)?,
+                access_count: row.get(5)?,
+                importance: row.get::<_, Option<f64>>(6)?.map(|i| i as f32),
+                retention: stats.map(|s| self.policy.retention.score(&s, now)),
+            })
+        })?.collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+}
+
+/// 启动后台 worker，周期性地执行层级策略
+pub(crate) fn spawn_tier_worker(manager: Arc<TierManager>, indexer: Arc<Indexer>, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match manager.apply_policy() {
+                Ok(changes) => {
+                    for change in changes {
+                        indexer.flush_fact(change.id).await;
+                    }
+                }
+                Err(e) => eprintln!("[TierManager] Failed to apply tier policy: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expiry::ManualClock;
+    use crate::retention::DEFAULT_IMPORTANCE;
+    use crate::test_support::{migrated_pool, TestDir};
+    use chrono::TimeZone;
+
+    fn created() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()
+    }
+
+    fn stats(importance: f32, access_count: i64, last_accessed_days: Option<i64>) -> MemoStats {
+        MemoStats {
+            created_at: created(),
+            last_accessed_at: last_accessed_days.map(|d| created() + ChronoDuration::days(d)),
+            access_count,
+            importance,
+        }
+    }
+
+    /// 默认策略下最早在第几天（整天）被归档
+    fn archived_on(policy: &TierPolicy, stats: &MemoStats) -> Option<i64> {
+        (0..=1000).find(|day| policy.evaluate(MemoryTier::Active, stats, created() + ChronoDuration::days(*day)).is_some())
+    }
+
+    #[test]
+    fn archival_threshold_depends_on_importance_and_access() {
+        let policy = TierPolicy::default();
+        // 保持度降到 0.5 以下且闲置满 30 天：不重要的第 31 天、中等重要的第 91 天、最重要的第 151 天
+        assert_eq!(archived_on(&policy, &stats(0.0, 0, None)), Some(31));
+        assert_eq!(archived_on(&policy, &stats(DEFAULT_IMPORTANCE, 0, None)), Some(91));
+        assert_eq!(archived_on(&policy, &stats(1.0, 0, None)), Some(151));
+        // 常被召回的记忆保留得更久
+        let accessed = archived_on(&policy, &stats(DEFAULT_IMPORTANCE, 5, Some(0))).unwrap();
+        assert!(accessed > 91, "archived on day {}", accessed);
+    }
+
+    #[test]
+    fn recently_recalled_memos_are_not_archived() {
+        // 半衰期很短时保持度早已低于阈值，但 10 天前刚被召回过，闲置天数不够，仍不归档
+        let policy = TierPolicy { retention: RetentionPolicy { half_life_days: 2.0, ..RetentionPolicy::default() }, ..TierPolicy::default() };
+        let recalled = stats(0.0, 1, Some(190));
+        let now = created() + ChronoDuration::days(200);
+        assert!(policy.retention.score(&recalled, now) < policy.archive_below_retention);
+        assert_eq!(policy.evaluate(MemoryTier::Active, &recalled, now), None);
+        assert_eq!(policy.evaluate(MemoryTier::Active, &recalled, created() + ChronoDuration::days(220)), Some(MemoryTier::Archive));
+
+        // 已归档的不再评估；archive_after_idle_days 为 0 时不自动降级
+        let faded = stats(0.0, 0, None);
+        assert_eq!(policy.evaluate(MemoryTier::Active, &faded, now), Some(MemoryTier::Archive));
+        assert_eq!(policy.evaluate(MemoryTier::Archive, &faded, now), None);
+        let disabled = TierPolicy { archive_after_idle_days: 0, ..policy };
+        assert_eq!(disabled.evaluate(MemoryTier::Active, &faded, now), None);
+    }
+
+    #[test]
+    fn apply_policy_archives_fading_memos_and_recall_promotes_them() {
+        let dir = TestDir::new();
+        let pool = migrated_pool(&dir);
+        let ids: Vec<i64> = {
+            let conn = pool.get().unwrap();
+            [Some(0.0), None, Some(1.0)].into_iter().map(|importance: Option<f64>| {
+                conn.execute(
+                    "INSERT INTO facts (content, created_at, updated_at, importance) VALUES ('记忆', ?1, ?1, ?2)",
+                    params![created().to_rfc3339(), importance],
+                ).unwrap();
+                conn.last_insert_rowid()
+            }).collect()
+        };
+        let clock = Arc::new(ManualClock::new(created() + ChronoDuration::days(60)));
+        let manager = TierManager::new(pool.clone(), clock.clone(), TierPolicy::default());
+
+        let archived: Vec<i64> = manager.apply_policy().unwrap().into_iter().map(|c| c.id).collect();
+        assert_eq!(archived, vec![ids[0]]);
+        clock.advance(ChronoDuration::days(40));
+        let archived: Vec<i64> = manager.apply_policy().unwrap().into_iter().map(|c| c.id).collect();
+        assert_eq!(archived, vec![ids[1]]);
+
+        assert_eq!(manager.record_access(&[ids[0]]).unwrap(), vec![ids[0]]);
+        let tier: String = pool.get().unwrap().query_row("SELECT tier FROM facts WHERE id = ?1", [ids[0]], |row| row.get(0)).unwrap();
+        assert_eq!(tier, MemoryTier::Active.as_str());
+        assert_eq!(manager.list(Some(MemoryTier::Archive), 10).unwrap().iter().map(|m| m.id).collect::<Vec<_>>(), vec![ids[1]]);
+
+        // 手动设置层级，层级未变时返回 None
+        assert_eq!(manager.set_tier(ids[2], MemoryTier::Archive).unwrap().map(|c| c.to), Some(MemoryTier::Archive));
+        assert!(manager.set_tier(ids[2], MemoryTier::Archive).unwrap().is_none());
+        assert!(manager.set_tier(9999, MemoryTier::Active).is_err());
+    }
+}
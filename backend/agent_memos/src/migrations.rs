@@ -0,0 +1,400 @@
+// agent_memos/src/migrations.rs
+
+// SQLite schema 迁移：
+// - 当前版本号记录在 SQLite 自带的 PRAGMA user_version 中（0 表示从未迁移过，包括 V5.0 之前的老库）；
+// - MIGRATIONS 按版本号升序排列，启动时依次执行所有尚未应用的迁移；
+// - 每个迁移与版本号的更新在同一个事务里提交，失败则整体回滚，下次启动重试。
+// 新增表或列时，只需在末尾追加一个迁移，不要修改已经发布的迁移。
+
+use jieba_rs::Jieba;
+use rusqlite::{params, Connection, Transaction};
+use std::collections::HashSet;
+
+pub(crate) struct Migration {
+    pub version: i64,
+    pub description: &'static str,
+    pub apply: fn(&Transaction) -> Result<(), anyhow::Error>,
+}
+
+pub(crate) const MIGRATIONS: &[Migration] = &[
+    Migration { version: 1, description: "facts table (V5.0 columns)", apply: migrate_v1_facts },
+    Migration { version: 2, description: "index outbox", apply: migrate_v2_index_outbox },
+    Migration { version: 3, description: "index metadata", apply: migrate_v3_index_meta },
+    Migration { version: 4, description: "embedded vector store", apply: migrate_v4_embedded_vectors },
+    Migration { version: 5, description: "memory tiers and access tracking", apply: migrate_v5_tiers },
+    Migration { version: 6, description: "fact revision history", apply: migrate_v6_fact_revisions },
+    Migration { version: 7, description: "normalized content key for deduplication", apply: migrate_v7_content_key },
+    Migration { version: 8, description: "fact supersession", apply: migrate_v8_superseded_by },
+    Migration { version: 9, description: "keyword full-text index", apply: migrate_v9_keyword_index },
+    Migration { version: 10, description: "entity graph", apply: migrate_v10_entity_graph },
+    Migration { version: 11, description: "memo importance", apply: migrate_v11_importance },
+];
+
+/// 代码所支持的最新 schema 版本
+pub(crate) fn latest_version() -> i64 {
+    MIGRATIONS.last().map_or(0, |m| m.version)
+}
+
+pub(crate) fn current_version(conn: &Connection) -> Result<i64, anyhow::Error> {
+    Ok(conn.query_row("PRAGMA user_version", [], |row| row.get(0))?)
+}
+
+/// 把数据库升级到最新版本，返回本次实际应用的迁移数量
+pub(crate) fn run(conn: &mut Connection) -> Result<usize, anyhow::Error> {
+    let current = current_version(conn)?;
+    let latest = latest_version();
+    if current > latest {
+        return Err(anyhow::anyhow!(
+            "Database schema version {} is newer than this build supports ({}). Please upgrade memos_agent.",
+            current, latest
+        ));
+    }
+
+    let mut applied = 0;
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let tx = conn.transaction()?;
+        (migration.apply)(&tx).map_err(|e| {
+            anyhow::anyhow!("Migration v{} ({}) failed: {}", migration.version, migration.description, e)
+        })?;
+        // user_version 写在数据库文件头里，同样受事务保护
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+        println!("[MemosAgent-DB] Applied migration v{}: {}.", migration.version, migration.description);
+        applied += 1;
+    }
+    Ok(applied)
+}
+
+fn has_column(tx: &Transaction, table: &str, column: &str) -> Result<bool, anyhow::Error> {
+    let mut stmt = tx.prepare(&format!("PRAGMA table_info({})", table))?;
+    let names = stmt.query_map([], |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(names.iter().any(|n| n == column))
+}
+
+/// v1：facts 表。V5.0 之前的老库只有 id/content 等少数列，这里补齐缺失的列；
+/// ALTER TABLE 不允许非常量默认值，所以时间列先以 NULL 加入再回填。
+fn migrate_v1_facts(tx: &Transaction) -> Result<(), anyhow::Error> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS facts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            content TEXT NOT NULL,
+            metadata TEXT,
+            expires_at DATETIME,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        );",
+    )?;
+    for column in ["metadata", "expires_at"] {
+        if !has_column(tx, "facts", column)? {
+            tx.execute_batch(&format!("ALTER TABLE facts ADD COLUMN {} TEXT", column))?;
+        }
+    }
+    if !has_column(tx, "facts", "created_at")? {
+        tx.execute_batch(
+            "ALTER TABLE facts ADD COLUMN created_at DATETIME;
+             UPDATE facts SET created_at = CURRENT_TIMESTAMP WHERE created_at IS NULL;",
+        )?;
+    }
+    if !has_column(tx, "facts", "updated_at")? {
+        tx.execute_batch(
+            "ALTER TABLE facts ADD COLUMN updated_at DATETIME;
+             UPDATE facts SET updated_at = created_at WHERE updated_at IS NULL;",
+        )?;
+    }
+    Ok(())
+}
+
+/// v2：索引发件箱，记录尚未同步到向量库的操作，由后台 worker 重试
+fn migrate_v2_index_outbox(tx: &Transaction) -> Result<(), anyhow::Error> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS index_outbox (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            fact_id INTEGER NOT NULL,
+            op TEXT NOT NULL,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            last_error TEXT,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            next_attempt_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        );",
+    )?;
+    Ok(())
+}
+
+/// v3：索引元数据，记录生效的向量集合、嵌入模型与维度，以及进行中的重建进度
+fn migrate_v3_index_meta(tx: &Transaction) -> Result<(), anyhow::Error> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS index_meta (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );",
+    )?;
+    Ok(())
+}
+
+/// v4：内嵌向量存储的集合与点。此前由 EmbeddedVectorStore::new 以 IF NOT EXISTS 建表，已有这两张表的库可直接跳过
+fn migrate_v4_embedded_vectors(tx: &Transaction) -> Result<(), anyhow::Error> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS vector_collections (
+            name TEXT PRIMARY KEY,
+            dim INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS vector_points (
+            collection TEXT NOT NULL,
+            id INTEGER NOT NULL,
+            vector BLOB NOT NULL,
+            payload TEXT NOT NULL,
+            PRIMARY KEY (collection, id)
+        );",
+    )?;
+    Ok(())
+}
+
+/// v5：记忆分层（已有记忆默认为 active，由层级策略按年龄降级）与召回访问记录
+fn migrate_v5_tiers(tx: &Transaction) -> Result<(), anyhow::Error> {
+    tx.execute_batch(
+        "ALTER TABLE facts ADD COLUMN tier TEXT NOT NULL DEFAULT 'active';
+         ALTER TABLE facts ADD COLUMN access_count INTEGER NOT NULL DEFAULT 0;
+         ALTER TABLE facts ADD COLUMN last_accessed_at DATETIME;
+         CREATE INDEX IF NOT EXISTS idx_facts_tier ON facts(tier);",
+    )?;
+    Ok(())
+}
+
+/// v6：记忆修订历史。每次创建、修改、删除、恢复都记录一份变更后的快照（删除记录的是删除前的内容）；
+/// 已有记忆补一条 system 来源的 create 修订作为基线，撤销时不会把它们当作“刚才的修改”。
+fn migrate_v6_fact_revisions(tx: &Transaction) -> Result<(), anyhow::Error> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS fact_revisions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            fact_id INTEGER NOT NULL,
+            revision INTEGER NOT NULL,
+            op TEXT NOT NULL,
+            source TEXT NOT NULL DEFAULT 'user',
+            content TEXT NOT NULL,
+            metadata TEXT,
+            expires_at DATETIME,
+            tier TEXT NOT NULL DEFAULT 'active',
+            request TEXT,
+            reverted_by INTEGER,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE (fact_id, revision)
+        );
+        INSERT INTO fact_revisions (fact_id, revision, op, source, content, metadata, expires_at, tier, created_at)
+            SELECT id, 1, 'create', 'system', content, metadata, expires_at, tier, COALESCE(created_at, CURRENT_TIMESTAMP)
+            FROM facts;",
+    )?;
+    Ok(())
+}
+
+/// v7：规范化内容（与 dedupe::normalize 同义），保存前的精确去重改为按索引查询，并为已有记忆回填。
+/// 回填按 v7 发布时的规范化规则（去掉空白、标点，转小写），此后 dedupe 的改动不影响本迁移
+fn migrate_v7_content_key(tx: &Transaction) -> Result<(), anyhow::Error> {
+    tx.execute_batch(
+        "ALTER TABLE facts ADD COLUMN content_key TEXT;
+         CREATE INDEX IF NOT EXISTS idx_facts_content_key ON facts(content_key);",
+    )?;
+    let facts: Vec<(i64, String)> = {
+        let mut stmt = tx.prepare("SELECT id, content FROM facts")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?.collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+    for (id, content) in &facts {
+        let content_key: String = content.chars()
+            .filter(|c| !c.is_whitespace() && !c.is_ascii_punctuation() && !"，。！？；：、“”‘’（）《》…—".contains(*c))
+            .flat_map(|c| c.to_lowercase())
+            .collect();
+        tx.execute("UPDATE facts SET content_key = ?1 WHERE id = ?2", params![content_key, id])?;
+    }
+    Ok(())
+}
+
+/// v8：被新事实取代的记忆记录 superseded_by（取代它的记忆 ID）；修订快照同样保存该列，撤销时可恢复
+fn migrate_v8_superseded_by(tx: &Transaction) -> Result<(), anyhow::Error> {
+    tx.execute_batch(
+        "ALTER TABLE facts ADD COLUMN superseded_by INTEGER;
+         ALTER TABLE fact_revisions ADD COLUMN superseded_by INTEGER;",
+    )?;
+    Ok(())
+}
+
+/// v9：关键词全文索引（FTS5），内容为 jieba 分词结果，并为已有记忆回填。
+/// 回填按 v9 发布时的分词规则（搜索引擎模式、转小写、去停用词与纯标点），此后 keyword_index 的改动不影响本迁移
+fn migrate_v9_keyword_index(tx: &Transaction) -> Result<(), anyhow::Error> {
+    tx.execute_batch("CREATE VIRTUAL TABLE IF NOT EXISTS facts_fts USING fts5(tokens);")?;
+    let facts: Vec<(i64, String)> = {
+        let mut stmt = tx.prepare("SELECT id, content FROM facts")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?.collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+    if facts.is_empty() {
+        return Ok(());
+    }
+    let jieba = Jieba::new();
+    let stop_words: HashSet<String> = stop_words::get(stop_words::LANGUAGE::Chinese).into_iter().collect();
+    for (id, content) in &facts {
+        let tokens: Vec<String> = jieba.cut_for_search(content, true)
+            .into_iter()
+            .map(|s| s.trim().to_lowercase())
+            .filter(|w| w.chars().any(char::is_alphanumeric) && !stop_words.contains(w))
+            .collect();
+        tx.execute("INSERT INTO facts_fts (rowid, tokens) VALUES (?1, ?2)", params![id, tokens.join(" ")])?;
+    }
+    Ok(())
+}
+
+/// v10：实体图。entities 为规范后的实体，entity_aliases 把各种写法（含名称本身，按规范化后的 alias_key 比对）映射到实体，
+/// fact_entities 为记忆与实体的关联。NER 需要模型，迁移里不回填，已有记忆由 rebuild 补齐。
+fn migrate_v10_entity_graph(tx: &Transaction) -> Result<(), anyhow::Error> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS entities (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE TABLE IF NOT EXISTS entity_aliases (
+            alias_key TEXT PRIMARY KEY,
+            alias TEXT NOT NULL,
+            entity_id INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_entity_aliases_entity ON entity_aliases(entity_id);
+        CREATE TABLE IF NOT EXISTS fact_entities (
+            fact_id INTEGER NOT NULL,
+            entity_id INTEGER NOT NULL,
+            mention TEXT NOT NULL,
+            PRIMARY KEY (fact_id, entity_id)
+        );
+        CREATE INDEX IF NOT EXISTS idx_fact_entities_entity ON fact_entities(entity_id);",
+    )?;
+    Ok(())
+}
+
+/// v11：记忆重要度（0 到 1，NULL 表示未评估）及其来源（user / llm），与访问记录一起决定保持度
+fn migrate_v11_importance(tx: &Transaction) -> Result<(), anyhow::Error> {
+    tx.execute_batch(
+        "ALTER TABLE facts ADD COLUMN importance REAL;
+         ALTER TABLE facts ADD COLUMN importance_source TEXT;",
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TABLES: &[&str] = &[
+        "facts", "index_outbox", "index_meta", "vector_collections", "vector_points", "fact_revisions", "facts_fts",
+        "entities", "entity_aliases", "fact_entities",
+    ];
+
+    /// 只应用到 version（含）为止的迁移，得到该版本发布时的 schema
+    fn migrate_to(conn: &mut Connection, version: i64) {
+        for migration in MIGRATIONS.iter().filter(|m| m.version <= version) {
+            let tx = conn.transaction().unwrap();
+            (migration.apply)(&tx).unwrap();
+            tx.pragma_update(None, "user_version", migration.version).unwrap();
+            tx.commit().unwrap();
+        }
+    }
+
+    fn tables(conn: &Connection) -> Vec<String> {
+        let mut stmt = conn.prepare("SELECT name FROM sqlite_master WHERE type IN ('table', 'view')").unwrap();
+        let names = stmt.query_map([], |row| row.get(0)).unwrap().collect::<Result<Vec<String>, _>>().unwrap();
+        names
+    }
+
+    /// 升级到最新版本后的通用检查：版本号、表与列齐全，老记录及其内容仍在
+    fn assert_upgraded(conn: &mut Connection, from: i64, fact_id: i64) {
+        let applied = run(conn).unwrap();
+        assert_eq!(applied as i64, latest_version() - from, "from v{}", from);
+        assert_eq!(current_version(conn).unwrap(), latest_version());
+
+        let names = tables(conn);
+        for table in TABLES {
+            assert!(names.iter().any(|n| n == table), "table {} missing after upgrading from v{}", table, from);
+        }
+        let tx = conn.transaction().unwrap();
+        let columns = [
+            "metadata", "expires_at", "created_at", "updated_at", "tier", "access_count", "last_accessed_at",
+            "content_key", "superseded_by", "importance", "importance_source",
+        ];
+        for column in columns {
+            assert!(has_column(&tx, "facts", column).unwrap(), "facts.{} missing after upgrading from v{}", column, from);
+        }
+        drop(tx);
+
+        let (content, tier, created_at): (String, String, Option<String>) = conn
+            .query_row("SELECT content, tier, created_at FROM facts WHERE id = ?1", [fact_id], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .unwrap();
+        assert_eq!(content, "我的车停在B2-103");
+        assert_eq!(tier, "active");
+        assert!(created_at.is_some(), "created_at not backfilled when upgrading from v{}", from);
+
+        // 早于 v6 / v7 / v9 的库由迁移补齐修订基线、规范化内容与关键词索引
+        if from < 6 {
+            let op: String = conn
+                .query_row("SELECT op FROM fact_revisions WHERE fact_id = ?1 AND revision = 1", [fact_id], |row| row.get(0))
+                .unwrap();
+            assert_eq!(op, "create");
+        }
+        if from < 7 {
+            let content_key: Option<String> = conn.query_row("SELECT content_key FROM facts WHERE id = ?1", [fact_id], |row| row.get(0)).unwrap();
+            assert_eq!(content_key.as_deref(), Some("我的车停在b2103"));
+        }
+        if from < 9 {
+            let tokens: String = conn.query_row("SELECT tokens FROM facts_fts WHERE rowid = ?1", [fact_id], |row| row.get(0)).unwrap();
+            assert!(tokens.contains("停"), "tokens: {}", tokens);
+        }
+
+        // 再次运行不做任何事
+        assert_eq!(run(conn).unwrap(), 0);
+    }
+
+    #[test]
+    fn fresh_database_is_migrated_to_latest() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        assert_eq!(current_version(&conn).unwrap(), 0);
+        assert_eq!(run(&mut conn).unwrap(), MIGRATIONS.len());
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+        let names = tables(&conn);
+        assert!(TABLES.iter().all(|t| names.iter().any(|n| n == t)));
+    }
+
+    #[test]
+    fn pre_framework_database_is_upgraded() {
+        // V5.0 之前的老库：user_version 为 0，facts 只有 id 与 content
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE facts (id INTEGER PRIMARY KEY AUTOINCREMENT, content TEXT NOT NULL);
+             INSERT INTO facts (content) VALUES ('我的车停在B2-103');",
+        ).unwrap();
+        let id = conn.last_insert_rowid();
+        assert_upgraded(&mut conn, 0, id);
+    }
+
+    #[test]
+    fn every_released_schema_is_upgraded() {
+        for version in 1..latest_version() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            migrate_to(&mut conn, version);
+            assert_eq!(current_version(&conn).unwrap(), version);
+            conn.execute(
+                "INSERT INTO facts (content, metadata) VALUES ('我的车停在B2-103', '{\"tags\":[\"停车\"]}')",
+                [],
+            ).unwrap();
+            let id = conn.last_insert_rowid();
+            assert_upgraded(&mut conn, version, id);
+            let metadata: String = conn.query_row("SELECT metadata FROM facts WHERE id = ?1", [id], |row| row.get(0)).unwrap();
+            assert_eq!(metadata, "{\"tags\":[\"停车\"]}");
+        }
+    }
+
+    #[test]
+    fn newer_schema_is_rejected() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", latest_version() + 1).unwrap();
+        assert!(run(&mut conn).is_err());
+    }
+}
@@ -0,0 +1,17 @@
+// agent_memos/src/ner.rs
+
+// 实体识别抽象：索引、矛盾检测与实体扩展召回只通过 EntityExtractor 取得实体。
+// 生产环境使用 micromodels 的 ONNX NER 模型；测试中换成不依赖模型文件的实现。
+
+use micromodels::NerClassifier;
+use std::sync::Mutex;
+
+pub(crate) trait EntityExtractor: Send + Sync {
+    fn extract(&self, text: &str) -> Result<Vec<String>, anyhow::Error>;
+}
+
+impl EntityExtractor for Mutex<NerClassifier> {
+    fn extract(&self, text: &str) -> Result<Vec<String>, anyhow::Error> {
+        self.lock().unwrap().predict(text)
+    }
+}
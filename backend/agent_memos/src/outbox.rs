@@ -0,0 +1,625 @@
+// agent_memos/src/outbox.rs
+
+// 双存储一致性：SQLite 是事实的唯一来源，向量库只是它的索引。
+// 每次写 facts 时在同一事务里向 index_outbox 追加一条待办，随后由 Indexer 同步到向量库；
+// 同步失败的待办留在表里，由后台 worker 按退避策略重试。
+
+use crate::chunking;
+use crate::db::DbPool;
+use crate::embedding::EmbeddingProvider;
+use crate::entity_graph;
+use crate::expiry::{self, Clock};
+use crate::ner::EntityExtractor;
+use crate::tags;
+use crate::vector_store::{fields, merge_payload, Payload, VectorPoint, VectorStore};
+use chrono::Duration as ChronoDuration;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::json;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+const MAX_BACKOFF_SECS: i64 = 600;
+const WORKER_BATCH_SIZE: usize = 32;
+
+/// facts 表中建索引所需的列：content, created_at, updated_at, expires_at, tier, superseded_by, metadata
+type FactRow = (String, Option<String>, Option<String>, Option<String>, String, Option<i64>, Option<String>);
+
+/// 发件箱中的操作类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OutboxOp {
+    Upsert,
+    Delete,
+}
+
+impl OutboxOp {
+    fn as_str(&self) -> &'static str {
+        match self {
+            OutboxOp::Upsert => "upsert",
+            OutboxOp::Delete => "delete",
+        }
+    }
+}
+
+/// 在调用方的事务（或连接）中追加一条待办；now 为调用方从注入的 Clock 取得的 RFC 3339 时间，待办立即到期
+pub(crate) fn enqueue(conn: &Connection, fact_id: i64, op: OutboxOp, now: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO index_outbox (fact_id, op, created_at, next_attempt_at) VALUES (?1, ?2, ?3, ?3)",
+        params![fact_id, op.as_str(), now],
+    )?;
+    Ok(())
+}
+
+/// 一次一致性校验的结果
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct ReconcileReport {
+    /// SQLite 中存在、向量库中缺失的记忆 ID
+    pub missing_in_index: Vec<i64>,
+    /// 向量库中存在、SQLite 中已无对应行的点 ID
+    pub orphaned_points: Vec<i64>,
+    /// 父记忆已不存在，或序号超出父记忆当前块数的块点 ID（直接删除）
+    pub orphaned_chunks: Vec<i64>,
+    /// 本次成功修复的数量
+    pub repaired: usize,
+    /// 修复后仍留在发件箱中等待重试的数量
+    pub still_pending: usize,
+}
+
+/// 负责把 facts 表的状态同步到向量库
+pub(crate) struct Indexer {
+    pool: DbPool,
+    vector_store: Arc<dyn VectorStore>,
+    embedder: Arc<dyn EmbeddingProvider>,
+    entity_extractor: Arc<dyn EntityExtractor>,
+    clock: Arc<dyn Clock>,
+    /// 当前生效的向量集合
+    collection: RwLock<String>,
+    /// 重建索引期间的影子集合：写操作会同时落到这里，保证切换时不丢更新
+    shadow_collection: RwLock<Option<String>>,
+}
+
+impl Indexer {
+    pub(crate) fn new(
+        pool: DbPool,
+        vector_store: Arc<dyn VectorStore>,
+        embedder: Arc<dyn EmbeddingProvider>,
+        entity_extractor: Arc<dyn EntityExtractor>,
+        clock: Arc<dyn Clock>,
+        collection: &str,
+    ) -> Self {
+        Self {
+            pool,
+            vector_store,
+            embedder,
+            entity_extractor,
+            clock,
+            collection: RwLock::new(collection.to_string()),
+            shadow_collection: RwLock::new(None),
+        }
+    }
+
+    pub(crate) fn active_collection(&self) -> String {
+        self.collection.read().unwrap().clone()
+    }
+
+    /// 切换生效集合，返回被替换下来的旧集合名
+    pub(crate) fn switch_collection(&self, collection: &str) -> String {
+        std::mem::replace(&mut *self.collection.write().unwrap(), collection.to_string())
+    }
+
+    /// 重建索引期间的影子集合
+    pub(crate) fn shadow_collection(&self) -> Option<String> {
+        self.shadow_collection.read().unwrap().clone()
+    }
+
+    pub(crate) fn set_shadow_collection(&self, collection: Option<&str>) {
+        *self.shadow_collection.write().unwrap() = collection.map(|c| c.to_string());
+    }
+
+    /// 根据 SQLite 中的当前状态构造向量点，并按 NER 结果更新实体图；行已不存在或已过期时返回 None
+    async fn build_point(&self, fact_id: i64) -> Result<Option<VectorPoint>, anyhow::Error> {
+        let row: Option<FactRow> = {
+            let conn = self.pool.get()?;
+            conn.query_row(
+                "SELECT content, created_at, updated_at, expires_at, tier, superseded_by, metadata FROM facts WHERE id = ?1",
+                [fact_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?, row.get(6)?)),
+            ).optional()?
+        };
+        let Some((content, created_at, updated_at, expires_at, tier, superseded_by, metadata)) = row else { return Ok(None) };
+        let mut payload = Payload::new();
+        if let Some(expires_at) = expires_at {
+            payload.insert(fields::EXPIRES_AT.to_string(), json!(expires_at));
+            if expiry::is_expired(&payload, self.clock.now()) {
+                return Ok(None);
+            }
+        }
+
+        let entities: Vec<String> = self.entity_extractor.extract(&content)?;
+        println!("[MemosAgent-NER] Extracted entities: {:?}", entities);
+        {
+            let conn = self.pool.get()?;
+            entity_graph::link(&conn, fact_id, &entities, &self.clock.now().to_rfc3339())?;
+        }
+        let vector = self.embedder.embed(&content).await?;
+        payload.insert(fields::CONTENT.to_string(), json!(content));
+        payload.insert(fields::ENTITIES.to_string(), json!(entities));
+        payload.insert(fields::TIER.to_string(), json!(tier));
+        if let Some(created_at) = created_at {
+            payload.insert(fields::CREATED_AT.to_string(), json!(created_at));
+        }
+        if let Some(updated_at) = updated_at {
+            payload.insert(fields::UPDATED_AT.to_string(), json!(updated_at));
+        }
+        if let Some(superseded_by) = superseded_by {
+            payload.insert(fields::SUPERSEDED_BY.to_string(), json!(superseded_by));
+        }
+        let tags = tags::from_metadata(metadata.as_deref());
+        if !tags.is_empty() {
+            payload.insert(fields::TAGS.to_string(), json!(tags));
+        }
+        Ok(Some(VectorPoint { id: fact_id, vector, payload }))
+    }
+
+    /// 长记忆的块点：每块单独向量化，payload 沿用父记忆的过滤字段（实体除外），另记父记忆 ID 与块序号
+    async fn build_chunk_points(&self, parent: &VectorPoint) -> Result<Vec<VectorPoint>, anyhow::Error> {
+        let content = parent.payload.get(fields::CONTENT).and_then(|v| v.as_str()).unwrap_or_default();
+        let chunks = chunking::split(content);
+        let mut points = Vec::with_capacity(chunks.len());
+        for chunk in chunks {
+            let mut payload = parent.payload.clone();
+            payload.remove(fields::ENTITIES);
+            payload.insert(fields::CONTENT.to_string(), json!(chunk.text));
+            payload.insert(fields::PARENT_ID.to_string(), json!(parent.id));
+            payload.insert(fields::CHUNK_INDEX.to_string(), json!(chunk.index));
+            let vector = self.embedder.embed(&chunk.text).await?;
+            points.push(VectorPoint { id: chunking::chunk_point_id(parent.id, chunk.index), vector, payload });
+        }
+        Ok(points)
+    }
+
+    /// 把某条记忆同步到指定集合：行存在则 upsert（长记忆连同它的块点），不存在则删除对应的点
+    pub(crate) async fn index_into(&self, collection: &str, fact_id: i64) -> Result<(), anyhow::Error> {
+        // 上次写入该集合时的块数，多出来的块点（内容变短或被删除）需要清理
+        let existing = self.vector_store.get_payload(collection, fact_id).await?;
+        let previous_chunks = chunking::chunk_count(existing.as_ref());
+        match self.build_point(fact_id).await? {
+            Some(mut point) => {
+                let chunk_points = self.build_chunk_points(&point).await?;
+                let chunk_count = chunk_points.len();
+                if chunk_count > 0 {
+                    point.payload.insert(fields::CHUNK_COUNT.to_string(), json!(chunk_count));
+                }
+                // 与已有 payload 合并：受管字段整体重算（包括重新跑 NER），其他写入方添加的字段保留
+                point.payload = merge_payload(existing, point.payload);
+                let mut points = vec![point];
+                points.extend(chunk_points);
+                self.vector_store.upsert(collection, points).await?;
+                if previous_chunks > chunk_count {
+                    self.vector_store.delete(collection, &chunking::chunk_point_ids(fact_id, chunk_count, previous_chunks)).await?;
+                }
+                println!("[MemosAgent-Outbox] Indexed fact {} ({} chunk(s)) into '{}'.", fact_id, chunk_count, collection);
+            }
+            None => {
+                {
+                    let conn = self.pool.get()?;
+                    entity_graph::unlink(&conn, fact_id)?;
+                }
+                let mut ids = vec![fact_id];
+                ids.extend(chunking::chunk_point_ids(fact_id, 0, previous_chunks));
+                self.vector_store.delete(collection, &ids).await?;
+                println!("[MemosAgent-Outbox] Removed fact {} from '{}'.", fact_id, collection);
+            }
+        }
+        Ok(())
+    }
+
+    /// 把某条记忆在 SQLite 中的当前状态同步到向量库。
+    /// 由于总是读取最新状态，重复执行是幂等的。
+    async fn sync_fact(&self, fact_id: i64) -> Result<(), anyhow::Error> {
+        if let Some(shadow) = self.shadow_collection() {
+            // 影子集合写失败不影响主流程，重建结束前会被游标再次覆盖
+            if let Err(e) = self.index_into(&shadow, fact_id).await {
+                eprintln!("[MemosAgent-Outbox] Failed to mirror fact {} into '{}': {}", fact_id, shadow, e);
+            }
+        }
+        self.index_into(&self.active_collection(), fact_id).await
+    }
+
+    /// 处理某条记忆的所有待办（写入后立即调用）。失败不会向上抛出，只记录下来留给 worker 重试。
+    pub(crate) async fn flush_fact(&self, fact_id: i64) -> bool {
+        let pending = match self.pending_entries(Some(fact_id), usize::MAX) {
+            Ok(entries) => entries,
+            Err(e) => {
+                eprintln!("[MemosAgent-Outbox] Failed to read outbox for fact {}: {}", fact_id, e);
+                return false;
+            }
+        };
+        let Some(&(last_entry_id, _)) = pending.last() else { return true };
+        self.process(fact_id, last_entry_id).await
+    }
+
+    /// 处理到期的待办，返回成功处理的记忆数
+    pub(crate) async fn drain(&self) -> Result<usize, anyhow::Error> {
+        let due = self.pending_entries(None, WORKER_BATCH_SIZE)?;
+        // 同一条记忆的多条待办只需同步一次，以最新的一条为准
+        let mut latest: Vec<(i64, i64)> = Vec::new();
+        let mut seen = HashSet::new();
+        for (entry_id, fact_id) in due.into_iter().rev() {
+            if seen.insert(fact_id) {
+                latest.push((entry_id, fact_id));
+            }
+        }
+        let mut processed = 0;
+        for (entry_id, fact_id) in latest {
+            if self.process(fact_id, entry_id).await {
+                processed += 1;
+            }
+        }
+        Ok(processed)
+    }
+
+    async fn process(&self, fact_id: i64, up_to_entry_id: i64) -> bool {
+        match self.sync_fact(fact_id).await {
+            Ok(()) => {
+                if let Err(e) = self.complete(fact_id, up_to_entry_id) {
+                    eprintln!("[MemosAgent-Outbox] Failed to clear outbox for fact {}: {}", fact_id, e);
+                }
+                true
+            }
+            Err(e) => {
+                eprintln!("[MemosAgent-Outbox] Sync failed for fact {}: {}. Will retry later.", fact_id, e);
+                if let Err(db_err) = self.record_failure(fact_id, &e.to_string()) {
+                    eprintln!("[MemosAgent-Outbox] Failed to record failure for fact {}: {}", fact_id, db_err);
+                }
+                false
+            }
+        }
+    }
+
+    /// 读取待办 (entry_id, fact_id)，按写入顺序排列；未指定 fact_id 时只返回已到重试时间的
+    fn pending_entries(&self, fact_id: Option<i64>, limit: usize) -> Result<Vec<(i64, i64)>, anyhow::Error> {
+        let conn = self.pool.get()?;
+        let limit = limit.min(i64::MAX as usize) as i64;
+        let rows = match fact_id {
+            Some(id) => {
+                let mut stmt = conn.prepare("SELECT id, fact_id FROM index_outbox WHERE fact_id = ?1 ORDER BY id LIMIT ?2")?;
+                let rows = stmt.query_map(params![id, limit], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .collect::<Result<Vec<_>, _>>()?;
+                rows
+            }
+            None => {
+                let now = self.clock.now().to_rfc3339();
+                let mut stmt = conn.prepare("SELECT id, fact_id FROM index_outbox WHERE next_attempt_at <= ?1 ORDER BY id LIMIT ?2")?;
+                let rows = stmt.query_map(params![now, limit], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .collect::<Result<Vec<_>, _>>()?;
+                rows
+            }
+        };
+        Ok(rows)
+    }
+
+    fn complete(&self, fact_id: i64, up_to_entry_id: i64) -> Result<(), anyhow::Error> {
+        let conn = self.pool.get()?;
+        conn.execute("DELETE FROM index_outbox WHERE fact_id = ?1 AND id <= ?2", params![fact_id, up_to_entry_id])?;
+        Ok(())
+    }
+
+    /// 记录失败并按指数退避推迟下一次重试（上限 10 分钟）
+    fn record_failure(&self, fact_id: i64, error: &str) -> Result<(), anyhow::Error> {
+        let conn = self.pool.get()?;
+        let attempts: i64 = conn.query_row(
+            "SELECT COALESCE(MAX(attempts), 0) FROM index_outbox WHERE fact_id = ?1",
+            [fact_id],
+            |row| row.get(0),
+        )?;
+        let backoff = (5i64 << attempts.min(10)).min(MAX_BACKOFF_SECS);
+        let next_attempt_at = (self.clock.now() + ChronoDuration::seconds(backoff)).to_rfc3339();
+        conn.execute(
+            "UPDATE index_outbox SET attempts = attempts + 1, last_error = ?1, next_attempt_at = ?2 WHERE fact_id = ?3",
+            params![error, next_attempt_at, fact_id],
+        )?;
+        Ok(())
+    }
+
+    /// 发件箱中仍未完成的待办数量
+    pub(crate) fn pending_count(&self) -> Result<usize, anyhow::Error> {
+        let conn = self.pool.get()?;
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM index_outbox", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    /// 找出应当删除的块点：父记忆不在 fact_ids 中（已删除或已过期），或序号不小于父记忆点记录的块数
+    async fn orphaned_chunks(&self, collection: &str, fact_ids: &HashSet<i64>) -> Result<Vec<i64>, anyhow::Error> {
+        let mut by_parent: BTreeMap<i64, Vec<(i64, usize)>> = BTreeMap::new();
+        for id in self.vector_store.list_ids(collection).await? {
+            if let Some((parent_id, index)) = chunking::split_chunk_point_id(id) {
+                by_parent.entry(parent_id).or_default().push((id, index));
+            }
+        }
+        let mut orphaned = Vec::new();
+        for (parent_id, chunks) in by_parent {
+            let keep = if fact_ids.contains(&parent_id) {
+                chunking::chunk_count(self.vector_store.get_payload(collection, parent_id).await?.as_ref())
+            } else {
+                0
+            };
+            orphaned.extend(chunks.into_iter().filter(|(_, index)| *index >= keep).map(|(id, _)| id));
+        }
+        Ok(orphaned)
+    }
+
+    /// 对比 SQLite 与向量库：缺失的补建索引，孤立的点（包括父记忆已不存在的块点）删除
+    pub(crate) async fn reconcile(&self) -> Result<ReconcileReport, anyhow::Error> {
+        let collection = self.active_collection();
+        println!("[MemosAgent-Reconcile] Comparing SQLite facts with vector collection '{}'...", collection);
+        let fact_ids: HashSet<i64> = {
+            let conn = self.pool.get()?;
+            // 已过期的记忆不应出现在索引中
+            let now = expiry::to_db_timestamp(self.clock.now());
+            let mut stmt = conn.prepare("SELECT id FROM facts WHERE expires_at IS NULL OR expires_at > ?1")?;
+            let ids = stmt.query_map([now], |row| row.get(0))?.collect::<Result<HashSet<i64>, _>>()?;
+            ids
+        };
+        // 块点不单独参与比对，记忆本身修复完之后再按父记忆清理
+        let point_ids: HashSet<i64> = self.vector_store.list_ids(&collection).await?
+            .into_iter()
+            .filter(|id| !chunking::is_chunk_point(*id))
+            .collect();
+
+        let mut report = ReconcileReport {
+            missing_in_index: fact_ids.difference(&point_ids).copied().collect(),
+            orphaned_points: point_ids.difference(&fact_ids).copied().collect(),
+            ..Default::default()
+        };
+        report.missing_in_index.sort_unstable();
+        report.orphaned_points.sort_unstable();
+        println!(
+            "[MemosAgent-Reconcile] {} fact(s) missing from index, {} orphaned point(s).",
+            report.missing_in_index.len(), report.orphaned_points.len()
+        );
+
+        {
+            let conn = self.pool.get()?;
+            let now = self.clock.now().to_rfc3339();
+            for id in &report.missing_in_index {
+                enqueue(&conn, *id, OutboxOp::Upsert, &now)?;
+            }
+            for id in &report.orphaned_points {
+                enqueue(&conn, *id, OutboxOp::Delete, &now)?;
+            }
+        }
+        for id in report.missing_in_index.iter().chain(report.orphaned_points.iter()) {
+            if self.flush_fact(*id).await {
+                report.repaired += 1;
+            }
+        }
+
+        report.orphaned_chunks = self.orphaned_chunks(&collection, &fact_ids).await?;
+        if !report.orphaned_chunks.is_empty() {
+            println!("[MemosAgent-Reconcile] Removing {} orphaned chunk point(s).", report.orphaned_chunks.len());
+            match self.vector_store.delete(&collection, &report.orphaned_chunks).await {
+                Ok(()) => report.repaired += report.orphaned_chunks.len(),
+                Err(e) => eprintln!("[MemosAgent-Reconcile] Failed to remove orphaned chunk points: {}", e),
+            }
+        }
+        report.still_pending = self.pending_count()?;
+        println!("[MemosAgent-Reconcile] Repaired {} item(s); {} outbox entrie(s) still pending.", report.repaired, report.still_pending);
+        Ok(report)
+    }
+}
+
+/// 启动后台 worker，周期性地重试发件箱中到期的待办
+pub(crate) fn spawn_worker(indexer: Arc<Indexer>, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match indexer.drain().await {
+                Ok(0) => {}
+                Ok(n) => println!("[MemosAgent-Outbox] Worker synced {} pending fact(s).", n),
+                Err(e) => eprintln!("[MemosAgent-Outbox] Worker failed to drain outbox: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embedding::HashingEmbeddingProvider;
+    use crate::expiry::ManualClock;
+    use crate::test_support::{migrated_pool, test_ner, TestDir};
+    use crate::vector_store::EmbeddedVectorStore;
+    use chrono::{TimeZone, Utc};
+
+    const COLLECTION: &str = "memos_test";
+
+    async fn test_indexer(dir: &TestDir) -> (Indexer, Arc<ManualClock>) {
+        let pool = migrated_pool(dir);
+        let store = Arc::new(EmbeddedVectorStore::new(pool.clone()).unwrap());
+        store.ensure_collection(COLLECTION, 512).await.unwrap();
+        let clock = Arc::new(ManualClock::new(Utc.with_ymd_and_hms(2025, 3, 10, 9, 0, 0).unwrap()));
+        let indexer = Indexer::new(pool, store, Arc::new(HashingEmbeddingProvider::default()), test_ner(), clock.clone(), COLLECTION);
+        (indexer, clock)
+    }
+
+    fn insert_fact(indexer: &Indexer, content: &str, created_at: &str) -> i64 {
+        let conn = indexer.pool.get().unwrap();
+        conn.execute(
+            "INSERT INTO facts (content, created_at, updated_at) VALUES (?1, ?2, ?2)",
+            params![content, created_at],
+        ).unwrap();
+        conn.last_insert_rowid()
+    }
+
+    async fn payload(indexer: &Indexer, id: i64) -> Payload {
+        indexer.vector_store.get_payload(COLLECTION, id).await.unwrap().unwrap()
+    }
+
+    fn expected(value: serde_json::Value) -> Payload {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[tokio::test]
+    async fn payload_is_stable_across_save_update_and_resave() {
+        let dir = TestDir::new();
+        let (indexer, _) = test_indexer(&dir).await;
+        let id = insert_fact(&indexer, "周五晚上和小李吃火锅", "2025-03-10T09:00:00+00:00");
+        {
+            let conn = indexer.pool.get().unwrap();
+            tags::set(&conn, id, &["聚餐".to_string()]).unwrap();
+        }
+
+        // 保存
+        indexer.index_into(COLLECTION, id).await.unwrap();
+        let saved = payload(&indexer, id).await;
+        assert_eq!(saved, expected(json!({
+            "content": "周五晚上和小李吃火锅",
+            "entities": ["小李", "火锅"],
+            "tier": "active",
+            "created_at": "2025-03-10T09:00:00+00:00",
+            "updated_at": "2025-03-10T09:00:00+00:00",
+            "tags": ["聚餐"],
+        })));
+
+        // 原样重新保存：payload 逐字段不变
+        indexer.index_into(COLLECTION, id).await.unwrap();
+        assert_eq!(payload(&indexer, id).await, saved);
+
+        // 其他写入方添加的字段在更新后保留；受管字段按新状态整体重算，清空的列对应字段消失
+        let mut with_extra = saved.clone();
+        with_extra.insert("source".to_string(), json!("import"));
+        indexer.vector_store.upsert(COLLECTION, vec![VectorPoint {
+            id,
+            vector: HashingEmbeddingProvider::default().embed_sync("周五晚上和小李吃火锅"),
+            payload: with_extra,
+        }]).await.unwrap();
+        {
+            let conn = indexer.pool.get().unwrap();
+            conn.execute(
+                "UPDATE facts SET content = '周六中午和小王吃火锅', updated_at = '2025-03-11T12:00:00+00:00' WHERE id = ?1",
+                [id],
+            ).unwrap();
+            tags::set(&conn, id, &[]).unwrap();
+        }
+        indexer.index_into(COLLECTION, id).await.unwrap();
+        let updated = payload(&indexer, id).await;
+        assert_eq!(updated, expected(json!({
+            "content": "周六中午和小王吃火锅",
+            "entities": ["小王", "火锅"],
+            "tier": "active",
+            "created_at": "2025-03-10T09:00:00+00:00",
+            "updated_at": "2025-03-11T12:00:00+00:00",
+            "source": "import",
+        })));
+
+        // 更新后再次保存同样幂等
+        indexer.index_into(COLLECTION, id).await.unwrap();
+        assert_eq!(payload(&indexer, id).await, updated);
+    }
+
+    #[test]
+    fn merge_replaces_every_managed_field() {
+        let mut existing = Payload::new();
+        for field in fields::MANAGED {
+            existing.insert(field.to_string(), json!("stale"));
+        }
+        existing.insert("source".to_string(), json!("import"));
+        let fresh = expected(json!({ "content": "新内容", "tier": "archived" }));
+        assert_eq!(merge_payload(Some(existing), fresh.clone()), expected(json!({
+            "content": "新内容",
+            "tier": "archived",
+            "source": "import",
+        })));
+        assert_eq!(merge_payload(None, fresh.clone()), fresh);
+    }
+
+    #[tokio::test]
+    async fn expired_or_deleted_facts_are_removed() {
+        let dir = TestDir::new();
+        let (indexer, clock) = test_indexer(&dir).await;
+        let id = insert_fact(&indexer, "今天停车在B2-103", "2025-03-10T09:00:00+00:00");
+        {
+            let conn = indexer.pool.get().unwrap();
+            conn.execute("UPDATE facts SET expires_at = '2025-03-10 23:59:59' WHERE id = ?1", [id]).unwrap();
+        }
+        indexer.index_into(COLLECTION, id).await.unwrap();
+        assert_eq!(payload(&indexer, id).await.get(fields::EXPIRES_AT), Some(&json!("2025-03-10 23:59:59")));
+
+        clock.advance(ChronoDuration::days(1));
+        indexer.index_into(COLLECTION, id).await.unwrap();
+        assert!(indexer.vector_store.list_ids(COLLECTION).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn retry_backoff_follows_the_injected_clock() {
+        let dir = TestDir::new();
+        let (indexer, clock) = test_indexer(&dir).await;
+        let id = insert_fact(&indexer, "今天停车在B2-103", "2025-03-10T09:00:00+00:00");
+        {
+            let conn = indexer.pool.get().unwrap();
+            enqueue(&conn, id, OutboxOp::Upsert, &clock.now().to_rfc3339()).unwrap();
+        }
+        assert_eq!(indexer.pending_entries(None, 10).unwrap().len(), 1);
+
+        // 第一次失败推迟 5 秒，第二次 10 秒
+        for backoff in [5, 10] {
+            indexer.record_failure(id, "embedding service unavailable").unwrap();
+            clock.advance(ChronoDuration::seconds(backoff - 1));
+            assert!(indexer.pending_entries(None, 10).unwrap().is_empty());
+            clock.advance(ChronoDuration::seconds(1));
+            assert_eq!(indexer.pending_entries(None, 10).unwrap().len(), 1);
+        }
+
+        assert_eq!(indexer.drain().await.unwrap(), 1);
+        assert_eq!(indexer.pending_count().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn reconcile_removes_chunks_of_lost_parents() {
+        let dir = TestDir::new();
+        let (indexer, _) = test_indexer(&dir).await;
+        let long = "今天和小李讨论了下个季度的预算安排以及人员调整方案。".repeat(30);
+        let kept = insert_fact(&indexer, &long, "2025-03-10T09:00:00+00:00");
+        let lost = insert_fact(&indexer, &long.replace("小李", "小王"), "2025-03-10T09:00:00+00:00");
+        let dropped = insert_fact(&indexer, &long.replace("预算", "采购"), "2025-03-10T09:00:00+00:00");
+        for id in [kept, lost, dropped] {
+            indexer.index_into(COLLECTION, id).await.unwrap();
+        }
+        let chunks = chunking::chunk_count(Some(&payload(&indexer, kept).await));
+        assert!(chunks > 1);
+        assert_eq!(indexer.vector_store.list_ids(COLLECTION).await.unwrap().len(), 3 * (chunks + 1));
+
+        // lost：行被删除但删除没有同步到向量库；dropped：行与父记忆点都没了，只剩块点；
+        // kept：多出一个超出块数的残留块点
+        {
+            let conn = indexer.pool.get().unwrap();
+            conn.execute("DELETE FROM facts WHERE id IN (?1, ?2)", params![lost, dropped]).unwrap();
+        }
+        indexer.vector_store.delete(COLLECTION, &[dropped]).await.unwrap();
+        let stale = chunking::chunk_point_id(kept, chunks + 3);
+        indexer.vector_store.upsert(COLLECTION, vec![VectorPoint {
+            id: stale,
+            vector: HashingEmbeddingProvider::default().embed_sync("残留"),
+            payload: Payload::new(),
+        }]).await.unwrap();
+
+        let report = indexer.reconcile().await.unwrap();
+        assert!(report.missing_in_index.is_empty());
+        assert_eq!(report.orphaned_points, vec![lost]);
+        let mut expected_chunks = chunking::chunk_point_ids(dropped, 0, chunks);
+        expected_chunks.push(stale);
+        expected_chunks.sort_unstable();
+        let mut orphaned_chunks = report.orphaned_chunks.clone();
+        orphaned_chunks.sort_unstable();
+        assert_eq!(orphaned_chunks, expected_chunks);
+        assert_eq!(report.still_pending, 0);
+
+        let mut remaining = indexer.vector_store.list_ids(COLLECTION).await.unwrap();
+        remaining.sort_unstable();
+        let mut expected = vec![kept];
+        expected.extend(chunking::chunk_point_ids(kept, 0, chunks));
+        assert_eq!(remaining, expected);
+    }
+}
@@ -1,41 +1,73 @@
 // agent_memos/src/query_expander.rs
 
-use std::collections::HashMap;
+// 查询扩展：同义词替换 + 关键词重组。
+// 同义词来自数据目录下的 synonyms.tsv：每行“词<TAB>同义词1<TAB>同义词2…”，# 开头为注释，同一个词可以出现在多行。
+// 文件不存在时写入内置的几组同义词作为起点；文件被外部修改后，下一次扩展时自动重新加载。
+// 数据目录下若有 dict.txt（jieba 词典格式），会作为自定义分词词典加载，与 Classifier::load 的做法一致。
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::SystemTime;
 use jieba_rs::Jieba;
 
+const SYNONYMS_FILE: &str = "synonyms.tsv";
+const DICT_FILE: &str = "dict.txt";
+
+// 首次运行时写入的同义词
+const DEFAULT_SYNONYMS: &[(&str, &[&str])] = &[
+    ("会议", &["周会", "讨论会"]),
+    ("喜欢", &["偏好", "最爱"]),
+    ("优点", &["优势", "好处"]),
+    ("如何", &["怎样", "怎么"]),
+    ("运作", &["工作", "运行"]),
+];
+
+// 当前生效的分词器与同义词表，以及加载时两个文件的修改时间
+struct Loaded {
+    jieba: Jieba,
+    synonym_map: BTreeMap<String, Vec<String>>,
+    stamps: (Option<SystemTime>, Option<SystemTime>),
+}
+
 // 定义查询扩展器的结构体
 pub struct QueryExpander {
-    jieba: Jieba,
-    synonym_map: HashMap<String, Vec<String>>,
+    synonyms_path: PathBuf,
+    dict_path: PathBuf,
+    state: RwLock<Loaded>,
 }
 
 impl QueryExpander {
-    // 创建一个新的 QueryExpander 实例
-    pub fn new() -> Self {
-        let mut synonym_map = HashMap::new();
-        // 初始化一个简单的、硬编码的同义词词典
-        // 未来这部分可以从外部文件加载
-        synonym_map.insert("会议".to_string(), vec!["周会".to_string(), "讨论会".to_string()]);
-        synonym_map.insert("喜欢".to_string(), vec!["偏好".to_string(), "最爱".to_string()]);
-        synonym_map.insert("优点".to_string(), vec!["优势".to_string(), "好处".to_string()]);
-        synonym_map.insert("如何".to_string(), vec!["怎样".to_string(), "怎么".to_string()]);
-        synonym_map.insert("运作".to_string(), vec!["工作".to_string(), "运行".to_string()]);
-
-        Self {
-            jieba: Jieba::new(),
-            synonym_map,
+    // 从数据目录加载同义词与自定义词典
+    pub fn new(data_dir: &Path) -> Result<Self, anyhow::Error> {
+        let synonyms_path = data_dir.join(SYNONYMS_FILE);
+        let dict_path = data_dir.join(DICT_FILE);
+        if !synonyms_path.exists() {
+            let mut text = String::from("# 同义词：每行“词<TAB>同义词1<TAB>同义词2…”，修改后自动生效\n");
+            for (word, synonyms) in DEFAULT_SYNONYMS {
+                text.push_str(&format!("{}\t{}\n", word, synonyms.join("\t")));
+            }
+            fs::write(&synonyms_path, text)?;
+            println!("[QueryExpander] Created default synonyms file at {:?}", synonyms_path);
         }
+        let state = load(&synonyms_path, &dict_path)?;
+        println!("[QueryExpander] Loaded {} synonym entries.", state.synonym_map.len());
+        Ok(Self { synonyms_path, dict_path, state: RwLock::new(state) })
     }
 
     // 核心的查询扩展方法
     pub fn expand(&self, original_query: &str) -> Vec<String> {
         println!("[QueryExpander] Expanding query: '{}'", original_query);
+        self.reload_if_changed();
+        let state = self.state.read().unwrap();
         let mut expansions = vec![original_query.to_string()];
 
         // --- 1. 同义词扩展 ---
-        let tokens = self.jieba.cut(original_query, false); // 使用精确模式分词
+        let tokens = state.jieba.cut(original_query, false); // 使用精确模式分词
         for token in &tokens {
-            if let Some(synonyms) = self.synonym_map.get(*token) {
+            if let Some(synonyms) = state.synonym_map.get(*token) {
                 for synonym in synonyms {
                     // 生成一个新的查询，其中一个词被同义词替换
                     let new_query = original_query.replace(token, synonym);
@@ -48,11 +80,11 @@ impl QueryExpander {
 
         // --- 2. 关键词重组扩展 ---
         // 我们复用 MemosAgent 中的 extract_keywords 逻辑，但在这里简化实现
-        let keywords: Vec<&str> = self.jieba.cut_for_search(original_query, true)
+        let keywords: Vec<&str> = state.jieba.cut_for_search(original_query, true)
             .into_iter()
             .filter(|k| !is_stop_word(k)) // 使用一个简单的停用词过滤函数
             .collect();
-        
+
         if !keywords.is_empty() {
             let keyword_query = keywords.join(" ");
             if !expansions.contains(&keyword_query) {
@@ -63,10 +95,139 @@ impl QueryExpander {
         println!("[QueryExpander] Generated expansions: {:?}", expansions);
         expansions
     }
+
+    // 当前的同义词表（按词排序）
+    pub fn synonyms(&self) -> BTreeMap<String, Vec<String>> {
+        self.reload_if_changed();
+        self.state.read().unwrap().synonym_map.clone()
+    }
+
+    // 为 word 添加一个同义词（追加到文件末尾）；已存在时返回 false
+    pub fn add_synonym(&self, word: &str, synonym: &str) -> Result<bool, anyhow::Error> {
+        let (word, synonym) = (validate_term(word)?, validate_term(synonym)?);
+        if word == synonym {
+            return Err(anyhow::anyhow!("A word cannot be its own synonym"));
+        }
+        if self.synonyms().get(word).is_some_and(|s| s.iter().any(|s| s == synonym)) {
+            return Ok(false);
+        }
+        let existing = fs::read_to_string(&self.synonyms_path).unwrap_or_default();
+        let mut file = fs::OpenOptions::new().create(true).append(true).open(&self.synonyms_path)?;
+        if !existing.is_empty() && !existing.ends_with('\n') {
+            writeln!(file)?;
+        }
+        writeln!(file, "{}\t{}", word, synonym)?;
+        self.reload()?;
+        println!("[QueryExpander] Added synonym '{}' -> '{}'.", word, synonym);
+        Ok(true)
+    }
+
+    // 删除 word 的一个同义词；注释和其他行原样保留，删空的行整行移除。不存在时返回 false
+    pub fn remove_synonym(&self, word: &str, synonym: &str) -> Result<bool, anyhow::Error> {
+        let text = fs::read_to_string(&self.synonyms_path)?;
+        let mut removed = false;
+        let mut kept = Vec::new();
+        for line in text.lines() {
+            let fields = parse_line(line);
+            match fields.split_first() {
+                Some((key, synonyms)) if *key == word && synonyms.contains(&synonym) => {
+                    removed = true;
+                    let rest: Vec<&str> = synonyms.iter().copied().filter(|s| *s != synonym).collect();
+                    if !rest.is_empty() {
+                        kept.push(format!("{}\t{}", key, rest.join("\t")));
+                    }
+                }
+                _ => kept.push(line.to_string()),
+            }
+        }
+        if removed {
+            fs::write(&self.synonyms_path, kept.join("\n") + "\n")?;
+            self.reload()?;
+            println!("[QueryExpander] Removed synonym '{}' -> '{}'.", word, synonym);
+        }
+        Ok(removed)
+    }
+
+    // 文件自上次加载后有变化时重新加载；加载失败时沿用旧的词表
+    fn reload_if_changed(&self) {
+        let stamps = (modified(&self.synonyms_path), modified(&self.dict_path));
+        if self.state.read().unwrap().stamps == stamps {
+            return;
+        }
+        match load(&self.synonyms_path, &self.dict_path) {
+            Ok(state) => {
+                println!("[QueryExpander] Reloaded {} synonym entries.", state.synonym_map.len());
+                *self.state.write().unwrap() = state;
+            }
+            Err(e) => {
+                eprintln!("[QueryExpander] Failed to reload synonyms, keeping previous ones: {}", e);
+                // 记下新的时间戳，避免每次查询都重复报错
+                self.state.write().unwrap().stamps = stamps;
+            }
+        }
+    }
+
+    fn reload(&self) -> Result<(), anyhow::Error> {
+        *self.state.write().unwrap() = load(&self.synonyms_path, &self.dict_path)?;
+        Ok(())
+    }
+}
+
+fn load(synonyms_path: &Path, dict_path: &Path) -> Result<Loaded, anyhow::Error> {
+    let stamps = (modified(synonyms_path), modified(dict_path));
+    let text = fs::read_to_string(synonyms_path)?;
+    let mut synonym_map: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for line in text.lines() {
+        if let Some((word, synonyms)) = parse_line(line).split_first() {
+            let entry = synonym_map.entry(word.to_string()).or_default();
+            for synonym in synonyms {
+                if !entry.iter().any(|s| s == synonym) {
+                    entry.push(synonym.to_string());
+                }
+            }
+        }
+    }
+
+    let mut jieba = Jieba::new();
+    if dict_path.exists() {
+        let mut reader = BufReader::new(fs::File::open(dict_path)?);
+        jieba.load_dict(&mut reader)?;
+        println!("[QueryExpander] Loaded custom dictionary from {:?}", dict_path);
+    }
+    // 同义词表中的词都作为整词参与分词，否则像“例会”这样的词可能被切开而匹配不上
+    for (word, synonyms) in &synonym_map {
+        jieba.add_word(word, None, None);
+        for synonym in synonyms {
+            jieba.add_word(synonym, None, None);
+        }
+    }
+
+    Ok(Loaded { jieba, synonym_map, stamps })
+}
+
+// 拆分一行：跳过空行和注释，返回非空字段
+fn parse_line(line: &str) -> Vec<&str> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Vec::new();
+    }
+    line.split('\t').map(str::trim).filter(|f| !f.is_empty()).collect()
+}
+
+fn validate_term(term: &str) -> Result<&str, anyhow::Error> {
+    let term = term.trim();
+    if term.is_empty() || term.starts_with('#') || term.contains(['\t', '\n', '\r']) {
+        return Err(anyhow::anyhow!("Invalid synonym term '{}'", term));
+    }
+    Ok(term)
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
 }
 
 // 简单的停用词判断函数
 fn is_stop_word(word: &str) -> bool {
     // 实际项目中，应该使用 stop-words 库或一个更完整的列表
     matches!(word, "的" | "是" | "了" | "吗" | "呢" | "吧" | "啊" | "我" | "你" | "他" | "她" | "它")
-}
\ No newline at end of file
+}
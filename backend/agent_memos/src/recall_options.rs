@@ -0,0 +1,318 @@
+// agent_memos/src/recall_options.rs
+
+// 召回管线的可调参数：各路检索（含可选的 HyDE、按时间列出、新近度、保持度与实体扩展）的开关、条数与权重，向量分数阈值，融合方式与动态截断，
+// 以及按标签过滤。
+// 原有几路（向量、关键词）的条数、权重与阈值、融合参数的默认值与原先写死的常量一致；之后加入的几路中，
+// 保持度（权重 0.5）与实体扩展默认开启，HyDE 与按时间列出只在满足条件时生效，新近度默认关闭。
+// 部署时可通过 MEMOS_RECALL_CONFIG 指向一个 JSON 文件覆盖其中任意字段，
+// 运行中也可以整体替换（服务端据此对不同参数做 A/B 对比）。
+
+use crate::recall_trace::{FusedHit, FusionContribution};
+use crate::tags;
+use crate::vector_store::ScoredMemo;
+use std::collections::HashMap;
+
+/// 单路检索的参数
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ChannelOptions {
+    pub enabled: bool,
+    /// 该路最多取回的候选数
+    pub limit: u64,
+    /// 融合时的权重
+    pub weight: f32,
+}
+
+impl Default for ChannelOptions {
+    fn default() -> Self {
+        Self { enabled: true, limit: 5, weight: 1.0 }
+    }
+}
+
+/// 多路结果的融合方式
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum FusionMethod {
+    /// 加权倒数排名融合：每路贡献 weight / (k + rank)
+    Rrf { k: u32 },
+    /// 每路分数先归一化到 [0, 1]，再按权重求和
+    Weighted,
+}
+
+impl Default for FusionMethod {
+    fn default() -> Self {
+        FusionMethod::Rrf { k: 60 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct RecallOptions {
+    /// 原始查询的向量检索
+    pub original_vector: ChannelOptions,
+    /// 扩展查询（同义词展开）的向量检索
+    pub expanded_vector: ChannelOptions,
+    /// BM25 关键词检索
+    pub keyword: ChannelOptions,
+    /// HyDE：用 LLM 生成的假想记忆做向量检索；只在配置了 LLM 且非性能优先模式时生效
+    pub hyde: ChannelOptions,
+    /// HyDE 生成与向量化的时间预算（毫秒），超时则跳过这一路
+    pub hyde_budget_ms: u64,
+    /// 按创建时间列出查询所指时间段内的记忆（最新的在前）；只在查询中含有时间表达式时生效
+    pub time_range: ChannelOptions,
+    /// 新近度：把其他各路的候选按新近度排序，作为额外的一路参与融合；limit 为获得加分的条数。
+    /// 默认关闭；查询中笼统地说“最近”（没有具体区间）时，该次召回自动打开
+    pub recency: ChannelOptions,
+    /// 新近度的半衰期（天）
+    pub recency_half_life_days: f32,
+    /// 保持度：把其他各路的候选按保持度（重要度、召回次数与遗忘曲线，见 retention）排序，作为额外的一路参与融合
+    pub retention: ChannelOptions,
+    /// 实体扩展：查询中的实体及其共现邻居（实体图）关联的记忆；只在识别出已知实体时生效
+    pub entity: ChannelOptions,
+    /// 实体扩展时每个查询实体最多带上的邻居数
+    pub entity_neighbours: usize,
+    /// 向量检索的最低相似度
+    pub vector_score_threshold: f32,
+    pub fusion: FusionMethod,
+    /// 动态截断：相邻两条的分数落差超过前一条的这个比例时，从落差处截断；0 表示不截断
+    pub drop_ratio: f32,
+    /// 融合后只剩一条结果时，分数至少要达到这个值
+    pub min_single_score: f32,
+    /// 目标查找（修改、删除时定位记忆）最多返回的候选数
+    pub precise_limit: u32,
+    /// 只召回同时带有这些标签的记忆；为空时不限
+    pub tags: Vec<String>,
+}
+
+impl Default for RecallOptions {
+    fn default() -> Self {
+        Self {
+            original_vector: ChannelOptions::default(),
+            expanded_vector: ChannelOptions::default(),
+            keyword: ChannelOptions::default(),
+            hyde: ChannelOptions::default(),
+            hyde_budget_ms: 1500,
+            time_range: ChannelOptions { limit: 10, ..ChannelOptions::default() },
+            recency: ChannelOptions { enabled: false, ..ChannelOptions::default() },
+            recency_half_life_days: 30.0,
+            retention: ChannelOptions { weight: 0.5, ..ChannelOptions::default() },
+            entity: ChannelOptions::default(),
+            entity_neighbours: 3,
+            vector_score_threshold: 0.5,
+            fusion: FusionMethod::default(),
+            drop_ratio: 0.3,
+            min_single_score: 0.01,
+            precise_limit: 5,
+            tags: Vec::new(),
+        }
+    }
+}
+
+impl RecallOptions {
+    /// 默认值叠加 MEMOS_RECALL_CONFIG 指向的 JSON 文件（只需写出要覆盖的字段）；文件无效时沿用默认值
+    pub fn from_env() -> Self {
+        let Ok(path) = std::env::var("MEMOS_RECALL_CONFIG") else { return Self::default() };
+        let loaded = std::fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|text| Ok(serde_json::from_str::<Self>(&text)?))
+            .and_then(|options| options.validate().map(|_| options));
+        match loaded {
+            Ok(options) => {
+                println!("[MemosAgent-Recall] Loaded recall options from '{}'.", path);
+                options
+            }
+            Err(e) => {
+                eprintln!("[MemosAgent-Recall] Ignoring recall config '{}': {}", path, e);
+                Self::default()
+            }
+        }
+    }
+
+    /// 检查参数是否合理
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        let channels = [
+            ("original_vector", &self.original_vector),
+            ("expanded_vector", &self.expanded_vector),
+            ("keyword", &self.keyword),
+            ("hyde", &self.hyde),
+            ("time_range", &self.time_range),
+            ("recency", &self.recency),
+            ("retention", &self.retention),
+            ("entity", &self.entity),
+        ];
+        // HyDE 可能因模式或超时被跳过，时间、新近度、保持度与实体扩展几路依附于其他条件，都不能作为唯一的一路
+        if !channels[..3].iter().any(|(_, c)| c.enabled) {
+            return Err(anyhow::anyhow!("At least one non-HyDE recall channel must be enabled"));
+        }
+        for (name, channel) in channels {
+            if channel.enabled && channel.limit == 0 {
+                return Err(anyhow::anyhow!("Channel '{}' is enabled but its limit is 0", name));
+            }
+            if !channel.weight.is_finite() || channel.weight < 0.0 {
+                return Err(anyhow::anyhow!("Channel '{}' has an invalid weight {}", name, channel.weight));
+            }
+        }
+        if !(-1.0..=1.0).contains(&self.vector_score_threshold) {
+            return Err(anyhow::anyhow!("vector_score_threshold must be within [-1, 1]"));
+        }
+        if !(0.0..=1.0).contains(&self.drop_ratio) {
+            return Err(anyhow::anyhow!("drop_ratio must be within [0, 1]"));
+        }
+        if matches!(self.fusion, FusionMethod::Rrf { k: 0 }) {
+            return Err(anyhow::anyhow!("RRF k must be positive"));
+        }
+        if self.hyde.enabled && self.hyde_budget_ms == 0 {
+            return Err(anyhow::anyhow!("hyde_budget_ms must be positive when HyDE is enabled"));
+        }
+        if !(self.recency_half_life_days.is_finite() && self.recency_half_life_days > 0.0) {
+            return Err(anyhow::anyhow!("recency_half_life_days must be positive"));
+        }
+        if self.precise_limit == 0 {
+            return Err(anyhow::anyhow!("precise_limit must be positive"));
+        }
+        if let Some(tag) = self.tags.iter().find(|t| tags::normalize(t).is_none()) {
+            return Err(anyhow::anyhow!("Invalid tag filter '{}'", tag));
+        }
+        Ok(())
+    }
+}
+
+/// 按 method 融合若干路 (名称, 权重, 结果) 列表，返回按融合分数降序（同分按 ID 升序）的结果，以及每条结果的分数构成
+pub(crate) fn fuse(ranked_lists: Vec<(&str, f32, Vec<ScoredMemo>)>, method: &FusionMethod) -> (Vec<ScoredMemo>, Vec<FusedHit>) {
+    let mut contributions: HashMap<i64, Vec<FusionContribution>> = HashMap::new();
+    let mut point_data: HashMap<i64, ScoredMemo> = HashMap::new();
+    for (channel, weight, list) in ranked_lists {
+        let (min, max) = list.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), p| (lo.min(p.score), hi.max(p.score)));
+        for (rank, point) in list.into_iter().enumerate() {
+            let contribution = match method {
+                FusionMethod::Rrf { k } => weight / (*k as f32 + (rank + 1) as f32),
+                FusionMethod::Weighted if max > min => weight * (point.score - min) / (max - min),
+                // 该路只有一条或分数全部相同：都视为满分
+                FusionMethod::Weighted => weight,
+            };
+            contributions.entry(point.id).or_default().push(FusionContribution {
+                channel: channel.to_string(),
+                rank: rank + 1,
+                contribution,
+            });
+            point_data.entry(point.id).or_insert(point);
+        }
+    }
+    let mut fused: Vec<FusedHit> = contributions.into_iter()
+        .map(|(id, contributions)| FusedHit { id, fused_score: contributions.iter().map(|c| c.contribution).sum(), contributions })
+        .collect();
+    fused.sort_by(|a, b| b.fused_score.partial_cmp(&a.fused_score).unwrap_or(std::cmp::Ordering::Equal).then(a.id.cmp(&b.id)));
+    let points = fused.iter()
+        .filter_map(|hit| {
+            point_data.remove(&hit.id).map(|mut point| {
+                point.score = hit.fused_score;
+                point
+            })
+        })
+        .collect();
+    (points, fused)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector_store::Payload;
+
+    fn list(scored: &[(i64, f32)]) -> Vec<ScoredMemo> {
+        scored.iter().map(|&(id, score)| ScoredMemo { id, score, payload: Payload::new() }).collect()
+    }
+
+    fn ranking(points: &[ScoredMemo]) -> Vec<(i64, f32)> {
+        points.iter().map(|p| (p.id, p.score)).collect()
+    }
+
+    #[test]
+    fn rrf_sums_weighted_reciprocal_ranks() {
+        let lists = vec![
+            ("original_vector", 1.0, list(&[(1, 0.9), (2, 0.8)])),
+            ("keyword", 0.5, list(&[(2, 7.0), (3, 3.0)])),
+        ];
+        let (points, hits) = fuse(lists, &FusionMethod::Rrf { k: 1 });
+        // 1: 1/2；2: 1/3 + 0.5/2；3: 0.5/3
+        assert_eq!(points.iter().map(|p| p.id).collect::<Vec<_>>(), vec![2, 1, 3]);
+        assert!((points[0].score - (1.0 / 3.0 + 0.25)).abs() < 1e-6);
+        assert!((points[1].score - 0.5).abs() < 1e-6);
+        assert!((points[2].score - 0.5 / 3.0).abs() < 1e-6);
+        let contributions: Vec<(&str, usize)> = hits[0].contributions.iter().map(|c| (c.channel.as_str(), c.rank)).collect();
+        assert_eq!(contributions, vec![("original_vector", 2), ("keyword", 1)]);
+    }
+
+    #[test]
+    fn weighted_fusion_normalizes_each_channel() {
+        let lists = vec![
+            ("original_vector", 1.0, list(&[(1, 0.9), (2, 0.7), (3, 0.5)])),
+            ("keyword", 2.0, list(&[(3, 12.0)])),
+            ("entity", 1.0, Vec::new()),
+        ];
+        let (points, _) = fuse(lists, &FusionMethod::Weighted);
+        // 向量一路归一化为 1、0.5、0；关键词一路只有一条，视为满分
+        assert_eq!(ranking(&points), vec![(3, 2.0), (1, 1.0), (2, 0.5)]);
+    }
+
+    #[test]
+    fn ties_are_broken_by_id() {
+        for _ in 0..20 {
+            let lists = vec![
+                ("original_vector", 1.0, list(&[(9, 0.9), (4, 0.8)])),
+                ("keyword", 1.0, list(&[(4, 2.0), (9, 1.0)])),
+                ("entity", 1.0, list(&[(7, 1.0), (2, 1.0)])),
+            ];
+            let (points, hits) = fuse(lists, &FusionMethod::Rrf { k: 60 });
+            assert_eq!(points.iter().map(|p| p.id).collect::<Vec<_>>(), vec![4, 9, 7, 2]);
+            assert_eq!(hits.iter().map(|h| h.id).collect::<Vec<_>>(), vec![4, 9, 7, 2]);
+        }
+        let (points, _) = fuse(vec![("keyword", 1.0, list(&[(5, 1.0), (3, 1.0), (8, 1.0)]))], &FusionMethod::Weighted);
+        assert_eq!(points.iter().map(|p| p.id).collect::<Vec<_>>(), vec![3, 5, 8]);
+    }
+
+    #[test]
+    fn validate_accepts_defaults_and_rejects_bad_values() {
+        assert!(RecallOptions::default().validate().is_ok());
+
+        type Edit = fn(&mut RecallOptions);
+        let invalid: [(&str, Edit); 11] = [
+            ("only HyDE", |o| {
+                o.original_vector.enabled = false;
+                o.expanded_vector.enabled = false;
+                o.keyword.enabled = false;
+            }),
+            ("zero limit", |o| o.keyword.limit = 0),
+            ("negative weight", |o| o.entity.weight = -1.0),
+            ("NaN weight", |o| o.retention.weight = f32::NAN),
+            ("threshold", |o| o.vector_score_threshold = 1.5),
+            ("drop ratio", |o| o.drop_ratio = -0.1),
+            ("rrf k", |o| o.fusion = FusionMethod::Rrf { k: 0 }),
+            ("hyde budget", |o| o.hyde_budget_ms = 0),
+            ("half life", |o| o.recency_half_life_days = 0.0),
+            ("precise limit", |o| o.precise_limit = 0),
+            ("tag", |o| o.tags = vec!["两个 词".to_string()]),
+        ];
+        for (name, edit) in invalid {
+            let mut options = RecallOptions::default();
+            edit(&mut options);
+            assert!(options.validate().is_err(), "{} should be rejected", name);
+        }
+
+        // 关闭的一路不检查条数；HyDE 关闭时预算可以为 0
+        let options = RecallOptions {
+            keyword: ChannelOptions { enabled: false, limit: 0, weight: 1.0 },
+            hyde: ChannelOptions { enabled: false, ..ChannelOptions::default() },
+            hyde_budget_ms: 0,
+            ..RecallOptions::default()
+        };
+        assert!(options.validate().is_ok());
+    }
+
+    #[test]
+    fn partial_json_overrides_only_the_given_fields() {
+        let options: RecallOptions = serde_json::from_str(r#"{"keyword": {"weight": 2.0}, "fusion": {"method": "weighted"}}"#).unwrap();
+        assert_eq!(options.keyword, ChannelOptions { weight: 2.0, ..ChannelOptions::default() });
+        assert_eq!(options.fusion, FusionMethod::Weighted);
+        assert_eq!(options.original_vector, RecallOptions::default().original_vector);
+    }
+}
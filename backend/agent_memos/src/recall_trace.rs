@@ -0,0 +1,123 @@
+// agent_memos/src/recall_trace.rs
+
+// 召回诊断：recall_explain() 在正常召回的同时记录每一步的中间结果，
+// 用于回答“为什么召回了这条而不是那条”，不必再翻日志。
+// 编排器会在此基础上补充重排序（reranker）的打分。
+
+use crate::entity_graph::ExpandedEntity;
+use crate::memory_tier_manager::MemoryTier;
+use crate::recall_options::RecallOptions;
+use crate::target_resolver::{RecallMode, TargetScore};
+use crate::temporal::TimeRange;
+use crate::vector_store::ScoredMemo;
+
+/// 召回中的一条命中
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TraceHit {
+    pub id: i64,
+    pub score: f32,
+    pub content: Option<String>,
+}
+
+impl From<&ScoredMemo> for TraceHit {
+    fn from(point: &ScoredMemo) -> Self {
+        Self { id: point.id, score: point.score, content: point.content().map(str::to_string) }
+    }
+}
+
+/// 某一路检索的命中（按该路的原始分数排序）
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ChannelTrace {
+    pub channel: String,
+    pub weight: f32,
+    pub hits: Vec<TraceHit>,
+    /// 因已过期被丢弃的命中
+    pub dropped_expired: Vec<i64>,
+}
+
+/// 一路检索对某条记忆融合分数的贡献
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct FusionContribution {
+    pub channel: String,
+    /// 在该路中的名次，从 1 开始
+    pub rank: usize,
+    pub contribution: f32,
+}
+
+/// 融合后的一条记忆及其分数构成
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct FusedHit {
+    pub id: i64,
+    pub fused_score: f32,
+    pub contributions: Vec<FusionContribution>,
+}
+
+/// 动态截断的结果
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct ThresholdTrace {
+    /// 截断位置（保留前 cut_at 条）；None 表示未截断
+    pub cut_at: Option<usize>,
+    pub kept: usize,
+    pub reason: String,
+}
+
+/// 在某个层级内的一次检索
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TierTrace {
+    pub tier: MemoryTier,
+    /// 目标查找模式下各候选的置信度构成；模糊召回时为 None
+    pub targets: Option<Vec<TargetScore>>,
+    pub channels: Vec<ChannelTrace>,
+    pub fused: Vec<FusedHit>,
+    pub threshold: Option<ThresholdTrace>,
+}
+
+impl TierTrace {
+    pub(crate) fn new(tier: MemoryTier) -> Self {
+        Self { tier, targets: None, channels: Vec::new(), fused: Vec::new(), threshold: None }
+    }
+}
+
+/// 重排序对一条候选的打分
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RerankScore {
+    pub id: i64,
+    pub score: f32,
+}
+
+/// HyDE 通道的执行情况
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct HydeTrace {
+    /// 生成的假想记忆；被跳过时为 None
+    pub document: Option<String>,
+    pub elapsed_ms: u64,
+    /// 被跳过的原因（未配置、失败或超出时间预算）
+    pub skipped: Option<String>,
+}
+
+/// 一次召回的完整诊断
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct RecallTrace {
+    pub query: String,
+    pub options: RecallOptions,
+    pub entities: Vec<String>,
+    /// 实体来自对话上下文而不是从查询中提取
+    pub entities_from_context: bool,
+    pub mode: RecallMode,
+    /// 查询扩展结果，第一项为原始查询
+    pub expansions: Vec<String>,
+    pub keywords: Vec<String>,
+    /// 从查询中识别出的时间区间（按创建时间过滤）；没有时间表达式时为 None
+    pub time_range: Option<TimeRange>,
+    /// HyDE 通道；未启用时为 None
+    pub hyde: Option<HydeTrace>,
+    /// 实体扩展：查询实体在实体图中对应的实体及其邻居；未启用或没有已知实体时为空
+    pub entity_expansion: Vec<ExpandedEntity>,
+    /// 按检索顺序记录的各层级；某层有结果后即停止，之后的层级不会出现
+    pub tiers: Vec<TierTrace>,
+    /// 最终结果来自哪条路径："target"、"fuzzy" 或 "none"
+    pub resolved_by: String,
+    pub results: Vec<TraceHit>,
+    /// 重排序打分（按分数降序）；未启用重排序时为 None
+    pub rerank: Option<Vec<RerankScore>>,
+}
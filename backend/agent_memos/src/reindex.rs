@@ -0,0 +1,281 @@
+// agent_memos/src/reindex.rs
+
+// 向量索引的版本管理：
+// - index_meta 记录当前生效的集合名、生成它的嵌入模型与维度；
+// - 启动时与当前 EmbeddingProvider 对比，不一致则标记为需要重建；
+// - reindex() 从 facts 表重新向量化到一个新的版本化集合（memos_v2、memos_v3...），
+//   进度游标持久化在 index_meta 中，中断后再次调用会从断点继续；完成后在一个事务里原子切换。
+
+use crate::db::{self, DbPool};
+use crate::embedding::EmbeddingProvider;
+use crate::MemosAgent;
+use std::sync::atomic::Ordering;
+
+/// 旧版本固定使用的集合名与维度，用于为没有元数据的老用户补齐记录
+pub(crate) const LEGACY_COLLECTION: &str = "memos";
+pub(crate) const LEGACY_EMBEDDING_DIM: u64 = 512;
+const REINDEX_BATCH_SIZE: i64 = 64;
+
+const META_ACTIVE_COLLECTION: &str = "active_collection";
+const META_COLLECTION_VERSION: &str = "collection_version";
+const META_EMBEDDING_MODEL: &str = "embedding_model";
+const META_EMBEDDING_DIM: &str = "embedding_dim";
+const META_REINDEX_TARGET: &str = "reindex_target";
+const META_REINDEX_MODEL: &str = "reindex_model";
+const META_REINDEX_DIM: &str = "reindex_dim";
+const META_REINDEX_CURSOR: &str = "reindex_cursor";
+
+/// 启动时解析出的索引状态
+pub(crate) struct IndexState {
+    pub collection: String,
+    pub dim: u64,
+    /// 当前嵌入模型与集合不匹配，需要执行 reindex
+    pub needs_reindex: bool,
+}
+
+/// 重建过程中的进度快照
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ReindexProgress {
+    pub collection: String,
+    pub processed: usize,
+    pub total: usize,
+}
+
+/// 重建完成后的汇总
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ReindexReport {
+    pub previous_collection: String,
+    pub collection: String,
+    pub embedding_model: String,
+    pub dim: u64,
+    pub indexed: usize,
+    /// 本次是否从上一次中断的位置继续
+    pub resumed: bool,
+}
+
+/// 获取嵌入维度：提供方已知则直接使用，否则做一次探测调用
+pub(crate) async fn probe_dimension(embedder: &dyn EmbeddingProvider) -> Result<u64, anyhow::Error> {
+    if let Some(dim) = embedder.dimension() {
+        return Ok(dim as u64);
+    }
+    let probe = embedder.embed("维度探测").await?;
+    Ok(probe.len() as u64)
+}
+
+/// 读取（必要时初始化）索引元数据，并与当前嵌入模型比对
+pub(crate) async fn resolve_index_state(pool: &DbPool, embedder: &dyn EmbeddingProvider) -> Result<IndexState, anyhow::Error> {
+    let model = embedder.model_name().to_string();
+    let current_dim = match probe_dimension(embedder).await {
+        Ok(dim) => Some(dim),
+        Err(e) => {
+            eprintln!("[MemosAgent-Index] WARNING: Could not determine embedding dimension at startup: {}", e);
+            None
+        }
+    };
+
+    let conn = pool.get()?;
+    let Some(collection) = db::get_meta(&conn, META_ACTIVE_COLLECTION)? else {
+        // 没有元数据：要么是全新安装，要么是升级前的老库（固定使用 512 维的 memos 集合）
+        let has_facts: bool = conn.query_row("SELECT EXISTS(SELECT 1 FROM facts)", [], |row| row.get(0))?;
+        let dim = if has_facts { LEGACY_EMBEDDING_DIM } else { current_dim.unwrap_or(LEGACY_EMBEDDING_DIM) };
+        db::set_meta(&conn, META_ACTIVE_COLLECTION, LEGACY_COLLECTION)?;
+        db::set_meta(&conn, META_COLLECTION_VERSION, "1")?;
+        db::set_meta(&conn, META_EMBEDDING_MODEL, &model)?;
+        db::set_meta(&conn, META_EMBEDDING_DIM, &dim.to_string())?;
+        println!("[MemosAgent-Index] Initialized index metadata: collection '{}', model '{}', {}d.", LEGACY_COLLECTION, model, dim);
+        let needs_reindex = current_dim.is_some_and(|d| d != dim);
+        if needs_reindex {
+            report_mismatch(LEGACY_COLLECTION, &model, dim, &model, current_dim);
+        }
+        return Ok(IndexState { collection: LEGACY_COLLECTION.to_string(), dim, needs_reindex });
+    };
+
+    let stored_model = db::get_meta(&conn, META_EMBEDDING_MODEL)?.unwrap_or_default();
+    let stored_dim: u64 = db::get_meta(&conn, META_EMBEDDING_DIM)?
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(LEGACY_EMBEDDING_DIM);
+
+    let dim_mismatch = current_dim.is_some_and(|d| d != stored_dim);
+    let model_mismatch = !stored_model.is_empty() && stored_model != model;
+    let needs_reindex = dim_mismatch || model_mismatch;
+    if needs_reindex {
+        report_mismatch(&collection, &stored_model, stored_dim, &model, current_dim);
+    } else {
+        println!("[MemosAgent-Index] Active collection '{}' matches model '{}' ({}d).", collection, stored_model, stored_dim);
+    }
+    Ok(IndexState { collection, dim: stored_dim, needs_reindex })
+}
+
+fn report_mismatch(collection: &str, stored_model: &str, stored_dim: u64, model: &str, dim: Option<u64>) {
+    eprintln!("CRITICAL WARNING: Embedding model mismatch for vector collection '{}'.", collection);
+    eprintln!("  Indexed with: '{}' ({}d)", stored_model, stored_dim);
+    eprintln!("  Current provider: '{}' ({})", model, dim.map_or("unknown dim".to_string(), |d| format!("{}d", d)));
+    eprintln!("  Recall is disabled until you run a reindex (CLI: /reindex).");
+}
+
+impl MemosAgent {
+    /// 索引与当前嵌入模型是否不一致
+    pub fn needs_reindex(&self) -> bool {
+        self.needs_reindex.load(Ordering::SeqCst)
+    }
+
+    /// 用当前嵌入模型重建全部向量，完成后原子切换到新集合
+    pub async fn reindex(&self, on_progress: &(dyn Fn(&ReindexProgress) + Send + Sync)) -> Result<ReindexReport, anyhow::Error> {
+        let _guard = self.reindex_lock.try_lock()
+            .map_err(|_| anyhow::anyhow!("A reindex is already in progress"))?;
+
+        let model = self.embedder.model_name().to_string();
+        let dim = probe_dimension(self.embedder.as_ref()).await?;
+
+        // 1. 确定目标集合：同模型同维度的未完成任务直接续跑，否则开一个新版本
+        let (target, mut cursor, resumed) = {
+            let conn = self.sql_pool.get()?;
+            let pending_target = db::get_meta(&conn, META_REINDEX_TARGET)?;
+            let pending_model = db::get_meta(&conn, META_REINDEX_MODEL)?;
+            let pending_dim = db::get_meta(&conn, META_REINDEX_DIM)?;
+            match pending_target {
+                Some(target) if pending_model.as_deref() == Some(model.as_str()) && pending_dim == Some(dim.to_string()) => {
+                    let cursor: i64 = db::get_meta(&conn, META_REINDEX_CURSOR)?.and_then(|v| v.parse().ok()).unwrap_or(0);
+                    println!("[MemosAgent-Reindex] Resuming reindex into '{}' after fact {}.", target, cursor);
+                    (target, cursor, true)
+                }
+                stale => {
+                    if let Some(stale) = stale {
+                        println!("[MemosAgent-Reindex] Discarding stale reindex target '{}'.", stale);
+                        self.vector_store.delete_collection(&stale).await?;
+                    }
+                    let version: u64 = db::get_meta(&conn, META_COLLECTION_VERSION)?.and_then(|v| v.parse().ok()).unwrap_or(1);
+                    let target = format!("{}_v{}", LEGACY_COLLECTION, version + 1);
+                    db::set_meta(&conn, META_REINDEX_TARGET, &target)?;
+                    db::set_meta(&conn, META_REINDEX_MODEL, &model)?;
+                    db::set_meta(&conn, META_REINDEX_DIM, &dim.to_string())?;
+                    db::set_meta(&conn, META_REINDEX_CURSOR, "0")?;
+                    println!("[MemosAgent-Reindex] Starting reindex into '{}' with model '{}' ({}d).", target, model, dim);
+                    (target, 0, false)
+                }
+            }
+        };
+
+        self.vector_store.ensure_collection(&target, dim).await?;
+        self.indexer.set_shadow_collection(Some(&target));
+
+        // 2. 原子切换：元数据在一个事务里更新，内存中的生效集合随后替换。
+        // 影子集合要等切换完成后才撤下，否则这期间的写入只落到即将被删除的旧集合；中途失败则撤下影子，留待下次续跑
+        let copied = self.copy_facts_into(&target, &mut cursor, on_progress).await
+            .and_then(|indexed| self.commit_switch(&target, &model, dim).map(|_| indexed));
+        let indexed = match copied {
+            Ok(indexed) => indexed,
+            Err(e) => {
+                self.indexer.set_shadow_collection(None);
+                return Err(e);
+            }
+        };
+        let previous = self.indexer.switch_collection(&target);
+        self.indexer.set_shadow_collection(None);
+        self.needs_reindex.store(false, Ordering::SeqCst);
+        println!("[MemosAgent-Reindex] Switched active collection from '{}' to '{}'.", previous, target);
+
+        if let Err(e) = self.vector_store.delete_collection(&previous).await {
+            eprintln!("[MemosAgent-Reindex] Failed to drop previous collection '{}': {}", previous, e);
+        }
+
+        Ok(ReindexReport {
+            previous_collection: previous,
+            collection: target,
+            embedding_model: model,
+            dim,
+            indexed,
+            resumed,
+        })
+    }
+
+    /// 在一个事务里把 target 记为生效集合，并清除重建进度
+    fn commit_switch(&self, target: &str, model: &str, dim: u64) -> Result<(), anyhow::Error> {
+        let mut conn = self.sql_pool.get()?;
+        let tx = conn.transaction()?;
+        let version = target.rsplit("_v").next().unwrap_or("1").to_string();
+        db::set_meta(&tx, META_ACTIVE_COLLECTION, target)?;
+        db::set_meta(&tx, META_COLLECTION_VERSION, &version)?;
+        db::set_meta(&tx, META_EMBEDDING_MODEL, model)?;
+        db::set_meta(&tx, META_EMBEDDING_DIM, &dim.to_string())?;
+        for key in [META_REINDEX_TARGET, META_REINDEX_MODEL, META_REINDEX_DIM, META_REINDEX_CURSOR] {
+            db::delete_meta(&tx, key)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// 按 ID 升序分批把 facts 写入目标集合，每批结束后持久化游标
+    async fn copy_facts_into(
+        &self,
+        target: &str,
+        cursor: &mut i64,
+        on_progress: &(dyn Fn(&ReindexProgress) + Send + Sync),
+    ) -> Result<usize, anyhow::Error> {
+        let (total, mut processed): (usize, usize) = {
+            let conn = self.sql_pool.get()?;
+            let total: i64 = conn.query_row("SELECT COUNT(*) FROM facts", [], |row| row.get(0))?;
+            let done: i64 = conn.query_row("SELECT COUNT(*) FROM facts WHERE id <= ?1", [*cursor], |row| row.get(0))?;
+            (total as usize, done as usize)
+        };
+        let mut indexed = 0;
+        on_progress(&ReindexProgress { collection: target.to_string(), processed, total });
+
+        loop {
+            let batch: Vec<i64> = {
+                let conn = self.sql_pool.get()?;
+                let mut stmt = conn.prepare("SELECT id FROM facts WHERE id > ?1 ORDER BY id LIMIT ?2")?;
+                let ids = stmt.query_map([*cursor, REINDEX_BATCH_SIZE], |row| row.get(0))?
+                    .collect::<Result<Vec<i64>, _>>()?;
+                ids
+            };
+            if batch.is_empty() {
+                break;
+            }
+            for id in &batch {
+                self.indexer.index_into(target, *id).await?;
+                indexed += 1;
+                processed += 1;
+            }
+            *cursor = *batch.last().unwrap();
+            {
+                let conn = self.sql_pool.get()?;
+                db::set_meta(&conn, META_REINDEX_CURSOR, &cursor.to_string())?;
+            }
+            on_progress(&ReindexProgress { collection: target.to_string(), processed: processed.min(total), total });
+        }
+        Ok(indexed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{embedded_agent, TestDir};
+    use crate::{Filter, ManualClock, SaveOutcome};
+    use chrono::{TimeZone, Utc};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn reindex_switches_collection_before_dropping_the_shadow() {
+        let dir = TestDir::new();
+        let clock = Arc::new(ManualClock::new(Utc.with_ymd_and_hms(2025, 3, 10, 9, 0, 0).unwrap()));
+        let agent = embedded_agent(&dir, clock).await;
+        let SaveOutcome::Inserted(id) = agent.save("我的车停在B2-103").await.unwrap() else { panic!("expected insert") };
+        let previous = agent.indexer.active_collection();
+
+        let report = agent.reindex(&|progress| {
+            // 重建期间写入同时落到影子集合
+            assert_eq!(agent.indexer.shadow_collection().as_deref(), Some(progress.collection.as_str()));
+        }).await.unwrap();
+        assert_eq!(report.previous_collection, previous);
+        assert_eq!(agent.indexer.active_collection(), report.collection);
+        assert!(agent.indexer.shadow_collection().is_none());
+        assert_eq!(agent.vector_store.list_ids(&report.collection).await.unwrap(), vec![id]);
+        assert!(agent.vector_store.list_ids(&previous).await.unwrap().is_empty());
+
+        // 切换后的写入落在新集合
+        agent.update(id, "我的车停在B3-201", None).await.unwrap();
+        let points = agent.vector_store.scroll(&report.collection, &Filter::default(), 10).await.unwrap();
+        assert_eq!(points.iter().map(|p| (p.id, p.content())).collect::<Vec<_>>(), vec![(id, Some("我的车停在B3-201"))]);
+    }
+}
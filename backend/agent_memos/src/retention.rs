@@ -0,0 +1,229 @@
+// agent_memos/src/retention.rs
+
+// 记忆保持度：仿照遗忘曲线，把重要度、召回次数与最近一次活动时间合成一个 (0, 1] 的分数。
+// - 保持度 = 0.5 ^ (距最近一次活动的天数 / 稳定期)，最近一次活动取最后召回时间，从未召回则取创建时间；
+// - 稳定期（半衰期）= half_life_days × (1 + importance_boost × 重要度) × (1 + access_boost × ln(1 + 召回次数))，
+//   越重要、被召回越多的记忆忘得越慢；
+// - 重要度在 [0, 1] 之间，来自用户的显式标记（“这个很重要”）或保存时 LLM 的估计，用户标记优先，未评估的按 DEFAULT_IMPORTANCE 计。
+// 召回时保持度作为一路参与融合，层级策略据此决定归档。评分只依赖传入的 now，由调用方从注入的 Clock 取得。
+
+use crate::expiry;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::HashMap;
+
+/// 未评估过重要度的记忆按中等重要计
+pub const DEFAULT_IMPORTANCE: f32 = 0.5;
+
+/// 重要度的来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportanceSource {
+    /// 用户显式标记，不会被 LLM 的估计覆盖
+    User,
+    /// 保存时由 LLM 估计
+    Llm,
+}
+
+impl ImportanceSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImportanceSource::User => "user",
+            ImportanceSource::Llm => "llm",
+        }
+    }
+}
+
+/// 计算保持度所需的一条记忆的统计
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoStats {
+    pub created_at: DateTime<Utc>,
+    pub last_accessed_at: Option<DateTime<Utc>>,
+    pub access_count: i64,
+    pub importance: f32,
+}
+
+/// 遗忘曲线的参数
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetentionPolicy {
+    /// 重要度为 0、从未召回的记忆保持度减半所需的天数
+    pub half_life_days: f32,
+    /// 重要度对半衰期的放大系数
+    pub importance_boost: f32,
+    /// 召回次数（取对数）对半衰期的放大系数
+    pub access_boost: f32,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        // 中等重要、从未召回的记忆半衰期为 90 天
+        Self { half_life_days: 30.0, importance_boost: 4.0, access_boost: 1.0 }
+    }
+}
+
+impl RetentionPolicy {
+    /// 可通过 MEMOS_RETENTION_HALF_LIFE_DAYS 调整基础半衰期
+    pub fn from_env() -> Self {
+        let mut policy = Self::default();
+        if let Some(days) = std::env::var("MEMOS_RETENTION_HALF_LIFE_DAYS").ok().and_then(|v| v.parse::<f32>().ok()).filter(|d| *d > 0.0) {
+            policy.half_life_days = days;
+        }
+        policy
+    }
+
+    /// 这条记忆当前的半衰期（天）
+    pub fn stability_days(&self, stats: &MemoStats) -> f32 {
+        let importance = stats.importance.clamp(0.0, 1.0);
+        let accesses = stats.access_count.max(0) as f32;
+        self.half_life_days * (1.0 + self.importance_boost * importance) * (1.0 + self.access_boost * accesses.ln_1p())
+    }
+
+    /// now 时刻的保持度，在 (0, 1] 之间；刚创建或刚被召回的记忆为 1
+    pub fn score(&self, stats: &MemoStats, now: DateTime<Utc>) -> f32 {
+        let last_activity = stats.last_accessed_at.unwrap_or(stats.created_at).max(stats.created_at);
+        let idle_days = ((now - last_activity).num_seconds() as f32 / 86_400.0).max(0.0);
+        0.5_f32.powf(idle_days / self.stability_days(stats))
+    }
+}
+
+/// 从用户的话里识别对重要度的表态（“这个很重要”“不重要”），返回对应的重要度
+pub fn parse_importance_mark(text: &str) -> Option<f32> {
+    const LOW: [&str; 6] = ["不重要", "不太重要", "没那么重要", "无关紧要", "不要紧", "随便记"];
+    const TOP: [&str; 6] = ["非常重要", "特别重要", "极其重要", "最重要", "超级重要", "十分重要"];
+    const HIGH: [&str; 3] = ["重要", "要紧", "千万别忘"];
+    if LOW.iter().any(|kw| text.contains(kw)) {
+        Some(0.2)
+    } else if TOP.iter().any(|kw| text.contains(kw)) {
+        Some(1.0)
+    } else if HIGH.iter().any(|kw| text.contains(kw)) {
+        Some(0.8)
+    } else {
+        None
+    }
+}
+
+fn parse_stats(created_at: Option<String>, last_accessed_at: Option<String>, access_count: i64, importance: Option<f64>) -> Option<MemoStats> {
+    Some(MemoStats {
+        created_at: created_at.as_deref().and_then(expiry::parse_timestamp)?,
+        last_accessed_at: last_accessed_at.as_deref().and_then(expiry::parse_timestamp),
+        access_count,
+        importance: importance.map_or(DEFAULT_IMPORTANCE, |i| i as f32),
+    })
+}
+
+/// 读取若干条记忆的统计；不存在或缺少创建时间的记忆不在结果中
+pub(crate) fn load(conn: &Connection, ids: &[i64]) -> rusqlite::Result<HashMap<i64, MemoStats>> {
+    let mut stmt = conn.prepare("SELECT created_at, last_accessed_at, access_count, importance FROM facts WHERE id = ?1")?;
+    let mut stats = HashMap::new();
+    for id in ids {
+        let row = stmt
+            .query_row([id], |row| Ok(parse_stats(row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))
+            .optional()?;
+        if let Some(memo) = row.flatten() {
+            stats.insert(*id, memo);
+        }
+    }
+    Ok(stats)
+}
+
+/// 由一行 (created_at, last_accessed_at, access_count, importance) 组装统计，供批量查询复用
+pub(crate) fn stats_from_row(row: &rusqlite::Row, offset: usize) -> rusqlite::Result<Option<MemoStats>> {
+    Ok(parse_stats(row.get(offset)?, row.get(offset + 1)?, row.get(offset + 2)?, row.get(offset + 3)?))
+}
+
+/// 设置重要度；LLM 的估计不覆盖用户标记。记忆不存在时返回 None，被用户标记挡住时返回 Some(false)
+pub(crate) fn set_importance(conn: &Connection, id: i64, importance: f32, source: ImportanceSource) -> rusqlite::Result<Option<bool>> {
+    let current: Option<Option<String>> = conn
+        .query_row("SELECT importance_source FROM facts WHERE id = ?1", [id], |row| row.get(0))
+        .optional()?;
+    let Some(current) = current else { return Ok(None) };
+    if source == ImportanceSource::Llm && current.as_deref() == Some(ImportanceSource::User.as_str()) {
+        return Ok(Some(false));
+    }
+    conn.execute(
+        "UPDATE facts SET importance = ?1, importance_source = ?2 WHERE id = ?3",
+        params![importance.clamp(0.0, 1.0) as f64, source.as_str(), id],
+    )?;
+    Ok(Some(true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration as ChronoDuration, TimeZone};
+
+    fn created() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()
+    }
+
+    fn stats(importance: f32, access_count: i64, last_accessed_days: Option<i64>) -> MemoStats {
+        MemoStats {
+            created_at: created(),
+            last_accessed_at: last_accessed_days.map(|d| created() + ChronoDuration::days(d)),
+            access_count,
+            importance,
+        }
+    }
+
+    fn score_after(policy: &RetentionPolicy, stats: &MemoStats, days: i64) -> f32 {
+        policy.score(stats, created() + ChronoDuration::days(days))
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-4, "expected {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn score_halves_every_stability_period() {
+        let policy = RetentionPolicy::default();
+        let medium = stats(DEFAULT_IMPORTANCE, 0, None);
+        assert_close(policy.stability_days(&medium), 90.0);
+        assert_close(score_after(&policy, &medium, 0), 1.0);
+        assert_close(score_after(&policy, &medium, 90), 0.5);
+        assert_close(score_after(&policy, &medium, 180), 0.25);
+        // 时钟早于创建时间时按刚创建计
+        assert_close(score_after(&policy, &medium, -5), 1.0);
+
+        let mut previous = 1.0;
+        for day in (10..=360).step_by(10) {
+            let score = score_after(&policy, &medium, day);
+            assert!(score < previous && score > 0.0, "day {}: {}", day, score);
+            previous = score;
+        }
+    }
+
+    #[test]
+    fn importance_slows_decay() {
+        let policy = RetentionPolicy::default();
+        assert_close(score_after(&policy, &stats(0.0, 0, None), 30), 0.5);
+        assert_close(score_after(&policy, &stats(1.0, 0, None), 150), 0.5);
+        // 重要度超出 [0, 1] 时截断
+        assert_close(policy.stability_days(&stats(3.0, 0, None)), 150.0);
+        assert_close(policy.stability_days(&stats(-1.0, 0, None)), 30.0);
+        let low = score_after(&policy, &stats(0.2, 0, None), 60);
+        let high = score_after(&policy, &stats(0.8, 0, None), 60);
+        assert!(high > low, "{} vs {}", high, low);
+    }
+
+    #[test]
+    fn recall_resets_and_slows_decay() {
+        let policy = RetentionPolicy::default();
+        // 召回 3 次：半衰期乘以 1 + ln(4)
+        let accessed = stats(DEFAULT_IMPORTANCE, 3, Some(80));
+        assert_close(policy.stability_days(&accessed), 90.0 * (1.0 + 4f32.ln()));
+        // 衰减从最后一次召回开始计算
+        assert_close(score_after(&policy, &accessed, 80), 1.0);
+        assert_close(score_after(&policy, &accessed, 90), 0.5f32.powf(10.0 / (90.0 * (1.0 + 4f32.ln()))));
+        let never = score_after(&policy, &stats(DEFAULT_IMPORTANCE, 0, None), 200);
+        let often = score_after(&policy, &stats(DEFAULT_IMPORTANCE, 10, Some(0)), 200);
+        assert!(often > never, "{} vs {}", often, never);
+    }
+
+    #[test]
+    fn importance_marks() {
+        assert_eq!(parse_importance_mark("这个很重要"), Some(0.8));
+        assert_eq!(parse_importance_mark("这条非常重要，千万别忘"), Some(1.0));
+        assert_eq!(parse_importance_mark("这个不重要"), Some(0.2));
+        assert_eq!(parse_importance_mark("明天下午开会"), None);
+    }
+}
@@ -0,0 +1,129 @@
+// agent_memos/src/supersede.rs
+
+// 事实取代：新的陈述与旧记忆矛盾时（“我搬到上海了”之于“我住在北京”），旧记忆不删除，
+// 而是在 facts.superseded_by 记下取代它的记忆 ID。
+// - 默认召回只检索未被取代的记忆（向量 payload 中没有 superseded_by 字段）；
+// - 取代本身记为一条 supersede 修订，可以在历史中查看，也可以撤销或恢复；
+// - 是否矛盾由上层（编排器的 LLM 判断）决定，这里只负责给出候选与落库。
+
+use crate::chunking;
+use crate::history::{self, RevisionOp, RevisionSource};
+use crate::outbox::{self, OutboxOp};
+use crate::vector_store::{fields, Condition, Filter, ScoredMemo};
+use crate::MemosAgent;
+use rusqlite::{params, OptionalExtension};
+use std::collections::HashSet;
+use std::sync::atomic::Ordering;
+
+/// 语义候选的最低相似度：矛盾的两句话往往只是“相关”，阈值比去重宽松得多
+const CANDIDATE_SCORE_THRESHOLD: f32 = 0.6;
+
+/// 只保留未被取代的记忆
+pub(crate) fn current_only() -> Filter {
+    Filter::must([Condition::is_empty(fields::SUPERSEDED_BY)])
+}
+
+impl MemosAgent {
+    /// 可能与记忆 id（内容为 content）相矛盾的已有记忆：实体重合或语义相近、未被取代、未过期，排除自身。
+    /// 语义相近的按相似度排在前面，最多返回 limit 条。
+    pub async fn contradiction_candidates(&self, id: i64, content: &str, limit: usize) -> Result<Vec<ScoredMemo>, anyhow::Error> {
+        // 索引与嵌入模型不一致时召回结果不可信，宁可不判断
+        if self.needs_reindex.load(Ordering::SeqCst) {
+            return Ok(Vec::new());
+        }
+        let collection = self.indexer.active_collection();
+
+        let vector = self.get_embedding(content).await?;
+        let mut candidates = self.vector_store
+            .search(&collection, vector, limit as u64 + 1, Some(CANDIDATE_SCORE_THRESHOLD), Some(&current_only().and(chunking::parents_only())))
+            .await?;
+        for entity in self.extract_entities(content)? {
+            let filter = Filter::must([Condition::text_contains(fields::ENTITIES, &entity)]).and(current_only());
+            candidates.extend(self.vector_store.scroll(&collection, &filter, limit as u32).await?);
+        }
+
+        let mut seen = HashSet::from([id]);
+        let candidates: Vec<ScoredMemo> = self.drop_expired(candidates)
+            .into_iter()
+            .filter(|p| seen.insert(p.id))
+            .take(limit)
+            .collect();
+        println!(
+            "[MemosAgent-Supersede] {} contradiction candidate(s) for memo {}: {:?}",
+            candidates.len(), id, candidates.iter().map(|p| p.id).collect::<Vec<_>>()
+        );
+        Ok(candidates)
+    }
+
+    /// 标记 old_id 已被 new_id 取代；request 是触发的用户原话，记入修订历史（可撤销）。
+    /// old_id 已经被取代时不做改动并返回 false。
+    pub async fn supersede(&self, old_id: i64, new_id: i64, request: Option<&str>) -> Result<bool, anyhow::Error> {
+        if old_id == new_id {
+            return Err(anyhow::anyhow!("Memo {} cannot supersede itself", old_id));
+        }
+        let now = self.clock.now().to_rfc3339();
+        {
+            let mut conn = self.sql_pool.get()?;
+            let tx = conn.transaction()?;
+            for id in [old_id, new_id] {
+                let exists = tx.query_row("SELECT 1 FROM facts WHERE id = ?1", [id], |_| Ok(())).optional()?;
+                if exists.is_none() {
+                    return Err(anyhow::anyhow!("Memo {} not found", id));
+                }
+            }
+            let changed = tx.execute(
+                "UPDATE facts SET superseded_by = ?1, updated_at = ?2 WHERE id = ?3 AND superseded_by IS NULL",
+                params![new_id, now, old_id],
+            )?;
+            if changed == 0 {
+                return Ok(false);
+            }
+            history::record(&tx, old_id, RevisionOp::Supersede, RevisionSource::User, request, &now)?;
+            outbox::enqueue(&tx, old_id, OutboxOp::Upsert, &now)?;
+            tx.commit()?;
+        }
+        println!("[MemosAgent-Supersede] Memo {} superseded by memo {}.", old_id, new_id);
+        self.indexer.flush_fact(old_id).await;
+        Ok(true)
+    }
+
+    /// 取代 id 的记忆 ID；未被取代时返回 None
+    pub fn superseded_by(&self, id: i64) -> Result<Option<i64>, anyhow::Error> {
+        let conn = self.sql_pool.get()?;
+        let successor: Option<Option<i64>> = conn
+            .query_row("SELECT superseded_by FROM facts WHERE id = ?1", [id], |row| row.get(0))
+            .optional()?;
+        Ok(successor.flatten())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{embedded_agent, TestDir};
+    use crate::{ManualClock, RecallMode, RevisionOp, SaveOutcome};
+    use chrono::{TimeZone, Utc};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn superseded_memos_leave_recall_and_can_be_restored_by_undo() {
+        let dir = TestDir::new();
+        let clock = Arc::new(ManualClock::new(Utc.with_ymd_and_hms(2025, 3, 10, 9, 0, 0).unwrap()));
+        let agent = embedded_agent(&dir, clock).await;
+        let SaveOutcome::Inserted(old) = agent.save("我住在北京朝阳区").await.unwrap() else { panic!("expected insert") };
+        let SaveOutcome::Inserted(new) = agent.save("我搬到上海浦东了").await.unwrap() else { panic!("expected insert") };
+        assert!(agent.recall("我住在北京", None, RecallMode::Fuzzy).await.unwrap().iter().any(|h| h.id == old));
+
+        assert!(agent.supersede(old, old, None).await.is_err());
+        assert!(agent.supersede(old, new, Some("我搬家了")).await.unwrap());
+        assert!(!agent.supersede(old, new, None).await.unwrap());
+        assert_eq!(agent.superseded_by(old).unwrap(), Some(new));
+        assert_eq!(agent.history(old).unwrap().last().map(|r| (r.op, r.superseded_by)), Some((RevisionOp::Supersede, Some(new))));
+        assert!(agent.recall("我住在北京", None, RecallMode::Fuzzy).await.unwrap().iter().all(|h| h.id != old));
+
+        // 撤销取代后旧记忆重新参与召回
+        let outcome = agent.undo_last_change(None).await.unwrap().unwrap();
+        assert_eq!((outcome.fact_id, outcome.undone), (old, RevisionOp::Supersede));
+        assert_eq!(agent.superseded_by(old).unwrap(), None);
+        assert!(agent.recall("我住在北京", None, RecallMode::Fuzzy).await.unwrap().iter().any(|h| h.id == old));
+    }
+}
@@ -0,0 +1,92 @@
+// agent_memos/src/synonym_miner.rs
+
+// 从用户自己的记忆中挖掘同义词候选（二阶共现）：
+// 同义词很少出现在同一条记忆里，但它们身边的词往往相同（“周三的例会”“周三的会议”）。
+// 因此对每个词统计它与哪些词共现，取共现向量的余弦相似度作为“语境相似度”，
+// 再按两词直接共现的比例打折。结果只作为建议，由用户确认后才写入同义词表。
+
+use crate::keyword_index;
+use rusqlite::Connection;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// 候选词至少要出现在这么多条记忆中，太少时语境不可信
+const MIN_DOCUMENT_FREQUENCY: usize = 2;
+
+/// 一条同义词建议
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SynonymSuggestion {
+    pub word: String,
+    /// 语境相似度（已按直接共现打折），0..1
+    pub score: f32,
+    /// 两个词共有的语境词数
+    pub shared_context: usize,
+}
+
+/// 为 word 挖掘至多 limit 条同义词候选；exclude 中的词（通常是已有的同义词）不会出现在结果里
+pub(crate) fn suggest(conn: &Connection, word: &str, exclude: &[String], limit: usize) -> Result<Vec<SynonymSuggestion>, anyhow::Error> {
+    let word = word.trim().to_lowercase();
+    let documents: Vec<BTreeSet<String>> = {
+        let mut stmt = conn.prepare("SELECT content FROM facts WHERE superseded_by IS NULL")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        rows.map(|content| content.map(|c| keyword_index::tokenize(&c).into_iter().filter(|t| t.chars().count() > 1).collect()))
+            .collect::<Result<_, _>>()?
+    };
+
+    // 每个词出现在哪些记忆中，以及它的共现向量
+    let mut postings: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (idx, tokens) in documents.iter().enumerate() {
+        for token in tokens {
+            postings.entry(token.as_str()).or_default().push(idx);
+        }
+    }
+    let context_of = |term: &str| -> HashMap<&str, f32> {
+        let mut context: HashMap<&str, f32> = HashMap::new();
+        for &idx in postings.get(term).map(Vec::as_slice).unwrap_or_default() {
+            for other in &documents[idx] {
+                if other != term {
+                    *context.entry(other.as_str()).or_default() += 1.0;
+                }
+            }
+        }
+        context
+    };
+
+    let Some(target_docs) = postings.get(word.as_str()) else { return Ok(Vec::new()) };
+    let target_docs: HashSet<usize> = target_docs.iter().copied().collect();
+    let target_context = context_of(&word);
+    let excluded: HashSet<String> = exclude.iter().map(|w| w.to_lowercase()).collect();
+
+    let mut suggestions: Vec<SynonymSuggestion> = postings.iter()
+        .filter(|(term, docs)| **term != word && docs.len() >= MIN_DOCUMENT_FREQUENCY && !excluded.contains(**term))
+        .filter_map(|(term, docs)| {
+            let context = context_of(term);
+            // 比较语境时不计入两个词本身
+            let dims = |c: &HashMap<&str, f32>| -> f32 {
+                c.iter().filter(|(k, _)| **k != word && **k != *term).map(|(_, v)| v * v).sum::<f32>().sqrt()
+            };
+            let mut shared_context = 0;
+            let mut dot = 0.0;
+            for (k, v) in &context {
+                if *k == word {
+                    continue;
+                }
+                if let Some(t) = target_context.get(k) {
+                    shared_context += 1;
+                    dot += v * t;
+                }
+            }
+            let norm = dims(&context) * dims(&target_context);
+            if shared_context == 0 || norm == 0.0 {
+                return None;
+            }
+            let together = docs.iter().filter(|d| target_docs.contains(d)).count();
+            let overlap = together as f32 / docs.len().min(target_docs.len()) as f32;
+            let score = dot / norm * (1.0 - overlap);
+            (score > 0.0).then(|| SynonymSuggestion { word: term.to_string(), score, shared_context })
+        })
+        .collect();
+    suggestions.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal).then_with(|| a.word.cmp(&b.word)));
+    suggestions.truncate(limit);
+    println!("[MemosAgent-Synonym] {} suggestion(s) for '{}' from {} memos.", suggestions.len(), word, documents.len());
+    Ok(suggestions)
+}
@@ -0,0 +1,287 @@
+// agent_memos/src/tags.rs
+
+// 标签：存放在 facts.metadata（JSON 对象）的 "tags" 数组中，随修订历史一起快照，也写入向量库 payload。
+// 标签来源有三种：内容中的 #话题 写法、调用方显式传入（CLI 参数、API），以及提炼事实时 LLM 给出的建议。
+// 标签统一做规范化（去掉 #、英文转小写、去掉首尾空白），同一条记忆中不重复。
+// 改名与合并会逐条改写受影响的记忆：记入修订历史（可撤销），并经发件箱同步到向量库。
+
+use crate::history::{self, RevisionOp, RevisionSource};
+use crate::outbox::{self, OutboxOp};
+use crate::vector_store::{fields, Condition};
+use regex::Regex;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::{json, Value};
+use std::sync::OnceLock;
+
+/// 单个标签的最大字符数
+const MAX_TAG_CHARS: usize = 32;
+
+/// 一个标签及使用它的记忆数
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: usize,
+}
+
+/// 规范化标签；不是合法标签时返回 None
+pub fn normalize(tag: &str) -> Option<String> {
+    let tag = tag.trim().trim_start_matches('#').trim().to_lowercase();
+    let valid = !tag.is_empty()
+        && tag.chars().count() <= MAX_TAG_CHARS
+        && tag.chars().all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '/' | '.'));
+    valid.then_some(tag)
+}
+
+/// 规范化并去重，保持原有顺序
+pub fn normalize_all<S: AsRef<str>>(tags: &[S]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags.iter().filter_map(|t| normalize(t.as_ref())) {
+        if !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    normalized
+}
+
+/// 从文本中取出 #话题 写法的标签，返回去掉标签后的文本与标签列表
+pub fn extract_hashtags(text: &str) -> (String, Vec<String>) {
+    static HASHTAG: OnceLock<Regex> = OnceLock::new();
+    let regex = HASHTAG.get_or_init(|| Regex::new(r"[#＃]([\p{L}\p{N}_\-/.]+)").expect("invalid hashtag pattern"));
+    let tags = normalize_all(&regex.captures_iter(text).map(|c| c[1].to_string()).collect::<Vec<_>>());
+    if tags.is_empty() {
+        return (text.to_string(), tags);
+    }
+    let stripped = regex.replace_all(text, "");
+    let cleaned = stripped.split_whitespace().collect::<Vec<_>>().join(" ");
+    (cleaned, tags)
+}
+
+/// 读取 metadata 中的标签
+pub(crate) fn from_metadata(metadata: Option<&str>) -> Vec<String> {
+    metadata
+        .and_then(|m| serde_json::from_str::<Value>(m).ok())
+        .and_then(|m| m.get("tags").cloned())
+        .and_then(|t| serde_json::from_value::<Vec<String>>(t).ok())
+        .unwrap_or_default()
+}
+
+/// 某条记忆当前的标签
+pub(crate) fn get(conn: &Connection, fact_id: i64) -> rusqlite::Result<Vec<String>> {
+    let metadata: Option<Option<String>> = conn
+        .query_row("SELECT metadata FROM facts WHERE id = ?1", [fact_id], |row| row.get(0))
+        .optional()?;
+    Ok(from_metadata(metadata.flatten().as_deref()))
+}
+
+/// 覆盖某条记忆的标签，metadata 中的其他字段保留；行不存在时返回 false。不记修订、不写发件箱，由调用方负责
+pub(crate) fn set(conn: &Connection, fact_id: i64, tags: &[String]) -> rusqlite::Result<bool> {
+    let metadata: Option<Option<String>> = conn
+        .query_row("SELECT metadata FROM facts WHERE id = ?1", [fact_id], |row| row.get(0))
+        .optional()?;
+    let Some(metadata) = metadata else { return Ok(false) };
+    let mut object = metadata
+        .and_then(|m| serde_json::from_str::<Value>(&m).ok())
+        .filter(Value::is_object)
+        .unwrap_or_else(|| json!({}));
+    if tags.is_empty() {
+        object.as_object_mut().map(|o| o.remove("tags"));
+    } else {
+        object["tags"] = json!(tags);
+    }
+    let metadata = (!object.as_object().is_some_and(|o| o.is_empty())).then(|| object.to_string());
+    conn.execute("UPDATE facts SET metadata = ?1 WHERE id = ?2", params![metadata, fact_id])?;
+    Ok(true)
+}
+
+/// 修改标签并记入修订历史、写入发件箱；标签没有变化时返回 false
+pub(crate) fn retag(conn: &Connection, fact_id: i64, tags: &[String], request: Option<&str>, now: &str) -> rusqlite::Result<bool> {
+    if get(conn, fact_id)? == tags {
+        return Ok(false);
+    }
+    if !set(conn, fact_id, tags)? {
+        return Ok(false);
+    }
+    conn.execute("UPDATE facts SET updated_at = ?1 WHERE id = ?2", params![now, fact_id])?;
+    history::record(conn, fact_id, RevisionOp::Retag, RevisionSource::User, request, now)?;
+    outbox::enqueue(conn, fact_id, OutboxOp::Upsert, now)?;
+    Ok(true)
+}
+
+/// 所有未被取代的记忆中出现过的标签，按使用次数降序
+pub(crate) fn list(conn: &Connection) -> rusqlite::Result<Vec<TagCount>> {
+    let mut stmt = conn.prepare(
+        "SELECT t.value, COUNT(*) FROM facts f, json_each(f.metadata, '$.tags') t
+         WHERE f.metadata IS NOT NULL AND json_valid(f.metadata) AND f.superseded_by IS NULL
+         GROUP BY t.value ORDER BY COUNT(*) DESC, t.value",
+    )?;
+    let rows = stmt.query_map([], |row| Ok(TagCount { tag: row.get(0)?, count: row.get::<_, i64>(1)? as usize }))?;
+    rows.collect()
+}
+
+/// 带有 tag 的全部记忆 ID
+fn facts_with(conn: &Connection, tag: &str) -> rusqlite::Result<Vec<i64>> {
+    let mut stmt = conn.prepare(
+        "SELECT f.id FROM facts f, json_each(f.metadata, '$.tags') t
+         WHERE f.metadata IS NOT NULL AND json_valid(f.metadata) AND t.value = ?1 ORDER BY f.id",
+    )?;
+    let ids = stmt.query_map([tag], |row| row.get(0))?.collect();
+    ids
+}
+
+/// 把 sources 中的标签全部合并为 into（改名即只有一个来源的合并），返回被改写的记忆 ID
+pub(crate) fn merge(conn: &Connection, sources: &[String], into: &str, request: Option<&str>, now: &str) -> rusqlite::Result<Vec<i64>> {
+    let mut affected: Vec<i64> = Vec::new();
+    for source in sources.iter().filter(|s| s.as_str() != into) {
+        for id in facts_with(conn, source)? {
+            if !affected.contains(&id) {
+                affected.push(id);
+            }
+        }
+    }
+    for id in &affected {
+        let mut tags: Vec<String> = Vec::new();
+        for tag in get(conn, *id)? {
+            let tag = if sources.contains(&tag) { into.to_string() } else { tag };
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+        retag(conn, *id, &tags, request, now)?;
+    }
+    Ok(affected)
+}
+
+/// “带有全部给定标签”的向量库过滤条件
+pub(crate) fn conditions(tags: &[String]) -> impl Iterator<Item = Condition> + '_ {
+    tags.iter().map(|tag| Condition::equals(fields::TAGS, tag))
+}
+
+/// “带有全部给定标签”的 SQL 谓词；tags 已规范化，单引号按 SQL 规则转义
+pub(crate) fn sql_predicate(column: &str, tags: &[String]) -> String {
+    if tags.is_empty() {
+        return "1".to_string();
+    }
+    tags.iter()
+        .map(|tag| format!(
+            "EXISTS (SELECT 1 FROM json_each(CASE WHEN json_valid({col}) THEN {col} END, '$.tags') WHERE value = '{tag}')",
+            col = column,
+            tag = tag.replace('\'', "''"),
+        ))
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{embedded_agent, migrated_pool, TestDir};
+    use crate::ManualClock;
+    use chrono::{TimeZone, Utc};
+    use std::sync::Arc;
+
+    const NOW: &str = "2025-03-10T09:00:00+00:00";
+
+    fn create(conn: &Connection, content: &str, metadata: Option<&str>) -> i64 {
+        conn.execute("INSERT INTO facts (content, metadata, created_at, updated_at) VALUES (?1, ?2, ?3, ?3)", params![content, metadata, NOW]).unwrap();
+        conn.last_insert_rowid()
+    }
+
+    fn tags(values: &[&str]) -> Vec<String> {
+        values.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn normalize_strips_hash_and_lowercases() {
+        assert_eq!(normalize(" #Work "), Some("work".to_string()));
+        assert_eq!(normalize("＃聚餐"), None);
+        assert_eq!(normalize("titan/q1.plan_v2-final"), Some("titan/q1.plan_v2-final".to_string()));
+        assert_eq!(normalize("#"), None);
+        assert_eq!(normalize("two words"), None);
+        assert_eq!(normalize("o'neil"), None);
+        assert_eq!(normalize(&"长".repeat(MAX_TAG_CHARS)), Some("长".repeat(MAX_TAG_CHARS)));
+        assert_eq!(normalize(&"长".repeat(MAX_TAG_CHARS + 1)), None);
+        assert_eq!(normalize_all(&["Work", "#work", "聚餐", "bad tag", "WORK"]), tags(&["work", "聚餐"]));
+    }
+
+    #[test]
+    fn hashtags_are_extracted_and_removed_from_the_text() {
+        assert_eq!(extract_hashtags("周五和小李吃火锅 #聚餐 ＃Friday"), ("周五和小李吃火锅".to_string(), tags(&["聚餐", "friday"])));
+        assert_eq!(extract_hashtags("没有标签"), ("没有标签".to_string(), Vec::new()));
+    }
+
+    #[test]
+    fn sql_predicate_requires_every_tag_and_escapes_quotes() {
+        let dir = TestDir::new();
+        let pool = migrated_pool(&dir);
+        let conn = pool.get().unwrap();
+        let both = create(&conn, "a", Some(r#"{"tags":["work","o'neil"]}"#));
+        let work = create(&conn, "b", Some(r#"{"tags":["work"]}"#));
+        create(&conn, "c", Some("not json"));
+        create(&conn, "d", None);
+
+        let matching = |tags: &[String]| -> Vec<i64> {
+            let sql = format!("SELECT id FROM facts WHERE {} ORDER BY id", sql_predicate("metadata", tags));
+            let mut stmt = conn.prepare(&sql).unwrap();
+            let ids = stmt.query_map([], |row| row.get(0)).unwrap().collect::<rusqlite::Result<Vec<i64>>>().unwrap();
+            ids
+        };
+        assert_eq!(sql_predicate("metadata", &[]), "1");
+        assert_eq!(matching(&tags(&["work"])), vec![both, work]);
+        assert_eq!(matching(&tags(&["work", "o'neil"])), vec![both]);
+        assert_eq!(matching(&tags(&["o' OR 1=1 --"])), Vec::<i64>::new());
+    }
+
+    #[test]
+    fn retag_keeps_other_metadata_and_records_a_revision() {
+        let dir = TestDir::new();
+        let pool = migrated_pool(&dir);
+        let conn = pool.get().unwrap();
+        let id = create(&conn, "周五和小李吃火锅", Some(r#"{"source":"cli","tags":["聚餐"]}"#));
+
+        assert!(!retag(&conn, id, &tags(&["聚餐"]), None, NOW).unwrap(), "标签没变不记修订");
+        assert!(retag(&conn, id, &tags(&["聚餐", "friday"]), Some("加个 friday 标签"), NOW).unwrap());
+        assert_eq!(get(&conn, id).unwrap(), tags(&["聚餐", "friday"]));
+        assert!(retag(&conn, id, &[], None, NOW).unwrap());
+        let metadata: Option<String> = conn.query_row("SELECT metadata FROM facts WHERE id = ?1", [id], |row| row.get(0)).unwrap();
+        assert_eq!(metadata.as_deref(), Some(r#"{"source":"cli"}"#));
+        assert!(!retag(&conn, id + 1, &tags(&["x"]), None, NOW).unwrap(), "不存在的记忆");
+
+        let revisions: Vec<(String, Option<String>)> = conn
+            .prepare("SELECT op, request FROM fact_revisions WHERE fact_id = ?1 ORDER BY revision").unwrap()
+            .query_map([id], |row| Ok((row.get(0)?, row.get(1)?))).unwrap()
+            .collect::<rusqlite::Result<_>>().unwrap();
+        assert_eq!(revisions, vec![("retag".to_string(), Some("加个 friday 标签".to_string())), ("retag".to_string(), None)]);
+        let queued: i64 = conn.query_row("SELECT COUNT(*) FROM index_outbox WHERE fact_id = ?1", [id], |row| row.get(0)).unwrap();
+        assert!(queued > 0);
+    }
+
+    #[tokio::test]
+    async fn merge_and_rename_rewrite_affected_memos() {
+        let dir = TestDir::new();
+        let clock = Arc::new(ManualClock::new(Utc.with_ymd_and_hms(2025, 3, 10, 9, 0, 0).unwrap()));
+        let agent = embedded_agent(&dir, clock).await;
+        let dinner = agent.insert("周五和小李吃火锅", None, &tags(&["聚餐", "food"]), None).await.unwrap();
+        let lunch = agent.insert("周三和小王吃面", None, &tags(&["吃饭"]), None).await.unwrap();
+        let meeting = agent.insert("Titan 周会", None, &tags(&["work"]), None).await.unwrap();
+
+        // 两个来源合并到已有的标签时不产生重复
+        let affected = agent.merge_tags(&tags(&["food", "#吃饭"]), "聚餐", Some("把吃饭和 food 并到聚餐")).await.unwrap();
+        assert_eq!(affected, vec![dinner, lunch]);
+        assert_eq!(agent.tags_of(dinner).unwrap(), tags(&["聚餐"]));
+        assert_eq!(agent.tags_of(lunch).unwrap(), tags(&["聚餐"]));
+        assert_eq!(agent.tags_of(meeting).unwrap(), tags(&["work"]));
+        let counts: Vec<(String, usize)> = agent.list_tags().unwrap().into_iter().map(|t| (t.tag, t.count)).collect();
+        assert_eq!(counts, vec![("聚餐".to_string(), 2), ("work".to_string(), 1)]);
+
+        assert_eq!(agent.rename_tag("Work", "#job", None).await.unwrap(), vec![meeting]);
+        assert_eq!(agent.tags_of(meeting).unwrap(), tags(&["job"]));
+        assert!(agent.rename_tag("missing", "job", None).await.unwrap().is_empty());
+        assert!(agent.rename_tag("job", "bad tag", None).await.is_err());
+        assert!(agent.merge_tags(&tags(&["bad tag"]), "job", None).await.is_err());
+
+        // 每条受影响的记忆各记一条修订，可以逐条撤销
+        let undone = agent.undo_last_change(None).await.unwrap().unwrap();
+        assert_eq!(undone.fact_id, meeting);
+        assert_eq!(agent.tags_of(meeting).unwrap(), tags(&["work"]));
+    }
+}
@@ -0,0 +1,163 @@
+// agent_memos/src/target_resolver.rs
+
+// 目标查找：修改、删除等操作需要定位“用户指的是哪一条记忆”，这与回答问题时的模糊召回不同。
+// 召回模式由编排器显式指定，存储层不再根据措辞猜测意图。
+// 目标查找把几路证据合成一个 0..1 的置信度：
+// - 实体匹配：查询（或上下文）中的实体有多少出现在该记忆里；
+// - 关键词：BM25 分数，按本次最高分归一化；
+// - 语义：原始查询与记忆的向量相似度，兜住换了说法的情况；
+// - 新近度：越近修改过的记忆越可能是用户正在说的那条（半衰期 30 天，见 temporal.rs）。
+// 编排器据此决定直接选中，还是列出候选让用户选择。
+
+use crate::temporal;
+use crate::vector_store::{fields, ScoredMemo};
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use std::collections::HashMap;
+
+const ENTITY_WEIGHT: f32 = 0.4;
+const KEYWORD_WEIGHT: f32 = 0.3;
+const SEMANTIC_WEIGHT: f32 = 0.2;
+const RECENCY_WEIGHT: f32 = 0.1;
+
+/// 召回模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecallMode {
+    /// 多路检索融合，用于回答问题
+    #[default]
+    Fuzzy,
+    /// 定位某一条具体的记忆（修改、删除），结果分数为置信度
+    TargetLookup,
+}
+
+/// 一个目标候选的置信度及其构成
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TargetScore {
+    pub id: i64,
+    pub confidence: f32,
+    pub entity: f32,
+    pub keyword: f32,
+    pub semantic: f32,
+    pub recency: f32,
+}
+
+/// 目标查找在一个层级内收集到的各路命中
+#[derive(Default)]
+pub(crate) struct TargetEvidence {
+    pub entity_hits: Vec<ScoredMemo>,
+    pub keyword_hits: Vec<ScoredMemo>,
+    pub semantic_hits: Vec<ScoredMemo>,
+}
+
+/// 合并各路命中并打分，按置信度降序返回至多 limit 条；结果中 ScoredMemo.score 即置信度
+pub(crate) fn score(evidence: TargetEvidence, entities: &[String], now: DateTime<Utc>, limit: usize) -> (Vec<ScoredMemo>, Vec<TargetScore>) {
+    let max_keyword = evidence.keyword_hits.iter().map(|p| p.score).fold(0.0_f32, f32::max);
+    let keyword_scores: HashMap<i64, f32> = evidence.keyword_hits.iter()
+        .map(|p| (p.id, if max_keyword > 0.0 { (p.score / max_keyword).clamp(0.0, 1.0) } else { 1.0 }))
+        .collect();
+    let semantic_scores: HashMap<i64, f32> = evidence.semantic_hits.iter().map(|p| (p.id, p.score.clamp(0.0, 1.0))).collect();
+
+    // 同一条记忆可能来自多路，优先保留带实体字段的 payload（向量库的 payload 比关键词索引的完整）
+    let mut candidates: HashMap<i64, ScoredMemo> = HashMap::new();
+    for point in evidence.entity_hits.into_iter().chain(evidence.semantic_hits).chain(evidence.keyword_hits) {
+        candidates.entry(point.id).or_insert(point);
+    }
+
+    let mut scored: Vec<(ScoredMemo, TargetScore)> = candidates.into_values()
+        .map(|mut point| {
+            let entity = entity_match(&point, entities);
+            let keyword = keyword_scores.get(&point.id).copied().unwrap_or(0.0);
+            let semantic = semantic_scores.get(&point.id).copied().unwrap_or(0.0);
+            let recency = temporal::recency_score(&point.payload, now, temporal::DEFAULT_HALF_LIFE_DAYS);
+            let confidence = ENTITY_WEIGHT * entity + KEYWORD_WEIGHT * keyword + SEMANTIC_WEIGHT * semantic + RECENCY_WEIGHT * recency;
+            point.score = confidence;
+            let score = TargetScore { id: point.id, confidence, entity, keyword, semantic, recency };
+            (point, score)
+        })
+        .collect();
+    scored.sort_by(|a, b| b.1.confidence.partial_cmp(&a.1.confidence).unwrap_or(std::cmp::Ordering::Equal).then(a.0.id.cmp(&b.0.id)));
+    scored.truncate(limit);
+    scored.into_iter().unzip()
+}
+
+/// 实体中出现在该记忆（实体字段或正文）里的比例
+fn entity_match(point: &ScoredMemo, entities: &[String]) -> f32 {
+    if entities.is_empty() {
+        return 0.0;
+    }
+    let linked: Vec<&str> = match point.payload.get(fields::ENTITIES) {
+        Some(Value::Array(items)) => items.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    };
+    let content = point.content().unwrap_or_default();
+    let matched = entities.iter()
+        .filter(|e| content.contains(e.as_str()) || linked.iter().any(|l| l.contains(e.as_str())))
+        .count();
+    matched as f32 / entities.len() as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    fn point(id: i64, score: f32, payload: Value) -> ScoredMemo {
+        ScoredMemo { id, score, payload: payload.as_object().cloned().unwrap_or_default() }
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 3, 31, 0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn entity_match_checks_linked_entities_and_content() {
+        let entities = vec!["小李".to_string(), "Titan".to_string()];
+        let linked = point(1, 0.0, json!({ "content": "周会改到周五", "entities": ["小李", "Titan 项目"] }));
+        let in_content = point(2, 0.0, json!({ "content": "和小李吃火锅" }));
+        assert_eq!(entity_match(&linked, &entities), 1.0);
+        assert_eq!(entity_match(&in_content, &entities), 0.5);
+        assert_eq!(entity_match(&in_content, &[]), 0.0);
+    }
+
+    #[test]
+    fn score_combines_weighted_evidence_and_merges_channels() {
+        let fresh = json!({ "content": "小李的车停在 B2", "entities": ["小李"], "updated_at": "2025-03-31 00:00:00" });
+        let month_old = json!({ "content": "小王的车停在 B3", "created_at": "2025-03-01 00:00:00" });
+        let evidence = TargetEvidence {
+            entity_hits: vec![point(1, 0.9, fresh.clone())],
+            // 关键词分按本次最高分归一化：8 → 1.0，4 → 0.5
+            keyword_hits: vec![point(1, 8.0, json!({ "content": "小李的车停在 B2" })), point(2, 4.0, json!({ "content": "小王的车停在 B3" }))],
+            semantic_hits: vec![point(2, 0.5, month_old)],
+        };
+        let (points, scores) = score(evidence, &["小李".to_string()], now(), 5);
+
+        assert_eq!(points.iter().map(|p| p.id).collect::<Vec<_>>(), vec![1, 2]);
+        // 同一条记忆在多路命中时保留实体路的完整 payload
+        assert!(points[0].payload.contains_key(fields::ENTITIES));
+        let first = &scores[0];
+        assert_eq!((first.entity, first.keyword, first.semantic, first.recency), (1.0, 1.0, 0.0, 1.0));
+        assert!((first.confidence - 0.8).abs() < 1e-6);
+        assert_eq!(points[0].score, first.confidence);
+        let second = &scores[1];
+        assert_eq!((second.entity, second.keyword, second.semantic), (0.0, 0.5, 0.5));
+        assert!((second.recency - 0.5).abs() < 1e-3, "30 天恰好衰减一半：{}", second.recency);
+        assert!((second.confidence - (0.15 + 0.1 + 0.05)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn score_breaks_ties_by_id_and_truncates() {
+        let evidence = TargetEvidence {
+            semantic_hits: vec![point(7, 0.6, json!({})), point(3, 0.6, json!({})), point(5, 0.2, json!({}))],
+            ..TargetEvidence::default()
+        };
+        let (points, scores) = score(evidence, &[], now(), 2);
+        assert_eq!(points.iter().map(|p| p.id).collect::<Vec<_>>(), vec![3, 7]);
+        assert_eq!(scores.len(), 2);
+        // 关键词一路全为零分时不能除以零
+        let evidence = TargetEvidence { keyword_hits: vec![point(1, 0.0, json!({}))], ..TargetEvidence::default() };
+        let (_, scores) = score(evidence, &[], now(), 1);
+        assert_eq!(scores[0].keyword, 1.0);
+    }
+}
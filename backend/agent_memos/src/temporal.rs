@@ -0,0 +1,342 @@
+// agent_memos/src/temporal.rs
+
+// 时间表达式解析：把查询中的“昨天”“上周”“最近三天”“last week”“3 days ago”等相对时间，
+// 按参考时间 now（及其所在时区）换算成一个左闭右开的 UTC 时间区间。
+// 召回时该区间作用于记忆的创建时间：向量检索加过滤条件，SQLite 检索加 WHERE 谓词，
+// 并额外增加一路“按时间列出”的检索，让“我上周记了什么”这类没有具体内容的问题也能答上。
+// 同一句中出现多个时间词时取最先出现的那个。笼统的“最近”“近期”不构成区间，召回时改为新近度加权（见 mentions_recency）。
+// 新近度（越新越好）的打分也放在这里，供融合与目标查找共用。
+
+use crate::expiry;
+use crate::memory_tier_manager::MemoryTier;
+use crate::tags;
+use crate::vector_store::{fields, Condition, Payload, ScoredMemo};
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, NaiveDate, TimeZone, Utc};
+use regex::Regex;
+use rusqlite::{params, Connection};
+use serde_json::Value;
+use std::sync::OnceLock;
+
+/// 一个时间区间 [start, end)
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct TimeRange {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// 查询中被识别为时间表达式的原文
+    pub expression: String,
+}
+
+impl TimeRange {
+    /// 对应的向量库过滤条件（按创建时间）
+    pub(crate) fn condition(&self) -> Condition {
+        Condition::datetime_range(fields::CREATED_AT, Some(self.start), Some(self.end))
+    }
+
+    /// 对应的 SQL 谓词；column 兼容 RFC3339 与 SQLite CURRENT_TIMESTAMP 两种存储格式
+    pub(crate) fn sql_predicate(&self, column: &str) -> String {
+        format!(
+            "datetime({col}) >= datetime('{start}') AND datetime({col}) < datetime('{end}')",
+            col = column,
+            start = expiry::to_db_timestamp(self.start),
+            end = expiry::to_db_timestamp(self.end),
+        )
+    }
+
+    /// 去掉时间表达式后的查询，用于语义检索；剩下的内容为空时返回 None
+    pub fn strip_from(&self, query: &str) -> Option<String> {
+        let rest = query.replacen(&self.expression, "", 1);
+        rest.chars().any(char::is_alphanumeric).then(|| rest.trim().to_string())
+    }
+}
+
+// 相对日期规则：(正则, 由匹配结果与今天算出 [起始日期, 结束日期) 的函数)
+type DayRange = fn(&regex::Captures, NaiveDate) -> Option<(NaiveDate, NaiveDate)>;
+
+fn rules() -> &'static [(Regex, DayRange)] {
+    static RULES: OnceLock<Vec<(Regex, DayRange)>> = OnceLock::new();
+    RULES.get_or_init(|| {
+        let rule = |pattern: &str, f: DayRange| (Regex::new(pattern).expect("invalid temporal pattern"), f);
+        const NUM: &str = r"(\d+|[零一二两三四五六七八九十]+)";
+        vec![
+            // 较长、较具体的写法放在前面，避免“大前天”被“前天”截胡
+            rule(&format!(r"(?:最近|近|过去|这)\s*{}\s*(天|日|周|个?星期|个?礼拜|个?月|年)", NUM), |c, today| {
+                let n = parse_number(&c[1])?;
+                let start = today - unit_days(&c[2], n)?;
+                Some((start + ChronoDuration::days(1), today + ChronoDuration::days(1)))
+            }),
+            rule(&format!(r"{}\s*(天|日|周|个?星期|个?礼拜|个?月|年)(?:以前|之前|前)", NUM), |c, today| {
+                let day = today - unit_days(&c[2], parse_number(&c[1])?)?;
+                Some((day, day + ChronoDuration::days(1)))
+            }),
+            rule(r"大前天", |_, today| Some(single_day(today - ChronoDuration::days(3)))),
+            rule(r"前天", |_, today| Some(single_day(today - ChronoDuration::days(2)))),
+            rule(r"昨天|昨日|昨晚|昨夜", |_, today| Some(single_day(today - ChronoDuration::days(1)))),
+            rule(r"今天|今日|今晚|今早|今夜", |_, today| Some(single_day(today))),
+            rule(r"上上(?:周|个?星期|个?礼拜)", |_, today| Some(week_of(today, -2))),
+            rule(r"上(?:周|个?星期|个?礼拜)", |_, today| Some(week_of(today, -1))),
+            rule(r"(?:这|本)(?:周|个?星期|个?礼拜)", |_, today| Some(week_of(today, 0))),
+            rule(r"上上个?月", |_, today| month_of(today, -2)),
+            rule(r"上个?月", |_, today| month_of(today, -1)),
+            rule(r"(?:这个|本)月", |_, today| month_of(today, 0)),
+            rule(r"前年", |_, today| year_of(today, -2)),
+            rule(r"去年", |_, today| year_of(today, -1)),
+            rule(r"今年", |_, today| year_of(today, 0)),
+            rule(r"前几天", |_, today| Some((today - ChronoDuration::days(6), today + ChronoDuration::days(1)))),
+            // English
+            rule(r"(?i)\b(?:last|past)\s+(\d+)\s+(day|week|month|year)s?\b", |c, today| {
+                let n = c[1].parse().ok()?;
+                let start = today - unit_days(&c[2], n)?;
+                Some((start + ChronoDuration::days(1), today + ChronoDuration::days(1)))
+            }),
+            rule(r"(?i)\b(\d+|a|one|two|three|four|five|six|seven)\s+(day|week|month|year)s?\s+ago\b", |c, today| {
+                let n = parse_english_number(&c[1])?;
+                let day = today - unit_days(&c[2], n)?;
+                Some((day, day + ChronoDuration::days(1)))
+            }),
+            rule(r"(?i)\bday\s+before\s+yesterday\b", |_, today| Some(single_day(today - ChronoDuration::days(2)))),
+            rule(r"(?i)\byesterday\b", |_, today| Some(single_day(today - ChronoDuration::days(1)))),
+            rule(r"(?i)\btoday\b|\btonight\b|\bthis\s+morning\b", |_, today| Some(single_day(today))),
+            rule(r"(?i)\blast\s+week\b", |_, today| Some(week_of(today, -1))),
+            rule(r"(?i)\bthis\s+week\b", |_, today| Some(week_of(today, 0))),
+            rule(r"(?i)\blast\s+month\b", |_, today| month_of(today, -1)),
+            rule(r"(?i)\bthis\s+month\b", |_, today| month_of(today, 0)),
+            rule(r"(?i)\blast\s+year\b", |_, today| year_of(today, -1)),
+            rule(r"(?i)\bthis\s+year\b", |_, today| year_of(today, 0)),
+            rule(r"(?i)\bthe\s+other\s+day\b", |_, today| Some((today - ChronoDuration::days(6), today + ChronoDuration::days(1)))),
+        ]
+    })
+}
+
+/// 查询是否只笼统地提到“最近”（没有给出具体区间时由调用方改为新近度加权，而不是按时间硬过滤）
+pub(crate) fn mentions_recency(text: &str) -> bool {
+    static RECENCY: OnceLock<Regex> = OnceLock::new();
+    RECENCY.get_or_init(|| Regex::new(r"(?i)最近|近期|近来|这阵子|\brecently\b|\blately\b").expect("invalid recency pattern")).is_match(text)
+}
+
+/// 解析 text 中的时间表达式；日期边界按 now 所在的时区计算，结果换算为 UTC
+pub fn parse_time_range<Tz: TimeZone>(text: &str, now: &DateTime<Tz>) -> Option<TimeRange> {
+    let today = now.date_naive();
+    // 取在文中最先出现的表达式；同一位置有多条规则命中时，按规则顺序取第一条
+    let (m, start_day, end_day) = rules().iter()
+        .filter_map(|(regex, f)| {
+            let captures = regex.captures(text)?;
+            let (start, end) = f(&captures, today)?;
+            Some((captures.get(0)?, start, end))
+        })
+        .min_by_key(|(m, _, _)| m.start())?;
+    let to_utc = |day: NaiveDate| {
+        let midnight = day.and_hms_opt(0, 0, 0)?;
+        now.timezone().from_local_datetime(&midnight).earliest().map(|t| t.with_timezone(&Utc))
+    };
+    let range = TimeRange { start: to_utc(start_day)?, end: to_utc(end_day)?, expression: m.as_str().to_string() };
+    println!("[MemosAgent-Temporal] '{}' -> [{}, {})", range.expression, range.start, range.end);
+    Some(range)
+}
+
+/// 按创建时间列出某层级中落在 range 内的记忆，最新的在前；分数为新近度
+pub(crate) fn search_in_range(
+    conn: &Connection,
+    range: &TimeRange,
+    tier: MemoryTier,
+    tags: &[String],
+    now: DateTime<Utc>,
+    limit: usize,
+) -> Result<Vec<ScoredMemo>, anyhow::Error> {
+    let tier_clause = match tier {
+        MemoryTier::Active => "tier != 'archive'",
+        MemoryTier::Archive => "tier = 'archive'",
+    };
+    let sql = format!(
+        "SELECT id, content, tier, created_at, updated_at, expires_at FROM facts
+         WHERE {} AND superseded_by IS NULL AND {} AND {}
+         ORDER BY datetime(created_at) DESC, id DESC LIMIT ?1",
+        tier_clause,
+        range.sql_predicate("created_at"),
+        tags::sql_predicate("metadata", tags),
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let limit = limit.min(i64::MAX as usize) as i64;
+    let rows = stmt.query_map(params![limit], |row| {
+        let mut payload = Payload::new();
+        payload.insert(fields::CONTENT.to_string(), Value::from(row.get::<_, String>(1)?));
+        payload.insert(fields::TIER.to_string(), Value::from(row.get::<_, String>(2)?));
+        for (idx, field) in [(3, fields::CREATED_AT), (4, fields::UPDATED_AT), (5, fields::EXPIRES_AT)] {
+            if let Some(value) = row.get::<_, Option<String>>(idx)? {
+                payload.insert(field.to_string(), Value::from(value));
+            }
+        }
+        Ok(ScoredMemo { id: row.get(0)?, score: 0.0, payload })
+    })?.collect::<Result<Vec<_>, _>>()?;
+
+    Ok(rows.into_iter()
+        .filter(|p| !expiry::is_expired(&p.payload, now))
+        .map(|mut p| {
+            p.score = recency_score(&p.payload, now, DEFAULT_HALF_LIFE_DAYS);
+            p
+        })
+        .collect())
+}
+
+/// 新近度的默认半衰期（天）
+pub(crate) const DEFAULT_HALF_LIFE_DAYS: f32 = 30.0;
+
+/// 按最近一次修改（没有时用创建时间）计算的新近度，0..1，每过 half_life_days 减半
+pub(crate) fn recency_score(payload: &Payload, now: DateTime<Utc>, half_life_days: f32) -> f32 {
+    let timestamp = [fields::UPDATED_AT, fields::CREATED_AT].iter()
+        .find_map(|field| payload.get(*field).and_then(Value::as_str).and_then(expiry::parse_timestamp));
+    let Some(timestamp) = timestamp else { return 0.0 };
+    let age_days = (now - timestamp).num_seconds().max(0) as f32 / 86_400.0;
+    0.5_f32.powf(age_days / half_life_days)
+}
+
+fn single_day(day: NaiveDate) -> (NaiveDate, NaiveDate) {
+    (day, day + ChronoDuration::days(1))
+}
+
+/// 以周一为一周的开始；offset 为相对本周的周数
+fn week_of(today: NaiveDate, offset: i64) -> (NaiveDate, NaiveDate) {
+    let monday = today - ChronoDuration::days(today.weekday().num_days_from_monday() as i64) + ChronoDuration::weeks(offset);
+    (monday, monday + ChronoDuration::weeks(1))
+}
+
+fn month_of(today: NaiveDate, offset: i32) -> Option<(NaiveDate, NaiveDate)> {
+    let first = |months: i32| {
+        let index = today.year() * 12 + today.month0() as i32 + months;
+        NaiveDate::from_ymd_opt(index.div_euclid(12), index.rem_euclid(12) as u32 + 1, 1)
+    };
+    Some((first(offset)?, first(offset + 1)?))
+}
+
+fn year_of(today: NaiveDate, offset: i32) -> Option<(NaiveDate, NaiveDate)> {
+    let year = today.year() + offset;
+    Some((NaiveDate::from_ymd_opt(year, 1, 1)?, NaiveDate::from_ymd_opt(year + 1, 1, 1)?))
+}
+
+/// n 个单位对应的天数（月按 30 天、年按 365 天近似）
+fn unit_days(unit: &str, n: i64) -> Option<ChronoDuration> {
+    let days = match unit.trim_start_matches('个').to_lowercase().as_str() {
+        "天" | "日" | "day" => 1,
+        "周" | "星期" | "礼拜" | "week" => 7,
+        "月" | "month" => 30,
+        "年" | "year" => 365,
+        _ => return None,
+    };
+    (n > 0).then(|| ChronoDuration::days(days * n))
+}
+
+/// 解析阿拉伯数字或“三”“十二”“二十”这类中文数字（0..99）
+fn parse_number(text: &str) -> Option<i64> {
+    if let Ok(n) = text.parse() {
+        return Some(n);
+    }
+    let digit = |c: char| "零一二三四五六七八九".find(c).map(|i| (i / '零'.len_utf8()) as i64).or((c == '两').then_some(2));
+    let chars: Vec<char> = text.chars().collect();
+    match chars.as_slice() {
+        [c] if *c == '十' => Some(10),
+        [c] => digit(*c),
+        ['十', c] => Some(10 + digit(*c)?),
+        [t, '十'] => Some(digit(*t)? * 10),
+        [t, '十', c] => Some(digit(*t)? * 10 + digit(*c)?),
+        _ => None,
+    }
+}
+
+fn parse_english_number(text: &str) -> Option<i64> {
+    match text.to_lowercase().as_str() {
+        "a" | "one" => Some(1),
+        "two" => Some(2),
+        "three" => Some(3),
+        "four" => Some(4),
+        "five" => Some(5),
+        "six" => Some(6),
+        "seven" => Some(7),
+        other => other.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+
+    /// 参考时间：2025-03-12（周三）15:30，UTC+8
+    fn now() -> DateTime<FixedOffset> {
+        FixedOffset::east_opt(8 * 3600).unwrap().with_ymd_and_hms(2025, 3, 12, 15, 30, 0).unwrap()
+    }
+
+    /// UTC+8 某天零点对应的 UTC 时间
+    fn midnight(month: u32, day: u32) -> DateTime<Utc> {
+        FixedOffset::east_opt(8 * 3600).unwrap().with_ymd_and_hms(2025, month, day, 0, 0, 0).unwrap().with_timezone(&Utc)
+    }
+
+    /// (查询, 识别出的表达式, 起始 (月, 日), 结束 (月, 日))
+    type Case = (&'static str, &'static str, (u32, u32), (u32, u32));
+
+    #[test]
+    fn golden_time_ranges() {
+        let cases: &[Case] = &[
+            ("我昨天吃了什么", "昨天", (3, 11), (3, 12)),
+            ("前天去哪了", "前天", (3, 10), (3, 11)),
+            ("大前天见了谁", "大前天", (3, 9), (3, 10)),
+            ("今天的安排", "今天", (3, 12), (3, 13)),
+            ("上周记了什么", "上周", (3, 3), (3, 10)),
+            ("上个星期的会议", "上个星期", (3, 3), (3, 10)),
+            ("这周的待办", "这周", (3, 10), (3, 17)),
+            ("最近三天的记录", "最近三天", (3, 10), (3, 13)),
+            ("近7天", "近7天", (3, 6), (3, 13)),
+            ("两天前说的话", "两天前", (3, 10), (3, 11)),
+            ("上个月的账单", "上个月", (2, 1), (3, 1)),
+            ("前几天说的那家店", "前几天", (3, 6), (3, 13)),
+            ("what did I save last week", "last week", (3, 3), (3, 10)),
+            ("notes from 3 days ago", "3 days ago", (3, 9), (3, 10)),
+            ("the day before yesterday", "day before yesterday", (3, 10), (3, 11)),
+            ("past 2 weeks", "past 2 weeks", (2, 27), (3, 13)),
+            // 多个时间词取最先出现的
+            ("昨天和上周", "昨天", (3, 11), (3, 12)),
+        ];
+        for (query, expression, (sm, sd), (em, ed)) in cases {
+            let range = parse_time_range(query, &now()).unwrap_or_else(|| panic!("no range for '{}'", query));
+            assert_eq!(range.expression, *expression, "query '{}'", query);
+            assert_eq!((range.start, range.end), (midnight(*sm, *sd), midnight(*em, *ed)), "query '{}'", query);
+        }
+    }
+
+    #[test]
+    fn vague_recency_is_not_a_hard_range() {
+        for query in ["我最近喜欢什么", "近期有什么计划", "what have I been reading recently", "我喜欢什么"] {
+            assert_eq!(parse_time_range(query, &now()), None, "query '{}'", query);
+        }
+        assert!(mentions_recency("我最近喜欢什么"));
+        assert!(mentions_recency("what have I been reading lately"));
+        assert!(!mentions_recency("我喜欢什么"));
+        // 带具体区间的“最近”仍是硬过滤
+        assert!(parse_time_range("最近一周", &now()).is_some());
+    }
+
+    #[test]
+    fn strip_removes_the_expression() {
+        let range = parse_time_range("上周和小李吃了什么", &now()).unwrap();
+        assert_eq!(range.strip_from("上周和小李吃了什么").as_deref(), Some("和小李吃了什么"));
+        let range = parse_time_range("上周", &now()).unwrap();
+        assert_eq!(range.strip_from("上周"), None);
+    }
+
+    #[tokio::test]
+    async fn vague_recency_recall_keeps_older_memos() {
+        use crate::test_support::{embedded_agent, TestDir};
+        use crate::{ManualClock, RecallMode, SaveOutcome};
+        use std::sync::Arc;
+
+        let dir = TestDir::new();
+        let clock = Arc::new(ManualClock::new(Utc.with_ymd_and_hms(2025, 1, 6, 9, 0, 0).unwrap()));
+        let agent = embedded_agent(&dir, clock.clone()).await;
+        let SaveOutcome::Inserted(old) = agent.save("我喜欢吃火锅").await.unwrap() else { panic!("expected insert") };
+        clock.advance(ChronoDuration::days(60));
+
+        let trace = agent.recall_explain("我最近喜欢什么", None, RecallMode::Fuzzy, None).await.unwrap();
+        assert_eq!(trace.time_range, None);
+        assert!(trace.options.recency.enabled);
+        let hits = agent.recall("我最近喜欢什么", None, RecallMode::Fuzzy).await.unwrap();
+        assert!(hits.iter().any(|h| h.id == old), "hits: {:?}", hits);
+    }
+}
@@ -0,0 +1,73 @@
+// agent_memos/src/test_support.rs
+
+// 测试公共设施：临时数据目录、已完成迁移的连接池、替身 NER，以及用内嵌向量存储 + 哈希向量组装的 MemosAgent。
+// 不依赖任何模型文件或外部服务。
+
+use crate::db::{self, DbPool};
+use crate::embedding::HashingEmbeddingProvider;
+use crate::expiry::Clock;
+use crate::ner::EntityExtractor;
+use crate::vector_store::EmbeddedVectorStore;
+use crate::MemosAgent;
+use r2d2_sqlite::SqliteConnectionManager;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// 测试结束时自动删除的临时目录
+pub(crate) struct TestDir(PathBuf);
+
+impl TestDir {
+    pub(crate) fn new() -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "memos_agent_test_{}_{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// dir 下 memos.db 的连接池，已迁移到最新 schema
+pub(crate) fn migrated_pool(dir: &TestDir) -> DbPool {
+    let manager = SqliteConnectionManager::file(dir.path().join("memos.db"))
+        .with_init(|c| c.busy_timeout(std::time::Duration::from_secs(5)));
+    let pool = r2d2::Pool::new(manager).unwrap();
+    db::init_db(&pool).unwrap();
+    pool
+}
+
+/// 替身 NER：返回文本中出现的词表词
+pub(crate) struct DictionaryNer(pub Vec<&'static str>);
+
+impl EntityExtractor for DictionaryNer {
+    fn extract(&self, text: &str) -> Result<Vec<String>, anyhow::Error> {
+        Ok(self.0.iter().filter(|w| text.contains(*w)).map(|w| w.to_string()).collect())
+    }
+}
+
+pub(crate) fn test_ner() -> Arc<DictionaryNer> {
+    Arc::new(DictionaryNer(vec!["小李", "小王", "B2", "火锅"]))
+}
+
+/// 以内嵌向量存储 + 哈希向量组装的 MemosAgent
+pub(crate) async fn embedded_agent(dir: &TestDir, clock: Arc<dyn Clock>) -> MemosAgent {
+    let pool = migrated_pool(dir);
+    let vector_store = Arc::new(EmbeddedVectorStore::new(pool.clone()).unwrap());
+    MemosAgent::from_parts(pool, vector_store, Arc::new(HashingEmbeddingProvider::default()), test_ner(), clock, dir.path())
+        .await
+        .unwrap()
+}
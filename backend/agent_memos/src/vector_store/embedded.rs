@@ -0,0 +1,237 @@
+// agent_memos/src/vector_store/embedded.rs
+
+// 内嵌向量存储：把向量和 payload 直接存进 memos.db，检索时做暴力余弦计算。
+// 个人记忆库的规模（数千到数万条）下足够快，换来的是单文件部署、CI 无需外部进程。
+
+use super::{cosine_similarity, Filter, Payload, ScoredMemo, VectorPoint, VectorStore};
+use crate::db::DbPool;
+use async_trait::async_trait;
+use rusqlite::{params, OptionalExtension};
+
+pub struct EmbeddedVectorStore {
+    pool: DbPool,
+}
+
+impl EmbeddedVectorStore {
+    /// 表结构由 schema 迁移 v4 创建，pool 必须已经过 db::init_db
+    pub fn new(pool: DbPool) -> Result<Self, anyhow::Error> {
+        println!("[MemosAgent-DB] Embedded vector store initialized.");
+        Ok(Self { pool })
+    }
+
+    fn collection_dim(&self, collection: &str) -> Result<Option<u64>, anyhow::Error> {
+        let conn = self.pool.get()?;
+        let dim: Option<i64> = conn.query_row(
+            "SELECT dim FROM vector_collections WHERE name = ?1",
+            [collection],
+            |row| row.get(0),
+        ).optional()?;
+        Ok(dim.map(|d| d as u64))
+    }
+
+    /// 读取集合中所有点，交给调用方在内存中过滤或打分
+    fn load_points(&self, collection: &str) -> Result<Vec<(i64, Vec<f32>, Payload)>, anyhow::Error> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare("SELECT id, vector, payload FROM vector_points WHERE collection = ?1 ORDER BY id")?;
+        let rows = stmt.query_map([collection], |row| {
+            let id: i64 = row.get(0)?;
+            let blob: Vec<u8> = row.get(1)?;
+            let payload: String = row.get(2)?;
+            Ok((id, blob, payload))
+        })?;
+        let mut points = Vec::new();
+        for row in rows {
+            let (id, blob, payload) = row?;
+            let payload: Payload = serde_json::from_str(&payload)?;
+            points.push((id, decode_vector(&blob), payload));
+        }
+        Ok(points)
+    }
+}
+
+fn encode_vector(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+fn decode_vector(bytes: &[u8]) -> Vec<f32> {
+    bytes.chunks_exact(4).map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]])).collect()
+}
+
+#[async_trait]
+impl VectorStore for EmbeddedVectorStore {
+    fn name(&self) -> &'static str { "embedded" }
+
+    async fn ensure_collection(&self, collection: &str, dim: u64) -> Result<(), anyhow::Error> {
+        if self.collection_dim(collection)?.is_none() {
+            let conn = self.pool.get()?;
+            conn.execute(
+                "INSERT INTO vector_collections (name, dim) VALUES (?1, ?2)",
+                params![collection, dim as i64],
+            )?;
+            println!("[MemosAgent-DB] Embedded collection '{}' created.", collection);
+        }
+        Ok(())
+    }
+
+    async fn delete_collection(&self, collection: &str) -> Result<(), anyhow::Error> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM vector_points WHERE collection = ?1", [collection])?;
+        tx.execute("DELETE FROM vector_collections WHERE name = ?1", [collection])?;
+        tx.commit()?;
+        println!("[MemosAgent-DB] Embedded collection '{}' deleted.", collection);
+        Ok(())
+    }
+
+    async fn upsert(&self, collection: &str, points: Vec<VectorPoint>) -> Result<(), anyhow::Error> {
+        let dim = self.collection_dim(collection)?
+            .ok_or_else(|| anyhow::anyhow!("Embedded collection '{}' does not exist", collection))?;
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+        for point in points {
+            if point.vector.len() as u64 != dim {
+                return Err(anyhow::anyhow!(
+                    "Vector dimension mismatch for point {}: expected {}, got {}",
+                    point.id, dim, point.vector.len()
+                ));
+            }
+            tx.execute(
+                "INSERT OR REPLACE INTO vector_points (collection, id, vector, payload) VALUES (?1, ?2, ?3, ?4)",
+                params![collection, point.id, encode_vector(&point.vector), serde_json::to_string(&point.payload)?],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    async fn search(
+        &self,
+        collection: &str,
+        vector: Vec<f32>,
+        limit: u64,
+        score_threshold: Option<f32>,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<ScoredMemo>, anyhow::Error> {
+        let mut scored: Vec<ScoredMemo> = self.load_points(collection)?
+            .into_iter()
+            .filter(|(_, _, payload)| filter.is_none_or(|f| f.matches(payload)))
+            .map(|(id, v, payload)| ScoredMemo { id, score: cosine_similarity(&vector, &v), payload })
+            .filter(|m| score_threshold.is_none_or(|t| m.score >= t))
+            .collect();
+        scored.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        scored.truncate(limit as usize);
+        Ok(scored)
+    }
+
+    async fn scroll(&self, collection: &str, filter: &Filter, limit: u32) -> Result<Vec<ScoredMemo>, anyhow::Error> {
+        Ok(self.load_points(collection)?
+            .into_iter()
+            .filter(|(_, _, payload)| filter.matches(payload))
+            .take(limit as usize)
+            .map(|(id, _, payload)| ScoredMemo { id, score: 1.0, payload })
+            .collect())
+    }
+
+    async fn get_payload(&self, collection: &str, id: i64) -> Result<Option<Payload>, anyhow::Error> {
+        let conn = self.pool.get()?;
+        let payload: Option<String> = conn.query_row(
+            "SELECT payload FROM vector_points WHERE collection = ?1 AND id = ?2",
+            params![collection, id],
+            |row| row.get(0),
+        ).optional()?;
+        Ok(payload.map(|p| serde_json::from_str(&p)).transpose()?)
+    }
+
+    async fn delete(&self, collection: &str, ids: &[i64]) -> Result<(), anyhow::Error> {
+        let conn = self.pool.get()?;
+        for id in ids {
+            conn.execute("DELETE FROM vector_points WHERE collection = ?1 AND id = ?2", params![collection, id])?;
+        }
+        Ok(())
+    }
+
+    async fn list_ids(&self, collection: &str) -> Result<Vec<i64>, anyhow::Error> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare("SELECT id FROM vector_points WHERE collection = ?1 ORDER BY id")?;
+        let ids = stmt.query_map([collection], |row| row.get(0))?.collect::<Result<Vec<i64>, _>>()?;
+        Ok(ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{embedded_agent, migrated_pool, TestDir};
+    use crate::vector_store::Condition;
+    use crate::{ManualClock, RecallMode, SaveOutcome};
+    use chrono::{TimeZone, Utc};
+    use std::sync::Arc;
+
+    fn payload(content: &str) -> Payload {
+        let mut payload = Payload::new();
+        payload.insert("content".to_string(), serde_json::json!(content));
+        payload
+    }
+
+    #[tokio::test]
+    async fn points_roundtrip_through_sqlite() {
+        let dir = TestDir::new();
+        let store = EmbeddedVectorStore::new(migrated_pool(&dir)).unwrap();
+        store.ensure_collection("memos", 3).await.unwrap();
+        store.upsert("memos", vec![
+            VectorPoint { id: 7, vector: vec![1.0, 0.0, 0.0], payload: payload("停车在B2") },
+            VectorPoint { id: 8, vector: vec![0.6, 0.8, 0.0], payload: payload("周五吃火锅") },
+        ]).await.unwrap();
+
+        let hits = store.search("memos", vec![1.0, 0.0, 0.0], 5, None, None).await.unwrap();
+        assert_eq!(hits.iter().map(|h| h.id).collect::<Vec<_>>(), vec![7, 8]);
+        assert_eq!(hits[0].payload, payload("停车在B2"));
+        let above = store.search("memos", vec![1.0, 0.0, 0.0], 5, Some(0.9), None).await.unwrap();
+        assert_eq!(above.iter().map(|h| h.id).collect::<Vec<_>>(), vec![7]);
+        let scrolled = store.scroll("memos", &Filter::must([Condition::text_contains("content", "火锅")]), 10).await.unwrap();
+        assert_eq!(scrolled.iter().map(|h| h.id).collect::<Vec<_>>(), vec![8]);
+        let filtered = store.search("memos", vec![1.0, 0.0, 0.0], 5, None, Some(&Filter::must([Condition::text_contains("content", "火锅")]))).await.unwrap();
+        assert_eq!(filtered.iter().map(|h| h.id).collect::<Vec<_>>(), vec![8]);
+        // 维度不符的点被拒绝
+        assert!(store.upsert("memos", vec![VectorPoint { id: 9, vector: vec![1.0], payload: Payload::new() }]).await.is_err());
+
+        // 覆盖写入与删除
+        store.upsert("memos", vec![VectorPoint { id: 7, vector: vec![0.0, 0.0, 1.0], payload: payload("停车在B3") }]).await.unwrap();
+        let hits = store.search("memos", vec![0.0, 0.0, 1.0], 1, None, None).await.unwrap();
+        assert_eq!((hits[0].id, hits[0].content()), (7, Some("停车在B3")));
+        store.delete("memos", &[7, 8]).await.unwrap();
+        assert!(store.scroll("memos", &Filter::default(), 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn save_recall_update_delete() {
+        let dir = TestDir::new();
+        let clock = Arc::new(ManualClock::new(Utc.with_ymd_and_hms(2025, 3, 10, 9, 0, 0).unwrap()));
+        let agent = embedded_agent(&dir, clock).await;
+        let collection = agent.indexer.active_collection();
+        let stored = || async { agent.vector_store.scroll(&collection, &Filter::default(), 10).await.unwrap() };
+
+        let SaveOutcome::Inserted(id) = agent.save("我的车停在B2-103").await.unwrap() else { panic!("expected insert") };
+        let other = match agent.save("周五晚上和小李吃火锅").await.unwrap() {
+            SaveOutcome::Inserted(other) => other,
+            outcome => panic!("expected insert, got {:?}", outcome),
+        };
+        assert_eq!(agent.pending_index_ops().unwrap(), 0);
+        assert_eq!(stored().await.iter().map(|p| p.id).collect::<Vec<_>>(), vec![id, other]);
+
+        let hits = agent.recall("我的车停在哪", None, RecallMode::Fuzzy).await.unwrap();
+        assert_eq!(hits.first().map(|h| h.id), Some(id));
+        assert_eq!(hits[0].content(), Some("我的车停在B2-103"));
+
+        agent.update(id, "我的车停在B3-201", None).await.unwrap();
+        let hits = agent.recall("车停在B3", None, RecallMode::Fuzzy).await.unwrap();
+        assert_eq!(hits.first().map(|h| h.id), Some(id));
+        assert_eq!(hits[0].content(), Some("我的车停在B3-201"));
+
+        agent.delete(id, None).await.unwrap();
+        assert_eq!(agent.get_by_id(id).await.unwrap(), None);
+        assert_eq!(stored().await.iter().map(|p| p.id).collect::<Vec<_>>(), vec![other]);
+        let hits = agent.recall("我的车停在哪", None, RecallMode::Fuzzy).await.unwrap();
+        assert!(hits.iter().all(|h| h.id != id));
+    }
+}
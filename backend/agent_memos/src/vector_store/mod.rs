@@ -0,0 +1,235 @@
+// agent_memos/src/vector_store/mod.rs
+
+// 向量存储抽象层：MemosAgent 只依赖这里定义的 trait 与数据类型，
+// 具体是 Qdrant 还是内嵌的 SQLite 实现，由启动时的 VectorBackend 决定。
+
+mod embedded;
+mod qdrant;
+
+pub use embedded::EmbeddedVectorStore;
+pub use qdrant::QdrantVectorStore;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+/// 与存储后端无关的 payload 表示
+pub type Payload = serde_json::Map<String, Value>;
+
+/// payload 字段名。MANAGED 中的字段由 Indexer 根据 facts 表重新计算，其余字段更新时原样保留。
+pub mod fields {
+    pub const CONTENT: &str = "content";
+    pub const CREATED_AT: &str = "created_at";
+    pub const UPDATED_AT: &str = "updated_at";
+    pub const EXPIRES_AT: &str = "expires_at";
+    pub const ENTITIES: &str = "entities";
+    pub const TIER: &str = "tier";
+    pub const SUPERSEDED_BY: &str = "superseded_by";
+    pub const TAGS: &str = "tags";
+    /// 块点所属的父记忆 ID（见 chunking），记忆本身的点没有这个字段
+    pub const PARENT_ID: &str = "parent_id";
+    /// 块点在父记忆中的序号；召回结果由块映射回父记忆时保留，表示命中的位置
+    pub const CHUNK_INDEX: &str = "chunk_index";
+    /// 父记忆的点上记录的块数
+    pub const CHUNK_COUNT: &str = "chunk_count";
+
+    pub const MANAGED: &[&str] = &[CONTENT, CREATED_AT, UPDATED_AT, EXPIRES_AT, ENTITIES, TIER, SUPERSEDED_BY, TAGS, PARENT_ID, CHUNK_INDEX, CHUNK_COUNT];
+}
+
+/// 合并 payload：先去掉旧 payload 中所有受管字段（对应列已清空时字段随之消失），再写入新计算的字段
+pub fn merge_payload(existing: Option<Payload>, fresh: Payload) -> Payload {
+    let mut merged = existing.unwrap_or_default();
+    for field in fields::MANAGED {
+        merged.remove(*field);
+    }
+    merged.extend(fresh);
+    merged
+}
+
+/// 一条带分数的召回结果，取代原先一路向上暴露的 Qdrant `ScoredPoint`
+#[derive(Debug, Clone)]
+pub struct ScoredMemo {
+    pub id: i64,
+    pub score: f32,
+    pub payload: Payload,
+}
+
+impl ScoredMemo {
+    /// 便捷方法：读取 payload 中的 content 字段
+    pub fn content(&self) -> Option<&str> {
+        self.payload.get(fields::CONTENT).and_then(|v| v.as_str())
+    }
+}
+
+/// 写入向量库的一个点
+#[derive(Debug, Clone)]
+pub struct VectorPoint {
+    pub id: i64,
+    pub vector: Vec<f32>,
+    pub payload: Payload,
+}
+
+/// 单个过滤条件
+#[derive(Debug, Clone)]
+pub enum Condition {
+    /// 字段（或数组字段中的任一元素）包含给定文本
+    TextContains { field: String, text: String },
+    /// 字段（或数组字段中的任一元素）与给定值完全相等
+    Equals { field: String, value: String },
+    /// 字段不存在、为 null 或为空数组
+    IsEmpty { field: String },
+    /// 时间字段落在 [gte, lt) 内（缺省的一端不限）；字段缺失或无法解析时不满足
+    DatetimeRange { field: String, gte: Option<DateTime<Utc>>, lt: Option<DateTime<Utc>> },
+}
+
+impl Condition {
+    pub fn text_contains(field: &str, text: &str) -> Self {
+        Condition::TextContains { field: field.to_string(), text: text.to_string() }
+    }
+
+    pub fn equals(field: &str, value: &str) -> Self {
+        Condition::Equals { field: field.to_string(), value: value.to_string() }
+    }
+
+    pub fn is_empty(field: &str) -> Self {
+        Condition::IsEmpty { field: field.to_string() }
+    }
+
+    pub fn datetime_range(field: &str, gte: Option<DateTime<Utc>>, lt: Option<DateTime<Utc>>) -> Self {
+        Condition::DatetimeRange { field: field.to_string(), gte, lt }
+    }
+
+    fn matches(&self, payload: &Payload) -> bool {
+        match self {
+            Condition::TextContains { field, text } => match payload.get(field) {
+                Some(Value::String(s)) => s.contains(text.as_str()),
+                Some(Value::Array(items)) => items.iter().any(|item| {
+                    item.as_str().is_some_and(|s| s.contains(text.as_str()))
+                }),
+                _ => false,
+            },
+            Condition::Equals { field, value } => match payload.get(field) {
+                Some(Value::String(s)) => s == value,
+                Some(Value::Array(items)) => items.iter().any(|item| item.as_str() == Some(value.as_str())),
+                _ => false,
+            },
+            Condition::IsEmpty { field } => match payload.get(field) {
+                None | Some(Value::Null) => true,
+                Some(Value::Array(items)) => items.is_empty(),
+                _ => false,
+            },
+            Condition::DatetimeRange { field, gte, lt } => {
+                match payload.get(field).and_then(|v| v.as_str()).and_then(crate::expiry::parse_timestamp) {
+                    Some(t) => gte.is_none_or(|gte| t >= gte) && lt.is_none_or(|lt| t < lt),
+                    None => false,
+                }
+            }
+        }
+    }
+}
+
+/// 过滤器：所有 must 条件都必须满足，且任何 must_not 条件都不能满足
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    pub must: Vec<Condition>,
+    pub must_not: Vec<Condition>,
+}
+
+impl Filter {
+    pub fn must(conditions: impl IntoIterator<Item = Condition>) -> Self {
+        Self { must: conditions.into_iter().collect(), must_not: Vec::new() }
+    }
+
+    pub fn must_not(conditions: impl IntoIterator<Item = Condition>) -> Self {
+        Self { must: Vec::new(), must_not: conditions.into_iter().collect() }
+    }
+
+    /// 合并另一个过滤器的全部条件
+    pub fn and(mut self, other: Filter) -> Self {
+        self.must.extend(other.must);
+        self.must_not.extend(other.must_not);
+        self
+    }
+
+    /// 在内存中对 payload 求值，供不支持原生过滤的后端使用
+    pub fn matches(&self, payload: &Payload) -> bool {
+        self.must.iter().all(|c| c.matches(payload)) && !self.must_not.iter().any(|c| c.matches(payload))
+    }
+}
+
+/// 所有向量存储后端必须实现的行为
+#[async_trait]
+pub trait VectorStore: Send + Sync {
+    /// 后端名称，仅用于日志
+    fn name(&self) -> &'static str;
+
+    /// 确保集合存在；不存在时按给定维度创建
+    async fn ensure_collection(&self, collection: &str, dim: u64) -> Result<(), anyhow::Error>;
+
+    /// 删除整个集合（不存在时视为成功）
+    async fn delete_collection(&self, collection: &str) -> Result<(), anyhow::Error>;
+
+    /// 插入或覆盖若干个点
+    async fn upsert(&self, collection: &str, points: Vec<VectorPoint>) -> Result<(), anyhow::Error>;
+
+    /// 向量相似度搜索，结果按分数降序；给定 filter 时只在满足条件的点中搜索
+    async fn search(
+        &self,
+        collection: &str,
+        vector: Vec<f32>,
+        limit: u64,
+        score_threshold: Option<f32>,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<ScoredMemo>, anyhow::Error>;
+
+    /// 按过滤条件滚动读取，不涉及向量计算；返回的分数统一为 1.0
+    async fn scroll(&self, collection: &str, filter: &Filter, limit: u32) -> Result<Vec<ScoredMemo>, anyhow::Error>;
+
+    /// 读取单个点的 payload，点不存在时返回 None
+    async fn get_payload(&self, collection: &str, id: i64) -> Result<Option<Payload>, anyhow::Error>;
+
+    /// 按 ID 删除若干个点
+    async fn delete(&self, collection: &str, ids: &[i64]) -> Result<(), anyhow::Error>;
+
+    /// 列出集合中的全部点 ID，供一致性校验使用
+    async fn list_ids(&self, collection: &str) -> Result<Vec<i64>, anyhow::Error>;
+}
+
+/// 启动时选择的向量存储后端
+#[derive(Debug, Clone)]
+pub enum VectorBackend {
+    /// 外部 Qdrant 服务
+    Qdrant { url: String },
+    /// 内嵌在 memos.db 中的向量表，无需任何外部进程
+    Embedded,
+}
+
+impl VectorBackend {
+    /// 通过环境变量 MEMOS_VECTOR_BACKEND=embedded 切换到内嵌实现，否则使用 Qdrant
+    pub fn from_env(qdrant_url: &str) -> Self {
+        match std::env::var("MEMOS_VECTOR_BACKEND") {
+            Ok(v) if v.eq_ignore_ascii_case("embedded") => {
+                println!("[VectorStore] MEMOS_VECTOR_BACKEND=embedded. Using embedded SQLite vector store.");
+                VectorBackend::Embedded
+            }
+            _ => VectorBackend::Qdrant { url: qdrant_url.to_string() },
+        }
+    }
+}
+
+/// 余弦相似度，维度不一致或零向量时返回 0
+pub(crate) fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let (mut dot, mut norm_a, mut norm_b) = (0.0f32, 0.0f32, 0.0f32);
+    for (x, y) in a.iter().zip(b.iter()) {
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a.sqrt() * norm_b.sqrt())
+}
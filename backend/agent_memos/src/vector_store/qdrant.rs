@@ -0,0 +1,168 @@
+// agent_memos/src/vector_store/qdrant.rs
+
+use super::{Condition, Filter, Payload, ScoredMemo, VectorPoint, VectorStore};
+use async_trait::async_trait;
+use qdrant_client::qdrant::{
+    point_id, r#match::MatchValue, Condition as QdrantCondition, CreateCollectionBuilder, CreateFieldIndexCollectionBuilder,
+    DatetimeRange, DeletePointsBuilder, Distance, FieldType, Filter as QdrantFilter, GetPointsBuilder, PointId, PointStruct, PointsIdsList,
+    ScrollPointsBuilder, SearchPointsBuilder, UpsertPointsBuilder, Value as QdrantValue,
+    Timestamp, VectorParamsBuilder,
+};
+use chrono::{DateTime, Utc};
+use qdrant_client::{Payload as QdrantPayload, Qdrant};
+use std::collections::HashMap;
+
+/// 基于外部 Qdrant 服务的向量存储
+pub struct QdrantVectorStore {
+    client: Qdrant,
+}
+
+impl QdrantVectorStore {
+    pub fn new(url: &str) -> Result<Self, anyhow::Error> {
+        let client = Qdrant::from_url(url).build()?;
+        println!("[MemosAgent-DB] Qdrant client initialized.");
+        Ok(Self { client })
+    }
+}
+
+fn point_id_to_i64(id: Option<PointId>) -> Option<i64> {
+    match id?.point_id_options? {
+        point_id::PointIdOptions::Num(num) => Some(num as i64),
+        _ => None,
+    }
+}
+
+fn payload_to_json(payload: HashMap<String, QdrantValue>) -> Payload {
+    QdrantPayload::from(payload).into()
+}
+
+fn to_qdrant_condition(condition: &Condition) -> QdrantCondition {
+    match condition {
+        Condition::TextContains { field, text } => {
+            QdrantCondition::matches(field.as_str(), MatchValue::Text(text.clone()))
+        }
+        Condition::Equals { field, value } => {
+            QdrantCondition::matches(field.as_str(), value.clone())
+        }
+        Condition::IsEmpty { field } => QdrantCondition::is_empty(field.as_str()),
+        Condition::DatetimeRange { field, gte, lt } => {
+            let timestamp = |t: &DateTime<Utc>| Timestamp { seconds: t.timestamp(), nanos: 0 };
+            QdrantCondition::datetime_range(field.as_str(), DatetimeRange {
+                gte: gte.as_ref().map(timestamp),
+                lt: lt.as_ref().map(timestamp),
+                ..Default::default()
+            })
+        }
+    }
+}
+
+fn to_qdrant_filter(filter: &Filter) -> QdrantFilter {
+    QdrantFilter {
+        must: filter.must.iter().map(to_qdrant_condition).collect(),
+        must_not: filter.must_not.iter().map(to_qdrant_condition).collect(),
+        ..Default::default()
+    }
+}
+
+#[async_trait]
+impl VectorStore for QdrantVectorStore {
+    fn name(&self) -> &'static str { "qdrant" }
+
+    async fn ensure_collection(&self, collection: &str, dim: u64) -> Result<(), anyhow::Error> {
+        if !self.client.collection_exists(collection).await? {
+            self.client.create_collection(
+                CreateCollectionBuilder::new(collection)
+                    .vectors_config(VectorParamsBuilder::new(dim, Distance::Cosine))
+            ).await?;
+            println!("[MemosAgent-DB] Qdrant collection '{}' created.", collection);
+        }
+        // 标签过滤是精确匹配，建立 keyword 索引；索引已存在时 Qdrant 不做任何事，旧集合也能补上
+        self.client.create_field_index(
+            CreateFieldIndexCollectionBuilder::new(collection, super::fields::TAGS, FieldType::Keyword)
+        ).await?;
+        Ok(())
+    }
+
+    async fn delete_collection(&self, collection: &str) -> Result<(), anyhow::Error> {
+        if self.client.collection_exists(collection).await? {
+            self.client.delete_collection(collection).await?;
+            println!("[MemosAgent-DB] Qdrant collection '{}' deleted.", collection);
+        }
+        Ok(())
+    }
+
+    async fn upsert(&self, collection: &str, points: Vec<VectorPoint>) -> Result<(), anyhow::Error> {
+        let points: Vec<PointStruct> = points.into_iter()
+            .map(|p| PointStruct::new(p.id as u64, p.vector, QdrantPayload::from(p.payload)))
+            .collect();
+        self.client.upsert_points(UpsertPointsBuilder::new(collection, points)).await?;
+        Ok(())
+    }
+
+    async fn search(
+        &self,
+        collection: &str,
+        vector: Vec<f32>,
+        limit: u64,
+        score_threshold: Option<f32>,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<ScoredMemo>, anyhow::Error> {
+        let mut builder = SearchPointsBuilder::new(collection, vector, limit).with_payload(true);
+        if let Some(threshold) = score_threshold {
+            builder = builder.score_threshold(threshold);
+        }
+        if let Some(filter) = filter {
+            builder = builder.filter(to_qdrant_filter(filter));
+        }
+        let response = self.client.search_points(builder).await?;
+        Ok(response.result.into_iter().filter_map(|p| {
+            Some(ScoredMemo { id: point_id_to_i64(p.id)?, score: p.score, payload: payload_to_json(p.payload) })
+        }).collect())
+    }
+
+    async fn scroll(&self, collection: &str, filter: &Filter, limit: u32) -> Result<Vec<ScoredMemo>, anyhow::Error> {
+        let response = self.client.scroll(
+            ScrollPointsBuilder::new(collection).filter(to_qdrant_filter(filter)).limit(limit).with_payload(true)
+        ).await?;
+        Ok(response.result.into_iter().filter_map(|p| {
+            Some(ScoredMemo { id: point_id_to_i64(p.id)?, score: 1.0, payload: payload_to_json(p.payload) })
+        }).collect())
+    }
+
+    async fn get_payload(&self, collection: &str, id: i64) -> Result<Option<Payload>, anyhow::Error> {
+        let response = self.client.get_points(
+            GetPointsBuilder::new(collection, vec![PointId::from(id as u64)]).with_payload(true).with_vectors(false)
+        ).await?;
+        Ok(response.result.into_iter().next().map(|p| payload_to_json(p.payload)))
+    }
+
+    async fn delete(&self, collection: &str, ids: &[i64]) -> Result<(), anyhow::Error> {
+        let points_list = PointsIdsList {
+            ids: ids.iter().map(|id| point_id::PointIdOptions::Num(*id as u64).into()).collect(),
+        };
+        self.client.delete_points(DeletePointsBuilder::new(collection).points(points_list)).await?;
+        Ok(())
+    }
+
+    async fn list_ids(&self, collection: &str) -> Result<Vec<i64>, anyhow::Error> {
+        const PAGE_SIZE: u32 = 256;
+        let mut ids = Vec::new();
+        let mut offset: Option<PointId> = None;
+        loop {
+            let mut builder = ScrollPointsBuilder::new(collection)
+                .limit(PAGE_SIZE)
+                .with_payload(false)
+                .with_vectors(false);
+            if let Some(next) = offset.take() {
+                builder = builder.offset(next);
+            }
+            let response = self.client.scroll(builder).await?;
+            ids.extend(response.result.into_iter().filter_map(|p| point_id_to_i64(p.id)));
+            match response.next_page_offset {
+                Some(next) => offset = Some(next),
+                None => break,
+            }
+        }
+        Ok(ids)
+    }
+}
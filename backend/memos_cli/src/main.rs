@@ -1,6 +1,7 @@
 use orchestrator::Orchestrator;
-use agent_memos::MemosAgent;
-use memos_core::{Agent, Command, Response};
+use agent_memos::{embedding, extract_hashtags, parse_time_bound, GraphFilter, GraphFormat, ImportanceSource, MemoryTier, MemosAgent, RecallMode, RecallTrace, SaveOutcome, VectorBackend, DEFAULT_IMPORTANCE};
+use common_utils::PerformanceMode;
+use memos_core::{Agent, Citation, Command, Response};
 use rustyline::DefaultEditor;
 use sysinfo::System;
 // 引入标准库中的 env 模块来处理环境变量
@@ -56,7 +57,12 @@ async fn main() -> Result<(), anyhow::Error> {
     let qdrant_url = "http://localhost:6334";
     let llm_url = "http://localhost:8282";
     let embedding_url = "http://localhost:8181";
-    let memos_agent = MemosAgent::new(qdrant_url, embedding_url, &models_path).await?;
+    let vector_backend = VectorBackend::from_env(qdrant_url);
+    let embedder = embedding::provider_from_env(embedding_url, &models_path)?;
+    // 与重排序一致：只在质量优先模式下启用 HyDE 召回通道
+    let mode = if reranker_llm_url.is_some() { PerformanceMode::QualityFirst } else { PerformanceMode::PerformanceFirst };
+    let memos_agent = MemosAgent::new(vector_backend, embedder, &models_path).await?
+        .with_hyde(llm_url, mode);
     let agents: Vec<Box<dyn Agent>> = vec![Box::new(memos_agent)];
     println!("Agents loaded: {} agent(s)", agents.len());
 
@@ -96,6 +102,162 @@ async fn main() -> Result<(), anyhow::Error> {
                 }
                 // --- 反馈指令处理结束 ---
 
+                // --- 重建向量索引（更换嵌入模型或维度后使用，中断后再次执行会从断点继续） ---
+                if input.eq_ignore_ascii_case("/reindex") {
+                    println!("\n[助理]:");
+                    let result = match orchestrator.memos_agent() {
+                        Ok(agent) => agent.reindex(&|p| {
+                            println!("> 重建进度: {}/{} (集合 {})", p.processed, p.total, p.collection);
+                        }).await,
+                        Err(e) => Err(e),
+                    };
+                    match result {
+                        Ok(report) => {
+                            println!("索引重建完成：已用模型 '{}'（{} 维）重新索引 {} 条记忆。", report.embedding_model, report.dim, report.indexed);
+                            println!("> 生效集合已从 '{}' 切换到 '{}'。", report.previous_collection, report.collection);
+                        }
+                        Err(e) => eprintln!("索引重建失败: {}（再次执行 /reindex 可从断点继续）", e),
+                    }
+                    println!();
+                    continue;
+                }
+
+                // --- 记忆分层：/tier list [active|archive] | /tier set <ID> <active|archive> | /tier apply ---
+                if let Some(args) = input.strip_prefix("/tier") {
+                    println!("\n[助理]:");
+                    if let Err(e) = handle_tier_command(&orchestrator, args.split_whitespace().collect()).await {
+                        eprintln!("分层指令执行失败: {}", e);
+                    }
+                    println!();
+                    continue;
+                }
+
+                // --- 修订历史：/history <ID> | /restore <ID> <修订号> | /undo ---
+                if input.starts_with("/history") || input.starts_with("/restore") || input.eq_ignore_ascii_case("/undo") {
+                    println!("\n[助理]:");
+                    if let Err(e) = handle_history_command(&orchestrator, input.split_whitespace().collect()).await {
+                        eprintln!("历史指令执行失败: {}", e);
+                    }
+                    println!();
+                    continue;
+                }
+
+                // --- 批量去重：/dedupe 只报告，/dedupe apply 实际删除（可用 /undo 逐条撤销） ---
+                if let Some(args) = input.strip_prefix("/dedupe") {
+                    println!("\n[助理]:");
+                    let apply = args.trim().eq_ignore_ascii_case("apply");
+                    let result = match orchestrator.memos_agent() {
+                        Ok(agent) => agent.dedupe_store(apply).await,
+                        Err(e) => Err(e),
+                    };
+                    match result {
+                        Ok(report) => {
+                            println!("扫描了 {} 条记忆，发现 {} 组重复。", report.scanned, report.groups.len());
+                            for group in &report.groups {
+                                println!("> 保留 {}，重复: {:?}", group.keep, group.duplicates);
+                            }
+                            if apply {
+                                println!("已删除 {} 条重复记忆。", report.removed);
+                            } else if !report.groups.is_empty() {
+                                println!("这只是预览，输入 /dedupe apply 执行删除。");
+                            }
+                        }
+                        Err(e) => eprintln!("去重失败: {}", e),
+                    }
+                    println!();
+                    continue;
+                }
+
+                // --- 同义词：/synonym list | /synonym add <词> <同义词…> | /synonym remove <词> <同义词> | /synonym suggest <词> ---
+                if let Some(args) = input.strip_prefix("/synonym") {
+                    println!("\n[助理]:");
+                    if let Err(e) = handle_synonym_command(&orchestrator, args.split_whitespace().collect()) {
+                        eprintln!("同义词指令执行失败: {}", e);
+                    }
+                    println!();
+                    continue;
+                }
+
+                // --- 标签：/tags [list] | /tags rename <旧> <新> | /tags merge <目标> <来源…> | /tag <ID> <标签…> | /untag <ID> <标签…> ---
+                if matches!(input.split_whitespace().next(), Some("/tags" | "/tag" | "/untag")) {
+                    println!("\n[助理]:");
+                    if let Err(e) = handle_tag_command(&orchestrator, input).await {
+                        eprintln!("标签指令执行失败: {}", e);
+                    }
+                    println!();
+                    continue;
+                }
+
+                // --- 实体图：/entities [数量] | /entities rebuild | /entity <名称> | /entity alias <名称> <别名…> | /entity merge <目标> <来源…> ---
+                if matches!(input.split_whitespace().next(), Some("/entities" | "/entity")) {
+                    println!("\n[助理]:");
+                    if let Err(e) = handle_entity_command(&orchestrator, input.split_whitespace().collect()) {
+                        eprintln!("实体指令执行失败: {}", e);
+                    }
+                    println!();
+                    continue;
+                }
+
+                // --- 知识图谱导出：/export <dot|graphml|jsonld> [--tag <标签>]… [--time <时间表达>] [--since <日期>] [--until <日期>] [--entity <名称>] [--out <文件>] ---
+                if input.split_whitespace().next() == Some("/export") {
+                    println!("\n[助理]:");
+                    if let Err(e) = handle_export_command(&orchestrator, input.split_whitespace().skip(1).collect()) {
+                        eprintln!("导出失败: {}", e);
+                    }
+                    println!();
+                    continue;
+                }
+
+                // --- 重要度：/importance <ID> 查看 | /importance <ID> <0~1|高|中|低> 设置 ---
+                if input.split_whitespace().next() == Some("/importance") {
+                    println!("\n[助理]:");
+                    if let Err(e) = handle_importance_command(&orchestrator, input.split_whitespace().skip(1).collect()) {
+                        eprintln!("重要度指令执行失败: {}", e);
+                    }
+                    println!();
+                    continue;
+                }
+
+                // --- 直接保存（不经 LLM 提炼）：/save [--tag <标签>]… <内容>，内容中的 #话题 也作为标签 ---
+                if let Some(args) = input.strip_prefix("/save") {
+                    println!("\n[助理]:");
+                    if let Err(e) = handle_save_command(&orchestrator, input, args).await {
+                        eprintln!("保存失败: {}", e);
+                    }
+                    println!();
+                    continue;
+                }
+
+                // --- 查看 / 修改 / 删除单条记忆（回答引用的出处）：/memo <ID> | /edit <ID> <新内容> | /delete <ID> ---
+                if input.starts_with("/memo") || input.starts_with("/edit") || input.starts_with("/delete") {
+                    println!("\n[助理]:");
+                    if let Err(e) = handle_memo_command(&orchestrator, input).await {
+                        eprintln!("记忆指令执行失败: {}", e);
+                    }
+                    println!();
+                    continue;
+                }
+
+                // --- 召回诊断：/debug [--target] <查询> 逐步展示各路命中、融合与截断（--target 按目标查找模式） ---
+                if let Some(query) = input.strip_prefix("/debug") {
+                    println!("\n[助理]:");
+                    let query = query.trim();
+                    let (mode, query) = match query.strip_prefix("--target") {
+                        Some(rest) => (RecallMode::TargetLookup, rest.trim()),
+                        None => (RecallMode::Fuzzy, query),
+                    };
+                    if query.is_empty() {
+                        println!("用法: /debug [--target] <查询>");
+                    } else {
+                        match orchestrator.explain_recall(query, mode, None).await {
+                            Ok(trace) => print_recall_trace(&trace),
+                            Err(e) => eprintln!("召回诊断失败: {}", e),
+                        }
+                    }
+                    println!();
+                    continue;
+                }
+
                 let _ = rl.add_history_entry(input);
 
                 let command = Command::ProcessText(input.to_string());
@@ -110,10 +272,14 @@ async fn main() -> Result<(), anyhow::Error> {
                             Response::Text(text) => {
                                 println!("{}", text);
                             }
+                            Response::Cited { text, citations } => {
+                                println!("{}", text);
+                                print_citations(&citations);
+                            }
                             Response::FileToOpen(path) => {
                                 println!("请求打开文件: {:?}", path);
                             }
-                            Response::Stream(mut receiver) => {
+                            Response::Stream { mut receiver, citations } => {
                                 let mut is_thinking = false;
                                 let mut full_response = String::new();
 
@@ -166,6 +332,7 @@ async fn main() -> Result<(), anyhow::Error> {
                                     print!("{}", full_response);
                                 }
                                 println!();
+                                print_citations(&citations);
                             }
                         }
                     },
@@ -183,4 +350,438 @@ async fn main() -> Result<(), anyhow::Error> {
 
     println!("感谢使用，再见！");
     Ok(())
-}
\ No newline at end of file
+}
+
+async fn handle_tier_command(orchestrator: &Orchestrator, args: Vec<&str>) -> Result<(), anyhow::Error> {
+    let agent = orchestrator.memos_agent()?;
+    match args.as_slice() {
+        [] | ["list"] | ["list", _] => {
+            let tier = match args.get(1) {
+                Some(t) => Some(MemoryTier::parse(t).ok_or_else(|| anyhow::anyhow!("未知的层级 '{}'，可选 active / archive", t))?),
+                None => None,
+            };
+            let memos = agent.list_memos(tier, 50)?;
+            if memos.is_empty() {
+                println!("没有找到记忆。");
+            }
+            for memo in memos {
+                let retention = memo.retention.map_or(String::new(), |r| format!("，保持度 {:.2}", r));
+                println!("[{}] ({}，召回 {} 次{}) {}", memo.id, memo.tier.as_str(), memo.access_count, retention, memo.content);
+            }
+        }
+        ["set", id, tier] => {
+            let id: i64 = id.parse().map_err(|_| anyhow::anyhow!("无效的记忆 ID '{}'", id))?;
+            let tier = MemoryTier::parse(tier).ok_or_else(|| anyhow::anyhow!("未知的层级 '{}'，可选 active / archive", tier))?;
+            match agent.set_tier(id, tier).await? {
+                Some(change) => println!("已将记忆 {} 从 {} 移到 {}。", change.id, change.from.as_str(), change.to.as_str()),
+                None => println!("记忆 {} 已经在 {} 层。", id, tier.as_str()),
+            }
+        }
+        ["apply"] => {
+            let changes = agent.apply_tier_policy().await?;
+            println!("分层策略执行完成，{} 条记忆被归档。", changes.len());
+            for change in changes {
+                println!("> 记忆 {}: {} -> {}", change.id, change.from.as_str(), change.to.as_str());
+            }
+        }
+        _ => println!("用法: /tier list [active|archive] | /tier set <ID> <active|archive> | /tier apply"),
+    }
+    Ok(())
+}
+
+fn handle_importance_command(orchestrator: &Orchestrator, args: Vec<&str>) -> Result<(), anyhow::Error> {
+    let agent = orchestrator.memos_agent()?;
+    let parse_id = |id: &str| id.parse::<i64>().map_err(|_| anyhow::anyhow!("无效的记忆 ID '{}'", id));
+    match args.as_slice() {
+        [id] => {
+            let id = parse_id(id)?;
+            let Some((stats, retention)) = agent.retention_of(id)? else {
+                println!("没有找到记忆 {}。", id);
+                return Ok(());
+            };
+            println!("记忆 {}: 重要度 {:.2}，召回 {} 次，保持度 {:.2}", id, stats.importance, stats.access_count, retention);
+        }
+        [id, value] => {
+            let id = parse_id(id)?;
+            let importance = match *value {
+                "高" | "high" => 0.8,
+                "中" | "medium" => DEFAULT_IMPORTANCE,
+                "低" | "low" => 0.2,
+                v => v.parse::<f32>().ok().filter(|i| (0.0..=1.0).contains(i))
+                    .ok_or_else(|| anyhow::anyhow!("无效的重要度 '{}'，可填 0~1 之间的数或 高 / 中 / 低", v))?,
+            };
+            agent.set_importance(id, importance, ImportanceSource::User)?;
+            println!("已将记忆 {} 的重要度设为 {:.2}。", id, importance);
+        }
+        _ => println!("用法: /importance <ID> | /importance <ID> <0~1|高|中|低>"),
+    }
+    Ok(())
+}
+
+fn handle_synonym_command(orchestrator: &Orchestrator, args: Vec<&str>) -> Result<(), anyhow::Error> {
+    let agent = orchestrator.memos_agent()?;
+    match args.as_slice() {
+        [] | ["list"] => {
+            let synonyms = agent.synonyms();
+            if synonyms.is_empty() {
+                println!("同义词表为空。");
+            }
+            for (word, list) in synonyms {
+                println!("> {}: {}", word, list.join("、"));
+            }
+        }
+        ["add", word, synonyms @ ..] if !synonyms.is_empty() => {
+            for synonym in synonyms {
+                if agent.add_synonym(word, synonym)? {
+                    println!("已添加同义词: {} -> {}", word, synonym);
+                } else {
+                    println!("“{}”已经是“{}”的同义词。", synonym, word);
+                }
+            }
+        }
+        ["remove", word, synonym] => {
+            if agent.remove_synonym(word, synonym)? {
+                println!("已删除同义词: {} -> {}", word, synonym);
+            } else {
+                println!("“{}”不是“{}”的同义词。", synonym, word);
+            }
+        }
+        ["suggest", word] => {
+            let suggestions = agent.suggest_synonyms(word, 10)?;
+            if suggestions.is_empty() {
+                println!("记忆中没有足够的语境为“{}”推荐同义词。", word);
+            }
+            for s in &suggestions {
+                println!("> {} (相似度 {:.3}, 共同语境 {} 个)", s.word, s.score, s.shared_context);
+            }
+            if let Some(first) = suggestions.first() {
+                println!("确认后可用 /synonym add {} {} 加入同义词表。", word, first.word);
+            }
+        }
+        _ => println!("用法: /synonym list | /synonym add <词> <同义词…> | /synonym remove <词> <同义词> | /synonym suggest <词>"),
+    }
+    Ok(())
+}
+
+async fn handle_tag_command(orchestrator: &Orchestrator, input: &str) -> Result<(), anyhow::Error> {
+    let agent = orchestrator.memos_agent()?;
+    let args: Vec<&str> = input.split_whitespace().collect();
+    let parse_id = |id: &str| id.parse::<i64>().map_err(|_| anyhow::anyhow!("无效的记忆 ID '{}'", id));
+    let to_owned = |tags: &[&str]| tags.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+    match args.as_slice() {
+        ["/tags"] | ["/tags", "list"] => {
+            let tags = agent.list_tags()?;
+            if tags.is_empty() {
+                println!("还没有任何标签。保存时在内容中写 #标签，或用 /tag <ID> <标签…> 添加。");
+            }
+            for t in &tags {
+                println!("> #{} ({} 条)", t.tag, t.count);
+            }
+        }
+        ["/tags", "rename", from, to] => {
+            let affected = agent.rename_tag(from, to, Some(input)).await?;
+            println!("已将标签 #{} 改名为 #{}，涉及 {} 条记忆。", from, to, affected.len());
+        }
+        ["/tags", "merge", into, sources @ ..] if !sources.is_empty() => {
+            let affected = agent.merge_tags(&to_owned(sources), into, Some(input)).await?;
+            println!("已将 {} 合并为 #{}，涉及 {} 条记忆。", sources.join("、"), into, affected.len());
+        }
+        ["/tag", id, tags @ ..] if !tags.is_empty() => {
+            let id = parse_id(id)?;
+            let tags = agent.add_tags(id, &to_owned(tags), Some(input)).await?;
+            println!("记忆 {} 的标签: {}", id, format_tags(&tags));
+        }
+        ["/untag", id, tags @ ..] if !tags.is_empty() => {
+            let id = parse_id(id)?;
+            let tags = agent.remove_tags(id, &to_owned(tags), Some(input)).await?;
+            println!("记忆 {} 的标签: {}", id, if tags.is_empty() { "（无）".to_string() } else { format_tags(&tags) });
+        }
+        _ => println!("用法: /tags [list] | /tags rename <旧> <新> | /tags merge <目标> <来源…> | /tag <ID> <标签…> | /untag <ID> <标签…>"),
+    }
+    Ok(())
+}
+
+fn handle_entity_command(orchestrator: &Orchestrator, args: Vec<&str>) -> Result<(), anyhow::Error> {
+    let agent = orchestrator.memos_agent()?;
+    let print_summary = |e: &agent_memos::EntitySummary| {
+        let aliases = if e.aliases.is_empty() { String::new() } else { format!("（别名: {}）", e.aliases.join("、")) };
+        println!("> {}{}: {} 条记忆", e.name, aliases, e.memo_count);
+    };
+    match args.as_slice() {
+        ["/entities", "rebuild"] => {
+            let count = agent.rebuild_entity_graph()?;
+            println!("已为 {} 条记忆重建实体关联。", count);
+        }
+        ["/entities"] | ["/entities", _] => {
+            let limit = match args.get(1) {
+                Some(n) => n.parse::<usize>().map_err(|_| anyhow::anyhow!("无效的数量 '{}'", n))?,
+                None => 20,
+            };
+            let entities = agent.list_entities(limit)?;
+            if entities.is_empty() {
+                println!("还没有识别出任何实体。已有记忆可用 /entities rebuild 补齐。");
+            }
+            for e in &entities {
+                print_summary(e);
+            }
+        }
+        ["/entity", "alias", name, aliases @ ..] if !aliases.is_empty() => {
+            for alias in aliases {
+                print_summary(&agent.add_entity_alias(name, alias)?);
+            }
+        }
+        ["/entity", "merge", into, sources @ ..] if !sources.is_empty() => {
+            let sources: Vec<String> = sources.iter().map(|s| s.to_string()).collect();
+            print_summary(&agent.merge_entities(into, &sources)?);
+        }
+        ["/entity", name @ ..] if !name.is_empty() => {
+            let name = name.join(" ");
+            let Some(detail) = agent.entity(&name, 20, 10)? else {
+                println!("没有叫“{}”的实体。", name);
+                return Ok(());
+            };
+            print_summary(&detail.entity);
+            for memo in &detail.memos {
+                println!(">   #{} {}", memo.id, memo.content);
+            }
+            if !detail.neighbours.is_empty() {
+                let neighbours: Vec<String> = detail.neighbours.iter().map(|n| format!("{}({})", n.name, n.shared)).collect();
+                println!("> 常一起出现: {}", neighbours.join("、"));
+            }
+        }
+        _ => println!("用法: /entities [数量] | /entities rebuild | /entity <名称> | /entity alias <名称> <别名…> | /entity merge <目标> <来源…>"),
+    }
+    Ok(())
+}
+
+fn handle_export_command(orchestrator: &Orchestrator, args: Vec<&str>) -> Result<(), anyhow::Error> {
+    const USAGE: &str = "用法: /export <dot|graphml|jsonld> [--tag <标签>]… [--time <时间表达>] [--since <日期>] [--until <日期>] [--entity <名称>] [--out <文件>]";
+    let agent = orchestrator.memos_agent()?;
+    let Some(format) = args.first().and_then(|f| GraphFormat::parse(f)) else {
+        println!("{}", USAGE);
+        return Ok(());
+    };
+    let parse_bound = |value: &str| parse_time_bound(value).ok_or_else(|| anyhow::anyhow!("无法识别的日期 '{}'（支持 YYYY-MM-DD 或 RFC3339）", value));
+    let mut filter = GraphFilter::default();
+    let mut out = format!("memos_graph.{}", format.extension());
+    let mut rest = args[1..].iter();
+    while let Some(flag) = rest.next() {
+        let Some(value) = rest.next() else {
+            println!("{}", USAGE);
+            return Ok(());
+        };
+        match *flag {
+            "--tag" => filter.tags.push(value.to_string()),
+            "--time" => {
+                let range = agent.parse_time_range(value).ok_or_else(|| anyhow::anyhow!("无法识别的时间表达 '{}'", value))?;
+                filter.since = Some(range.start);
+                filter.until = Some(range.end);
+            }
+            "--since" => filter.since = Some(parse_bound(value)?),
+            "--until" => filter.until = Some(parse_bound(value)?),
+            "--entity" => filter.entity = Some(value.to_string()),
+            "--out" => out = value.to_string(),
+            _ => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+        }
+    }
+    let graph = agent.export_graph(format, &filter)?;
+    std::fs::write(&out, graph)?;
+    println!("知识图谱已导出到 {}。", out);
+    Ok(())
+}
+
+async fn handle_save_command(orchestrator: &Orchestrator, input: &str, args: &str) -> Result<(), anyhow::Error> {
+    let agent = orchestrator.memos_agent()?;
+    let mut tags = Vec::new();
+    let mut rest = args.trim();
+    while let Some(after) = rest.strip_prefix("--tag") {
+        let after = after.trim_start();
+        let (tag, remaining) = after.split_once(char::is_whitespace).unwrap_or((after, ""));
+        tags.push(tag.to_string());
+        rest = remaining.trim_start();
+    }
+    let (content, hashtags) = extract_hashtags(rest);
+    tags.extend(hashtags);
+    if content.is_empty() {
+        println!("用法: /save [--tag <标签>]… <内容>");
+        return Ok(());
+    }
+    match agent.save_with_expiry(&content, agent.infer_expiry(&content), &tags, Some(input)).await? {
+        SaveOutcome::Inserted(id) => {
+            let tags = agent.tags_of(id)?;
+            println!("已保存为记忆 {}{}", id, if tags.is_empty() { String::new() } else { format!("，标签: {}", format_tags(&tags)) });
+        }
+        SaveOutcome::Duplicate(id) => println!("与记忆 {} 重复，未保存。", id),
+        SaveOutcome::Similar(ids) => println!("与记忆 {:?} 相似，未保存。可用 /edit 修改已有记忆，或改写内容后再保存。", ids),
+    }
+    Ok(())
+}
+
+fn format_tags(tags: &[String]) -> String {
+    tags.iter().map(|t| format!("#{}", t)).collect::<Vec<_>>().join(" ")
+}
+
+async fn handle_memo_command(orchestrator: &Orchestrator, input: &str) -> Result<(), anyhow::Error> {
+    let agent = orchestrator.memos_agent()?;
+    let mut parts = input.splitn(3, char::is_whitespace);
+    let (command, id, rest) = (parts.next().unwrap_or_default(), parts.next(), parts.next().map(str::trim));
+    let Some(id) = id else {
+        println!("用法: /memo <ID> | /edit <ID> <新内容> | /delete <ID>");
+        return Ok(());
+    };
+    let id: i64 = id.parse().map_err(|_| anyhow::anyhow!("无效的记忆 ID '{}'", id))?;
+    let Some(content) = agent.get_by_id(id).await? else {
+        println!("记忆 {} 不存在。", id);
+        return Ok(());
+    };
+    match (command, rest) {
+        ("/memo", _) => {
+            println!("记忆 {}: {}", id, content);
+            let tags = agent.tags_of(id)?;
+            if !tags.is_empty() {
+                println!("> 标签: {}", format_tags(&tags));
+            }
+        }
+        ("/edit", Some(new_content)) if !new_content.is_empty() => {
+            agent.update(id, new_content, Some(input)).await?;
+            println!("已将记忆 {} 修改为: {}", id, new_content);
+        }
+        ("/delete", _) => {
+            agent.delete(id, Some(input)).await?;
+            println!("已删除记忆 {}: {}（可用 /undo 撤销）", id, content);
+        }
+        _ => println!("用法: /memo <ID> | /edit <ID> <新内容> | /delete <ID>"),
+    }
+    Ok(())
+}
+
+async fn handle_history_command(orchestrator: &Orchestrator, args: Vec<&str>) -> Result<(), anyhow::Error> {
+    let agent = orchestrator.memos_agent()?;
+    match args.as_slice() {
+        ["/history", id] => {
+            let id: i64 = id.parse().map_err(|_| anyhow::anyhow!("无效的记忆 ID '{}'", id))?;
+            let revisions = agent.history(id)?;
+            if revisions.is_empty() {
+                println!("记忆 {} 没有历史记录。", id);
+            }
+            for r in revisions {
+                let reverted = if r.reverted_by.is_some() { "（已撤销）" } else { "" };
+                let superseded = r.superseded_by.map(|by| format!("（已被 {} 取代）", by)).unwrap_or_default();
+                println!("#{} {:?}{}{} @ {}: {}", r.revision, r.op, reverted, superseded, r.created_at.unwrap_or_default(), r.content);
+                if !r.tags.is_empty() {
+                    println!(">   标签: {}", format_tags(&r.tags));
+                }
+                if let Some(request) = r.request {
+                    println!(">   原话: {}", request);
+                }
+            }
+        }
+        ["/restore", id, revision] => {
+            let id: i64 = id.parse().map_err(|_| anyhow::anyhow!("无效的记忆 ID '{}'", id))?;
+            let revision: i64 = revision.parse().map_err(|_| anyhow::anyhow!("无效的修订号 '{}'", revision))?;
+            agent.restore_revision(id, revision, Some(&args.join(" "))).await?;
+            println!("已将记忆 {} 恢复到修订 #{}。", id, revision);
+        }
+        ["/undo"] => match agent.undo_last_change(Some("/undo")).await? {
+            Some(outcome) => println!(
+                "已撤销记忆 {} 的 {:?} 操作。当前内容: {}",
+                outcome.fact_id, outcome.undone, outcome.content.as_deref().unwrap_or("（已移除）")
+            ),
+            None => println!("没有可以撤销的修改。"),
+        },
+        _ => println!("用法: /history <ID> | /restore <ID> <修订号> | /undo"),
+    }
+    Ok(())
+}
+
+fn print_citations(citations: &[Citation]) {
+    if citations.is_empty() {
+        return;
+    }
+    println!("出处:");
+    for c in citations {
+        let date = c.updated_at.as_deref().or(c.created_at.as_deref()).unwrap_or("未知时间");
+        println!("> [{}] {} (相关度 {:.3}, {})", c.memory_id, c.snippet, c.score, date);
+    }
+    println!("（可用 /memo <ID> 查看、/edit <ID> <新内容> 修改、/delete <ID> 删除出处记忆）");
+}
+
+fn print_recall_trace(trace: &RecallTrace) {
+    println!("查询: {}", trace.query);
+    println!("实体: {:?}{}", trace.entities, if trace.entities_from_context { "（来自上下文）" } else { "" });
+    println!("召回模式: {}", match trace.mode { RecallMode::Fuzzy => "模糊召回", RecallMode::TargetLookup => "目标查找" });
+    if !trace.expansions.is_empty() {
+        println!("查询扩展: {:?}", trace.expansions);
+    }
+    if !trace.keywords.is_empty() {
+        println!("关键词: {:?}", trace.keywords);
+    }
+    if !trace.entity_expansion.is_empty() {
+        let expansion: Vec<String> = trace.entity_expansion.iter()
+            .map(|e| match &e.via {
+                Some(via) => format!("{}({:.2}, 经 {})", e.name, e.weight, via),
+                None => format!("{}({:.2})", e.name, e.weight),
+            })
+            .collect();
+        println!("实体扩展: {}", expansion.join("、"));
+    }
+    if let Some(range) = &trace.time_range {
+        println!("时间范围: “{}” -> [{}, {})", range.expression, range.start, range.end);
+    }
+    if let Some(hyde) = &trace.hyde {
+        match (&hyde.document, &hyde.skipped) {
+            (Some(document), _) => println!("HyDE ({} ms): {}", hyde.elapsed_ms, document),
+            (None, Some(reason)) => println!("HyDE 已跳过 ({} ms): {}", hyde.elapsed_ms, reason),
+            (None, None) => {}
+        }
+    }
+    for tier in &trace.tiers {
+        println!("--- 层级 {} ---", tier.tier.as_str());
+        if let Some(targets) = &tier.targets {
+            println!("  [目标候选] {} 条", targets.len());
+            for t in targets {
+                println!(
+                    "    #{} 置信度 {:.3}（实体 {:.2} / 关键词 {:.2} / 语义 {:.2} / 新近 {:.2}）",
+                    t.id, t.confidence, t.entity, t.keyword, t.semantic, t.recency
+                );
+            }
+        }
+        for channel in &tier.channels {
+            println!("  [{}] 权重 {}，{} 条", channel.channel, channel.weight, channel.hits.len());
+            for hit in &channel.hits {
+                println!("    #{} {:.4} {}", hit.id, hit.score, hit.content.as_deref().unwrap_or(""));
+            }
+            if !channel.dropped_expired.is_empty() {
+                println!("    已过期被丢弃: {:?}", channel.dropped_expired);
+            }
+        }
+        if !tier.fused.is_empty() {
+            println!("  [融合]");
+            for hit in &tier.fused {
+                let parts: Vec<String> = hit.contributions.iter()
+                    .map(|c| format!("{}#{}={:.4}", c.channel, c.rank, c.contribution))
+                    .collect();
+                println!("    #{} {:.4} ({})", hit.id, hit.fused_score, parts.join(" + "));
+            }
+        }
+        if let Some(threshold) = &tier.threshold {
+            match threshold.cut_at {
+                Some(cut) => println!("  [截断] 保留前 {} 条：{}", cut, threshold.reason),
+                None => println!("  [截断] 未截断，保留 {} 条：{}", threshold.kept, threshold.reason),
+            }
+        }
+    }
+    println!("--- 结果（{}） ---", trace.resolved_by);
+    for hit in &trace.results {
+        println!("  #{} {:.4} {}", hit.id, hit.score, hit.content.as_deref().unwrap_or(""));
+    }
+    if let Some(scores) = &trace.rerank {
+        println!("--- 重排序 ---");
+        for score in scores {
+            println!("  #{} {:.4}", score.id, score.score);
+        }
+    }
+}
@@ -11,13 +11,28 @@ pub enum Command {
     // 未来可以扩展: ProcessAudioChunk(Vec<f32>), etc.
 }
 
+// 回答所引用的一条记忆，UI 可据此跳转查看、编辑或删除原始记忆
+#[derive(Debug, Clone)]
+pub struct Citation {
+    pub memory_id: i64,
+    // 记忆内容的摘录（过长时截断）
+    pub snippet: String,
+    // 相关度：有重排序时为重排序分数，否则为召回的融合分数
+    pub score: f32,
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+}
+
 // 2. 标准化的响应：调度器返回给UI层的唯一出口
 // 移除 Clone 派生，因为 mpsc::Receiver 不能 Clone
 #[derive(Debug)] // 只有 Debug，没有 Clone
 pub enum Response {
     Text(String),
     FileToOpen(PathBuf),
-    Stream(mpsc::Receiver<String>), // 新增：用于流式文本
+    // 流式文本，以空字符串表示结束；citations 是回答所依据的记忆（没有时为空）
+    Stream { receiver: mpsc::Receiver<String>, citations: Vec<Citation> },
+    // 带出处的完整回答
+    Cited { text: String, citations: Vec<Citation> },
     // ... etc.
 }
 
@@ -0,0 +1,169 @@
+// backend/micromodels/src/embedder.rs
+
+// 进程内句向量模型（BERT 家族，例如 bge-small-zh 导出的 ONNX）。
+// 复用与分类器相同的 ort Session 加载方式，自带一个最小可用的 WordPiece 分词器，
+// 这样就不需要额外的 embedding 服务进程。
+
+use anyhow::{anyhow, Result};
+use ort::inputs;
+use ort::session::{builder::GraphOptimizationLevel, Session};
+use ort::value::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+const MAX_SEQUENCE_LENGTH: usize = 512;
+const MAX_WORDPIECE_CHARS: usize = 100;
+
+pub struct TextEmbedder {
+    session: Session,
+    vocab: HashMap<String, i64>,
+    cls_id: i64,
+    sep_id: i64,
+    unk_id: i64,
+    needs_token_type_ids: bool,
+    dimension: usize,
+}
+
+impl TextEmbedder {
+    /// model_path 指向 ONNX 模型，vocab_path 指向与之配套的 vocab.txt（每行一个词元）
+    pub fn load(model_path: impl AsRef<Path>, vocab_path: impl AsRef<Path>) -> Result<Self> {
+        println!("[TextEmbedder] Loading embedding model from: {:?}", model_path.as_ref());
+        let _ = ort::init().with_name("zhzAI-micromodels").commit();
+
+        let model_bytes = fs::read(&model_path)?;
+        let session = Session::builder()
+            .map_err(|e| anyhow!("Failed to create session builder: {}", e))?
+            .with_optimization_level(GraphOptimizationLevel::Level3)
+            .map_err(|e| anyhow!("Failed to set optimization level: {}", e))?
+            .commit_from_memory(&model_bytes)
+            .map_err(|e| anyhow!("Failed to commit model from memory: {}", e))?;
+
+        let vocab: HashMap<String, i64> = fs::read_to_string(vocab_path)?
+            .lines()
+            .enumerate()
+            .map(|(i, token)| (token.trim_end().to_string(), i as i64))
+            .collect();
+        let lookup = |token: &str| vocab.get(token).copied().ok_or_else(|| anyhow!("{} token not found in vocabulary", token));
+        let cls_id = lookup("[CLS]")?;
+        let sep_id = lookup("[SEP]")?;
+        let unk_id = lookup("[UNK]")?;
+
+        let needs_token_type_ids = session.inputs.iter().any(|i| i.name == "token_type_ids");
+
+        let mut embedder = Self { session, vocab, cls_id, sep_id, unk_id, needs_token_type_ids, dimension: 0 };
+        // 用一次探测推理确定输出维度
+        embedder.dimension = embedder.embed("维度")?.len();
+        println!("[TextEmbedder] Embedding model loaded successfully. Dimension: {}", embedder.dimension);
+        Ok(embedder)
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    /// 生成 L2 归一化后的句向量（取 [CLS] 位置的隐藏状态）
+    pub fn embed(&mut self, text: &str) -> Result<Vec<f32>> {
+        let mut ids = vec![self.cls_id];
+        ids.extend(self.tokenize(text));
+        ids.truncate(MAX_SEQUENCE_LENGTH - 1);
+        ids.push(self.sep_id);
+
+        let seq_len = ids.len();
+        let input_ids = Value::from_array(([1, seq_len], ids))
+            .map_err(|e| anyhow!("Failed to create input_ids tensor: {}", e))?;
+        let attention_mask = Value::from_array(([1, seq_len], vec![1i64; seq_len]))
+            .map_err(|e| anyhow!("Failed to create attention_mask tensor: {}", e))?;
+
+        let outputs = if self.needs_token_type_ids {
+            let token_type_ids = Value::from_array(([1, seq_len], vec![0i64; seq_len]))
+                .map_err(|e| anyhow!("Failed to create token_type_ids tensor: {}", e))?;
+            self.session.run(inputs![
+                "input_ids" => input_ids,
+                "attention_mask" => attention_mask,
+                "token_type_ids" => token_type_ids
+            ])
+        } else {
+            self.session.run(inputs![
+                "input_ids" => input_ids,
+                "attention_mask" => attention_mask
+            ])
+        }.map_err(|e| anyhow!("ONNX session run failed: {}", e))?;
+
+        let (shape, data) = outputs[0].try_extract_tensor::<f32>()
+            .map_err(|e| anyhow!("Failed to extract f32 tensor from ONNX output: {}", e))?;
+
+        // [1, seq, hidden] 取第一个位置；[1, hidden] 说明模型已经做过池化
+        let hidden = *shape.last().ok_or_else(|| anyhow!("ONNX output has empty shape"))? as usize;
+        let mut vector = data.get(..hidden)
+            .ok_or_else(|| anyhow!("ONNX output shorter than hidden size {}", hidden))?
+            .to_vec();
+
+        let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|v| *v /= norm);
+        }
+        Ok(vector)
+    }
+
+    /// BERT 基础分词 + WordPiece：中日韩字符逐字切分，其余按空白和标点切分
+    fn tokenize(&self, text: &str) -> Vec<i64> {
+        let mut words: Vec<String> = Vec::new();
+        let mut current = String::new();
+        for c in text.to_lowercase().chars() {
+            if c.is_whitespace() || is_cjk(c) || c.is_ascii_punctuation() || (!c.is_alphanumeric() && !c.is_ascii()) {
+                if !current.is_empty() {
+                    words.push(std::mem::take(&mut current));
+                }
+                if !c.is_whitespace() {
+                    words.push(c.to_string());
+                }
+            } else {
+                current.push(c);
+            }
+        }
+        if !current.is_empty() {
+            words.push(current);
+        }
+
+        words.iter().flat_map(|w| self.wordpiece(w)).collect()
+    }
+
+    fn wordpiece(&self, word: &str) -> Vec<i64> {
+        let chars: Vec<char> = word.chars().collect();
+        if chars.len() > MAX_WORDPIECE_CHARS {
+            return vec![self.unk_id];
+        }
+        let mut pieces = Vec::new();
+        let mut start = 0;
+        while start < chars.len() {
+            let mut end = chars.len();
+            let mut found = None;
+            while start < end {
+                let mut candidate: String = chars[start..end].iter().collect();
+                if start > 0 {
+                    candidate.insert_str(0, "##");
+                }
+                if let Some(&id) = self.vocab.get(&candidate) {
+                    found = Some(id);
+                    break;
+                }
+                end -= 1;
+            }
+            match found {
+                Some(id) => {
+                    pieces.push(id);
+                    start = end;
+                }
+                None => return vec![self.unk_id],
+            }
+        }
+        pieces
+    }
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x4E00..=0x9FFF | 0x3400..=0x4DBF | 0x20000..=0x2A6DF | 0x2A700..=0x2B73F
+        | 0x2B740..=0x2B81F | 0x2B820..=0x2CEAF | 0xF900..=0xFAFF | 0x2F800..=0x2FA1F)
+}
@@ -14,6 +14,9 @@ use std::fs;
 use prost::Message;
 use std::io::BufReader;
 
+mod embedder;
+pub use embedder::TextEmbedder;
+
 // 包含由build.rs在OUT_DIR中生成的代码
 pub mod proto {
     include!(concat!(env!("OUT_DIR"), "/micromodels.rs"));
@@ -65,7 +68,6 @@ impl Classifier {
             let file = fs::File::open(&dict_path)?;
             let mut reader = BufReader::new(file);
             jieba.load_dict(&mut reader)?;
-        } else {
         }
 
         Ok(Self { 
@@ -116,7 +118,7 @@ impl Classifier {
             };
 
             if let Ok(strings_vec_tuple) = outputs[0].try_extract_strings() {
-                strings_vec_tuple.1.iter().next().map(|s| s.to_string())
+                strings_vec_tuple.1.first().map(|s| s.to_string())
             } else {
                 None
             }
@@ -263,7 +265,7 @@ impl NerClassifier {
 
             let token_tag_pairs: Vec<(String, String)> = tokens
                 .into_iter()
-                .zip(tag_indices.into_iter())
+                .zip(tag_indices)
                 .map(|(token, tag_ix)| {
                     let tag = self.ix_to_tag.get(&(tag_ix as i32)).cloned().unwrap_or_else(|| "O".to_string());
                     (token.to_string(), tag)
@@ -0,0 +1,49 @@
+// orchestrator/src/experts/memos_agent/contradiction_expert.rs
+
+use serde::Deserialize;
+use serde_json::Value;
+
+// 定义 ContradictionExpert 的输出结构：被新事实推翻的旧记忆编号（从 1 开始）
+#[derive(Deserialize, Debug)]
+pub struct ContradictionVerdict {
+    pub contradicted: Vec<usize>,
+}
+
+// 获取判断新事实是否推翻旧记忆的 Prompt
+pub fn get_contradiction_prompt(new_fact: &str, existing_memories: &[String]) -> Vec<Value> {
+    let numbered = existing_memories.iter().enumerate()
+        .map(|(i, m)| format!("{}. {}", i + 1, m))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let system_prompt = format!(
+r#"You are a careful fact checker for a personal memory assistant. The user just told the assistant a NEW fact. Decide which of the EXISTING memories are no longer true because of the new fact.
+
+**CRITICAL INSTRUCTIONS:**
+- An existing memory is contradicted only if both cannot be true at the same time, e.g. "I live in Beijing" vs "I moved to Shanghai", or "My favorite language is Rust" vs "My favorite language is now Go".
+- Memories that merely talk about the same topic, add details, or can both be true are NOT contradicted.
+- If unsure, do NOT list the memory.
+- Your output MUST be a valid JSON object with a single field "contradicted": an array of the numbers of contradicted memories (empty if none).
+
+**New Fact:**
+---
+{}
+---
+
+**Existing Memories:**
+---
+{}
+---"#, new_fact, numbered);
+
+    vec![
+        serde_json::json!({"role": "system", "content": system_prompt}),
+    ]
+}
+
+// 只允许输出编号数组，避免模型自由发挥
+pub fn get_contradiction_gbnf_schema() -> &'static str {
+    r#"
+root   ::= "{" ws "\"contradicted\"" ws ":" ws "[" ws (number (ws "," ws number)*)? ws "]" ws "}"
+number ::= [1-9] [0-9]?
+ws     ::= ([ \t\n\r])*
+"#
+}
@@ -2,9 +2,11 @@
 
 // 声明所有与 MemosAgent 相关的专家模块
 
+pub mod contradiction_expert;
 pub mod delete_expert;
 pub mod modify_expert;
 pub mod re_ranker;
 pub mod recall_expert;
 
-pub mod save_expert;
\ No newline at end of file
+pub mod save_expert;
+pub mod synthesis_expert;
\ No newline at end of file
@@ -24,7 +24,10 @@ pub struct RankedDocument {
 // --- 策略定义 ---
 #[derive(Debug)] // ReRankStrategy 不再需要 Clone
 pub enum ReRankStrategy {
-    ValidateTopOne { threshold: f32 },
+    /// 返回分数达到阈值的前 limit 条（按分数降序）；limit 为 1 时即“只验证第一名”
+    TopN { threshold: f32, limit: usize },
+    /// 返回全部文档的打分（按分数降序），用于诊断
+    ScoreAll,
 }
 
 // --- 内部 LLM API 交互结构 ---
@@ -96,7 +99,7 @@ impl ReRanker {
         }
 
         let mut ranked_docs: Vec<RankedDocument> = request.documents.into_iter()
-            .zip(scores.into_iter())
+            .zip(scores)
             .map(|(doc, score)| {
                 RankedDocument {
                     text: doc.text.to_string(),
@@ -106,22 +109,18 @@ impl ReRanker {
             .collect();
 
         ranked_docs.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
-        println!("[ReRanker] Ranking completed. Top score: {}", ranked_docs.get(0).map_or(0.0, |d| d.score));
+        println!("[ReRanker] Ranking completed. Top score: {}", ranked_docs.first().map_or(0.0, |d| d.score));
 
         match strategy {
-            ReRankStrategy::ValidateTopOne { threshold } => {
-                if let Some(top_doc) = ranked_docs.get(0) {
-                    if top_doc.score >= threshold {
-                        println!("[ReRanker] Top document score {} exceeds threshold {}. Returning.", top_doc.score, threshold);
-                        Ok(vec![top_doc.clone()])
-                    } else {
-                        println!("[ReRanker] Top document score {} did not exceed threshold {}.", top_doc.score, threshold);
-                        Ok(vec![])
-                    }
-                } else {
-                    Ok(vec![])
-                }
+            ReRankStrategy::TopN { threshold, limit } => {
+                let passed: Vec<RankedDocument> = ranked_docs.into_iter()
+                    .take_while(|doc| doc.score >= threshold)
+                    .take(limit)
+                    .collect();
+                println!("[ReRanker] {} document(s) reached threshold {} (limit {}).", passed.len(), threshold, limit);
+                Ok(passed)
             }
+            ReRankStrategy::ScoreAll => Ok(ranked_docs),
         }
     }
 }
\ No newline at end of file
@@ -6,15 +6,30 @@ use serde_json::Value;
 #[derive(serde::Deserialize, Debug)]
 pub struct ExtractedFact {
     pub fact: String,
+    // 模型建议的分类标签，可以为空
+    #[serde(default)]
+    pub tags: Vec<String>,
+    // 模型估计的重要度，1（琐碎）到 5（关键），缺省表示未估计
+    #[serde(default)]
+    pub importance: Option<u8>,
+}
+
+impl ExtractedFact {
+    /// 把 1–5 的估计换算到 [0, 1]
+    pub fn importance_score(&self) -> Option<f32> {
+        self.importance.filter(|i| (1..=5).contains(i)).map(|i| (i - 1) as f32 / 4.0)
+    }
 }
 
 pub fn get_fact_extraction_prompt(user_input: &str) -> Vec<Value> {
-    let system_prompt = r#"Your task is to extract the core fact from the user's input. Output ONLY the cleaned, pure fact in a JSON object.
+    let system_prompt = r#"Your task is to extract the core fact from the user's input. Output ONLY the cleaned, pure fact in a JSON object, together with up to 3 short topic tags (e.g. "工作", "家庭", "健康", "编程") that categorize it. Use an empty list when no tag fits. Also rate how important the fact is to remember long-term, from 1 (trivial, short-lived) to 5 (critical: health, identity, obligations, key dates).
 
 **Your Output MUST be a valid JSON object:**
 ```json
 {
-  "fact": "The extracted fact goes here."
+  "fact": "The extracted fact goes here.",
+  "tags": ["tag"],
+  "importance": 3
 }
 ```
 
@@ -22,7 +37,9 @@ pub fn get_fact_extraction_prompt(user_input: &str) -> Vec<Value> {
 <user_input>帮我记一下：我最喜欢的编程语言是Rust。</user_input>
 <assistant_response>
 {
-  "fact": "我最喜欢的编程语言是Rust。"
+  "fact": "我最喜欢的编程语言是Rust。",
+  "tags": ["编程"],
+  "importance": 2
 }
 </assistant_response>
 </example>
@@ -37,7 +54,8 @@ Now, extract the core fact from the user's input."#;
 
 // 新增：为 SaveExpert 添加 GBNF Schema
 pub fn get_fact_extraction_gbnf_schema() -> &'static str {
-    r#"root ::= "{" ws "\"fact\":" ws string ws "}"
+    r#"root ::= "{" ws "\"fact\":" ws string ws "," ws "\"tags\":" ws tags ws "," ws "\"importance\":" ws [1-5] ws "}"
+tags ::= "[" ws ( string ( ws "," ws string )? ( ws "," ws string )? )? ws "]"
 string ::= "\"" (
   [^"\\] |
   "\\" (["\\/bfnrt] | "u" [0-9a-fA-F] [0-9a-fA-F] [0-9a-fA-F] [0-9a-fA-F])
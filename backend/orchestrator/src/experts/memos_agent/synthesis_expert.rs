@@ -0,0 +1,56 @@
+// orchestrator/src/experts/memos_agent/synthesis_expert.rs
+
+use agent_memos::prompts;
+use serde_json::Value;
+
+// 获取综合多条记忆作答的 Prompt：系统提示词沿用 agent_memos 中调好的版本，记忆片段作为上下文
+pub fn get_synthesis_messages(user_query: &str, memories: &[String]) -> Vec<Value> {
+    let context = memories.iter()
+        .map(|m| format!("- {}", m))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let user_prompt = format!(
+        "<User_Query>{}</User_Query>\n<Context>\n{}\n</Context>",
+        user_query, context
+    );
+
+    vec![
+        serde_json::json!({"role": "system", "content": prompts::get_synthesis_prompt()}),
+        serde_json::json!({"role": "user", "content": user_prompt}),
+    ]
+}
+
+// 从一行 SSE（"data: {...}"）中取出增量文本：不是数据行时返回 None，流结束标记 [DONE] 返回 Some(None)
+pub fn parse_stream_line(line: &str) -> Option<Option<String>> {
+    let data = line.trim().strip_prefix("data:")?.trim();
+    if data == "[DONE]" {
+        return Some(None);
+    }
+    let chunk: Value = serde_json::from_str(data).ok()?;
+    let delta = chunk["choices"][0]["delta"]["content"].as_str().unwrap_or_default();
+    Some(Some(delta.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_stream_line_handles_sse_framing() {
+        assert_eq!(parse_stream_line(r#"data: {"choices":[{"delta":{"content":"周五"}}]}"#), Some(Some("周五".to_string())));
+        assert_eq!(parse_stream_line(r#"data:{"choices":[{"delta":{"content":" 吃火锅"}}]}"#), Some(Some(" 吃火锅".to_string())));
+        assert_eq!(parse_stream_line("data: [DONE]"), Some(None));
+        assert_eq!(parse_stream_line("  data: [DONE]\r"), Some(None));
+        // 只带 role 或空内容的增量
+        assert_eq!(parse_stream_line(r#"data: {"choices":[{"delta":{"role":"assistant"}}]}"#), Some(Some(String::new())));
+        assert_eq!(parse_stream_line(r#"data: {"choices":[{"delta":{"content":""}}]}"#), Some(Some(String::new())));
+        assert_eq!(parse_stream_line(r#"data: {"choices":[]}"#), Some(Some(String::new())));
+        // 空行、注释形式的保活行、其他字段都不是数据行
+        assert_eq!(parse_stream_line(""), None);
+        assert_eq!(parse_stream_line(": keep-alive"), None);
+        assert_eq!(parse_stream_line("event: ping"), None);
+        // 残缺的 JSON 跳过，不中断流
+        assert_eq!(parse_stream_line(r#"data: {"choices":[{"delta":"#), None);
+        assert_eq!(parse_stream_line("data: "), None);
+    }
+}
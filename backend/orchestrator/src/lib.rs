@@ -4,8 +4,8 @@ mod experts;
 mod preprocessors;
 use micromodels::{Classifier, Intent as MicroIntent}; // 使用别名避免与未来可能的内部Intent冲突
 use std::path::Path;
-use agent_memos::MemosAgent;
-use memos_core::{Agent, Command, Response};
+use agent_memos::{fields, ImportanceSource, MemosAgent, RecallMode, RecallOptions, RecallTrace, RerankScore, RevisionOp, SaveOutcome, ScoredMemo};
+use memos_core::{Agent, Citation, Command, Response};
 use reqwest::Client;
 use serde::Deserialize;
 use serde_json::json;
@@ -15,10 +15,24 @@ use std::sync::{Arc, Mutex};
 use experts::memos_agent::{
     save_expert,
     modify_expert,
+    contradiction_expert,
+    synthesis_expert,
     re_ranker::{ReRanker, ReRankRequest, DocumentToRank, ReRankStrategy},
 };
 use std::fs::OpenOptions;
 use std::io::Write;
+use futures_util::StreamExt;
+use tokio::sync::mpsc;
+
+/// 参与综合作答的记忆条数上限
+const SYNTHESIS_TOP_N: usize = 3;
+/// 重排序分数低于该值的候选不参与作答
+const RERANK_RELEVANCE_THRESHOLD: f32 = 0.1;
+/// 引用摘录的最大字符数
+const CITATION_SNIPPET_CHARS: usize = 80;
+/// 目标查找时，最高置信度达到该值且领先第二名足够多，就直接选中而不让用户挑选
+const TARGET_AUTO_SELECT_CONFIDENCE: f32 = 0.6;
+const TARGET_AUTO_SELECT_MARGIN: f32 = 0.15;
 
 #[derive(Debug, Clone)]
 pub enum PendingActionType {
@@ -31,6 +45,36 @@ pub enum PendingActionType {
         // 存储原始意图，以便用户做出选择后，我们知道是该修改还是删除
         original_intent: ClarifiableIntent,
     },
+    // --- 新增状态：待保存的内容与已有记忆相似，等待用户选择合并、替换还是都保留 ---
+    DuplicateResolution { new_content: String, tags: Vec<String>, existing_id: i64, existing_content: String },
+}
+
+/// 相似记忆的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DuplicateChoice {
+    Merge,
+    Replace,
+    KeepBoth,
+}
+
+impl DuplicateChoice {
+    /// 解析用户的选择：选项编号或明确的说法。带否定的回复（“不要合并”“不用保留”）不算选择，
+    /// 交给确认分类器判断是否放弃保存
+    fn parse(text: &str) -> Option<Self> {
+        let reply = text.trim().to_lowercase();
+        if reply.contains('不') || reply.contains('别') {
+            return None;
+        }
+        match reply.as_str() {
+            "1" => Some(DuplicateChoice::Merge),
+            "2" => Some(DuplicateChoice::Replace),
+            "3" => Some(DuplicateChoice::KeepBoth),
+            _ if reply.contains("合并") => Some(DuplicateChoice::Merge),
+            _ if reply.contains("替换") || reply.contains("覆盖") => Some(DuplicateChoice::Replace),
+            _ if reply.contains("都保留") => Some(DuplicateChoice::KeepBoth),
+            _ => None,
+        }
+    }
 }
 
 // --- 新增枚举：定义哪些意图是需要澄清的 ---
@@ -132,12 +176,20 @@ impl Orchestrator {
         }
     }
 
+    /// 供 CLI / 服务端直接调用 MemosAgent 的维护类接口（例如重建索引）
+    pub fn memos_agent(&self) -> Result<&MemosAgent, anyhow::Error> {
+        self.agents.iter()
+            .find_map(|a| a.as_any().downcast_ref::<MemosAgent>())
+            .ok_or_else(|| anyhow::anyhow!("MemosAgent not found"))
+    }
 
     async fn handle_save(&self, text: &str) -> Result<String, anyhow::Error> {
         let memos_agent = self.agents.iter().find_map(|a| a.as_any().downcast_ref::<MemosAgent>()).ok_or_else(|| anyhow::anyhow!("MemosAgent not found"))?;
         println!("[SaveExpert] Extracting fact from raw text: '{}'", text);
-        
-        let messages = save_expert::get_fact_extraction_prompt(text);
+        // #话题 写法由用户显式给出，直接作为标签，不交给 LLM 提炼
+        let (untagged_text, mut tags) = agent_memos::extract_hashtags(text);
+
+        let messages = save_expert::get_fact_extraction_prompt(&untagged_text);
         let gbnf_schema = save_expert::get_fact_extraction_gbnf_schema();
         let request_body = json!({ "messages": messages, "temperature": 0.0, "grammar": gbnf_schema });
         let chat_url = format!("{}/v1/chat/completions", self.llm_config.llm_url);
@@ -149,14 +201,56 @@ impl Orchestrator {
         
         let response = self.llm_config.client.post(&chat_url).json(&request_body).send().await?;
         let chat_response: ChatCompletionResponse = response.json().await?;
-        let content_str = chat_response.choices.get(0).map(|c| c.message.content.trim()).unwrap_or("{}");
+        let content_str = chat_response.choices.first().map(|c| c.message.content.trim()).unwrap_or("{}");
         let extracted_fact_obj: save_expert::ExtractedFact = serde_json::from_str(content_str)?;
         let fact_to_save = &extracted_fact_obj.fact;
+        tags.extend(extracted_fact_obj.tags.iter().cloned());
+
+        println!("[SaveExpert] Fact to save: '{}' with tags {:?}", fact_to_save, tags);
         
-        println!("[SaveExpert] Fact to save: '{}'", fact_to_save);
-        
-        // 调用修改后的save方法，并接收返回的ID
-        let new_memory_id = memos_agent.save(fact_to_save).await?;
+        // 失效时间从用户原话推断：提炼后的事实可能已经丢掉了“今天”“这周”之类的措辞
+        let expires_at = memos_agent.infer_expiry(text).or_else(|| memos_agent.infer_expiry(fact_to_save));
+
+        // 调用修改后的save方法；重复或相似时不会写入
+        let new_memory_id = match memos_agent.save_with_expiry(fact_to_save, expires_at, &tags, Some(text)).await? {
+            SaveOutcome::Inserted(id) => {
+                // 用户原话里的“很重要”等表态优先于 LLM 的估计
+                let importance = agent_memos::parse_importance_mark(text).map(|i| (i, ImportanceSource::User))
+                    .or_else(|| extracted_fact_obj.importance_score().map(|i| (i, ImportanceSource::Llm)));
+                if let Some((importance, source)) = importance {
+                    if let Err(e) = memos_agent.set_importance(id, importance, source) {
+                        eprintln!("[SaveExpert] Failed to set importance of memo {}: {}", id, e);
+                    }
+                }
+                id
+            }
+            SaveOutcome::Duplicate(id) => {
+                let existing = memos_agent.get_by_id(id).await?.unwrap_or_else(|| fact_to_save.clone());
+                *self.last_interaction_context.lock().unwrap() = Some(InteractionContext {
+                    last_action: ContextualAction::Save { memory_id: id },
+                });
+                return Ok(format!("这条信息我之前已经记过了：\n\n---\n{}\n---", existing));
+            }
+            SaveOutcome::Similar(ids) => {
+                // 合并或替换都以最相似的一条为准
+                let existing_id = ids[0];
+                let existing_content = memos_agent.get_by_id(existing_id).await?.unwrap_or_default();
+                *self.pending_action.lock().unwrap() = Some(PendingAction {
+                    action_type: PendingActionType::DuplicateResolution {
+                        new_content: fact_to_save.clone(),
+                        tags: tags.clone(),
+                        existing_id,
+                        existing_content: existing_content.clone(),
+                    },
+                    original_user_request: text.to_string(),
+                });
+                println!("[Orchestrator] Pending action set: DuplicateResolution against ID {}", existing_id);
+                return Ok(format!(
+                    "我发现已有一条相似的记忆：\n\n---\n{}\n---\n\n要怎么处理？回复 1 合并到已有记忆，2 用新内容替换它，3 两条都保留。",
+                    existing_content
+                ));
+            }
+        };
 
         // --- 新增：更新短期上下文 ---
         let context = InteractionContext {
@@ -166,81 +260,225 @@ impl Orchestrator {
         println!("[Orchestrator-DST] Updated context: Last action was Save with ID {}", new_memory_id);
         // --- 更新结束 ---
 
-        Ok("好的，已经记下了。".to_string())
+        let mut response = "好的，已经记下了。".to_string();
+        if expires_at.is_some() {
+            response.push_str("这条记忆是临时的，到期后会自动失效。");
+        }
+        let superseded = self.supersede_contradicted(memos_agent, new_memory_id, fact_to_save, text).await;
+        if !superseded.is_empty() {
+            response.push_str(&format!(
+                "\n\n以下旧记忆与新内容矛盾，已标记为过时（可在历史中查看或撤销）：\n\n---\n{}\n---",
+                superseded.join("\n")
+            ));
+        }
+        Ok(response)
+    }
+
+    /// 检查新保存的事实是否推翻了相关的旧记忆（NER 实体与语义召回候选，再由 LLM 判断），
+    /// 把被推翻的标记为已被取代，返回它们的内容。判断失败只记录日志，不影响保存本身。
+    async fn supersede_contradicted(&self, memos_agent: &MemosAgent, new_id: i64, new_fact: &str, request: &str) -> Vec<String> {
+        const MAX_CANDIDATES: usize = 5;
+        let candidates = match memos_agent.contradiction_candidates(new_id, new_fact, MAX_CANDIDATES).await {
+            Ok(candidates) => candidates,
+            Err(e) => {
+                eprintln!("[ContradictionExpert] Failed to collect candidates: {}", e);
+                return Vec::new();
+            }
+        };
+        let candidates: Vec<(i64, String)> = candidates.iter()
+            .filter_map(|p| p.content().map(|c| (p.id, c.to_string())))
+            .collect();
+        if candidates.is_empty() {
+            return Vec::new();
+        }
+
+        let existing: Vec<String> = candidates.iter().map(|(_, c)| c.clone()).collect();
+        let contradicted = match self.judge_contradictions(new_fact, &existing).await {
+            Ok(indices) => indices,
+            Err(e) => {
+                eprintln!("[ContradictionExpert] LLM judgement failed: {}", e);
+                return Vec::new();
+            }
+        };
+        println!("[ContradictionExpert] New memo {} contradicts candidate(s) {:?}", new_id, contradicted);
+
+        let mut superseded = Vec::new();
+        for (old_id, content) in contradicted.into_iter().filter_map(|i| i.checked_sub(1).and_then(|i| candidates.get(i))) {
+            match memos_agent.supersede(*old_id, new_id, Some(request)).await {
+                Ok(true) => superseded.push(content.clone()),
+                Ok(false) => {}
+                Err(e) => eprintln!("[ContradictionExpert] Failed to supersede memo {}: {}", old_id, e),
+            }
+        }
+        superseded
+    }
+
+    /// 让 LLM 判断哪些已有记忆被新事实推翻，返回从 1 开始的编号
+    async fn judge_contradictions(&self, new_fact: &str, existing: &[String]) -> Result<Vec<usize>, anyhow::Error> {
+        #[derive(Deserialize)] struct ChatChoice { message: ChatMessageContent }
+        #[derive(Deserialize)] struct ChatMessageContent { content: String }
+        #[derive(Deserialize)] struct ChatCompletionResponse { choices: Vec<ChatChoice> }
+
+        let messages = contradiction_expert::get_contradiction_prompt(new_fact, existing);
+        let gbnf_schema = contradiction_expert::get_contradiction_gbnf_schema();
+        let request_body = json!({ "messages": messages, "temperature": 0.0, "grammar": gbnf_schema });
+        let chat_url = format!("{}/v1/chat/completions", self.llm_config.llm_url);
+        let response = self.llm_config.client.post(&chat_url).json(&request_body).send().await?;
+
+        let chat_response: ChatCompletionResponse = response.json().await?;
+        let content_str = chat_response.choices.first().map(|c| c.message.content.trim())
+            .ok_or_else(|| anyhow::anyhow!("Contradiction LLM response is empty"))?;
+        let verdict: contradiction_expert::ContradictionVerdict = serde_json::from_str(content_str)?;
+        Ok(verdict.contradicted)
     }
 
 
-async fn handle_recall(&self, text: &str) -> Result<String, anyhow::Error> {
+    /// 召回诊断：MemosAgent 的召回过程，加上重排序（若启用）对每条结果的打分
+    pub async fn explain_recall(&self, text: &str, mode: RecallMode, options: Option<&RecallOptions>) -> Result<RecallTrace, anyhow::Error> {
+        let mut trace = self.memos_agent()?.recall_explain(text, None, mode, options).await?;
+        if let Some(reranker) = &self.reranker {
+            let documents: Vec<DocumentToRank> = trace.results.iter()
+                .filter_map(|hit| hit.content.as_deref().map(|text| DocumentToRank { text }))
+                .collect();
+            let ranked = reranker.rank(ReRankRequest { query: text, documents }, ReRankStrategy::ScoreAll).await?;
+            let scores = ranked.into_iter()
+                .filter_map(|doc| {
+                    trace.results.iter()
+                        .find(|hit| hit.content.as_deref() == Some(doc.text.as_str()))
+                        .map(|hit| RerankScore { id: hit.id, score: doc.score })
+                })
+                .collect();
+            trace.rerank = Some(scores);
+        }
+        Ok(trace)
+    }
+
+async fn handle_recall(&self, text: &str) -> Result<Response, anyhow::Error> {
         println!("[RecallExpert] Received recall request for: '{}'", text);
         
         let memos_agent = self.agents.iter()
             .find_map(|a| a.as_any().downcast_ref::<MemosAgent>())
             .ok_or_else(|| anyhow::anyhow!("MemosAgent not found"))?;
         
-        // 初次召回，不带任何上下文
-        let candidate_points = memos_agent.recall(text, None).await?;
+        // 初次召回，不带任何上下文；查询中的 #话题 作为标签过滤条件
+        let (untagged_query, tags) = agent_memos::extract_hashtags(text);
+        let candidate_points = if tags.is_empty() {
+            memos_agent.recall(text, None, RecallMode::Fuzzy).await?
+        } else {
+            println!("[RecallExpert] Filtering by tags: {:?}", tags);
+            let options = RecallOptions { tags, ..memos_agent.recall_options() };
+            let query = if untagged_query.is_empty() { text } else { untagged_query.as_str() };
+            memos_agent.recall_with_options(query, None, RecallMode::Fuzzy, &options).await?
+        };
         
         if candidate_points.is_empty() {
-            return Ok(format!("关于“{}”，我好像没什么印象...", text));
+            return Ok(Response::Text(format!("关于“{}”，我好像没什么印象...", text)));
         }
 
-        let final_content: String;
-        let top_point: &qdrant_client::qdrant::ScoredPoint;
-
-        if let Some(reranker) = &self.reranker {
+        // 选出参与作答的记忆及其相关度：有重排序时取通过相关性阈值的前 N 条（重排序分数），否则直接取召回的前 N 条（融合分数）
+        let selected: Vec<(&ScoredMemo, f32)> = if let Some(reranker) = &self.reranker {
             println!("[RecallExpert] Re-ranking candidates...");
             let documents_to_rank: Vec<DocumentToRank> = candidate_points.iter()
-                .filter_map(|p| p.payload.get("content").and_then(|v| v.as_str()).map(|s| DocumentToRank { text: s }))
+                .filter_map(|p| p.content().map(|s| DocumentToRank { text: s }))
                 .collect();
             let rerank_request = ReRankRequest { query: text, documents: documents_to_rank };
-            let strategy = ReRankStrategy::ValidateTopOne { threshold: 0.1 };
-            let final_results = reranker.rank(rerank_request, strategy).await?;
-
-            if let Some(top_doc) = final_results.get(0) {
-                top_point = candidate_points.iter().find(|p| {
-                    p.payload.get("content").and_then(|v| v.as_str()) == Some(&top_doc.text)
-                }).unwrap();
-                final_content = top_doc.text.clone();
-            } else {
-                let summary: Vec<String> = candidate_points.iter().take(3)
-                    .filter_map(|p| p.payload.get("content").and_then(|v| v.as_str()).map(|s| format!("- {}", s)))
-                    .collect();
-                return Ok(format!("关于“{}”，我没有找到直接答案，但发现一些可能相关的内容：\n{}", text, summary.join("\n")));
-            }
+            let strategy = ReRankStrategy::TopN { threshold: RERANK_RELEVANCE_THRESHOLD, limit: SYNTHESIS_TOP_N };
+            let ranked = reranker.rank(rerank_request, strategy).await?;
+            ranked.iter()
+                .filter_map(|doc| {
+                    candidate_points.iter()
+                        .find(|p| p.content() == Some(doc.text.as_str()))
+                        .map(|p| (p, doc.score))
+                })
+                .collect()
         } else {
             println!("[RecallExpert] Skipping re-ranking.");
-            top_point = candidate_points.get(0).unwrap();
-            final_content = top_point.payload.get("content")
-                                .and_then(|v| v.as_str())
-                                .map_or("".to_string(), |v| v.to_string());
-        }
+            candidate_points.iter().take(SYNTHESIS_TOP_N).map(|p| (p, p.score)).collect()
+        };
 
-        let memory_id = top_point.id.as_ref().and_then(|id| match &id.point_id_options {
-            Some(qdrant_client::qdrant::point_id::PointIdOptions::Num(num)) => Some(*num as i64),
-            _ => None,
-        }).unwrap_or(-1);
+        let Some((top_point, top_score)) = selected.first().copied() else {
+            let related: Vec<&ScoredMemo> = candidate_points.iter().take(3).collect();
+            memos_agent.mark_accessed(&related.iter().map(|p| p.id).collect::<Vec<_>>()).await;
+            let summary: Vec<String> = related.iter()
+                .filter_map(|p| p.content().map(|s| format!("- {}", s)))
+                .collect();
+            return Ok(Response::Cited {
+                text: format!("关于“{}”，我没有找到直接答案，但发现一些可能相关的内容：\n{}", text, summary.join("\n")),
+                citations: related.iter().map(|p| citation_for(p, p.score)).collect(),
+            });
+        };
+        let top_content = top_point.content().unwrap_or_default().to_string();
+        let memory_id = top_point.id;
+        // 参与作答的记忆才算被访问
+        memos_agent.mark_accessed(&selected.iter().map(|(p, _)| p.id).collect::<Vec<_>>()).await;
 
         // --- 核心修复：不再依赖 payload，而是对成功召回的内容主动进行NER，以获取最准确的上下文实体 ---
-        let memos_agent_for_ner = self.agents.iter()
-            .find_map(|a| a.as_any().downcast_ref::<MemosAgent>())
-            .ok_or_else(|| anyhow::anyhow!("MemosAgent not found for context NER"))?;
-        
-        // 对最终确认的召回内容，通过新的公共方法提取实体
-        let entities = memos_agent_for_ner.extract_entities(&final_content)?;
+        // 对最可信的那条召回内容，通过公共方法提取实体
+        let entities = memos_agent.extract_entities(&top_content)?;
         println!("[Orchestrator-DST] Extracted entities for context: {:?}", entities);
         // --- 修复结束 ---
 
         let context = InteractionContext {
             last_action: ContextualAction::Recall {
                 memory_id,
-                content: final_content.clone(),
+                content: top_content.clone(),
                 entities,
             },
         };
         *self.last_interaction_context.lock().unwrap() = Some(context);
-        println!("[Orchestrator-DST] Updated context: Last action was Recall with ID {} and content '{}'", memory_id, final_content);
+        println!("[Orchestrator-DST] Updated context: Last action was Recall with ID {} and content '{}'", memory_id, top_content);
 
-        Ok(final_content)
+        // 综合多条记忆作答；LLM 不可用时退回到原样返回最相关的一条
+        let memories: Vec<String> = selected.iter().filter_map(|(p, _)| p.content().map(str::to_string)).collect();
+        match self.stream_synthesis(text, &memories).await {
+            Ok(receiver) => {
+                let citations: Vec<Citation> = selected.iter().map(|(p, score)| citation_for(p, *score)).collect();
+                println!("[SynthesisExpert] Streaming answer grounded in memos {:?}", citations.iter().map(|c| c.memory_id).collect::<Vec<_>>());
+                Ok(Response::Stream { receiver, citations })
+            }
+            Err(e) => {
+                eprintln!("[SynthesisExpert] Synthesis unavailable, returning top memo: {}", e);
+                Ok(Response::Cited { citations: vec![citation_for(top_point, top_score)], text: top_content })
+            }
+        }
+    }
+
+    /// 让 LLM 基于若干条记忆流式生成回答，逐段发送增量文本，结束时发送空字符串
+    async fn stream_synthesis(&self, user_query: &str, memories: &[String]) -> Result<mpsc::Receiver<String>, anyhow::Error> {
+        let messages = synthesis_expert::get_synthesis_messages(user_query, memories);
+        let request_body = json!({ "messages": messages, "temperature": 0.2, "stream": true });
+        let chat_url = format!("{}/v1/chat/completions", self.llm_config.llm_url);
+        let response = self.llm_config.client.post(&chat_url).json(&request_body).send().await?.error_for_status()?;
+
+        let (tx, rx) = mpsc::channel(64);
+        tokio::spawn(async move {
+            let mut stream = response.bytes_stream();
+            let mut buffer = String::new();
+            'receive: while let Some(chunk) = stream.next().await {
+                let chunk = match chunk {
+                    Ok(chunk) => chunk,
+                    Err(e) => {
+                        eprintln!("[SynthesisExpert] Stream interrupted: {}", e);
+                        break;
+                    }
+                };
+                buffer.push_str(&String::from_utf8_lossy(&chunk));
+                while let Some(newline) = buffer.find('\n') {
+                    let line: String = buffer.drain(..=newline).collect();
+                    let delta = match synthesis_expert::parse_stream_line(&line) {
+                        Some(Some(delta)) if !delta.is_empty() => delta,
+                        Some(None) => break 'receive,
+                        _ => continue,
+                    };
+                    // 接收方已放弃，不必再读
+                    if tx.send(delta).await.is_err() {
+                        break 'receive;
+                    }
+                }
+            }
+            let _ = tx.send(String::new()).await;
+        });
+        Ok(rx)
     }
 
     async fn handle_modify(&self, text: &str) -> Result<String, anyhow::Error> {
@@ -265,20 +503,16 @@ async fn handle_recall(&self, text: &str) -> Result<String, anyhow::Error> {
             .find_map(|a| a.as_any().downcast_ref::<MemosAgent>())
             .ok_or_else(|| anyhow::anyhow!("MemosAgent not found"))?;
 
-        // 将原始用户输入和（可能存在的）上下文实体，分别传递给 recall 函数
-        let candidate_points = memos_agent.recall(text, context_entities).await?;
+        // 将原始用户输入和（可能存在的）上下文实体，分别传递给 recall 函数，按目标查找模式定位
+        let candidate_points = auto_select_target(memos_agent.recall(text, context_entities, RecallMode::TargetLookup).await?);
         
         match candidate_points.len() {
             0 => Ok("抱歉，我没有找到与您描述相关的记忆。".to_string()),
             1 => {
                 // 行为不变：只有一个匹配项，直接进入确认流程
-                let top_point = candidate_points.get(0).unwrap();
-                let memory_id = top_point.id.as_ref().and_then(|id| match &id.point_id_options {
-                    Some(qdrant_client::qdrant::point_id::PointIdOptions::Num(num)) => Some(*num as i64),
-                    _ => None,
-                }).ok_or_else(|| anyhow::anyhow!("Found a point without a numeric ID"))?;
-                let content = top_point.payload.get("content")
-                    .and_then(|v| v.as_str())
+                let top_point = &candidate_points[0];
+                let memory_id = top_point.id;
+                let content = top_point.content()
                     .map_or("无法解析内容".to_string(), |v| v.to_string());
                 
                 let pending_action = PendingAction {
@@ -296,15 +530,9 @@ async fn handle_recall(&self, text: &str) -> Result<String, anyhow::Error> {
             _ => {
                 // 核心改造：有多个匹配项，进入澄清流程
                 println!("[Orchestrator] Multiple candidates found. Entering clarification mode.");
-                let options: Vec<(i64, String)> = candidate_points.into_iter().filter_map(|p| {
-                    let id = p.id.as_ref()?.point_id_options.as_ref()?;
-                    let content = p.payload.get("content")?.as_str()?;
-                    if let qdrant_client::qdrant::point_id::PointIdOptions::Num(num) = id {
-                        Some((*num as i64, content.to_string()))
-                    } else {
-                        None
-                    }
-                }).collect();
+                let options: Vec<(i64, String)> = candidate_points.iter()
+                    .filter_map(|p| Some((p.id, p.content()?.to_string())))
+                    .collect();
 
                 let pending_action = PendingAction {
                     action_type: PendingActionType::Clarification {
@@ -346,20 +574,16 @@ async fn handle_recall(&self, text: &str) -> Result<String, anyhow::Error> {
             .find_map(|a| a.as_any().downcast_ref::<MemosAgent>())
             .ok_or_else(|| anyhow::anyhow!("MemosAgent not found"))?;
 
-        // 将原始用户输入和（可能存在的）上下文实体，分别传递给 recall 函数
-        let candidate_points = memos_agent.recall(text, context_entities).await?;
+        // 将原始用户输入和（可能存在的）上下文实体，分别传递给 recall 函数，按目标查找模式定位
+        let candidate_points = auto_select_target(memos_agent.recall(text, context_entities, RecallMode::TargetLookup).await?);
 
         match candidate_points.len() {
             0 => Ok("抱歉，我没有找到与您描述相关的记忆可以删除。".to_string()),
             1 => {
                 // 行为不变：只有一个匹配项
-                let top_point = candidate_points.get(0).unwrap();
-                let memory_id = top_point.id.as_ref().and_then(|id| match &id.point_id_options {
-                    Some(qdrant_client::qdrant::point_id::PointIdOptions::Num(num)) => Some(*num as i64),
-                    _ => None,
-                }).ok_or_else(|| anyhow::anyhow!("Found a point without a numeric ID"))?;
-                let content = top_point.payload.get("content")
-                    .and_then(|v| v.as_str())
+                let top_point = &candidate_points[0];
+                let memory_id = top_point.id;
+                let content = top_point.content()
                     .map_or("无法解析内容".to_string(), |v| v.to_string());
                 
                 let pending_action = PendingAction {
@@ -376,15 +600,9 @@ async fn handle_recall(&self, text: &str) -> Result<String, anyhow::Error> {
             _ => {
                 // 核心改造：有多个匹配项
                 println!("[Orchestrator] Multiple candidates found. Entering clarification mode for deletion.");
-                let options: Vec<(i64, String)> = candidate_points.into_iter().filter_map(|p| {
-                    let id = p.id.as_ref()?.point_id_options.as_ref()?;
-                    let content = p.payload.get("content")?.as_str()?;
-                    if let qdrant_client::qdrant::point_id::PointIdOptions::Num(num) = id {
-                        Some((*num as i64, content.to_string()))
-                    } else {
-                        None
-                    }
-                }).collect();
+                let options: Vec<(i64, String)> = candidate_points.iter()
+                    .filter_map(|p| Some((p.id, p.content()?.to_string())))
+                    .collect();
 
                 let pending_action = PendingAction {
                     action_type: PendingActionType::Clarification {
@@ -405,6 +623,44 @@ async fn handle_recall(&self, text: &str) -> Result<String, anyhow::Error> {
         }
     }
     
+    /// 用户对上一条保存或召回的记忆表态（“这个很重要”“这条不重要”），按用户标记设置其重要度
+    async fn handle_importance_mark(&self, text: &str, importance: f32) -> Result<String, anyhow::Error> {
+        println!("[ImportanceExpert] Received importance mark {:.2}: '{}'", importance, text);
+        let memos_agent = self.memos_agent()?;
+        let memory_id = match self.last_interaction_context.lock().unwrap().as_ref().map(|c| &c.last_action) {
+            Some(ContextualAction::Save { memory_id }) | Some(ContextualAction::Recall { memory_id, .. }) => *memory_id,
+            None => return Ok("我不确定您指的是哪条记忆，可以先让我找到它，再告诉我它重不重要。".to_string()),
+        };
+        memos_agent.set_importance(memory_id, importance, ImportanceSource::User)?;
+        let content = memos_agent.get_by_id(memory_id).await?.unwrap_or_default();
+        let verdict = if importance > agent_memos::DEFAULT_IMPORTANCE { "重要，会更长久地保留它" } else { "不太重要，之后可能会被归档" };
+        Ok(format!("好的，已把这条记忆标记为{}：\n\n---\n{}\n---", verdict, content))
+    }
+
+    async fn handle_undo(&self, text: &str) -> Result<String, anyhow::Error> {
+        println!("[UndoExpert] Received undo request: '{}'", text);
+        let memos_agent = self.memos_agent()?;
+
+        let Some(outcome) = memos_agent.undo_last_change(Some(text)).await? else {
+            return Ok("没有可以撤销的修改。".to_string());
+        };
+        // 撤销后记忆的内容变了，之前的上下文不再可靠
+        *self.last_interaction_context.lock().unwrap() = None;
+
+        let response = match (outcome.undone, outcome.content) {
+            (RevisionOp::Create, _) => format!(
+                "好的，已撤销刚才的保存，这条记忆已移除：\n\n---\n{}\n---",
+                outcome.previous_content.unwrap_or_default()
+            ),
+            (RevisionOp::Delete, Some(content)) => format!("好的，已撤销删除，这条记忆已恢复：\n\n---\n{}\n---", content),
+            (RevisionOp::Supersede, Some(content)) => format!("好的，这条记忆不再标记为过时：\n\n---\n{}\n---", content),
+            (RevisionOp::Retag, Some(content)) => format!("好的，已撤销刚才的标签修改：\n\n---\n{}\n---", content),
+            (_, Some(content)) => format!("好的，已撤销刚才的修改，这条记忆现在是：\n\n---\n{}\n---", content),
+            (_, None) => "好的，已撤销刚才的操作。".to_string(),
+        };
+        Ok(response)
+    }
+
     async fn handle_confirmation(&self, text: &str) -> Result<String, anyhow::Error> {
         // 1. 先取出当前的待办事项
         let taken_action = self.pending_action.lock().unwrap().take();
@@ -446,6 +702,19 @@ async fn handle_recall(&self, text: &str) -> Result<String, anyhow::Error> {
                     Ok("好的，已取消操作。".to_string())
                 }
                 
+                PendingActionType::DuplicateResolution { .. } => {
+                    match DuplicateChoice::parse(text) {
+                        Some(choice) => self.resolve_duplicate(action, choice).await,
+                        None if matches!(self.confirmation_classifier.lock().unwrap().predict(text), MicroIntent::Deny) => {
+                            Ok("好的，这条就先不记了。".to_string())
+                        }
+                        None => {
+                            *self.pending_action.lock().unwrap() = Some(action);
+                            Ok("抱歉，我没太明白。请回复 1（合并）、2（替换）或 3（都保留）。".to_string())
+                        }
+                    }
+                }
+
                 PendingActionType::ModifyConfirmation { .. } | PendingActionType::DeleteConfirmation { .. } => {
                     let intent = self.confirmation_classifier.lock().unwrap().predict(text);
                     match intent {
@@ -471,28 +740,65 @@ async fn handle_recall(&self, text: &str) -> Result<String, anyhow::Error> {
         }
     }
 
+    /// 按用户的选择处理与已有记忆相似的新内容
+    async fn resolve_duplicate(&self, action: PendingAction, choice: DuplicateChoice) -> Result<String, anyhow::Error> {
+        let PendingActionType::DuplicateResolution { new_content, tags, existing_id, existing_content } = action.action_type else {
+            return Err(anyhow::anyhow!("[Logic Error] resolve_duplicate called with a non-duplicate action."));
+        };
+        let request = action.original_user_request;
+        let memos_agent = self.memos_agent()?;
+        println!("[Orchestrator] Resolving duplicate against ID {} with {:?}", existing_id, choice);
+
+        let (memory_id, response) = match choice {
+            DuplicateChoice::Merge => {
+                let instruction = format!("把这条新信息合并进去，去掉重复的部分：{}", new_content);
+                let merged = self.rewrite_with_llm(&existing_content, &instruction).await?;
+                memos_agent.update_with_tags(existing_id, &merged, &tags, Some(&request)).await?;
+                (existing_id, format!("好的，已经合并到原来的记忆里：\n\n---\n{}\n---", merged))
+            }
+            DuplicateChoice::Replace => {
+                memos_agent.update_with_tags(existing_id, &new_content, &tags, Some(&request)).await?;
+                (existing_id, "好的，已经用新内容替换了原来的记忆。".to_string())
+            }
+            DuplicateChoice::KeepBoth => {
+                let expires_at = memos_agent.infer_expiry(&request).or_else(|| memos_agent.infer_expiry(&new_content));
+                let id = memos_agent.insert(&new_content, expires_at, &tags, Some(&request)).await?;
+                (id, "好的，两条都保留了。".to_string())
+            }
+        };
+        *self.last_interaction_context.lock().unwrap() = Some(InteractionContext {
+            last_action: ContextualAction::Save { memory_id },
+        });
+        Ok(response)
+    }
+
+    /// 让 LLM 按用户的要求改写一段记忆文本
+    async fn rewrite_with_llm(&self, original_content: &str, user_request: &str) -> Result<String, anyhow::Error> {
+        #[derive(Deserialize)] struct ChatChoice { message: ChatMessageContent }
+        #[derive(Deserialize)] struct ChatMessageContent { content: String }
+        #[derive(Deserialize)] struct ChatCompletionResponse { choices: Vec<ChatChoice> }
+
+        let messages = modify_expert::get_text_modification_prompt(original_content, user_request);
+        let gbnf_schema = modify_expert::get_text_modification_gbnf_schema();
+        let request_body = json!({ "messages": messages, "temperature": 0.0, "grammar": gbnf_schema });
+        let chat_url = format!("{}/v1/chat/completions", self.llm_config.llm_url);
+        let response = self.llm_config.client.post(&chat_url).json(&request_body).send().await?;
+
+        let chat_response: ChatCompletionResponse = response.json().await?;
+        let content_str = chat_response.choices.first().map(|c| c.message.content.trim())
+            .ok_or_else(|| anyhow::anyhow!("Modify LLM response is empty"))?;
+
+        let modified_text_obj: modify_expert::ModifiedText = serde_json::from_str(content_str)?;
+        Ok(modified_text_obj.modified_text)
+    }
+
     async fn execute_pending_action(&self, action: PendingAction) -> Result<String, anyhow::Error> {
             match action.action_type {
                 PendingActionType::ModifyConfirmation { memory_id, original_content } => {
                     // ... (此部分代码保持不变)
                     println!("[ModifyExpert-Phase2] Executing modification for ID: {}", memory_id);
-                    
-                    #[derive(Deserialize)] struct ChatChoice { message: ChatMessageContent }
-                    #[derive(Deserialize)] struct ChatMessageContent { content: String }
-                    #[derive(Deserialize)] struct ChatCompletionResponse { choices: Vec<ChatChoice> }
-
-                    let messages = modify_expert::get_text_modification_prompt(&original_content, &action.original_user_request);
-                    let gbnf_schema = modify_expert::get_text_modification_gbnf_schema();
-                    let request_body = json!({ "messages": messages, "temperature": 0.0, "grammar": gbnf_schema });
-                    let chat_url = format!("{}/v1/chat/completions", self.llm_config.llm_url);
-                    let response = self.llm_config.client.post(&chat_url).json(&request_body).send().await?;
-                    
-                    let chat_response: ChatCompletionResponse = response.json().await?;
-                    let content_str = chat_response.choices.get(0).map(|c| c.message.content.trim())
-                        .ok_or_else(|| anyhow::anyhow!("Modify LLM response is empty"))?;
-                    
-                    let modified_text_obj: modify_expert::ModifiedText = serde_json::from_str(content_str)?;
-                    let new_content = &modified_text_obj.modified_text;
+
+                    let new_content = &self.rewrite_with_llm(&original_content, &action.original_user_request).await?;
                     
                     println!("[ModifyExpert-Phase2] LLM generated new text: '{}'", new_content);
                     
@@ -500,7 +806,7 @@ async fn handle_recall(&self, text: &str) -> Result<String, anyhow::Error> {
                         .find_map(|a| a.as_any().downcast_ref::<MemosAgent>())
                         .ok_or_else(|| anyhow::anyhow!("MemosAgent not found"))?;
                     
-                    memos_agent.update(memory_id, new_content).await?;
+                    memos_agent.update(memory_id, new_content, Some(&action.original_user_request)).await?;
                     Ok("好的，我已经更新了这条记忆。".to_string())
                 }
                 PendingActionType::DeleteConfirmation { memory_id, .. } => {
@@ -511,15 +817,15 @@ async fn handle_recall(&self, text: &str) -> Result<String, anyhow::Error> {
                         .find_map(|a| a.as_any().downcast_ref::<MemosAgent>())
                         .ok_or_else(|| anyhow::anyhow!("MemosAgent not found"))?;
                     
-                    memos_agent.delete(memory_id).await?;
+                    memos_agent.delete(memory_id, Some(&action.original_user_request)).await?;
                     Ok("好的，我已经删除了这条记忆。".to_string())
                 }
                 // --- 修复：处理被遗漏的 Clarification 分支 ---
-                PendingActionType::Clarification { .. } => {
-                    // 这是一个逻辑错误，澄清动作不应该被“执行”。
+                PendingActionType::Clarification { .. } | PendingActionType::DuplicateResolution { .. } => {
+                    // 这是一个逻辑错误，澄清与去重选择都不应该被“执行”。
                     // 我们返回一个错误，而不是让程序崩溃。
                     Err(anyhow::anyhow!(
-                        "[Logic Error] Attempted to execute a 'Clarification' action. This should not happen."
+                        "[Logic Error] Attempted to execute a '{:?}' action. This should not happen.", action.action_type
                     ))
                 }
             }
@@ -538,7 +844,6 @@ async fn handle_recall(&self, text: &str) -> Result<String, anyhow::Error> {
             // 打开或创建一个名为 feedback.jsonl 的文件，以追加模式写入
             if let Ok(mut file) = OpenOptions::new()
                 .create(true)
-                .write(true)
                 .append(true)
                 .open("feedback.jsonl")
             {
@@ -563,6 +868,8 @@ async fn handle_recall(&self, text: &str) -> Result<String, anyhow::Error> {
                 println!("[Orchestrator] V10.3 Routing with Enhanced Heuristics...");
 
                 let final_response: String;
+                // 召回的回答带有出处（可能是流式的），单独返回；流式回答的历史在流结束后记录
+                let mut structured: Option<Response> = None;
 
                 // 1. "海马体"层：优先检查是否存在待处理的上下文动作。
                 if self.pending_action.lock().unwrap().is_some() {
@@ -571,21 +878,33 @@ async fn handle_recall(&self, text: &str) -> Result<String, anyhow::Error> {
                 } else {
                     // 2. "脑干"层：增强型启发式规则引擎。
                     let lower_text = text.to_lowercase();
+                    let undo_keywords = ["撤销", "撤回", "还原刚才", "恢复刚才"];
                     let modify_keywords = ["修改", "改成", "更新", "编辑"];
                     let delete_keywords = ["删除", "忘掉", "去掉", "移除"];
                     let save_keywords = ["记一下", "记录", "帮我记"];
+                    // 简短地指代上一条记忆并表态重要与否（“这个很重要”），而不是保存新内容
+                    let previous_refs = ["这个", "这条", "那条", "那个", "刚才", "刚刚", "上一条"];
+                    let importance_mark = agent_memos::parse_importance_mark(&lower_text)
+                        .filter(|_| text.chars().count() <= 16 && previous_refs.iter().any(|&kw| lower_text.contains(kw)));
 
                     // --- 修复：补上被遗漏的 is_declarative 定义 ---
                     // 陈述性模式 (更智能的“保险丝”)
                     let is_declarative = (lower_text.contains("是") || lower_text.contains("为")) && !lower_text.contains('？') && !lower_text.contains('?');
 
-                    if modify_keywords.iter().any(|&kw| lower_text.contains(kw)) {
+                    // 撤销要先于修改判断：“撤销刚才的修改”同样包含“修改”
+                    if undo_keywords.iter().any(|&kw| lower_text.contains(kw)) {
+                        println!("[Orchestrator] Heuristic Route: Detected UndoTool.");
+                        final_response = self.handle_undo(text).await?;
+                    } else if modify_keywords.iter().any(|&kw| lower_text.contains(kw)) {
                         println!("[Orchestrator] Heuristic Route: Detected ModifyTool.");
                         final_response = self.handle_modify(text).await?; // <-- 直接使用原始 text
                     } else if delete_keywords.iter().any(|&kw| lower_text.contains(kw)) {
                         println!("[Orchestrator] Heuristic Route: Detected DeleteTool.");
                         final_response = self.handle_delete(text).await?; // <-- 直接使用原始 text
                         
+                    } else if let Some(importance) = importance_mark {
+                        println!("[Orchestrator] Heuristic Route: Detected ImportanceMark.");
+                        final_response = self.handle_importance_mark(text, importance).await?;
                     } else if save_keywords.iter().any(|&kw| lower_text.contains(kw)) || is_declarative {
                         println!("[Orchestrator] Heuristic Route: Detected SaveTool by keyword or declarative pattern.");
                         final_response = self.handle_save(text).await?;
@@ -597,7 +916,17 @@ async fn handle_recall(&self, text: &str) -> Result<String, anyhow::Error> {
                         final_response = match intent {
                             MicroIntent::Question => {
                                 println!("[Orchestrator] Micromodel classified as 'Question'. Routing to RecallExpert.");
-                                self.handle_recall(text).await?
+                                match self.handle_recall(text).await? {
+                                    Response::Text(answer) => answer,
+                                    Response::Cited { text: answer, citations } => {
+                                        structured = Some(Response::Cited { text: answer.clone(), citations });
+                                        answer
+                                    }
+                                    other => {
+                                        structured = Some(other);
+                                        String::new()
+                                    }
+                                }
                             }
                             // 如果微模型也认为是Statement，那就一定是Save
                             MicroIntent::Statement => {
@@ -614,23 +943,171 @@ async fn handle_recall(&self, text: &str) -> Result<String, anyhow::Error> {
                     }
                 }
 
-                // --- 统一处理历史记录 (保持不变) ---
-                let mut history = self.conversation_history.lock().unwrap();
-                history.push(format!("User: {}", text));
-                history.push(format!("Assistant: {}", final_response));
-                const MAX_HISTORY_SIZE: usize = 8;
-                if history.len() > MAX_HISTORY_SIZE {
-                    // 1. 先进行不可变借用，计算出需要移除的数量，并将结果存到一个新变量中。
-                    // 在这行代码结束后，对 history.len() 的不可变借用就结束了。
-                    let drain_count = history.len() - MAX_HISTORY_SIZE;
-                    
-                    // 2. 然后，再对 history 进行可变借用，执行 drain 操作。
-                    // 此时不存在任何不可变借用，操作是安全的。
-                    history.drain(..drain_count);
+                // --- 统一处理历史记录 ---
+                if let Some(Response::Stream { receiver, citations }) = structured {
+                    let receiver = record_turn_when_streamed(
+                        self.conversation_history.clone(),
+                        self.last_full_interaction.clone(),
+                        text.to_string(),
+                        receiver,
+                    );
+                    return Ok(Response::Stream { receiver, citations });
                 }
-                println!("[Orchestrator] Updated history: {:?}", history);
-                *self.last_full_interaction.lock().unwrap() = Some((text.to_string(), final_response.clone()));
-                Ok(Response::Text(final_response))
+                record_turn(&self.conversation_history, &self.last_full_interaction, text, &final_response);
+                Ok(structured.unwrap_or(Response::Text(final_response)))
             }
         }
-    }}
\ No newline at end of file
+    }}
+
+/// 目标查找的候选中，第一名置信度足够高且明显领先时只保留它（随后直接进入确认），否则原样返回供用户挑选
+fn auto_select_target(mut candidates: Vec<ScoredMemo>) -> Vec<ScoredMemo> {
+    let top = candidates.first().map_or(0.0, |p| p.score);
+    let runner_up = candidates.get(1).map_or(0.0, |p| p.score);
+    if candidates.len() > 1 && top >= TARGET_AUTO_SELECT_CONFIDENCE && top - runner_up >= TARGET_AUTO_SELECT_MARGIN {
+        println!("[Orchestrator] Auto-selected target {} (confidence {:.3}, runner-up {:.3}).", candidates[0].id, top, runner_up);
+        candidates.truncate(1);
+    }
+    candidates
+}
+
+/// 由召回结果生成一条引用：摘录截断到固定长度，时间取自 payload
+fn citation_for(point: &ScoredMemo, score: f32) -> Citation {
+    let content = point.content().unwrap_or_default();
+    let mut snippet: String = content.chars().take(CITATION_SNIPPET_CHARS).collect();
+    if snippet.len() < content.len() {
+        snippet.push('…');
+    }
+    let timestamp = |field: &str| point.payload.get(field).and_then(|v| v.as_str()).map(str::to_string);
+    Citation {
+        memory_id: point.id,
+        snippet,
+        score,
+        created_at: timestamp(fields::CREATED_AT),
+        updated_at: timestamp(fields::UPDATED_AT),
+    }
+}
+
+/// 记录一轮对话：写入最近的对话历史（只保留最后几条），并记下完整的一问一答供反馈使用
+fn record_turn(
+    conversation_history: &Mutex<Vec<String>>,
+    last_full_interaction: &Mutex<Option<(String, String)>>,
+    user_text: &str,
+    assistant_text: &str,
+) {
+    let mut history = conversation_history.lock().unwrap();
+    history.push(format!("User: {}", user_text));
+    history.push(format!("Assistant: {}", assistant_text));
+    const MAX_HISTORY_SIZE: usize = 8;
+    if history.len() > MAX_HISTORY_SIZE {
+        // 1. 先进行不可变借用，计算出需要移除的数量，并将结果存到一个新变量中。
+        // 在这行代码结束后，对 history.len() 的不可变借用就结束了。
+        let drain_count = history.len() - MAX_HISTORY_SIZE;
+        
+        // 2. 然后，再对 history 进行可变借用，执行 drain 操作。
+        // 此时不存在任何不可变借用，操作是安全的。
+        history.drain(..drain_count);
+    }
+    println!("[Orchestrator] Updated history: {:?}", history);
+    *last_full_interaction.lock().unwrap() = Some((user_text.to_string(), assistant_text.to_string()));
+}
+
+/// 原样转发流式回答，并在流结束后把完整回答记入对话历史
+fn record_turn_when_streamed(
+    conversation_history: Arc<Mutex<Vec<String>>>,
+    last_full_interaction: Arc<Mutex<Option<(String, String)>>>,
+    user_text: String,
+    mut receiver: mpsc::Receiver<String>,
+) -> mpsc::Receiver<String> {
+    let (tx, rx) = mpsc::channel(64);
+    tokio::spawn(async move {
+        let mut full_response = String::new();
+        while let Some(token) = receiver.recv().await {
+            if token.is_empty() {
+                break;
+            }
+            full_response.push_str(&token);
+            // 调用方不再读取时仍然收完整个回答，保证历史完整
+            let _ = tx.send(token).await;
+        }
+        let _ = tx.send(String::new()).await;
+        record_turn(&conversation_history, &last_full_interaction, &user_text, &full_response);
+    });
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duplicate_choice_accepts_option_numbers_and_explicit_replies() {
+        let cases = [
+            ("1", Some(DuplicateChoice::Merge)),
+            (" 2 ", Some(DuplicateChoice::Replace)),
+            ("3", Some(DuplicateChoice::KeepBoth)),
+            ("合并吧", Some(DuplicateChoice::Merge)),
+            ("用新的覆盖", Some(DuplicateChoice::Replace)),
+            ("替换掉旧的", Some(DuplicateChoice::Replace)),
+            ("两条都保留", Some(DuplicateChoice::KeepBoth)),
+            // 否定的回复交给确认分类器，不能当成选择
+            ("不要保留", None),
+            ("不用保留了", None),
+            ("不要合并", None),
+            ("别替换", None),
+            ("保留", None),
+            ("4", None),
+            ("算了", None),
+        ];
+        for (reply, expected) in cases {
+            assert_eq!(DuplicateChoice::parse(reply), expected, "reply: {}", reply);
+        }
+    }
+
+    fn candidates(scores: &[f32]) -> Vec<ScoredMemo> {
+        scores.iter().enumerate()
+            .map(|(i, &score)| ScoredMemo { id: i as i64 + 1, score, payload: Default::default() })
+            .collect()
+    }
+
+    fn ids(points: &[ScoredMemo]) -> Vec<i64> {
+        points.iter().map(|p| p.id).collect()
+    }
+
+    #[test]
+    fn auto_select_keeps_only_a_confident_clear_winner() {
+        // 置信度达到 0.6 且领先第二名至少 0.15
+        assert_eq!(ids(&auto_select_target(candidates(&[0.8, 0.6, 0.1]))), vec![1]);
+        assert_eq!(ids(&auto_select_target(candidates(&[0.6, 0.4]))), vec![1]);
+        // 置信度不够：列出候选
+        assert_eq!(ids(&auto_select_target(candidates(&[0.55, 0.1]))), vec![1, 2]);
+        // 领先不够：列出候选
+        assert_eq!(ids(&auto_select_target(candidates(&[0.9, 0.8]))), vec![1, 2]);
+        // 前两名同分：列出候选
+        assert_eq!(ids(&auto_select_target(candidates(&[0.7, 0.7, 0.2]))), vec![1, 2, 3]);
+        // 只有一个或没有候选时原样返回
+        assert_eq!(ids(&auto_select_target(candidates(&[0.3]))), vec![1]);
+        assert!(auto_select_target(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn citation_truncates_the_snippet_and_copies_timestamps() {
+        let long = "长".repeat(CITATION_SNIPPET_CHARS + 1);
+        let payload = json!({ "content": long, "created_at": "2025-03-07 12:00:00", "updated_at": "2025-03-08 09:30:00" });
+        let point = ScoredMemo { id: 42, score: 0.3, payload: payload.as_object().cloned().unwrap() };
+        let citation = citation_for(&point, 0.9);
+        assert_eq!(citation.memory_id, 42);
+        assert_eq!(citation.snippet, format!("{}…", "长".repeat(CITATION_SNIPPET_CHARS)));
+        assert_eq!(citation.score, 0.9);
+        assert_eq!(citation.created_at.as_deref(), Some("2025-03-07 12:00:00"));
+        assert_eq!(citation.updated_at.as_deref(), Some("2025-03-08 09:30:00"));
+
+        // 正好等于上限的内容不加省略号；缺少的字段为空
+        let exact = "长".repeat(CITATION_SNIPPET_CHARS);
+        let point = ScoredMemo { id: 7, score: 0.5, payload: json!({ "content": exact }).as_object().cloned().unwrap() };
+        let citation = citation_for(&point, point.score);
+        assert_eq!(citation.snippet, exact);
+        assert_eq!((citation.created_at, citation.updated_at), (None, None));
+        let empty = ScoredMemo { id: 8, score: 0.1, payload: Default::default() };
+        assert_eq!(citation_for(&empty, 0.1).snippet, "");
+    }
+}
@@ -2,15 +2,15 @@
 
 use axum::{
     debug_handler,
-    extract::State,
+    extract::{Path as UrlPath, Query, State},
     http::{StatusCode, HeaderMap, HeaderName, HeaderValue}, // 导入HeaderMap, HeaderName
     response::{IntoResponse, Response},
-    routing::post,
+    routing::{get, post, put},
     Json, Router,
 };
 use orchestrator::Orchestrator; 
-use memos_core::{Command, Response as CoreResponse};
-use agent_memos::MemosAgent;
+use memos_core::{Citation, Command, Response as CoreResponse};
+use agent_memos::{embedding, parse_time_bound, DedupeReport, EntityDetail, EntityNeighbour, EntitySummary, FactRevision, GraphFilter, GraphFormat, ImportanceSource, MemoryTier, MemosAgent, RecallMode, RecallOptions, RecallTrace, ReindexReport, TagCount, TierChange, TieredMemo, UndoOutcome, VectorBackend};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tower_http::{cors::{Any, CorsLayer}, trace::TraceLayer};
@@ -20,7 +20,24 @@ use common_utils::{detect_performance_mode, PerformanceMode, load_default_urls};
 use std::path::Path;
 
 // API 层 DTOs (保持不变)
-#[derive(Serialize)] #[serde(rename_all = "PascalCase")] struct ApiResponse { text: String }
+#[derive(Serialize)] #[serde(rename_all = "PascalCase")] struct ApiResponse {
+    text: String,
+    /// 回答所引用的记忆；可通过 /api/v1/memos/:id 查看、修改或删除
+    #[serde(skip_serializing_if = "Vec::is_empty")] citations: Vec<ApiCitation>,
+}
+#[derive(Serialize)] #[serde(rename_all = "PascalCase")] struct ApiCitation {
+    memory_id: i64,
+    snippet: String,
+    score: f32,
+    created_at: Option<String>,
+    updated_at: Option<String>,
+}
+
+impl From<Citation> for ApiCitation {
+    fn from(c: Citation) -> Self {
+        Self { memory_id: c.memory_id, snippet: c.snippet, score: c.score, created_at: c.created_at, updated_at: c.updated_at }
+    }
+}
 #[derive(Deserialize)] struct ApiCommand { #[serde(rename = "ProcessText")] process_text: String }
 
 // 专业的错误处理 (保持不变)
@@ -30,18 +47,30 @@ enum ApiError {
     TaskJoin(#[from] task::JoinError),
     #[error("Orchestrator dispatch failed")]
     Dispatch(anyhow::Error),
+    #[error("Reindex failed: {0}")]
+    Reindex(anyhow::Error),
+    #[error("Memo operation failed: {0}")]
+    Memos(anyhow::Error),
+    #[error("Bad request: {0}")]
+    BadRequest(String),
+    #[error("Not found: {0}")]
+    NotFound(String),
 }
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
         let error_message = self.to_string();
         eprintln!("[Server Error] {}", error_message);
-        let body = Json(serde_json::json!({ "Text": "An internal server error occurred." }));
+        let (status, body) = match &self {
+            ApiError::BadRequest(message) => (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "Text": message }))),
+            ApiError::NotFound(message) => (StatusCode::NOT_FOUND, Json(serde_json::json!({ "Text": message }))),
+            _ => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "Text": "An internal server error occurred." }))),
+        };
         
         let mut headers = HeaderMap::new();
         headers.insert(HeaderName::from_static("content-type"), HeaderValue::from_static("application/json; charset=utf-8"));
 
-        (StatusCode::INTERNAL_SERVER_ERROR, headers, body).into_response()
+        (status, headers, body).into_response()
     }
 }
 // Axum Handler (核心修正)
@@ -52,21 +81,35 @@ async fn dispatch_handler(
 ) -> Result<(StatusCode, HeaderMap, Json<ApiResponse>), ApiError> {
     let command = Command::ProcessText(payload.process_text);
 
-    let core_response = task::spawn_blocking(move || {
+    let api_response = task::spawn_blocking(move || {
         let rt = tokio::runtime::Builder::new_current_thread()
             .enable_all()
             .build()
             .unwrap();
         
-        rt.block_on(orchestrator.dispatch(&command))
+        // 流式回答由该运行时上的任务生成，必须在运行时销毁前收完
+        rt.block_on(async {
+            match orchestrator.dispatch(&command).await? {
+                CoreResponse::Stream { mut receiver, citations } => {
+                    let mut text = String::new();
+                    while let Some(token) = receiver.recv().await {
+                        if token.is_empty() {
+                            break;
+                        }
+                        text.push_str(&token);
+                    }
+                    Ok(ApiResponse { text, citations: citations.into_iter().map(ApiCitation::from).collect() })
+                }
+                CoreResponse::Cited { text, citations } => {
+                    Ok(ApiResponse { text, citations: citations.into_iter().map(ApiCitation::from).collect() })
+                }
+                CoreResponse::Text(text) => Ok(ApiResponse { text, citations: Vec::new() }),
+                _ => Ok(ApiResponse { text: "[Info] Backend returned a non-text response.".to_string(), citations: Vec::new() }),
+            }
+        })
     })
     .await?
     .map_err(ApiError::Dispatch)?;
-
-    let api_response = match core_response {
-        CoreResponse::Text(text) => ApiResponse { text },
-        _ => ApiResponse { text: "[Info] Backend returned a non-text response.".to_string() },
-    };
     
     let mut headers = HeaderMap::new();
     headers.insert(HeaderName::from_static("content-type"), HeaderValue::from_static("application/json; charset=utf-8"));
@@ -74,6 +117,637 @@ async fn dispatch_handler(
     Ok((StatusCode::OK, headers, Json(api_response)))
 }
 
+// 重建向量索引：同步执行到结束，返回汇总；进度输出在服务端日志中
+#[debug_handler]
+async fn reindex_handler(
+    State(orchestrator): State<Arc<Orchestrator>>,
+) -> Result<Json<ReindexReport>, ApiError> {
+    let report = task::spawn_blocking(move || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        rt.block_on(async {
+            let agent = orchestrator.memos_agent()?;
+            agent.reindex(&|p| {
+                println!("[Server] Reindex progress: {}/{} ({})", p.processed, p.total, p.collection);
+            }).await
+        })
+    })
+    .await?
+    .map_err(ApiError::Reindex)?;
+
+    Ok(Json(report))
+}
+
+#[derive(Deserialize)] struct ListMemosQuery { tier: Option<String>, limit: Option<usize> }
+#[derive(Deserialize)] struct SetTierRequest { tier: String }
+
+fn parse_tier(value: &str) -> Result<MemoryTier, ApiError> {
+    MemoryTier::parse(value).ok_or_else(|| ApiError::BadRequest(format!("Unknown tier '{}', expected 'active' or 'archive'", value)))
+}
+
+// 按层级列出记忆：GET /api/v1/memos?tier=archive&limit=50
+#[debug_handler]
+async fn list_memos_handler(
+    State(orchestrator): State<Arc<Orchestrator>>,
+    Query(query): Query<ListMemosQuery>,
+) -> Result<Json<Vec<TieredMemo>>, ApiError> {
+    let tier = query.tier.as_deref().map(parse_tier).transpose()?;
+    let limit = query.limit.unwrap_or(50);
+    let memos = task::spawn_blocking(move || orchestrator.memos_agent()?.list_memos(tier, limit))
+        .await?
+        .map_err(ApiError::Memos)?;
+    Ok(Json(memos))
+}
+
+// 手动调整层级：POST /api/v1/memos/:id/tier {"tier": "archive"}；层级未变时返回 null
+#[debug_handler]
+async fn set_tier_handler(
+    State(orchestrator): State<Arc<Orchestrator>>,
+    UrlPath(id): UrlPath<i64>,
+    Json(payload): Json<SetTierRequest>,
+) -> Result<Json<Option<TierChange>>, ApiError> {
+    let tier = parse_tier(&payload.tier)?;
+    let change = task::spawn_blocking(move || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        rt.block_on(async { orchestrator.memos_agent()?.set_tier(id, tier).await })
+    })
+    .await?
+    .map_err(ApiError::Memos)?;
+
+    Ok(Json(change))
+}
+
+#[derive(Deserialize)] struct SetImportanceRequest { importance: f32 }
+#[derive(Serialize)] struct ImportanceReport { id: i64, importance: f32, access_count: i64, retention: f32 }
+
+// 用户标记重要度（0 到 1，覆盖 LLM 的估计）：PUT /api/v1/memos/:id/importance {"importance": 0.9}
+#[debug_handler]
+async fn set_importance_handler(
+    State(orchestrator): State<Arc<Orchestrator>>,
+    UrlPath(id): UrlPath<i64>,
+    Json(payload): Json<SetImportanceRequest>,
+) -> Result<Json<ImportanceReport>, ApiError> {
+    if !(0.0..=1.0).contains(&payload.importance) {
+        return Err(ApiError::BadRequest("importance must be within [0, 1]".to_string()));
+    }
+    let (stats, retention) = task::spawn_blocking(move || {
+        let agent = orchestrator.memos_agent()?;
+        if agent.retention_of(id)?.is_none() {
+            return Ok(None);
+        }
+        agent.set_importance(id, payload.importance, ImportanceSource::User)?;
+        agent.retention_of(id)
+    })
+    .await?
+    .map_err(ApiError::Memos)?
+    .ok_or_else(|| ApiError::NotFound(format!("memo {}", id)))?;
+    Ok(Json(ImportanceReport { id, importance: stats.importance, access_count: stats.access_count, retention }))
+}
+
+// 立即执行分层策略：POST /api/v1/tiers/apply
+#[debug_handler]
+async fn apply_tier_policy_handler(
+    State(orchestrator): State<Arc<Orchestrator>>,
+) -> Result<Json<Vec<TierChange>>, ApiError> {
+    let changes = task::spawn_blocking(move || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        rt.block_on(async { orchestrator.memos_agent()?.apply_tier_policy().await })
+    })
+    .await?
+    .map_err(ApiError::Memos)?;
+
+    Ok(Json(changes))
+}
+
+#[derive(Deserialize)] struct RestoreRequest { revision: i64 }
+
+// 修订历史：GET /api/v1/memos/:id/history
+#[debug_handler]
+async fn history_handler(
+    State(orchestrator): State<Arc<Orchestrator>>,
+    UrlPath(id): UrlPath<i64>,
+) -> Result<Json<Vec<FactRevision>>, ApiError> {
+    let revisions = task::spawn_blocking(move || orchestrator.memos_agent()?.history(id))
+        .await?
+        .map_err(ApiError::Memos)?;
+    Ok(Json(revisions))
+}
+
+#[derive(Serialize)] struct MemoDetail { id: i64, content: String, tags: Vec<String> }
+#[derive(Deserialize)] struct UpdateMemoRequest { content: String }
+
+// 查看一条记忆（回答引用的出处）：GET /api/v1/memos/:id
+#[debug_handler]
+async fn get_memo_handler(
+    State(orchestrator): State<Arc<Orchestrator>>,
+    UrlPath(id): UrlPath<i64>,
+) -> Result<Json<MemoDetail>, ApiError> {
+    let content = task::spawn_blocking(move || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        rt.block_on(async {
+            let agent = orchestrator.memos_agent()?;
+            let Some(content) = agent.get_by_id(id).await? else { return Ok(None) };
+            Ok::<_, anyhow::Error>(Some((content, agent.tags_of(id)?)))
+        })
+    })
+    .await?
+    .map_err(ApiError::Memos)?
+    .ok_or_else(|| ApiError::NotFound(format!("memo {}", id)))?;
+
+    Ok(Json(MemoDetail { id, content: content.0, tags: content.1 }))
+}
+
+// 修改一条记忆：PUT /api/v1/memos/:id {"content": "..."}
+#[debug_handler]
+async fn update_memo_handler(
+    State(orchestrator): State<Arc<Orchestrator>>,
+    UrlPath(id): UrlPath<i64>,
+    Json(payload): Json<UpdateMemoRequest>,
+) -> Result<Json<MemoDetail>, ApiError> {
+    let content = payload.content.trim().to_string();
+    if content.is_empty() {
+        return Err(ApiError::BadRequest("content must not be empty".to_string()));
+    }
+    let updated = content.clone();
+    let tags = task::spawn_blocking(move || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        rt.block_on(async {
+            let agent = orchestrator.memos_agent()?;
+            if agent.get_by_id(id).await?.is_none() {
+                return Ok(None);
+            }
+            agent.update(id, &updated, None).await?;
+            agent.tags_of(id).map(Some)
+        })
+    })
+    .await?
+    .map_err(ApiError::Memos)?
+    .ok_or_else(|| ApiError::NotFound(format!("memo {}", id)))?;
+
+    Ok(Json(MemoDetail { id, content, tags }))
+}
+
+// 删除一条记忆（可用 /api/v1/undo 撤销）：DELETE /api/v1/memos/:id
+#[debug_handler]
+async fn delete_memo_handler(
+    State(orchestrator): State<Arc<Orchestrator>>,
+    UrlPath(id): UrlPath<i64>,
+) -> Result<StatusCode, ApiError> {
+    task::spawn_blocking(move || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        rt.block_on(async {
+            let agent = orchestrator.memos_agent()?;
+            if agent.get_by_id(id).await?.is_none() {
+                return Ok(None);
+            }
+            agent.delete(id, None).await.map(Some)
+        })
+    })
+    .await?
+    .map_err(ApiError::Memos)?
+    .ok_or_else(|| ApiError::NotFound(format!("memo {}", id)))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// 恢复到指定修订：POST /api/v1/memos/:id/restore {"revision": 2}
+#[debug_handler]
+async fn restore_handler(
+    State(orchestrator): State<Arc<Orchestrator>>,
+    UrlPath(id): UrlPath<i64>,
+    Json(payload): Json<RestoreRequest>,
+) -> Result<Json<Vec<FactRevision>>, ApiError> {
+    let revisions = task::spawn_blocking(move || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        rt.block_on(async {
+            let agent = orchestrator.memos_agent()?;
+            agent.restore_revision(id, payload.revision, None).await?;
+            agent.history(id)
+        })
+    })
+    .await?
+    .map_err(ApiError::Memos)?;
+
+    Ok(Json(revisions))
+}
+
+// 撤销最近一次修改：POST /api/v1/undo；没有可撤销的修改时返回 null
+#[debug_handler]
+async fn undo_handler(
+    State(orchestrator): State<Arc<Orchestrator>>,
+) -> Result<Json<Option<UndoOutcome>>, ApiError> {
+    let outcome = task::spawn_blocking(move || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        rt.block_on(async { orchestrator.memos_agent()?.undo_last_change(None).await })
+    })
+    .await?
+    .map_err(ApiError::Memos)?;
+
+    Ok(Json(outcome))
+}
+
+#[derive(Deserialize)] struct SetTagsRequest { tags: Vec<String> }
+#[derive(Deserialize)] struct RenameTagRequest { from: String, to: String }
+#[derive(Deserialize)] struct MergeTagsRequest { sources: Vec<String>, into: String }
+#[derive(Serialize)] struct MemoTags { id: i64, tags: Vec<String> }
+#[derive(Serialize)] struct RetagReport { affected: Vec<i64> }
+
+// 全部标签及使用次数：GET /api/v1/tags
+#[debug_handler]
+async fn list_tags_handler(State(orchestrator): State<Arc<Orchestrator>>) -> Result<Json<Vec<TagCount>>, ApiError> {
+    let tags = task::spawn_blocking(move || orchestrator.memos_agent()?.list_tags())
+        .await?
+        .map_err(ApiError::Memos)?;
+    Ok(Json(tags))
+}
+
+// 覆盖一条记忆的标签：PUT /api/v1/memos/:id/tags {"tags": ["工作"]}
+#[debug_handler]
+async fn set_tags_handler(
+    State(orchestrator): State<Arc<Orchestrator>>,
+    UrlPath(id): UrlPath<i64>,
+    Json(payload): Json<SetTagsRequest>,
+) -> Result<Json<MemoTags>, ApiError> {
+    if let Some(invalid) = payload.tags.iter().find(|t| agent_memos::normalize_tag(t).is_none()) {
+        return Err(ApiError::BadRequest(format!("invalid tag '{}'", invalid)));
+    }
+    let tags = task::spawn_blocking(move || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        rt.block_on(async {
+            let agent = orchestrator.memos_agent()?;
+            if agent.get_by_id(id).await?.is_none() {
+                return Ok(None);
+            }
+            agent.set_tags(id, &payload.tags, None).await.map(Some)
+        })
+    })
+    .await?
+    .map_err(ApiError::Memos)?
+    .ok_or_else(|| ApiError::NotFound(format!("memo {}", id)))?;
+
+    Ok(Json(MemoTags { id, tags }))
+}
+
+// 标签改名：POST /api/v1/tags/rename {"from": "旧", "to": "新"}
+#[debug_handler]
+async fn rename_tag_handler(
+    State(orchestrator): State<Arc<Orchestrator>>,
+    Json(payload): Json<RenameTagRequest>,
+) -> Result<Json<RetagReport>, ApiError> {
+    if agent_memos::normalize_tag(&payload.from).is_none() || agent_memos::normalize_tag(&payload.to).is_none() {
+        return Err(ApiError::BadRequest("from and to must be valid tags".to_string()));
+    }
+    let affected = task::spawn_blocking(move || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        rt.block_on(async { orchestrator.memos_agent()?.rename_tag(&payload.from, &payload.to, None).await })
+    })
+    .await?
+    .map_err(ApiError::Memos)?;
+
+    Ok(Json(RetagReport { affected }))
+}
+
+// 合并标签：POST /api/v1/tags/merge {"sources": ["a", "b"], "into": "c"}
+#[debug_handler]
+async fn merge_tags_handler(
+    State(orchestrator): State<Arc<Orchestrator>>,
+    Json(payload): Json<MergeTagsRequest>,
+) -> Result<Json<RetagReport>, ApiError> {
+    let all_valid = payload.sources.iter().chain([&payload.into]).all(|t| agent_memos::normalize_tag(t).is_some());
+    if payload.sources.is_empty() || !all_valid {
+        return Err(ApiError::BadRequest("sources and into must be valid tags".to_string()));
+    }
+    let affected = task::spawn_blocking(move || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        rt.block_on(async { orchestrator.memos_agent()?.merge_tags(&payload.sources, &payload.into, None).await })
+    })
+    .await?
+    .map_err(ApiError::Memos)?;
+
+    Ok(Json(RetagReport { affected }))
+}
+
+#[derive(Deserialize)] struct ListEntitiesQuery { limit: Option<usize> }
+#[derive(Deserialize)] struct EntityQuery { memo_limit: Option<usize>, neighbour_limit: Option<usize> }
+#[derive(Deserialize)] struct NeighboursQuery { limit: Option<usize> }
+#[derive(Deserialize)] struct AddAliasRequest { alias: String }
+#[derive(Deserialize)] struct MergeEntitiesRequest { sources: Vec<String>, into: String }
+#[derive(Serialize)] struct RebuildEntitiesReport { memos: usize }
+
+// 实体列表（按关联记忆数降序）：GET /api/v1/entities?limit=20
+#[debug_handler]
+async fn list_entities_handler(
+    State(orchestrator): State<Arc<Orchestrator>>,
+    Query(query): Query<ListEntitiesQuery>,
+) -> Result<Json<Vec<EntitySummary>>, ApiError> {
+    let limit = query.limit.unwrap_or(20);
+    let entities = task::spawn_blocking(move || orchestrator.memos_agent()?.list_entities(limit))
+        .await?
+        .map_err(ApiError::Memos)?;
+    Ok(Json(entities))
+}
+
+// 实体详情（名称或别名）：GET /api/v1/entities/:name?memo_limit=20&neighbour_limit=10
+#[debug_handler]
+async fn entity_handler(
+    State(orchestrator): State<Arc<Orchestrator>>,
+    UrlPath(name): UrlPath<String>,
+    Query(query): Query<EntityQuery>,
+) -> Result<Json<EntityDetail>, ApiError> {
+    let (memo_limit, neighbour_limit) = (query.memo_limit.unwrap_or(20), query.neighbour_limit.unwrap_or(10));
+    let lookup = name.clone();
+    let detail = task::spawn_blocking(move || orchestrator.memos_agent()?.entity(&lookup, memo_limit, neighbour_limit))
+        .await?
+        .map_err(ApiError::Memos)?
+        .ok_or_else(|| ApiError::NotFound(format!("entity {}", name)))?;
+    Ok(Json(detail))
+}
+
+// 共现邻居：GET /api/v1/entities/:name/neighbours?limit=10
+#[debug_handler]
+async fn entity_neighbours_handler(
+    State(orchestrator): State<Arc<Orchestrator>>,
+    UrlPath(name): UrlPath<String>,
+    Query(query): Query<NeighboursQuery>,
+) -> Result<Json<Vec<EntityNeighbour>>, ApiError> {
+    let limit = query.limit.unwrap_or(10);
+    let lookup = name.clone();
+    let neighbours = task::spawn_blocking(move || orchestrator.memos_agent()?.entity_neighbours(&lookup, limit))
+        .await?
+        .map_err(ApiError::Memos)?
+        .ok_or_else(|| ApiError::NotFound(format!("entity {}", name)))?;
+    Ok(Json(neighbours))
+}
+
+// 添加别名（别名已属于另一个实体时合并）：POST /api/v1/entities/:name/aliases {"alias": "泰坦项目"}
+#[debug_handler]
+async fn add_entity_alias_handler(
+    State(orchestrator): State<Arc<Orchestrator>>,
+    UrlPath(name): UrlPath<String>,
+    Json(payload): Json<AddAliasRequest>,
+) -> Result<Json<EntitySummary>, ApiError> {
+    if payload.alias.trim().is_empty() {
+        return Err(ApiError::BadRequest("alias must not be empty".to_string()));
+    }
+    let lookup = name.clone();
+    let entity = task::spawn_blocking(move || {
+        let agent = orchestrator.memos_agent()?;
+        if agent.entity(&lookup, 0, 0)?.is_none() {
+            return Ok(None);
+        }
+        agent.add_entity_alias(&lookup, &payload.alias).map(Some)
+    })
+    .await?
+    .map_err(ApiError::Memos)?
+    .ok_or_else(|| ApiError::NotFound(format!("entity {}", name)))?;
+    Ok(Json(entity))
+}
+
+// 合并实体：POST /api/v1/entities/merge {"sources": ["泰坦项目"], "into": "Titan"}
+#[debug_handler]
+async fn merge_entities_handler(
+    State(orchestrator): State<Arc<Orchestrator>>,
+    Json(payload): Json<MergeEntitiesRequest>,
+) -> Result<Json<EntitySummary>, ApiError> {
+    let merged = task::spawn_blocking(move || {
+        let agent = orchestrator.memos_agent()?;
+        for name in payload.sources.iter().chain([&payload.into]) {
+            if agent.entity(name, 0, 0)?.is_none() {
+                return Ok(Err(name.clone()));
+            }
+        }
+        agent.merge_entities(&payload.into, &payload.sources).map(Ok)
+    })
+    .await?
+    .map_err(ApiError::Memos)?;
+    let entity = merged.map_err(|name| ApiError::NotFound(format!("entity {}", name)))?;
+    Ok(Json(entity))
+}
+
+// 对全部记忆重新识别实体：POST /api/v1/entities/rebuild
+#[debug_handler]
+async fn rebuild_entities_handler(State(orchestrator): State<Arc<Orchestrator>>) -> Result<Json<RebuildEntitiesReport>, ApiError> {
+    let memos = task::spawn_blocking(move || orchestrator.memos_agent()?.rebuild_entity_graph())
+        .await?
+        .map_err(ApiError::Memos)?;
+    Ok(Json(RebuildEntitiesReport { memos }))
+}
+
+#[derive(Deserialize)]
+struct GraphExportQuery {
+    format: Option<String>,
+    tags: Option<String>,
+    time: Option<String>,
+    since: Option<String>,
+    until: Option<String>,
+    entity: Option<String>,
+}
+
+// 知识图谱导出：GET /api/v1/graph/export?format=dot|graphml|jsonld&tags=工作,项目&time=上周&since=2024-01-01&until=2024-02-01&entity=Titan
+// time 为时间表达，与 since/until 同时给出时 since/until 优先
+#[debug_handler]
+async fn graph_export_handler(
+    State(orchestrator): State<Arc<Orchestrator>>,
+    Query(query): Query<GraphExportQuery>,
+) -> Result<(StatusCode, HeaderMap, String), ApiError> {
+    let format_name = query.format.as_deref().unwrap_or("jsonld");
+    let format = GraphFormat::parse(format_name)
+        .ok_or_else(|| ApiError::BadRequest(format!("unknown graph format '{}', expected dot, graphml or jsonld", format_name)))?;
+    let agent = orchestrator.memos_agent().map_err(ApiError::Memos)?;
+
+    let mut filter = GraphFilter {
+        tags: query.tags.as_deref().unwrap_or("").split(',').map(str::trim).filter(|t| !t.is_empty()).map(str::to_string).collect(),
+        entity: query.entity.filter(|e| !e.trim().is_empty()),
+        ..GraphFilter::default()
+    };
+    if let Some(time) = query.time.as_deref().filter(|t| !t.trim().is_empty()) {
+        let range = agent.parse_time_range(time).ok_or_else(|| ApiError::BadRequest(format!("unrecognized time expression '{}'", time)))?;
+        filter.since = Some(range.start);
+        filter.until = Some(range.end);
+    }
+    let parse_bound = |value: &str| parse_time_bound(value).ok_or_else(|| ApiError::BadRequest(format!("invalid date '{}'", value)));
+    if let Some(since) = query.since.as_deref() {
+        filter.since = Some(parse_bound(since)?);
+    }
+    if let Some(until) = query.until.as_deref() {
+        filter.until = Some(parse_bound(until)?);
+    }
+
+    let body = task::spawn_blocking(move || orchestrator.memos_agent()?.export_graph(format, &filter))
+        .await?
+        .map_err(ApiError::Memos)?;
+    let mut headers = HeaderMap::new();
+    headers.insert(HeaderName::from_static("content-type"), HeaderValue::from_static(format.content_type()));
+    let disposition = format!("attachment; filename=\"memos_graph.{}\"", format.extension());
+    if let Ok(value) = HeaderValue::from_str(&disposition) {
+        headers.insert(HeaderName::from_static("content-disposition"), value);
+    }
+    Ok((StatusCode::OK, headers, body))
+}
+
+#[derive(Deserialize)] struct DedupeRequest { #[serde(default)] apply: bool }
+
+// 批量去重：POST /api/v1/dedupe {"apply": true}；apply 缺省为 false，只返回预览
+#[debug_handler]
+async fn dedupe_handler(
+    State(orchestrator): State<Arc<Orchestrator>>,
+    Json(payload): Json<DedupeRequest>,
+) -> Result<Json<DedupeReport>, ApiError> {
+    let report = task::spawn_blocking(move || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        rt.block_on(async { orchestrator.memos_agent()?.dedupe_store(payload.apply).await })
+    })
+    .await?
+    .map_err(ApiError::Memos)?;
+
+    Ok(Json(report))
+}
+
+#[derive(Deserialize)] struct RecallRequest {
+    query: String,
+    #[serde(default)] options: Option<serde_json::Value>,
+    /// "fuzzy"（默认）或 "target_lookup"
+    #[serde(default)] mode: RecallMode,
+}
+#[derive(Serialize)] struct RecalledMemo { id: i64, score: f32, content: Option<String> }
+
+/// 把 overrides 中出现的字段逐层覆盖到 base 上
+fn merge_json(base: &mut serde_json::Value, overrides: serde_json::Value) {
+    match (base, overrides) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                merge_json(base.entry(key).or_insert(serde_json::Value::Null), value);
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+/// 以当前默认参数为底，叠加请求中给出的字段
+fn resolve_recall_options(current: RecallOptions, overrides: Option<serde_json::Value>) -> Result<RecallOptions, ApiError> {
+    let Some(overrides) = overrides else { return Ok(current) };
+    let mut merged = serde_json::to_value(current).map_err(|e| ApiError::Memos(e.into()))?;
+    merge_json(&mut merged, overrides);
+    let options: RecallOptions = serde_json::from_value(merged).map_err(|e| ApiError::BadRequest(format!("Invalid recall options: {}", e)))?;
+    options.validate().map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    Ok(options)
+}
+
+// 当前默认的召回参数：GET /api/v1/recall/options
+#[debug_handler]
+async fn get_recall_options_handler(
+    State(orchestrator): State<Arc<Orchestrator>>,
+) -> Result<Json<RecallOptions>, ApiError> {
+    let options = orchestrator.memos_agent().map_err(ApiError::Memos)?.recall_options();
+    Ok(Json(options))
+}
+
+// 修改默认的召回参数（对话中的召回同样生效）：PUT /api/v1/recall/options，只需给出要改的字段
+#[debug_handler]
+async fn set_recall_options_handler(
+    State(orchestrator): State<Arc<Orchestrator>>,
+    Json(payload): Json<serde_json::Value>,
+) -> Result<Json<RecallOptions>, ApiError> {
+    let agent = orchestrator.memos_agent().map_err(ApiError::Memos)?;
+    let options = resolve_recall_options(agent.recall_options(), Some(payload))?;
+    agent.set_recall_options(options.clone()).map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    Ok(Json(options))
+}
+
+// 直接召回，可临时覆盖部分参数用于对比：POST /api/v1/recall {"query": "...", "mode": "fuzzy", "options": {"fusion": {"method": "weighted"}}}
+#[debug_handler]
+async fn recall_handler(
+    State(orchestrator): State<Arc<Orchestrator>>,
+    Json(payload): Json<RecallRequest>,
+) -> Result<Json<Vec<RecalledMemo>>, ApiError> {
+    let current = orchestrator.memos_agent().map_err(ApiError::Memos)?.recall_options();
+    let options = resolve_recall_options(current, payload.options)?;
+    let points = task::spawn_blocking(move || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        rt.block_on(async { orchestrator.memos_agent()?.recall_with_options(&payload.query, None, payload.mode, &options).await })
+    })
+    .await?
+    .map_err(ApiError::Memos)?;
+
+    let memos = points.into_iter()
+        .map(|p| RecalledMemo { id: p.id, score: p.score, content: p.content().map(str::to_string) })
+        .collect();
+    Ok(Json(memos))
+}
+
+// 召回诊断：POST /api/v1/recall/explain，请求体与 /api/v1/recall 相同，返回每一步的中间结果与重排序打分
+#[debug_handler]
+async fn recall_explain_handler(
+    State(orchestrator): State<Arc<Orchestrator>>,
+    Json(payload): Json<RecallRequest>,
+) -> Result<Json<RecallTrace>, ApiError> {
+    let current = orchestrator.memos_agent().map_err(ApiError::Memos)?.recall_options();
+    let options = resolve_recall_options(current, payload.options)?;
+    let trace = task::spawn_blocking(move || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        rt.block_on(orchestrator.explain_recall(&payload.query, payload.mode, Some(&options)))
+    })
+    .await?
+    .map_err(ApiError::Memos)?;
+
+    Ok(Json(trace))
+}
+
 // 主函数 (保持不变)
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -107,9 +781,10 @@ async fn main() -> anyhow::Result<()> {
     }
 
     println!("[Server] Initializing MemosAgent...");
-    // 注意：这里的 MemosAgent::new 调用可能需要根据WSL版的定义来调整
-    // 我们先假设WSL版的 new 函数需要 qdrant_url 和 embedding_url
-    let memos_agent = MemosAgent::new(qdrant_url, embedding_url, &models_path).await?;
+    let vector_backend = VectorBackend::from_env(&service_urls.qdrant_url);
+    let embedder = embedding::provider_from_env(&service_urls.embedding_url, &models_path)?;
+    let memos_agent = MemosAgent::new(vector_backend, embedder, &models_path).await?
+        .with_hyde(&service_urls.llm_url, mode);
     let agents: Vec<Box<dyn memos_core::Agent>> = vec![Box::new(memos_agent)];
     
     println!("[Server] Initializing Orchestrator...");
@@ -125,6 +800,30 @@ async fn main() -> anyhow::Result<()> {
 
     let app = Router::new()
         .route("/api/v1/dispatch", post(dispatch_handler))
+        .route("/api/v1/reindex", post(reindex_handler))
+        .route("/api/v1/memos", get(list_memos_handler))
+        .route("/api/v1/memos/:id", get(get_memo_handler).put(update_memo_handler).delete(delete_memo_handler))
+        .route("/api/v1/memos/:id/tier", post(set_tier_handler))
+        .route("/api/v1/memos/:id/tags", put(set_tags_handler))
+        .route("/api/v1/memos/:id/importance", put(set_importance_handler))
+        .route("/api/v1/tags", get(list_tags_handler))
+        .route("/api/v1/tags/rename", post(rename_tag_handler))
+        .route("/api/v1/tags/merge", post(merge_tags_handler))
+        .route("/api/v1/entities", get(list_entities_handler))
+        .route("/api/v1/entities/rebuild", post(rebuild_entities_handler))
+        .route("/api/v1/entities/merge", post(merge_entities_handler))
+        .route("/api/v1/entities/:name", get(entity_handler))
+        .route("/api/v1/entities/:name/neighbours", get(entity_neighbours_handler))
+        .route("/api/v1/entities/:name/aliases", post(add_entity_alias_handler))
+        .route("/api/v1/graph/export", get(graph_export_handler))
+        .route("/api/v1/tiers/apply", post(apply_tier_policy_handler))
+        .route("/api/v1/memos/:id/history", get(history_handler))
+        .route("/api/v1/memos/:id/restore", post(restore_handler))
+        .route("/api/v1/undo", post(undo_handler))
+        .route("/api/v1/dedupe", post(dedupe_handler))
+        .route("/api/v1/recall", post(recall_handler))
+        .route("/api/v1/recall/explain", post(recall_explain_handler))
+        .route("/api/v1/recall/options", get(get_recall_options_handler).put(set_recall_options_handler))
         .with_state(shared_state)
         .layer(CorsLayer::new().allow_origin(Any).allow_methods(Any))
         .layer(TraceLayer::new_for_http());